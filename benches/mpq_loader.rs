@@ -0,0 +1,64 @@
+//! Compares [`MPQLoader::load_raw_owned`] on a fresh loader, which has to read and decompress the file, with a loader
+//! that already holds the file in its cache. Uses the fixture archive of the mpq crate, see
+//! `mpq-rust/benches/fixtures/generate.py`.
+//!
+//! This crate has no library target, so the loader and the modules that it depends on are included by their path.
+#![allow(dead_code)]
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+use crate::io::common::loader::RawAssetLoader;
+use crate::io::mpq::loader::MPQLoader;
+
+#[path = "../src/io"]
+mod io {
+    pub mod asset_cache;
+    pub mod common;
+    pub mod mpq;
+}
+
+#[path = "../src/rendering"]
+mod rendering {
+    pub mod loader {
+        pub mod blp_loader;
+    }
+}
+
+const DATA_FOLDER: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/mpq-rust/benches/fixtures");
+const FILES: [(&str, &str); 4] = [
+    ("stored", "bench\\stored.bin"),
+    ("zlib", "bench\\zlib.bin"),
+    ("bzip2", "bench\\bzip2.bin"),
+    ("pkware", "bench\\pkware.bin"),
+];
+
+fn load_raw_owned(c: &mut Criterion) {
+    let mut group = c.benchmark_group("MPQLoader::load_raw_owned");
+
+    for (compression, path) in FILES {
+        let hot_loader = MPQLoader::new(DATA_FOLDER);
+        let size = hot_loader
+            .load_raw_owned(path)
+            .expect("Fixture file to exist")
+            .len();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        // only the load is measured, opening and dropping the archives is not
+        group.bench_function(BenchmarkId::new("cold", compression), |b| {
+            b.iter_batched_ref(
+                || MPQLoader::new(DATA_FOLDER),
+                |loader| loader.load_raw_owned(path),
+                BatchSize::PerIteration,
+            )
+        });
+
+        group.bench_function(BenchmarkId::new("hot", compression), |b| {
+            b.iter(|| hot_loader.load_raw_owned(path))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, load_raw_owned);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+//! Measures the read throughput of MPQ files per compression type.
+//! The fixture archive is generated by `benches/fixtures/generate.py`. LZMA is not covered, as it isn't supported.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use mpq::Archive;
+use std::io::Read;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/fixtures/bench.mpq");
+const FILES: [(&str, &str); 4] = [
+    ("stored", "bench\\stored.bin"),
+    ("zlib", "bench\\zlib.bin"),
+    ("bzip2", "bench\\bzip2.bin"),
+    ("pkware", "bench\\pkware.bin"),
+];
+
+fn file_read(c: &mut Criterion) {
+    // in memory, so that we measure decompression and not the disk
+    let mut archive = Archive::open_owned(FIXTURE).expect("Fixture archive to open");
+    let mut group = c.benchmark_group("File::read");
+
+    for (compression, path) in FILES {
+        let file = archive.open_file(path).expect("Fixture file to exist");
+        let mut buf = vec![0; file.size() as usize];
+        group.throughput(Throughput::Bytes(file.size() as u64));
+        group.bench_function(BenchmarkId::from_parameter(compression), |b| {
+            b.iter(|| {
+                file.read(&mut archive, &mut buf)
+                    .expect("Fixture file to read")
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn open_and_read(c: &mut Criterion) {
+    let mut archive = Archive::open_owned(FIXTURE).expect("Fixture archive to open");
+    let mut group = c.benchmark_group("FileReader");
+
+    for (compression, path) in FILES {
+        let size = archive
+            .open_file(path)
+            .expect("Fixture file to exist")
+            .size();
+        let mut buf = Vec::with_capacity(size as usize);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(BenchmarkId::from_parameter(compression), |b| {
+            b.iter(|| {
+                // includes the hash table lookup and sector offset table, like a loader would
+                let file = archive.open_file(path).expect("Fixture file to exist");
+                buf.clear();
+                file.reader(&mut archive)
+                    .read_to_end(&mut buf)
+                    .expect("Fixture file to read")
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, file_read, open_and_read);
+criterion_main!(benches);
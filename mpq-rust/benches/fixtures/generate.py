@@ -0,0 +1,274 @@
+#!/usr/bin/env python3
+"""Generates bench.mpq, the fixture archive used by the decompression benchmark.
+
+The archive contains the same pseudo-random, text-like payload once per supported compression type (stored, zlib, bzip2
+and PKWARE DCL), split into 4 KiB sectors like the WoW client archives. As the python standard library has no PKWARE DCL
+encoder, a simple one (binary mode, 4 KiB dictionary, greedy matching) is part of this script. LZMA is missing, as the
+reader doesn't support it. Additionally, there are two encrypted files (with the key fixed by the block position),
+one of them zlib compressed, that contain the first ENCRYPTED_SIZE bytes of the payload.
+
+Usage: python3 generate.py [output path]
+"""
+import bz2
+import struct
+import sys
+import zlib
+
+SECTOR_SIZE_SHIFT = 3
+SECTOR_SIZE = 512 << SECTOR_SIZE_SHIFT
+HASH_TABLE_COUNT = 16
+PAYLOAD_SIZE = 128 * 1024
+# Not a multiple of 4, as the trailing bytes aren't encrypted
+ENCRYPTED_SIZE = 10001
+
+FILE_COMPRESS = 0x00000200
+FILE_ENCRYPTED = 0x00010000
+FILE_FIX_KEY = 0x00020000
+FILE_EXISTS = 0x80000000
+
+COMPRESSION_ZLIB = 0x02
+COMPRESSION_PKWARE = 0x08
+COMPRESSION_BZIP2 = 0x10
+
+
+def crypt_table():
+    table = [0] * 0x500
+    seed = 0x00100001
+    for index1 in range(0x100):
+        index2 = index1
+        for _ in range(5):
+            seed = (seed * 125 + 3) % 0x2AAAAB
+            temp1 = (seed & 0xFFFF) << 0x10
+            seed = (seed * 125 + 3) % 0x2AAAAB
+            temp2 = seed & 0xFFFF
+            table[index2] = temp1 | temp2
+            index2 += 0x100
+    return table
+
+
+CRYPT_TABLE = crypt_table()
+
+
+def hash_string(key, offset):
+    seed1 = 0x7FED7FED
+    seed2 = 0xEEEEEEEE
+    for c in key.replace('/', '\\').upper():
+        ch = ord(c)
+        seed1 = CRYPT_TABLE[offset + ch] ^ ((seed1 + seed2) & 0xFFFFFFFF)
+        seed2 = (ch + seed1 + seed2 + (seed2 << 5) + 3) & 0xFFFFFFFF
+    return seed1
+
+
+def encrypt(data, seed):
+    seed2 = 0xEEEEEEEE
+    out = bytearray()
+    tail = len(data) % 4
+    for (value,) in struct.iter_unpack('<I', data[:len(data) - tail]):
+        seed2 = (seed2 + CRYPT_TABLE[0x400 + (seed & 0xFF)]) & 0xFFFFFFFF
+        out += struct.pack('<I', value ^ ((seed + seed2) & 0xFFFFFFFF))
+        seed = ((((~seed << 0x15) & 0xFFFFFFFF) + 0x11111111) & 0xFFFFFFFF) | (seed >> 0x0B)
+        seed2 = (value + seed2 + (seed2 << 5) + 3) & 0xFFFFFFFF
+    return bytes(out) + data[len(data) - tail:]
+
+
+DCL_LENGTH_BASES = [3, 2, 4, 5, 6, 7, 8, 9, 10, 12, 16, 24, 40, 72, 136, 264]
+DCL_LENGTH_EXTRA = [0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]
+DCL_DICT_BITS = 6  # 4 KiB dictionary
+DCL_MAX_LENGTH = 518
+# Bounds the match search, so that generating the fixture doesn't take minutes
+DCL_MAX_CANDIDATES = 32
+
+
+def dcl_codes(run_lengths):
+    """Canonical codes of the run length encoded code lengths, as (code, length) per symbol."""
+    lengths = []
+    for value in run_lengths:
+        lengths += [value & 0xF] * ((value >> 4) + 1)
+    codes = [None] * len(lengths)
+    code = 0
+    for length in range(1, max(lengths) + 1):
+        for symbol, symbol_length in enumerate(lengths):
+            if symbol_length == length:
+                codes[symbol] = (code, length)
+                code += 1
+        code <<= 1
+    return codes
+
+
+DCL_LENGTH_CODES = dcl_codes([2, 35, 36, 53, 38, 23])
+DCL_DISTANCE_CODES = dcl_codes([2, 20, 53, 230, 247, 151, 248])
+
+
+class BitWriter:
+    def __init__(self):
+        self.out = bytearray()
+        self.buffer = 0
+        self.count = 0
+
+    def bits(self, value, count):
+        """Least significant bit first"""
+        self.buffer |= value << self.count
+        self.count += count
+        while self.count >= 8:
+            self.out.append(self.buffer & 0xFF)
+            self.buffer >>= 8
+            self.count -= 8
+
+    def code(self, code):
+        """Huffman codes are stored most significant bit first and inverted"""
+        value, length = code
+        for bit in range(length - 1, -1, -1):
+            self.bits(((value >> bit) & 1) ^ 1, 1)
+
+    def finish(self):
+        if self.count:
+            self.out.append(self.buffer & 0xFF)
+        return bytes(self.out)
+
+
+def implode(data):
+    """PKWARE DCL compression in binary mode, with greedy matching."""
+    writer = BitWriter()
+    writer.bits(0, 8)
+    writer.bits(DCL_DICT_BITS, 8)
+
+    def length(value):
+        symbol = next(i for i, base in enumerate(DCL_LENGTH_BASES) if 0 <= value - base < 1 << DCL_LENGTH_EXTRA[i])
+        writer.bits(1, 1)
+        writer.code(DCL_LENGTH_CODES[symbol])
+        writer.bits(value - DCL_LENGTH_BASES[symbol], DCL_LENGTH_EXTRA[symbol])
+
+    window = 1 << (DCL_DICT_BITS + 6)
+    positions = {}
+    pos = 0
+    while pos < len(data):
+        best_length, best_distance = 0, 0
+        for candidate in reversed(positions.get(data[pos:pos + 3], [])[-DCL_MAX_CANDIDATES:]):
+            distance = pos - candidate
+            if distance > window:
+                break
+            match = 0
+            while match < DCL_MAX_LENGTH and pos + match < len(data) and data[candidate + match] == data[pos + match]:
+                match += 1
+            if match > best_length:
+                best_length, best_distance = match, distance
+
+        if best_length >= 3:
+            length(best_length)
+            distance = best_distance - 1
+            writer.code(DCL_DISTANCE_CODES[distance >> DCL_DICT_BITS])
+            writer.bits(distance & ((1 << DCL_DICT_BITS) - 1), DCL_DICT_BITS)
+        else:
+            best_length = 1
+            writer.bits(data[pos] << 1, 9)
+
+        for i in range(pos, pos + best_length):
+            positions.setdefault(data[i:i + 3], []).append(i)
+        pos += best_length
+
+    length(DCL_MAX_LENGTH + 1)  # end code
+    return writer.finish()
+
+
+def payload():
+    """Text-like data that compresses roughly like the DBC and M2 files in the client archives."""
+    words = [b'terrain', b'doodad', b'texture', b'model', b'sector', b'archive', b'chunk', b'vertex', b'normal',
+             b'alpha', b'shadow', b'liquid', b'\x00\x00\x80\x3f', b'\xff\xff\xff\xff', b'\x01\x00', b'\n']
+    state = 0x1234567
+    out = bytearray()
+    while len(out) < PAYLOAD_SIZE:
+        state = (state * 1103515245 + 12345) & 0x7FFFFFFF
+        out += words[(state >> 16) & 0xF]
+        out += struct.pack('<H', state & 0x3FF)
+    return bytes(out[:PAYLOAD_SIZE])
+
+
+def sectors(data):
+    return [data[ofs:ofs + SECTOR_SIZE] for ofs in range(0, len(data), SECTOR_SIZE)]
+
+
+def sector_file(data, compress, key=None):
+    packed_sectors = []
+    for i, raw in enumerate(sectors(data)):
+        packed = compress(raw)
+        # sectors that don't shrink are stored as is, which the reader detects by their size
+        packed = packed if len(packed) < len(raw) else raw
+        packed_sectors.append(packed if key is None else encrypt(packed, (key + i) & 0xFFFFFFFF))
+
+    offsets = [(len(packed_sectors) + 1) * 4]
+    for sector in packed_sectors:
+        offsets.append(offsets[-1] + len(sector))
+
+    offset_table = struct.pack('<%dI' % len(offsets), *offsets)
+    if key is not None:
+        offset_table = encrypt(offset_table, (key - 1) & 0xFFFFFFFF)
+
+    return offset_table + b''.join(packed_sectors)
+
+
+def file_key(name, block_offset, size):
+    """The key of files that are both FILE_ENCRYPTED and FILE_FIX_KEY"""
+    key = hash_string(name.split('\\')[-1], 0x300)
+    return ((key + block_offset) & 0xFFFFFFFF) ^ size
+
+
+def main():
+    out_path = sys.argv[1] if len(sys.argv) > 1 else 'bench.mpq'
+    data = payload()
+
+    compress_zlib = lambda raw: bytes([COMPRESSION_ZLIB]) + zlib.compress(raw, 9)
+    files = [
+        ('bench\\stored.bin', data, None, False),
+        ('bench\\zlib.bin', data, compress_zlib, False),
+        ('bench\\bzip2.bin', data, lambda raw: bytes([COMPRESSION_BZIP2]) + bz2.compress(raw, 9), False),
+        ('bench\\pkware.bin', data, lambda raw: bytes([COMPRESSION_PKWARE]) + implode(raw), False),
+        ('bench\\encrypted.bin', data[:ENCRYPTED_SIZE], compress_zlib, True),
+        ('bench\\encrypted_stored.bin', data[:ENCRYPTED_SIZE], None, True),
+    ]
+
+    body = bytearray()
+    blocks = []
+    hash_table = [(0xFFFFFFFF, 0xFFFFFFFF, 0xFFFF, 0xFFFF, 0xFFFFFFFF)] * HASH_TABLE_COUNT
+    header_size = 0x20
+
+    for block_index, (name, content, compress, encrypted) in enumerate(files):
+        block_offset = header_size + len(body)
+        key = file_key(name, block_offset, len(content)) if encrypted else None
+        flags = FILE_EXISTS | (FILE_ENCRYPTED | FILE_FIX_KEY if encrypted else 0)
+
+        if compress:
+            packed = sector_file(content, compress, key)
+            flags |= FILE_COMPRESS
+        elif encrypted:
+            packed = b''.join(encrypt(raw, (key + i) & 0xFFFFFFFF) for i, raw in enumerate(sectors(content)))
+        else:
+            packed = content
+
+        blocks.append((block_offset, len(packed), len(content), flags))
+        body += packed
+
+        # the reader doesn't wrap around when probing, so neither may we
+        slot = hash_string(name, 0x000) & (HASH_TABLE_COUNT - 1)
+        while hash_table[slot][4] != 0xFFFFFFFF:
+            slot += 1
+        hash_table[slot] = (hash_string(name, 0x100), hash_string(name, 0x200), 0, 0, block_index)
+
+    hash_bytes = b''.join(struct.pack('<IIHHI', *entry) for entry in hash_table)
+    block_bytes = b''.join(struct.pack('<IIII', *entry) for entry in blocks)
+
+    hash_table_offset = header_size + len(body)
+    block_table_offset = hash_table_offset + len(hash_bytes)
+    archive_size = block_table_offset + len(block_bytes)
+
+    header = struct.pack('<4sIIHHIIII', b'MPQ\x1a', header_size, archive_size, 0, SECTOR_SIZE_SHIFT,
+                         hash_table_offset, block_table_offset, HASH_TABLE_COUNT, len(blocks))
+
+    with open(out_path, 'wb') as f:
+        f.write(header)
+        f.write(body)
+        f.write(encrypt(hash_bytes, hash_string('(hash table)', 0x300)))
+        f.write(encrypt(block_bytes, hash_string('(block table)', 0x300)))
+
+
+if __name__ == '__main__':
+    main()
@@ -1,15 +1,18 @@
-use crate::compression::*;
-use crate::crypt::{decrypt, hash_string};
+use crate::compression::{compress_zlib, decompress_into, explode};
+use crate::crypt::{decrypt, encrypt, hash_string};
+use crate::error::MpqError;
 use adler32::RollingAdler32;
 use byteorder::{ByteOrder, LittleEndian};
+use std::borrow::BorrowMut;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fs;
+use std::io::{self, Error, ErrorKind};
 use std::io::{BufReader, SeekFrom};
 use std::io::{Cursor, prelude::*};
-use std::io::{Error, ErrorKind};
 use std::mem;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 
 const HEADER_SIZE_V1: usize = 0x20;
 //const HEADER_SIZE_V2: usize = 0x2C;
@@ -20,6 +23,11 @@ const USER_HEADER_SIZE: usize = 16;
 const ID_MPQA: &[u8] = b"MPQ\x1A";
 const ID_MPQB: &[u8] = b"MPQ\x1B";
 
+const SIGNATURE_FILE: &str = "(signature)";
+/// The weak signature file consists of 8 unused bytes, followed by the 512-bit RSA signature.
+const WEAK_SIGNATURE_HEADER_SIZE: usize = 8;
+const WEAK_SIGNATURE_SIZE: usize = 64;
+
 const FILE_IMPLODE: u32 = 0x00000100; // implode method by pkware compression library
 const FILE_COMPRESS: u32 = 0x00000200; // compress methods by multiple methods
 const FILE_ENCRYPTED: u32 = 0x00010000; // file is encrypted
@@ -28,8 +36,17 @@ const FILE_PATCH_FILE: u32 = 0x00100000; // file is a patch file. file data begi
 const FILE_SINGLE_UNIT: u32 = 0x01000000; // file is stored as single unit
 const FILE_SECTOR_CRC: u32 = 0x04000000;
 const FILE_COMPRESS_MASK: u32 = 0x0000FF00;
+const FILE_EXISTS: u32 = 0x80000000;
 
-#[derive(Debug)]
+const LISTFILE: &str = "(listfile)";
+
+const ATTRIBUTES_FILE: &str = "(attributes)";
+const ATTRIBUTES_CRC32: u32 = 0x00000001;
+
+/// Sectors smaller than this are stored, as the compression mask and zlib header outweigh the gains.
+const MIN_COMPRESS_SIZE: usize = 64;
+
+#[derive(Debug, Clone)]
 struct Header {
     _magic: [u8; 4],
     _header_size: u32,
@@ -66,7 +83,7 @@ impl Header {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct UserDataHeader {
     _magic: [u8; 4],
     user_data_size: u32,
@@ -100,6 +117,14 @@ struct Hash {
 }
 
 impl Hash {
+    fn write(&self, dst: &mut [u8]) {
+        LittleEndian::write_u32(dst, self.hash_a);
+        LittleEndian::write_u32(&mut dst[4..], self.hash_b);
+        LittleEndian::write_u16(&mut dst[8..], self._locale);
+        LittleEndian::write_u16(&mut dst[10..], self._platform);
+        LittleEndian::write_u32(&mut dst[12..], self.block_index);
+    }
+
     pub fn new(src: &[u8]) -> Hash {
         Hash {
             hash_a: LittleEndian::read_u32(src),
@@ -124,6 +149,13 @@ struct Block {
 }
 
 impl Block {
+    fn write(&self, dst: &mut [u8]) {
+        LittleEndian::write_u32(dst, self.offset);
+        LittleEndian::write_u32(&mut dst[0x4..], self.packed_size);
+        LittleEndian::write_u32(&mut dst[0x8..], self.unpacked_size);
+        LittleEndian::write_u32(&mut dst[0xC..], self.flags);
+    }
+
     pub fn new(src: &[u8]) -> Block {
         Block {
             offset: LittleEndian::read_u32(src),
@@ -140,14 +172,43 @@ impl<T: Read + Seek> ReadAndSeek for T {}
 
 type Reader = Box<dyn ReadAndSeek + Sync + Send>;
 
+/// The hashes that are needed to look up a file name, so that names which are looked up in multiple archives only have
+/// to be hashed once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileHash {
+    index: u32,
+    hash_a: u32,
+    hash_b: u32,
+}
+
+impl FileHash {
+    pub fn new(filename: &str) -> FileHash {
+        FileHash {
+            index: hash_string(filename, 0x0),
+            hash_a: hash_string(filename, 0x100),
+            hash_b: hash_string(filename, 0x200),
+        }
+    }
+}
+
+/// Where the archive has been loaded from, so that additional readers can be created.
+#[derive(Debug, Clone)]
+enum Source {
+    Path(PathBuf),
+    Memory(Arc<[u8]>),
+}
+
 pub struct Archive {
     cursor: Reader,
+    source: Option<Source>,
     header: Header,
     user_data_header: Option<UserDataHeader>,
-    hash_table: Vec<Hash>,
-    block_table: Vec<Block>,
+    hash_table: Arc<[Hash]>,
+    block_table: Arc<[Block]>,
     sector_size: u32,
     offset: u64,
+    /// Readers of finished [`Archive::read_file`] calls, so that the next calls don't have to open the file again.
+    idle_readers: Mutex<Vec<Reader>>,
 }
 
 impl Archive {
@@ -156,12 +217,76 @@ impl Archive {
         let metadata = fs::metadata(&path).expect("unable to read metadata");
         let mut buf = vec![0; metadata.len() as usize];
         file.read_exact(&mut buf).expect("buffer overflow");
-        Self::load(Box::new(Cursor::<Arc<[u8]>>::new(buf.into())))
+
+        let buf: Arc<[u8]> = buf.into();
+        let mut archive = Self::load(Box::new(Cursor::new(buf.clone())))?;
+        archive.source = Some(Source::Memory(buf));
+        Ok(archive)
     }
 
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Archive, Error> {
         let file = fs::File::open(&path).expect("no file found");
-        Self::load(Box::new(BufReader::new(file)))
+        let mut archive = Self::load(Box::new(BufReader::new(file)))?;
+        archive.source = Some(Source::Path(path.as_ref().to_path_buf()));
+        Ok(archive)
+    }
+
+    /// Creates another handle to the archive with its own reader, the parsed tables are shared. Only archives that
+    /// have been opened through [`Archive::open`] or [`Archive::open_owned`] can be cloned, as arbitrary readers can't
+    /// be duplicated.
+    pub fn try_clone(&self) -> Result<Archive, Error> {
+        Ok(self.with_reader(self.open_reader()?))
+    }
+
+    fn open_reader(&self) -> Result<Reader, Error> {
+        match &self.source {
+            Some(Source::Path(path)) => Ok(Box::new(BufReader::new(fs::File::open(path)?))),
+            Some(Source::Memory(buf)) => Ok(Box::new(Cursor::new(buf.clone()))),
+            None => Err(Error::new(
+                ErrorKind::Unsupported,
+                "Archives loaded from a reader can't be cloned",
+            )),
+        }
+    }
+
+    fn with_reader(&self, cursor: Reader) -> Archive {
+        Archive {
+            cursor,
+            source: self.source.clone(),
+            header: self.header.clone(),
+            user_data_header: self.user_data_header.clone(),
+            hash_table: self.hash_table.clone(),
+            block_table: self.block_table.clone(),
+            sector_size: self.sector_size,
+            offset: self.offset,
+            idle_readers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Reads the whole file through a temporary handle (see [`Archive::try_clone`]), so that multiple threads can read
+    /// from the same archive without having to lock it. The readers are reused by later calls, so there are at most
+    /// as many as there have been concurrent calls.
+    pub fn read_file(&self, filename: &str) -> Result<Vec<u8>, Error> {
+        let idle_reader = self
+            .idle_readers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        let mut archive = self.with_reader(match idle_reader {
+            Some(reader) => reader,
+            None => self.open_reader()?,
+        });
+
+        let file = archive.open_file(filename)?;
+        let mut buf: Vec<u8> = vec![0; file.size() as usize];
+        file.read(&mut archive, &mut buf)?;
+
+        // Readers that failed are dropped instead, in case their handle is broken.
+        self.idle_readers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(archive.cursor);
+        Ok(buf)
     }
 
     pub fn load(mut cursor: Reader) -> Result<Archive, Error> {
@@ -241,132 +366,188 @@ impl Archive {
 
         Ok(Archive {
             cursor,
+            source: None,
             header,
             user_data_header,
-            hash_table,
-            block_table,
+            hash_table: hash_table.into(),
+            block_table: block_table.into(),
             sector_size,
             offset,
+            idle_readers: Mutex::new(Vec::new()),
         })
     }
 
-    // TODO: maybe refactor the common parts into a dedicated method.
     pub fn contains_file(&self, filename: &str) -> bool {
-        let start_index = (hash_string(filename, 0x0) & (self.header.hash_table_count - 1)) as usize;
-        let mut hash;
+        self.contains_hash(&FileHash::new(filename))
+    }
 
-        let hash_a = hash_string(filename, 0x100);
-        let hash_b = hash_string(filename, 0x200);
+    /// Like [`Archive::contains_file`], for names that are looked up in multiple archives.
+    pub fn contains_hash(&self, hash: &FileHash) -> bool {
+        self.find_hash(hash).is_some()
+    }
 
-        for i in start_index..self.hash_table.len() {
-            hash = &self.hash_table[i];
+    /// Where a file is stored, without reading it. Together with [`Archive::file_crcs`], this identifies the contents
+    /// of a file, e.g. to detect that a newer archive changed it.
+    pub fn file_info(&self, hash: &FileHash) -> Option<FileInfo> {
+        let hash = self.find_hash(hash)?;
+        let block = self.block_table.get(hash.block_index as usize)?;
+
+        Some(FileInfo {
+            block_index: hash.block_index as usize,
+            offset: u64::from(block.offset) + self.offset,
+            packed_size: block.packed_size,
+            unpacked_size: block.unpacked_size,
+        })
+    }
 
-            if hash.hash_a == hash_a && hash.hash_b == hash_b {
-                return true;
-            }
+    /// The CRC32 of every file as stored in the `(attributes)` file, indexed by [`FileInfo::block_index`]. Archives
+    /// without that file (or without checksums in it) yield an empty list.
+    pub fn file_crcs(&self) -> Result<Vec<u32>, Error> {
+        let data = match self.read_file(ATTRIBUTES_FILE) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        // the version (100) and the flags of the attributes that follow, the CRC32s come first
+        if data.len() < 8 || LittleEndian::read_u32(&data[4..]) & ATTRIBUTES_CRC32 == 0 {
+            return Ok(Vec::new());
         }
 
-        false
+        Ok(data[8..]
+            .chunks_exact(4)
+            .take(self.block_table.len())
+            .map(LittleEndian::read_u32)
+            .collect())
     }
 
-    pub fn open_file(&mut self, filename: &str) -> Result<File, Error> {
-        let start_index = (hash_string(filename, 0x0) & (self.header.hash_table_count - 1)) as usize;
-        let mut hash;
-
-        let hash_a = hash_string(filename, 0x100);
-        let hash_b = hash_string(filename, 0x200);
-        let mut file_key = 0;
-
-        for i in start_index..self.hash_table.len() {
-            hash = &self.hash_table[i];
-
-            if hash.hash_a == hash_a && hash.hash_b == hash_b {
-                let block = &self.block_table[hash.block_index as usize];
-                let mut sector_offsets: Vec<u32> = Vec::new();
-                let mut sector_checksums: Vec<u32> = Vec::new();
-
-                // file if encrypted, generate decryption key
-                if block.flags & FILE_ENCRYPTED != 0 {
-                    match filename.split(&['\\', '/'][..]).last() {
-                        Some(basename) => file_key = hash_string(basename, 0x300),
-                        None => {
-                            return Err(Error::new(
-                                ErrorKind::Other,
-                                "Unable to extract filename from path",
-                            ));
-                        }
-                    }
-
-                    // fix decryption key
-                    if block.flags & FILE_FIX_KEY != 0 {
-                        file_key = (file_key + (block.offset)) ^ block.unpacked_size;
-                    }
-                }
+    fn find_hash(&self, file_hash: &FileHash) -> Option<&Hash> {
+        let start_index = (file_hash.index & (self.header.hash_table_count - 1)) as usize;
 
-                // block split into sectors, read sector offsets
-                if block.flags & FILE_SINGLE_UNIT == 0 {
-                    // FixMe: handle empty files, packed and unpacked size should be 0
-
-                    if block.unpacked_size == 0 || self.sector_size == 0 {
-                        return Err(Error::new(ErrorKind::UnexpectedEof, filename));
-                    }
+        self.hash_table[start_index..]
+            .iter()
+            .find(|hash| hash.hash_a == file_hash.hash_a && hash.hash_b == file_hash.hash_b)
+    }
 
-                    let num_sectors = ((block.unpacked_size - 1) / self.sector_size) + 1;
+    pub fn open_file(&mut self, filename: &str) -> Result<File, Error> {
+        let hash = self
+            .find_hash(&FileHash::new(filename))
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, filename))?;
+        let mut file_key = 0;
 
-                    let mut sector_buff: Vec<u8> = vec![0; ((num_sectors as usize) + 1) * 4];
+        let block = &self.block_table[hash.block_index as usize];
+        let mut sector_offsets: Vec<u32> = Vec::new();
+        let mut sector_checksums: Vec<u32> = Vec::new();
 
-                    self.cursor
-                        .seek(SeekFrom::Start(u64::from(block.offset) + self.offset))?;
-                    self.cursor.read_exact(&mut sector_buff)?;
+        // file if encrypted, generate decryption key
+        if block.flags & FILE_ENCRYPTED != 0 {
+            file_key = self::file_key(filename, block.flags, block.offset, block.unpacked_size);
+        }
 
-                    if block.flags & FILE_ENCRYPTED != 0 {
-                        decrypt(&mut sector_buff, file_key - 1);
-                    }
+        // block split into sectors, read sector offsets
+        if block.flags & FILE_SINGLE_UNIT == 0 {
+            // FixMe: handle empty files, packed and unpacked size should be 0
 
-                    let mut x = 0;
-                    while x < sector_buff.len() - 3 {
-                        sector_offsets.push(LittleEndian::read_u32(&sector_buff[x..]));
-                        x += 4;
-                    }
+            if block.unpacked_size == 0 || self.sector_size == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, filename));
+            }
 
-                    // load sector checksums
-                    if block.flags & FILE_COMPRESS != 0 && block.flags & FILE_SECTOR_CRC != 0 {
-                        let mut buff: Vec<u8> = vec![0; 4];
+            let num_sectors = ((block.unpacked_size - 1) / self.sector_size) + 1;
+            let has_checksums = block.flags & FILE_COMPRESS != 0 && block.flags & FILE_SECTOR_CRC != 0;
 
-                        self.cursor.read_exact(&mut buff)?;
+            // with checksums, an additional offset marks the end of the checksum table
+            let num_offsets = num_sectors as usize + if has_checksums { 2 } else { 1 };
+            let mut sector_buff: Vec<u8> = vec![0; num_offsets * 4];
 
-                        let last_offset = LittleEndian::read_u32(&buff);
-                        let checksum_offset = sector_offsets[num_sectors as usize];
-                        let sector_size = last_offset - checksum_offset;
-                        let expected_size = num_sectors * mem::size_of::<u32>() as u32;
+            self.cursor
+                .seek(SeekFrom::Start(u64::from(block.offset) + self.offset))?;
+            self.cursor.read_exact(&mut sector_buff)?;
 
-                        // is checksum sector the expected size
-                        if sector_size == expected_size {
-                            self.cursor.seek(SeekFrom::Start(
-                                u64::from(block.offset) + u64::from(checksum_offset),
-                            ))?;
+            if block.flags & FILE_ENCRYPTED != 0 {
+                decrypt(&mut sector_buff, file_key.wrapping_sub(1));
+            }
 
-                            for _ in 0..num_sectors {
-                                self.cursor.read_exact(&mut buff)?;
+            let mut x = 0;
+            while x < sector_buff.len() - 3 {
+                sector_offsets.push(LittleEndian::read_u32(&sector_buff[x..]));
+                x += 4;
+            }
 
-                                sector_checksums.push(LittleEndian::read_u32(&buff));
-                            }
-                        }
+            // load sector checksums
+            if has_checksums {
+                let checksum_end = sector_offsets.pop().expect("Checksum table end offset");
+                let checksum_offset = sector_offsets[num_sectors as usize];
+                let checksum_size = checksum_end.saturating_sub(checksum_offset) as usize;
+                let expected_size = num_sectors as usize * mem::size_of::<u32>();
+
+                // the table is compressed like a sector, unless that didn't shrink it. Tables of an unexpected
+                // size are ignored, like the client does.
+                if checksum_size > 0 && checksum_size <= expected_size {
+                    let mut buff: Vec<u8> = vec![0; checksum_size];
+
+                    self.cursor.seek(SeekFrom::Start(
+                        u64::from(block.offset) + u64::from(checksum_offset) + self.offset,
+                    ))?;
+                    self.cursor.read_exact(&mut buff)?;
+
+                    if checksum_size < expected_size {
+                        let mut checksums: Vec<u8> = vec![0; expected_size];
+                        expect_size(decompress_into(&mut buff, &mut checksums)?, expected_size)?;
+                        buff = checksums;
                     }
-                }
 
-                return Ok(File {
-                    _name: String::from(filename),
-                    _hash: hash.clone(),
-                    block: block.clone(),
-                    sector_offsets,
-                    sector_checksums,
-                    file_key,
-                });
+                    sector_checksums = buff.chunks_exact(4).map(LittleEndian::read_u32).collect();
+                }
             }
         }
 
-        Err(Error::new(ErrorKind::NotFound, filename))
+        Ok(File {
+            _name: String::from(filename),
+            _hash: hash,
+            block: block.clone(),
+            sector_offsets,
+            sector_checksums,
+            file_key,
+        })
+    }
+
+    /// Reads the `(signature)` file that contains the weak digital signature, if the archive has one. The signature
+    /// itself isn't verified.
+    pub fn signature(&mut self) -> Result<Option<SignatureInfo>, Error> {
+        let file = match self.open_file(SIGNATURE_FILE) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut data: Vec<u8> = vec![0; file.size() as usize];
+        file.read(self, &mut data)?;
+
+        Ok(Some(SignatureInfo {
+            offset: self.offset + u64::from(file.block.offset),
+            data,
+        }))
+    }
+
+    /// The file names of the internal `(listfile)`, in the order in which they are listed. Names are separated by
+    /// line breaks (or semicolons in some older archives) and aren't necessarily UTF-8.
+    pub fn list_files(&mut self) -> Result<Vec<String>, MpqError> {
+        let file = match self.open_file(LISTFILE) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Err(MpqError::MissingListfile),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut data: Vec<u8> = vec![0; file.size() as usize];
+        file.read(self, &mut data)?;
+
+        Ok(String::from_utf8_lossy(&data)
+            .split(['\r', '\n', ';'])
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect())
     }
 
     pub fn read_user_data(&mut self) -> Result<Option<Vec<u8>>, Error> {
@@ -394,6 +575,38 @@ impl fmt::Debug for Archive {
     }
 }
 
+/// See [`Archive::file_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileInfo {
+    /// The index into the block table, which is also used by the `(attributes)` file.
+    pub block_index: usize,
+    /// The absolute position of the file data within the underlying reader.
+    pub offset: u64,
+    pub packed_size: u32,
+    pub unpacked_size: u32,
+}
+
+/// The contents of the `(signature)` file, see [`Archive::signature`].
+#[derive(Debug, Clone)]
+pub struct SignatureInfo {
+    /// The absolute position of the signature file within the underlying reader. Verification hashes the archive
+    /// with this region zeroed out.
+    pub offset: u64,
+    /// The raw contents of the signature file.
+    pub data: Vec<u8>,
+}
+
+impl SignatureInfo {
+    /// The 512-bit RSA signature (little endian), if the file has the size of a weak signature.
+    pub fn weak_signature(&self) -> Option<&[u8]> {
+        if self.data.len() != WEAK_SIGNATURE_HEADER_SIZE + WEAK_SIGNATURE_SIZE {
+            return None;
+        }
+
+        Some(&self.data[WEAK_SIGNATURE_HEADER_SIZE..])
+    }
+}
+
 #[derive(Debug)]
 pub struct File {
     _name: String,
@@ -411,74 +624,42 @@ impl File {
 
     // read data from file
     pub fn read(&self, archive: &mut Archive, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read_checked(archive, buf, false).map_err(Error::from)
+    }
+
+    /// Like [`File::read`], but every sector is validated against the stored sector checksums first. Files without
+    /// checksums (single unit and uncompressed files can't have them) are read as usual.
+    pub fn read_verified(&self, archive: &mut Archive, buf: &mut [u8]) -> Result<usize, MpqError> {
+        self.read_checked(archive, buf, true)
+    }
+
+    fn read_checked(&self, archive: &mut Archive, buf: &mut [u8], verify: bool) -> Result<usize, MpqError> {
         if self.block.flags & FILE_PATCH_FILE != 0 {
-            Err(Error::new(ErrorKind::Other, "Patch file not supported"))
+            Err(Error::new(ErrorKind::Other, "Patch file not supported").into())
         } else if self.block.flags & FILE_SINGLE_UNIT != 0 {
             // file is single block file
-            self.read_single_unit_file(
+            Ok(self.read_single_unit_file(
                 self.block.packed_size as usize,
                 &mut archive.cursor,
                 archive.offset,
                 buf,
-            )
+            )?)
         } else {
             // read as sector based MPQ file
-            self.read_sector_file(archive, buf)
+            self.read_sector_file(archive, buf, verify)
         }
     }
 
-    fn read_sector_file(&self, archive: &mut Archive, out: &mut [u8]) -> Result<usize, Error> {
-        let mut buff: Vec<u8> = vec![0; archive.sector_size as usize];
+    fn read_sector_file(&self, archive: &mut Archive, out: &mut [u8], verify: bool) -> Result<usize, MpqError> {
         let mut read: usize = 0;
 
         if self.block.flags & FILE_COMPRESS_MASK != 0 {
             for i in 0..self.sector_offsets.len() - 1 {
-                let sector_offset = self.sector_offsets[i];
-                let sector_size = self.sector_offsets[i + 1] - sector_offset;
-
-                let in_buf: &mut [u8] = &mut buff[0..sector_size as usize];
-                let out_buf: &mut [u8] = &mut out[read..];
-
-                archive.cursor.seek(SeekFrom::Start(
-                    u64::from(self.block.offset) + u64::from(sector_offset) + archive.offset,
-                ))?;
-
-                archive.cursor.read_exact(in_buf)?;
-
-                if self.block.flags & FILE_ENCRYPTED != 0 {
-                    decrypt(in_buf, self.file_key + i as u32);
-                }
-
-                // checksum verification
-                if !self.sector_checksums.is_empty() && self.sector_checksums[i] != 0 {
-                    let mut adler = RollingAdler32::from_value(0);
-
-                    adler.update_buffer(in_buf);
-
-                    if self.sector_checksums[i] != adler.hash() {
-                        return Err(Error::new(ErrorKind::Other, "Sector checksum error"));
-                    }
-                }
-
-                if self.block.flags & FILE_COMPRESS != 0 {
-                    if in_buf.len() == archive.sector_size as usize || in_buf.len() == out_buf.len() {
-                        for (dst, src) in out_buf.iter_mut().zip(in_buf) {
-                            *dst = *src;
-                            read += 1;
-                        }
-                    } else {
-                        read += decompress(in_buf, out_buf)?;
-                    }
-                } else if self.block.flags & FILE_IMPLODE != 0 {
-                    if in_buf.len() == archive.sector_size as usize || in_buf.len() == out_buf.len() {
-                        for (dst, src) in out_buf.iter_mut().zip(in_buf) {
-                            *dst = *src;
-                            read += 1;
-                        }
-                    } else {
-                        read += explode(in_buf, out_buf)?;
-                    }
-                }
+                let sector_end = out
+                    .len()
+                    .min(self.size() as usize)
+                    .min(read + archive.sector_size as usize);
+                read += self.read_sector(archive, i, &mut out[read..sector_end], verify)?;
             }
         } else {
             archive.cursor.seek(SeekFrom::Start(
@@ -486,12 +667,78 @@ impl File {
             ))?;
             archive.cursor.read_exact(out)?;
 
+            // uncompressed files have no sector offset table, but they are still encrypted per sector
+            if self.block.flags & FILE_ENCRYPTED != 0 {
+                for (i, sector) in out.chunks_mut(archive.sector_size as usize).enumerate() {
+                    decrypt(sector, self.file_key.wrapping_add(i as u32));
+                }
+            }
+
             read = out.len();
         }
 
         Ok(read)
     }
 
+    /// Decodes a single sector of a sector based file, `out` has to be the unpacked size of the sector.
+    fn read_sector(
+        &self,
+        archive: &mut Archive,
+        index: usize,
+        out: &mut [u8],
+        verify: bool,
+    ) -> Result<usize, MpqError> {
+        if self.block.flags & FILE_COMPRESS_MASK == 0 {
+            archive.cursor.seek(SeekFrom::Start(
+                u64::from(self.block.offset) + index as u64 * u64::from(archive.sector_size) + archive.offset,
+            ))?;
+            archive.cursor.read_exact(out)?;
+
+            if self.block.flags & FILE_ENCRYPTED != 0 {
+                decrypt(out, self.file_key.wrapping_add(index as u32));
+            }
+
+            return Ok(out.len());
+        }
+
+        let sector_offset = self.sector_offsets[index];
+        let sector_size = self.sector_offsets[index + 1] - sector_offset;
+        let mut in_buf: Vec<u8> = vec![0; sector_size as usize];
+
+        archive.cursor.seek(SeekFrom::Start(
+            u64::from(self.block.offset) + u64::from(sector_offset) + archive.offset,
+        ))?;
+
+        archive.cursor.read_exact(&mut in_buf)?;
+
+        if self.block.flags & FILE_ENCRYPTED != 0 {
+            decrypt(&mut in_buf, self.file_key.wrapping_add(index as u32));
+        }
+
+        // checksum verification, a checksum of 0 means that there is none
+        let checksum = self.sector_checksums.get(index).copied().unwrap_or(0);
+        if verify && checksum != 0 && checksum != sector_checksum(&in_buf) {
+            return Err(MpqError::SectorChecksumMismatch {
+                sector: index as u32,
+            });
+        }
+
+        // sectors that didn't shrink are stored
+        if in_buf.len() == archive.sector_size as usize || in_buf.len() == out.len() {
+            for (dst, src) in out.iter_mut().zip(&in_buf) {
+                *dst = *src;
+            }
+
+            Ok(in_buf.len().min(out.len()))
+        } else if self.block.flags & FILE_COMPRESS != 0 {
+            Ok(expect_size(decompress_into(&mut in_buf, out)?, out.len())?)
+        } else if self.block.flags & FILE_IMPLODE != 0 {
+            Ok(expect_size(explode(&mut in_buf, out)?, out.len())?)
+        } else {
+            Ok(0)
+        }
+    }
+
     fn read_single_unit_file(
         &self,
         buff_size: usize,
@@ -509,10 +756,12 @@ impl File {
             decrypt(&mut in_buff, self.file_key);
         }
 
+        let expected = out_buf.len().min(self.block.unpacked_size as usize);
+
         if self.block.flags & FILE_COMPRESS != 0 && out_buf.len() > in_buff.len() {
-            decompress(&mut in_buff, out_buf)
+            expect_size(decompress_into(&mut in_buff, out_buf)?, expected)
         } else if self.block.flags & FILE_IMPLODE != 0 {
-            explode(&mut in_buff, out_buf)
+            expect_size(explode(&mut in_buff, out_buf)?, expected)
         } else {
             for (dst, src) in out_buf.iter_mut().zip(&in_buff) {
                 *dst = *src
@@ -522,6 +771,26 @@ impl File {
         }
     }
 
+    // borrow the archive to expose the file through std::io::Read
+    pub fn reader<'a>(&'a self, archive: &'a mut Archive) -> FileReader<'a> {
+        FileReader {
+            file: self,
+            archive,
+            data: None,
+        }
+    }
+
+    /// Turns the file into a stream that decodes sectors lazily, only keeping the current one in memory. In contrast to
+    /// [`File::reader`], the archive can also be passed by value, so the stream can outlive the borrow of the archive.
+    pub fn into_reader<A: BorrowMut<Archive>>(self, archive: A) -> FileStream<A> {
+        FileStream {
+            file: self,
+            archive,
+            position: 0,
+            sector: None,
+        }
+    }
+
     // extract file from archive to the local filesystem
     pub fn extract<P: AsRef<Path>>(&self, archive: &mut Archive, path: P) -> Result<usize, Error> {
         let mut buf: Vec<u8> = vec![0; self.size() as usize];
@@ -543,3 +812,776 @@ impl File {
         file.write(&buf)
     }
 }
+
+/// Adapter that implements [`Read`] for a [`File`], so that the standard combinators (`read_to_end`, `BufReader`,
+/// `io::copy`) can be used. MPQ sectors can't be decompressed into partial buffers, so the whole file is decoded into
+/// memory on the first read and then served from there.
+pub struct FileReader<'a> {
+    file: &'a File,
+    archive: &'a mut Archive,
+    data: Option<Cursor<Vec<u8>>>,
+}
+
+impl Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.data.is_none() {
+            let mut data: Vec<u8> = vec![0; self.file.size() as usize];
+            self.file.read(self.archive, &mut data)?;
+            self.data = Some(Cursor::new(data));
+        }
+
+        self.data.as_mut().unwrap().read(buf)
+    }
+}
+
+/// How the sectors of a file are stored by the [`ArchiveBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// Sectors that don't shrink are stored as is.
+    Zlib,
+}
+
+struct PendingFile {
+    name: String,
+    data: Vec<u8>,
+    compression: Compression,
+    /// `FILE_ENCRYPTED`, optionally with `FILE_FIX_KEY`
+    encryption: u32,
+}
+
+/// Writes MPQ (v1) archives. Files are kept in memory until the archive is written and a `(listfile)` is generated
+/// from the added names, unless one has been added explicitly.
+pub struct ArchiveBuilder {
+    files: Vec<PendingFile>,
+    sector_size_shift: u16,
+    sector_checksums: bool,
+}
+
+impl Default for ArchiveBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> ArchiveBuilder {
+        ArchiveBuilder {
+            files: Vec::new(),
+            // 4 KiB sectors, like the archives shipped with the game
+            sector_size_shift: 3,
+            sector_checksums: false,
+        }
+    }
+
+    /// Stores a checksum table for every compressed file, see [`File::read_verified`].
+    pub fn sector_checksums(&mut self, enabled: bool) -> &mut Self {
+        self.sector_checksums = enabled;
+        self
+    }
+
+    /// Adds a file, replacing a previously added file of the same name in place, i.e. it keeps its position.
+    pub fn add_file(&mut self, name: &str, data: &[u8], compression: Compression) -> &mut Self {
+        self.add(name, data, compression, 0)
+    }
+
+    /// Adds a file that is encrypted with the key derived from its name. With `fix_key`, the key also depends on the
+    /// position of the file within the archive.
+    pub fn add_encrypted_file(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        compression: Compression,
+        fix_key: bool,
+    ) -> &mut Self {
+        let encryption = if fix_key {
+            FILE_ENCRYPTED | FILE_FIX_KEY
+        } else {
+            FILE_ENCRYPTED
+        };
+
+        self.add(name, data, compression, encryption)
+    }
+
+    fn add(&mut self, name: &str, data: &[u8], compression: Compression, encryption: u32) -> &mut Self {
+        let file = PendingFile {
+            name: name.to_string(),
+            data: data.to_vec(),
+            compression,
+            encryption,
+        };
+
+        match self
+            .files
+            .iter_mut()
+            .find(|pending| same_name(&pending.name, name))
+        {
+            Some(existing) => *existing = file,
+            None => self.files.push(file),
+        }
+
+        self
+    }
+
+    /// Writes the archive to the given path, replacing an existing file.
+    pub fn finalize<P: AsRef<Path>>(self, path: P) -> Result<(), Error> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        let sector_size = 512usize << self.sector_size_shift;
+        let listfile = self.listfile();
+        let files: Vec<&PendingFile> = self.files.iter().chain(listfile.as_ref()).collect();
+
+        let mut body: Vec<u8> = Vec::new();
+        let mut block_table: Vec<Block> = Vec::with_capacity(files.len());
+
+        for file in &files {
+            let offset = HEADER_SIZE_V1 + body.len();
+            let (data, flags) = Self::pack(file, sector_size, self.sector_checksums, to_u32(offset)?)?;
+
+            block_table.push(Block {
+                offset: to_u32(offset)?,
+                packed_size: to_u32(data.len())?,
+                unpacked_size: to_u32(file.data.len())?,
+                flags: flags | FILE_EXISTS,
+            });
+            body.extend_from_slice(&data);
+        }
+
+        let hash_table = Self::hash_table(&files);
+        let hash_table_offset = HEADER_SIZE_V1 + body.len();
+        let block_table_offset = hash_table_offset + hash_table.len() * mem::size_of::<Hash>();
+        let archive_size = block_table_offset + block_table.len() * mem::size_of::<Block>();
+
+        let mut header = [0; HEADER_SIZE_V1];
+        header[..4].copy_from_slice(ID_MPQA);
+        LittleEndian::write_u32(&mut header[0x04..], HEADER_SIZE_V1 as u32);
+        LittleEndian::write_u32(&mut header[0x08..], to_u32(archive_size)?);
+        LittleEndian::write_u16(&mut header[0x0C..], 0);
+        LittleEndian::write_u16(&mut header[0x0E..], self.sector_size_shift);
+        LittleEndian::write_u32(&mut header[0x10..], to_u32(hash_table_offset)?);
+        LittleEndian::write_u32(&mut header[0x14..], to_u32(block_table_offset)?);
+        LittleEndian::write_u32(&mut header[0x18..], hash_table.len() as u32);
+        LittleEndian::write_u32(&mut header[0x1C..], block_table.len() as u32);
+
+        let mut hash_buff: Vec<u8> = vec![0; hash_table.len() * mem::size_of::<Hash>()];
+        for (hash, dst) in hash_table
+            .iter()
+            .zip(hash_buff.chunks_exact_mut(mem::size_of::<Hash>()))
+        {
+            hash.write(dst);
+        }
+        encrypt(&mut hash_buff, hash_string("(hash table)", 0x300));
+
+        let mut block_buff: Vec<u8> = vec![0; block_table.len() * mem::size_of::<Block>()];
+        for (block, dst) in block_table
+            .iter()
+            .zip(block_buff.chunks_exact_mut(mem::size_of::<Block>()))
+        {
+            block.write(dst);
+        }
+        encrypt(&mut block_buff, hash_string("(block table)", 0x300));
+
+        out.write_all(&header)?;
+        out.write_all(&body)?;
+        out.write_all(&hash_buff)?;
+        out.write_all(&block_buff)
+    }
+
+    /// The generated listfile, unless one has been added explicitly.
+    fn listfile(&self) -> Option<PendingFile> {
+        if self
+            .files
+            .iter()
+            .any(|file| same_name(&file.name, LISTFILE))
+        {
+            return None;
+        }
+
+        let names: Vec<&str> = self.files.iter().map(|file| file.name.as_str()).collect();
+        Some(PendingFile {
+            name: LISTFILE.to_string(),
+            data: names.join("\r\n").into_bytes(),
+            compression: Compression::Zlib,
+            encryption: 0,
+        })
+    }
+
+    /// Returns the stored data and the block flags of the file.
+    fn pack(
+        file: &PendingFile,
+        sector_size: usize,
+        sector_checksums: bool,
+        offset: u32,
+    ) -> Result<(Vec<u8>, u32), Error> {
+        // The reader can't handle sector files without sectors.
+        if file.data.is_empty() {
+            return Ok((Vec::new(), FILE_SINGLE_UNIT));
+        }
+
+        let encrypted = file.encryption & FILE_ENCRYPTED != 0;
+        let key = file_key(
+            &file.name,
+            file.encryption,
+            offset,
+            to_u32(file.data.len())?,
+        );
+
+        // Uncompressed files don't have a sector offset table.
+        if file.compression == Compression::None {
+            let mut data = file.data.clone();
+            if encrypted {
+                for (i, sector) in data.chunks_mut(sector_size).enumerate() {
+                    encrypt(sector, key.wrapping_add(i as u32));
+                }
+            }
+
+            return Ok((data, file.encryption));
+        }
+
+        let num_sectors = file.data.len().div_ceil(sector_size);
+        let num_offsets = num_sectors + if sector_checksums { 2 } else { 1 };
+        let mut offsets: Vec<u32> = Vec::with_capacity(num_offsets);
+        let mut checksums: Vec<u32> = Vec::with_capacity(num_sectors);
+        let mut sectors: Vec<u8> = Vec::new();
+        let table_size = num_offsets * mem::size_of::<u32>();
+
+        for sector in file.data.chunks(sector_size) {
+            offsets.push(to_u32(table_size + sectors.len())?);
+            let start = sectors.len();
+
+            let compressed = if sector.len() >= MIN_COMPRESS_SIZE {
+                Some(compress_zlib(sector)?)
+            } else {
+                None
+            };
+
+            // The reader treats sectors of the full (remaining) size as stored, so only smaller results can be used.
+            match compressed {
+                Some(compressed) if compressed.len() < sector.len() => sectors.extend_from_slice(&compressed),
+                _ => sectors.extend_from_slice(sector),
+            }
+
+            // the checksums are verified after decrypting
+            checksums.push(sector_checksum(&sectors[start..]));
+            if encrypted {
+                encrypt(
+                    &mut sectors[start..],
+                    key.wrapping_add(checksums.len() as u32 - 1),
+                );
+            }
+        }
+        offsets.push(to_u32(table_size + sectors.len())?);
+
+        let mut flags = FILE_COMPRESS | file.encryption;
+        if sector_checksums {
+            // stored uncompressed, directly behind the last sector
+            let mut table: Vec<u8> = vec![0; checksums.len() * mem::size_of::<u32>()];
+            LittleEndian::write_u32_into(&checksums, &mut table);
+            sectors.extend_from_slice(&table);
+            offsets.push(to_u32(table_size + sectors.len())?);
+            flags |= FILE_SECTOR_CRC;
+        }
+
+        let mut data: Vec<u8> = vec![0; table_size];
+        LittleEndian::write_u32_into(&offsets, &mut data);
+        if encrypted {
+            encrypt(&mut data, key.wrapping_sub(1));
+        }
+        data.extend_from_slice(&sectors);
+
+        Ok((data, flags))
+    }
+
+    /// Builds the hash table, the index of an entry is the index into the block table. The reader doesn't wrap around
+    /// when probing, so the table grows until every file fits between its start index and the end of the table.
+    fn hash_table(files: &[&PendingFile]) -> Vec<Hash> {
+        let empty = Hash {
+            hash_a: 0xFFFFFFFF,
+            hash_b: 0xFFFFFFFF,
+            _locale: 0xFFFF,
+            _platform: 0xFFFF,
+            block_index: 0xFFFFFFFF,
+        };
+
+        let mut count = (files.len() * 2).max(16).next_power_of_two();
+
+        'grow: loop {
+            let mut table = vec![empty.clone(); count];
+
+            for (block_index, file) in files.iter().enumerate() {
+                let start_index = (hash_string(&file.name, 0x0) as usize) & (count - 1);
+
+                match table[start_index..]
+                    .iter_mut()
+                    .find(|hash| hash.block_index == 0xFFFFFFFF)
+                {
+                    Some(hash) => {
+                        *hash = Hash {
+                            hash_a: hash_string(&file.name, 0x100),
+                            hash_b: hash_string(&file.name, 0x200),
+                            _locale: 0,
+                            _platform: 0,
+                            block_index: block_index as u32,
+                        }
+                    }
+                    None => {
+                        count *= 2;
+                        continue 'grow;
+                    }
+                }
+            }
+
+            return table;
+        }
+    }
+}
+
+/// The key of an encrypted file is derived from its name without the path. With `FILE_FIX_KEY`, it also depends on the
+/// position of the block within the archive.
+fn file_key(filename: &str, flags: u32, block_offset: u32, unpacked_size: u32) -> u32 {
+    let basename = filename.rsplit(&['\\', '/'][..]).next().unwrap_or(filename);
+    let key = hash_string(basename, 0x300);
+
+    if flags & FILE_FIX_KEY != 0 {
+        key.wrapping_add(block_offset) ^ unpacked_size
+    } else {
+        key
+    }
+}
+
+/// The Adler32 checksum of the stored (i.e. compressed) sector data, MPQs start with 0 instead of 1.
+fn sector_checksum(data: &[u8]) -> u32 {
+    let mut adler = RollingAdler32::from_value(0);
+    adler.update_buffer(data);
+    adler.hash()
+}
+
+/// Decompressed data that is shorter than the stored size is corrupt (or uses an unknown compression).
+fn expect_size(decompressed: usize, expected: usize) -> Result<usize, Error> {
+    if decompressed != expected {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Decompressed {} bytes, but expected {}",
+                decompressed, expected
+            ),
+        ));
+    }
+
+    Ok(decompressed)
+}
+
+/// MPQ file names are case insensitive and treat both slashes the same.
+fn same_name(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.chars().zip(b.chars()).all(|(a, b)| {
+            let normalize = |c: char| {
+                if c == '/' {
+                    '\\'
+                } else {
+                    c.to_ascii_uppercase()
+                }
+            };
+            normalize(a) == normalize(b)
+        })
+}
+
+fn to_u32(value: usize) -> Result<u32, Error> {
+    u32::try_from(value).map_err(|_| Error::new(ErrorKind::InvalidInput, "Archive exceeds 4 GiB"))
+}
+
+/// A [`Read`] + [`Seek`] view of a [`File`], see [`File::into_reader`]. Seeking is free, the sector that contains the
+/// position is decoded on the next read and cached until the position leaves it.
+pub struct FileStream<A: BorrowMut<Archive>> {
+    file: File,
+    archive: A,
+    position: u64,
+    /// The index of the cached sector and its decoded data
+    sector: Option<(usize, Vec<u8>)>,
+}
+
+impl<A: BorrowMut<Archive>> FileStream<A> {
+    pub fn into_inner(self) -> (File, A) {
+        (self.file, self.archive)
+    }
+
+    /// Single unit files are treated as a single sector.
+    fn sector_size(&self) -> u64 {
+        if self.file.block.flags & FILE_SINGLE_UNIT != 0 {
+            u64::from(self.file.size())
+        } else {
+            u64::from(self.archive.borrow().sector_size)
+        }
+    }
+
+    fn load_sector(&mut self, index: usize, sector_size: u64) -> Result<&[u8], Error> {
+        if !matches!(&self.sector, Some((cached, _)) if *cached == index) {
+            let start = index as u64 * sector_size;
+            let len = sector_size.min(u64::from(self.file.size()) - start) as usize;
+
+            // reuse the allocation of the previous sector
+            let mut data = self.sector.take().map(|(_, data)| data).unwrap_or_default();
+            data.clear();
+            data.resize(len, 0);
+
+            let archive = self.archive.borrow_mut();
+            if self.file.block.flags & FILE_SINGLE_UNIT != 0 {
+                self.file.read_single_unit_file(
+                    self.file.block.packed_size as usize,
+                    &mut archive.cursor,
+                    archive.offset,
+                    &mut data,
+                )?;
+            } else {
+                self.file.read_sector(archive, index, &mut data, false)?;
+            }
+
+            self.sector = Some((index, data));
+        }
+
+        Ok(&self.sector.as_ref().expect("Sector to be loaded").1)
+    }
+}
+
+impl<A: BorrowMut<Archive>> Read for FileStream<A> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() || self.position >= u64::from(self.file.size()) {
+            return Ok(0);
+        }
+
+        let sector_size = self.sector_size();
+        let index = (self.position / sector_size) as usize;
+        let offset = (self.position % sector_size) as usize;
+
+        let data = self.load_sector(index, sector_size)?;
+        let len = buf.len().min(data.len() - offset);
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<A: BorrowMut<Archive>> Seek for FileStream<A> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => u64::from(self.file.size()).checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Archive, ArchiveBuilder, Compression, FileHash, MpqError};
+    use std::io::{Read, Seek, SeekFrom};
+
+    /// Generated by `benches/fixtures/generate.py`.
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/fixtures/bench.mpq");
+
+    /// Writes the archive to a temporary file, which is removed again once it has been opened. The tag keeps the
+    /// files of tests that run in parallel apart.
+    fn build_archive(builder: ArchiveBuilder, tag: &str) -> Archive {
+        let path = std::env::temp_dir().join(format!("mpq-{}-{}.mpq", tag, std::process::id()));
+        builder.finalize(&path).expect("Archive to be written");
+        let archive = Archive::open_owned(&path).expect("Written archive to open");
+        std::fs::remove_file(&path).unwrap();
+        archive
+    }
+
+    fn read(archive: &mut Archive, path: &str) -> Vec<u8> {
+        let file = archive.open_file(path).expect("Fixture file to exist");
+        let mut buf = vec![0; file.size() as usize];
+        file.read(archive, &mut buf)
+            .expect("Fixture file to be readable");
+        buf
+    }
+
+    #[test]
+    fn encrypted_fix_key() {
+        let mut archive = Archive::open_owned(FIXTURE).expect("Fixture archive to open");
+        let plain = read(&mut archive, "bench\\stored.bin");
+
+        for path in ["bench\\encrypted.bin", "bench\\encrypted_stored.bin"] {
+            let decrypted = read(&mut archive, path);
+            assert_eq!(decrypted.len(), 10001, "{}", path);
+            assert!(decrypted == plain[..decrypted.len()], "{} differs", path);
+        }
+    }
+
+    #[test]
+    fn concurrent_reads() {
+        let archive = Archive::open(FIXTURE).expect("Fixture archive to open");
+        let plain = archive.read_file("bench\\stored.bin").unwrap();
+
+        std::thread::scope(|scope| {
+            for path in ["bench\\zlib.bin", "bench\\bzip2.bin", "bench\\stored.bin"] {
+                let archive = &archive;
+                let plain = &plain;
+                scope.spawn(move || {
+                    for _ in 0..4 {
+                        assert!(
+                            &archive.read_file(path).unwrap() == plain,
+                            "{} differs",
+                            path
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn read_file_reuses_readers() {
+        let archive = Archive::open(FIXTURE).expect("Fixture archive to open");
+        for _ in 0..3 {
+            archive.read_file("bench\\stored.bin").unwrap();
+        }
+        assert_eq!(archive.idle_readers.lock().unwrap().len(), 1);
+
+        assert!(archive.read_file("bench\\missing.bin").is_err());
+        assert_eq!(archive.idle_readers.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn compressed_sectors() {
+        let mut archive = Archive::open_owned(FIXTURE).expect("Fixture archive to open");
+        let plain = read(&mut archive, "bench\\stored.bin");
+
+        for path in ["bench\\zlib.bin", "bench\\bzip2.bin", "bench\\pkware.bin"] {
+            assert!(read(&mut archive, path) == plain, "{} differs", path);
+        }
+    }
+
+    #[test]
+    fn list_files() {
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("a.txt", b"a", Compression::None);
+        builder.add_file(
+            "(listfile)",
+            b"a.txt;b.txt\r\n\r\nDIR/c.bin\n",
+            Compression::None,
+        );
+
+        let mut archive = build_archive(builder, "listfile");
+
+        assert_eq!(
+            archive.list_files().expect("Listfile to be readable"),
+            ["a.txt", "b.txt", "DIR/c.bin"]
+        );
+    }
+
+    #[test]
+    fn unsigned_signature() {
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("a.txt", b"a", Compression::None);
+
+        let mut archive = build_archive(builder, "signature");
+
+        assert!(
+            archive
+                .signature()
+                .expect("Archive to be readable")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn file_crcs() {
+        let mut attributes = vec![0x64, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00];
+        for crc in [0x11111111u32, 0x22222222, 0x33333333] {
+            attributes.extend(crc.to_le_bytes());
+        }
+
+        let mut builder = ArchiveBuilder::new();
+        builder
+            .add_file("a.txt", b"abc", Compression::None)
+            .add_encrypted_file("(attributes)", &attributes, Compression::None, false);
+
+        let archive = build_archive(builder, "attributes");
+
+        let info = archive.file_info(&FileHash::new("A.TXT")).unwrap();
+        assert_eq!(info.unpacked_size, 3);
+        assert_eq!(archive.file_crcs().unwrap()[info.block_index], 0x11111111);
+        assert!(archive.file_info(&FileHash::new("missing.bin")).is_none());
+    }
+
+    #[test]
+    fn builder_roundtrip() {
+        let compressible: Vec<u8> = (0..10000u32).map(|i| (i % 13) as u8).collect();
+        // a simple LCG, so zlib can't shrink it
+        let mut state: u32 = 1;
+        let noise: Vec<u8> = (0..5000)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect();
+
+        let files: [(&str, &[u8], Compression); 5] = [
+            ("empty.bin", &[], Compression::Zlib),
+            ("small.txt", b"tiny", Compression::Zlib),
+            ("dir\\compressible.bin", &compressible, Compression::Zlib),
+            ("dir\\noise.bin", &noise, Compression::Zlib),
+            ("stored.bin", &compressible, Compression::None),
+        ];
+
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("small.txt", b"replaced", Compression::None);
+        for (name, data, compression) in files {
+            builder.add_file(name, data, compression);
+        }
+
+        let mut archive = build_archive(builder, "builder");
+
+        for (name, data, _) in files {
+            assert_eq!(read(&mut archive, name), data, "{}", name);
+        }
+        assert_eq!(read(&mut archive, "DIR/NOISE.BIN"), noise);
+        assert!(!archive.contains_file("missing.bin"));
+
+        // re-adding a file replaces it in place, so small.txt keeps the position of its first add
+        let listfile = read(&mut archive, "(listfile)");
+        let names: Vec<&str> = std::str::from_utf8(&listfile).unwrap().lines().collect();
+        assert_eq!(
+            names,
+            [
+                "small.txt",
+                "empty.bin",
+                "dir\\compressible.bin",
+                "dir\\noise.bin",
+                "stored.bin"
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_seek() {
+        let plain: Vec<u8> = (0..20000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("compressed.bin", &plain, Compression::Zlib);
+        builder.add_file("stored.bin", &plain, Compression::None);
+
+        let mut archive = build_archive(builder, "stream");
+
+        for name in ["compressed.bin", "stored.bin", "(listfile)"] {
+            let expected = read(&mut archive, name);
+            let file = archive.open_file(name).unwrap();
+            let mut stream = file.into_reader(&mut archive);
+
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf, expected, "{}", name);
+
+            // across a sector boundary
+            let mut buf = [0; 100];
+            let len = buf.len().min(expected.len());
+            let start = 4096usize.saturating_sub(50).min(expected.len() - len);
+            stream.seek(SeekFrom::Start(start as u64)).unwrap();
+            stream.read_exact(&mut buf[..len]).unwrap();
+            assert_eq!(buf[..len], expected[start..start + len], "{}", name);
+
+            assert_eq!(
+                stream.seek(SeekFrom::End(-1)).unwrap(),
+                expected.len() as u64 - 1
+            );
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf, expected[expected.len() - 1..], "{}", name);
+            assert!(
+                stream
+                    .seek(SeekFrom::Current(-(expected.len() as i64) - 1))
+                    .is_err()
+            );
+        }
+
+        // encrypted files are decrypted per sector
+        let mut archive = Archive::open_owned(FIXTURE).expect("Fixture archive to open");
+        let expected = read(&mut archive, "bench\\encrypted.bin");
+        let file = archive.open_file("bench\\encrypted.bin").unwrap();
+        let mut stream = file.into_reader(archive);
+        stream.seek(SeekFrom::Start(5000)).unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, expected[5000..]);
+    }
+
+    #[test]
+    fn sector_checksums() {
+        let plain: Vec<u8> = (0..10000u32).map(|i| (i % 13) as u8).collect();
+
+        let mut builder = ArchiveBuilder::new();
+        builder
+            .sector_checksums(true)
+            .add_file("checked.bin", &plain, Compression::Zlib);
+
+        let mut archive = build_archive(builder, "checksums");
+
+        let mut file = archive.open_file("checked.bin").unwrap();
+        assert_eq!(file.sector_checksums.len(), 3);
+
+        let mut buf = vec![0; plain.len()];
+        file.read_verified(&mut archive, &mut buf).unwrap();
+        assert_eq!(buf, plain);
+
+        // corrupt the stored checksum instead of the archive
+        file.sector_checksums[1] ^= 1;
+        match file.read_verified(&mut archive, &mut buf) {
+            Err(MpqError::SectorChecksumMismatch { sector }) => assert_eq!(sector, 1),
+            other => panic!("Expected a checksum mismatch, got {:?}", other),
+        }
+
+        // reading without verification doesn't look at the checksums
+        let mut buf = vec![0; plain.len()];
+        file.read(&mut archive, &mut buf).unwrap();
+        assert_eq!(buf, plain);
+    }
+
+    #[test]
+    fn encrypted_files() {
+        // the header of the (attributes) file: version 100 and the CRC32 + file time flags
+        let mut attributes = vec![0x64, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00];
+        attributes.extend((0..600u32).map(|i| (i % 5) as u8));
+        let plain: Vec<u8> = (0..10000u32).map(|i| (i % 13) as u8).collect();
+
+        let mut builder = ArchiveBuilder::new();
+        builder
+            .sector_checksums(true)
+            .add_encrypted_file("(attributes)", &attributes, Compression::Zlib, false)
+            .add_encrypted_file("dir\\fix_key.bin", &plain, Compression::Zlib, true)
+            .add_encrypted_file("dir\\stored.bin", &plain, Compression::None, false);
+
+        let mut archive = build_archive(builder, "encrypted");
+
+        let decrypted = read(&mut archive, "(attributes)");
+        assert_eq!(
+            decrypted[..8],
+            [0x64, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(decrypted, attributes);
+
+        for name in ["dir\\fix_key.bin", "dir/stored.bin"] {
+            let file = archive.open_file(name).unwrap();
+            let mut buf = vec![0; file.size() as usize];
+            file.read_verified(&mut archive, &mut buf).unwrap();
+            assert!(buf == plain, "{} differs", name);
+        }
+    }
+}
@@ -3,6 +3,7 @@ use std::io::{Error, ErrorKind};
 use std::path::Path;
 
 use crate::archive::Archive;
+use crate::error::MpqError;
 
 #[derive(Default)]
 pub struct Chain {
@@ -76,6 +77,36 @@ impl Chain {
         Ok(contents.into_iter().collect::<Vec<String>>())
     }
 
+    /// The file names of all archives, see [`Archive::list_files`]. Names that are listed by multiple archives (in any
+    /// casing) are only returned once, as spelled by the archive with the highest priority, i.e. the one added last.
+    /// Archives without a listfile are skipped, but if none has one, [`MpqError::MissingListfile`] is returned.
+    pub fn list_files(&mut self) -> Result<Vec<String>, MpqError> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut names = Vec::new();
+        let mut any_listfile = false;
+
+        for archive in &mut self.chain {
+            let archive_names = match archive.list_files() {
+                Ok(archive_names) => archive_names,
+                Err(MpqError::MissingListfile) => continue,
+                Err(err) => return Err(err),
+            };
+
+            any_listfile = true;
+            for name in archive_names {
+                if seen.insert(name.replace('/', "\\").to_ascii_uppercase()) {
+                    names.push(name);
+                }
+            }
+        }
+
+        if !any_listfile {
+            return Err(MpqError::MissingListfile);
+        }
+
+        Ok(names)
+    }
+
     pub fn read_to_string(&mut self, filename: &str) -> Result<String, Error> {
         match self.read(filename) {
             Ok(buf) => match String::from_utf8(buf) {
@@ -103,3 +134,36 @@ impl Chain {
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Chain;
+    use crate::{ArchiveBuilder, Compression};
+
+    #[test]
+    fn list_files_prefers_the_last_added_archive() {
+        let mut chain = Chain::new();
+        let mut paths = Vec::new();
+        for (index, names) in [["common.txt", "base.txt"], ["COMMON.TXT", "patch.txt"]]
+            .iter()
+            .enumerate()
+        {
+            let mut builder = ArchiveBuilder::new();
+            for name in names {
+                builder.add_file(name, name.as_bytes(), Compression::None);
+            }
+
+            let path = std::env::temp_dir().join(format!("mpq-chain-{}-{}.mpq", std::process::id(), index));
+            builder.finalize(&path).expect("Archive to be written");
+            chain.add(&path).expect("Written archive to open");
+            paths.push(path);
+        }
+
+        let names = chain.list_files().expect("Listfiles to be readable");
+        for path in paths {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(names, ["COMMON.TXT", "patch.txt", "base.txt"]);
+    }
+}
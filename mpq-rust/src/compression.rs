@@ -1,7 +1,12 @@
+//! The MPQ compression schemes, which are identified by a leading compression mask byte.
+
 use bzip2_rs as bzip2;
 use implode::exploder::Exploder;
 use implode::symbol::DEFAULT_CODE_TABLE;
-use std::io::{self, Error, ErrorKind};
+use std::io::{self, Error, ErrorKind, Write};
+
+mod adpcm;
+mod huffman;
 
 const COMPRESSION_HUFFMAN: u8 = 0x01;
 const COMPRESSION_ZLIB: u8 = 0x02;
@@ -12,56 +17,44 @@ const COMPRESSION_ADPCM_MONO: u8 = 0x40;
 const COMPRESSION_ADPCM_STEREO: u8 = 0x80;
 const COMPRESSION_LZMA: u8 = 0x12;
 
-pub fn decompress(data: &mut [u8], out: &mut [u8]) -> Result<usize, Error> {
-    let compression_type = data[0];
-
-    if compression_type & COMPRESSION_BZIP2 != 0 {
-        let mut ouput = io::Cursor::new(out);
-        let mut reader = bzip2::DecoderReader::new(&data[1..]);
-        io::copy(&mut reader, &mut ouput)?;
-        return Ok(ouput.position() as usize);
+/// Decompresses a single MPQ sector (or single unit file) that starts with the compression mask byte. Data that is
+/// exactly `expected_size` bytes long is considered to be stored uncompressed, just like the archive reader does.
+/// The result is truncated to the amount of bytes that were actually decompressed.
+pub fn decompress(data: &[u8], expected_size: usize) -> Result<Vec<u8>, Error> {
+    if data.len() == expected_size {
+        return Ok(data.to_vec());
     }
 
-    if compression_type & COMPRESSION_ZLIB != 0 {
-        let mut zlib = flate2::Decompress::new(true);
-
-        match zlib.decompress(&data[1..], out, flate2::FlushDecompress::None) {
-            Ok(_) => {}
-            Err(e) => return Err(Error::new(ErrorKind::Other, e)),
-        }
-
-        return Ok(zlib.total_out() as usize);
-    }
-
-    if compression_type & COMPRESSION_PKWARE != 0 {
-        let mut exploder = Exploder::new(&DEFAULT_CODE_TABLE);
-
-        let mut cpos: u32 = 1;
-        let len = data.len();
-        let mut c = 0;
-
-        while !exploder.ended {
-            let abuf = &mut data[cpos as usize..len];
-
-            let x = exploder.explode_block(abuf).unwrap();
-
-            cpos += x.0 as u32;
-
-            let bf = x.1;
+    // The exploder works in-place
+    let mut data = data.to_vec();
+    let mut out = vec![0; expected_size];
+    let size = decompress_into(&mut data, &mut out)?;
+    out.truncate(size);
+    Ok(out)
+}
 
-            for (d, s) in out.iter_mut().zip(bf.iter()) {
-                *d = *s;
-                c += 1;
-            }
-        }
+/// Zlib compresses a sector, prefixed by the compression mask byte.
+pub(crate) fn compress_zlib(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = flate2::write::ZlibEncoder::new(vec![COMPRESSION_ZLIB], flate2::Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
 
-        return Ok(c);
-    }
+/// Decompresses a sector that starts with the compression mask byte. If multiple compressions have been applied, they
+/// are undone in reverse order, i.e. bzip2, PKWARE DCL, zlib, Huffman and then ADPCM.
+pub(crate) fn decompress_into(data: &mut [u8], out: &mut [u8]) -> Result<usize, Error> {
+    let Some(&compression_type) = data.first() else {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "Missing compression type",
+        ));
+    };
 
-    if compression_type & COMPRESSION_HUFFMAN != 0 {
+    // LZMA is not a flag, its mask overlaps with bzip2 and zlib
+    if compression_type == COMPRESSION_LZMA {
         return Err(Error::new(
             ErrorKind::Other,
-            "Compression algorithm Huffman not supported",
+            "Compression algorithm LZMA not supported",
         ));
     }
 
@@ -72,51 +65,206 @@ pub fn decompress(data: &mut [u8], out: &mut [u8]) -> Result<usize, Error> {
         ));
     }
 
-    if compression_type & COMPRESSION_ADPCM_STEREO != 0 {
-        return Err(Error::new(
-            ErrorKind::Other,
-            "Compression algorithm ADPCM Stereo not supported",
-        ));
-    }
+    let methods: Vec<u8> = [
+        COMPRESSION_BZIP2,
+        COMPRESSION_PKWARE,
+        COMPRESSION_ZLIB,
+        COMPRESSION_HUFFMAN,
+        COMPRESSION_ADPCM_STEREO,
+        COMPRESSION_ADPCM_MONO,
+    ]
+    .iter()
+    .copied()
+    .filter(|method| compression_type & method != 0)
+    .collect();
 
-    if compression_type & COMPRESSION_ADPCM_MONO != 0 {
-        return Err(Error::new(
-            ErrorKind::Other,
-            "Compression algorithm ADPCM Stereo not supported",
-        ));
-    }
+    match methods.split_last() {
+        None => Err(Error::new(ErrorKind::Other, "No compression type found")),
+        Some((&method, [])) => decompress_with(method, &mut data[1..], out),
+        Some((&last, rest)) => {
+            // The intermediate results are never larger than the output
+            let mut buffer = data[1..].to_vec();
+            for &method in rest {
+                let mut next = vec![0; out.len()];
+                let size = decompress_with(method, &mut buffer, &mut next)?;
+                next.truncate(size);
+                buffer = next;
+            }
 
-    if compression_type & COMPRESSION_LZMA != 0 {
-        return Err(Error::new(
-            ErrorKind::Other,
-            "Compression algorithm LZMA not supported",
-        ));
+            decompress_with(last, &mut buffer, out)
+        }
     }
+}
 
-    Err(Error::new(ErrorKind::Other, "No compression type found"))
+fn decompress_with(method: u8, data: &mut [u8], out: &mut [u8]) -> Result<usize, Error> {
+    match method {
+        COMPRESSION_BZIP2 => {
+            let mut output = io::Cursor::new(out);
+            let mut reader = bzip2::DecoderReader::new(&*data);
+            io::copy(&mut reader, &mut output)?;
+            Ok(output.position() as usize)
+        }
+        COMPRESSION_PKWARE => explode(data, out),
+        COMPRESSION_HUFFMAN => huffman::decompress(data, out),
+        COMPRESSION_ADPCM_STEREO => adpcm::decompress(data, out, 2),
+        COMPRESSION_ADPCM_MONO => adpcm::decompress(data, out, 1),
+        COMPRESSION_ZLIB => {
+            let mut zlib = flate2::Decompress::new(true);
+
+            match zlib.decompress(data, out, flate2::FlushDecompress::None) {
+                Ok(_) => {}
+                Err(e) => return Err(Error::new(ErrorKind::Other, e)),
+            }
+
+            Ok(zlib.total_out() as usize)
+        }
+        _ => unreachable!("Only called with supported methods"),
+    }
 }
 
-pub fn explode(data: &mut [u8], out: &mut [u8]) -> Result<usize, Error> {
+/// Decompresses PKWARE DCL (implode) data, which is used both with the compression mask and the legacy implode flag.
+pub(crate) fn explode(data: &mut [u8], out: &mut [u8]) -> Result<usize, Error> {
     let mut exploder = Exploder::new(&DEFAULT_CODE_TABLE);
 
-    let mut cpos: u32 = 0;
+    let mut cpos: usize = 0;
     let len = data.len();
     let mut c = 0;
 
     while !exploder.ended {
-        let abuf = &mut data[cpos as usize..len];
+        if cpos >= len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Truncated PKWARE DCL data",
+            ));
+        }
 
-        let x = exploder.explode_block(abuf).unwrap();
+        let abuf = &mut data[cpos..len];
 
-        cpos += x.0 as u32;
+        let x = match exploder.explode_block(abuf) {
+            Ok(x) => x,
+            Err(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Invalid PKWARE DCL data",
+                ));
+            }
+        };
+
+        cpos += x.0;
 
         let bf = x.1;
 
-        for (d, s) in out.iter_mut().zip(bf.iter()) {
-            *d = *s;
-            c += 1;
+        if c + bf.len() > out.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "PKWARE DCL data exceeds the expected size",
+            ));
         }
+
+        out[c..c + bf.len()].copy_from_slice(bf);
+        c += bf.len();
     }
 
     Ok(c)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{COMPRESSION_BZIP2, COMPRESSION_LZMA, COMPRESSION_PKWARE, compress_zlib, decompress};
+    use byteorder::{ByteOrder, LittleEndian};
+
+    const BZIP2_PLAIN: &[u8] = b"Sargerust reads bzip2 compressed sectors. ";
+
+    /// `BZIP2_PLAIN` repeated 4 times, compressed with Python's `bz2.compress(data, 9)`.
+    const BZIP2_SECTOR: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xc6, 0xfd, 0x9a, 0x5c, 0x00, 0x00, 0x0f, 0x9b,
+        0x80, 0x40, 0x01, 0x10, 0x00, 0x08, 0x00, 0x3e, 0xa2, 0xde, 0x10, 0x20, 0x00, 0x70, 0x53, 0x26, 0x26, 0x41,
+        0x91, 0x81, 0x55, 0x47, 0xa8, 0x68, 0xd3, 0x13, 0x09, 0xb9, 0x73, 0x83, 0x04, 0xce, 0x0b, 0x92, 0x28, 0x78,
+        0x40, 0x81, 0x33, 0x04, 0x85, 0x88, 0x1a, 0x32, 0x5c, 0xa9, 0x83, 0x25, 0x0b, 0x14, 0x3b, 0x2a, 0x7c, 0x50,
+        0x91, 0x63, 0x93, 0x44, 0x15, 0x3a, 0x34, 0x7e, 0x2e, 0xe4, 0x8a, 0x70, 0xa1, 0x21, 0x8d, 0xfb, 0x34, 0xb8,
+    ];
+
+    #[test]
+    fn decompress_zlib() {
+        let plain: Vec<u8> = (0..4096u32).map(|i| (i % 7) as u8).collect();
+        let packed = compress_zlib(&plain).unwrap();
+
+        assert_eq!(plain, decompress(&packed, plain.len()).unwrap());
+        // stored data is passed through
+        assert_eq!(plain, decompress(&plain, plain.len()).unwrap());
+        assert!(decompress(&[], 16).is_err());
+        assert!(decompress(&[0x00, 0x01, 0x02], 16).is_err());
+    }
+
+    #[test]
+    fn decompress_bzip2() {
+        let plain = BZIP2_PLAIN.repeat(4);
+        let mut packed = vec![COMPRESSION_BZIP2];
+        packed.extend_from_slice(BZIP2_SECTOR);
+
+        assert_eq!(plain, decompress(&packed, plain.len()).unwrap());
+        // the output can't hold the whole sector
+        assert!(decompress(&packed, plain.len() - 1).is_err());
+    }
+
+    #[test]
+    fn decompress_pkware() {
+        // binary mode with a 1 KiB dictionary: two literals, a match of length 11 and the end code
+        let packed = [
+            COMPRESSION_PKWARE,
+            0x00,
+            0x04,
+            0x82,
+            0x24,
+            0x25,
+            0x8f,
+            0x80,
+            0x7f,
+        ];
+
+        assert_eq!(b"AIAIAIAIAIAIA", &decompress(&packed, 13).unwrap()[..]);
+        // the data ends before the end code
+        assert!(decompress(&packed[..6], 13).is_err());
+    }
+
+    #[test]
+    fn decompress_huffman_adpcm() {
+        fn samples(packed: &[u8], count: usize) -> Vec<i16> {
+            decompress(packed, count * 2)
+                .unwrap()
+                .chunks_exact(2)
+                .map(LittleEndian::read_i16)
+                .collect()
+        }
+
+        // The ADPCM data of the ADPCM tests, Huffman compressed with the compression type 7 (which belongs to the ADPCM
+        // bit shift 4). The initial samples and 0x3F/0x7F aren't part of the weight table and are added on the fly.
+        let mono = [
+            0x41, 0x07, 0x19, 0x72, 0x29, 0x10, 0xbf, 0xeb, 0x3b, 0xfd, 0xd3, 0x69, 0x7f, 0xad, 0x9f, 0x00,
+        ];
+        let stereo = [
+            0x81, 0x07, 0x19, 0x72, 0x29, 0x10, 0xb9, 0xd3, 0xf0, 0xbf, 0x2b, 0xfb, 0x4e, 0x13, 0xe9, 0xfa, 0xf9, 0xef,
+            0xdb, 0x03,
+        ];
+
+        assert_eq!(
+            samples(&mono, 7),
+            [4096, 4743, 3959, 3959, 5294, 2430, 5643]
+        );
+        assert_eq!(
+            samples(&stereo, 8),
+            [4096, -4096, 4743, -4743, 4743, -4389, 4743, -5494]
+        );
+        // the Huffman data ends before the end symbol
+        assert!(decompress(&mono[..8], 14).is_err());
+    }
+
+    #[test]
+    fn lzma_is_not_bzip2() {
+        let mut packed = vec![COMPRESSION_LZMA];
+        packed.extend_from_slice(BZIP2_SECTOR);
+
+        let err = decompress(&packed, 168).unwrap_err();
+        assert!(err.to_string().contains("LZMA"), "{}", err);
+    }
+}
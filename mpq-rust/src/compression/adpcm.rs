@@ -0,0 +1,164 @@
+//! The IMA ADPCM variant of the MPQ format (as implemented by StormLib), which is used for WAV files. The output is
+//! 16-bit little endian PCM, with the samples of multiple channels interleaved.
+
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::{Error, ErrorKind};
+
+const MAX_CHANNELS: usize = 2;
+const INITIAL_STEP_INDEX: usize = 0x2C;
+const MAX_STEP_INDEX: usize = 0x58;
+
+/// Repeats the previous sample and lowers the step index
+const COMMAND_REPEAT: u8 = 0x80;
+/// Raises the step index without emitting a sample
+const COMMAND_STEP_UP: u8 = 0x81;
+
+const NEXT_STEP_TABLE: [i8; 32] = [
+    -1, 0, -1, 4, -1, 2, -1, 6, -1, 1, -1, 5, -1, 3, -1, 7, -1, 1, -1, 5, -1, 3, -1, 7, -1, 2, -1, 4, -1, 6, -1, 8,
+];
+
+const STEP_SIZE_TABLE: [i32; MAX_STEP_INDEX + 1] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107,
+    118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963,
+    1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894,
+    6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+    32767,
+];
+
+/// Decodes the ADPCM data into `out` and returns the amount of bytes written. Decoding stops once the output is full.
+pub(crate) fn decompress(data: &[u8], out: &mut [u8], channels: usize) -> Result<usize, Error> {
+    debug_assert!((1..=MAX_CHANNELS).contains(&channels));
+
+    // The first byte is always zero, the second one is the bit shift (compression level - 1)
+    let header_size = 2 + channels * 2;
+    if data.len() < header_size {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "Truncated ADPCM header",
+        ));
+    }
+
+    let bit_shift = u32::from(data[1]);
+    let mut step_indices = [INITIAL_STEP_INDEX; MAX_CHANNELS];
+    let mut predicted = [0i32; MAX_CHANNELS];
+    let mut written = 0;
+
+    for (channel, sample) in data[2..header_size].chunks_exact(2).enumerate() {
+        predicted[channel] = i32::from(LittleEndian::read_i16(sample));
+        if !write_sample(out, &mut written, predicted[channel]) {
+            return Ok(written);
+        }
+    }
+
+    let mut channel = channels - 1;
+    for &encoded in &data[header_size..] {
+        channel = (channel + 1) % channels;
+
+        match encoded {
+            COMMAND_REPEAT => {
+                step_indices[channel] = step_indices[channel].saturating_sub(1);
+
+                if !write_sample(out, &mut written, predicted[channel]) {
+                    break;
+                }
+            }
+            COMMAND_STEP_UP => {
+                step_indices[channel] = (step_indices[channel] + 8).min(MAX_STEP_INDEX);
+
+                // the next sample belongs to the same channel
+                channel = (channel + 1) % channels;
+            }
+            _ => {
+                let step_index = step_indices[channel];
+                predicted[channel] = decode_sample(
+                    predicted[channel],
+                    encoded,
+                    STEP_SIZE_TABLE[step_index],
+                    bit_shift,
+                );
+
+                if !write_sample(out, &mut written, predicted[channel]) {
+                    break;
+                }
+
+                let next = step_index as i32 + i32::from(NEXT_STEP_TABLE[(encoded & 0x1F) as usize]);
+                step_indices[channel] = next.clamp(0, MAX_STEP_INDEX as i32) as usize;
+            }
+        }
+    }
+
+    Ok(written)
+}
+
+/// Returns false if the output is full.
+fn write_sample(out: &mut [u8], written: &mut usize, sample: i32) -> bool {
+    if *written + 2 > out.len() {
+        return false;
+    }
+
+    LittleEndian::write_i16(&mut out[*written..], sample as i16);
+    *written += 2;
+    true
+}
+
+fn decode_sample(predicted: i32, encoded: u8, step_size: i32, bit_shift: u32) -> i32 {
+    let mut difference = step_size.checked_shr(bit_shift).unwrap_or(0);
+
+    for bit in 0..6 {
+        if encoded & (1 << bit) != 0 {
+            difference += step_size >> bit;
+        }
+    }
+
+    if encoded & 0x40 != 0 {
+        (predicted - difference).max(i32::from(i16::MIN))
+    } else {
+        (predicted + difference).min(i32::from(i16::MAX))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::decompress;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    fn samples(data: &[u8], channels: usize, capacity: usize) -> Vec<i16> {
+        let mut out = vec![0; capacity * 2];
+        let written = decompress(data, &mut out, channels).unwrap();
+        out[..written]
+            .chunks_exact(2)
+            .map(LittleEndian::read_i16)
+            .collect()
+    }
+
+    #[test]
+    fn mono() {
+        // shift 4, initial sample 4096, then deltas, a repeat and deltas with all magnitude bits set
+        let data = [0x00, 0x04, 0x00, 0x10, 0x05, 0x45, 0x80, 0x3f, 0x7f, 0x01];
+
+        assert_eq!(
+            samples(&data, 1, 16),
+            [4096, 4743, 3959, 3959, 5294, 2430, 5643]
+        );
+        // decoding stops once the output is full
+        assert_eq!(samples(&data, 1, 3), [4096, 4743, 3959]);
+    }
+
+    #[test]
+    fn stereo() {
+        // initial samples 4096 and -4096, 0x81 raises the step index of the left channel, which also decodes the next byte
+        let data = [
+            0x00, 0x04, 0x00, 0x10, 0x00, 0xf0, 0x05, 0x45, 0x81, 0x80, 0x22, 0x80, 0x7f,
+        ];
+
+        assert_eq!(
+            samples(&data, 2, 16),
+            [4096, -4096, 4743, -4743, 4743, -4389, 4743, -5494]
+        );
+    }
+
+    #[test]
+    fn truncated_header() {
+        assert!(decompress(&[0x00, 0x04, 0x00], &mut [0; 16], 2).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+//! The adaptive Huffman coding of the MPQ format (as implemented by StormLib), which is applied on top of the ADPCM
+//! compression of WAV files. The tree starts out with the weights of the compression type, which is the first byte of
+//! the data, and grows whenever a byte that is not part of it yet is stored verbatim.
+
+use std::io::{Error, ErrorKind};
+
+/// Ends the data
+const SYMBOL_END: usize = 0x100;
+/// Is followed by 8 bits of a byte that gets added to the tree
+const SYMBOL_NEW: usize = 0x101;
+const SYMBOL_COUNT: usize = 0x102;
+
+/// Every symbol can be a leaf and every leaf but one has a parent, plus the list head.
+const MAX_ITEMS: usize = 2 * SYMBOL_COUNT;
+const HEAD: usize = 0;
+const UNLINKED: usize = usize::MAX;
+
+/// The compression types 6 to 8 are used for WAV files and are picked by the ADPCM compression levels.
+const FIRST_WAVE_TYPE: u8 = 6;
+
+/// The initial weights of the WAVE compression types, which only cover the bytes that the ADPCM compression emits: the
+/// magnitudes at 0x00, the negative magnitudes at 0x40 and the commands at 0x80. Every other byte has no weight.
+const WAVE_WEIGHTS: [[&[u8]; 3]; 3] = [
+    [
+        &[0xC3, 0xCB, 0xF5, 0x41, 0xFF, 0x7B, 0xF7, 0x21, 0x11, 0x09],
+        &[0xBF, 0xCC, 0xF2, 0x40, 0xFD, 0x7C, 0xF7, 0x22, 0x12, 0x0A],
+        &[0x7A, 0x46],
+    ],
+    [
+        &[
+            0xC3, 0xD9, 0xEF, 0x3D, 0xF9, 0x7C, 0xE9, 0x1E, 0xFD, 0xAB, 0xF1, 0x2C, 0xFC, 0x5B, 0xFE, 0x17,
+        ],
+        &[
+            0xBD, 0xD9, 0xEC, 0x3D, 0xF5, 0x7D, 0xE8, 0x1D, 0xFB, 0xAE, 0xF0, 0x2C, 0xFB, 0x5C, 0xFF, 0x18,
+        ],
+        &[0x70, 0x6C],
+    ],
+    [
+        &[
+            0xBA, 0xC5, 0xDA, 0x33, 0xE3, 0x6D, 0xD8, 0x18, 0xE5, 0x94, 0xDA, 0x23, 0xDF, 0x4A, 0xD1, 0x10, 0xEE, 0xAF,
+            0xE4, 0x2C, 0xEA, 0x5A, 0xDE, 0x15, 0xF4, 0x87, 0xE9, 0x21, 0xF6, 0x43, 0xFC, 0x12,
+        ],
+        &[
+            0xB0, 0xC7, 0xD8, 0x33, 0xE3, 0x6B, 0xDC, 0x18, 0xE6, 0x94, 0xE2, 0x23, 0xE1, 0x4C, 0xD5, 0x10, 0xEC, 0xB0,
+            0xE3, 0x2B, 0xEA, 0x5B, 0xDC, 0x14, 0xF3, 0x89, 0xE7, 0x20, 0xF5, 0x44, 0xFD, 0x13,
+        ],
+        &[0x6E, 0x6C],
+    ],
+];
+
+/// Decodes the Huffman data into `out` and returns the amount of bytes written. Decoding stops once the output is full.
+pub(crate) fn decompress(data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut bits = BitReader::new(data);
+    let compression_type = bits.read_byte()?;
+
+    let mut tree = Tree::new(compression_type)?;
+    let mut written = 0;
+
+    while written < out.len() {
+        let mut symbol = tree.decode_symbol(&mut bits)?;
+        if symbol == SYMBOL_END {
+            break;
+        }
+
+        if symbol == SYMBOL_NEW {
+            symbol = usize::from(bits.read_byte()?);
+            tree.insert_symbol(symbol)?;
+        }
+
+        out[written] = symbol as u8;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Reads the bits of every byte starting with the least significant one.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Error> {
+        let Some(&byte) = self.data.get(self.position / 8) else {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Truncated Huffman data",
+            ));
+        };
+
+        let bit = (byte >> (self.position % 8)) & 1 != 0;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let mut byte = 0;
+        for bit in 0..8 {
+            byte |= u8::from(self.read_bit()?) << bit;
+        }
+
+        Ok(byte)
+    }
+}
+
+struct Item {
+    prev: usize,
+    next: usize,
+    symbol: usize,
+    weight: u32,
+    parent: Option<usize>,
+    /// The higher weight child is the item in front of it.
+    child_lo: Option<usize>,
+}
+
+/// The tree items are kept in a list that is sorted by descending weight, so the root is always the first item and
+/// both children of an item are next to each other.
+struct Tree {
+    items: Vec<Item>,
+}
+
+impl Tree {
+    fn new(compression_type: u8) -> Result<Self, Error> {
+        let Some(weights) = compression_type
+            .checked_sub(FIRST_WAVE_TYPE)
+            .and_then(|index| WAVE_WEIGHTS.get(usize::from(index)))
+        else {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Huffman compression type {} not supported",
+                    compression_type
+                ),
+            ));
+        };
+
+        let mut tree = Tree {
+            items: Vec::with_capacity(MAX_ITEMS),
+        };
+        tree.items.push(Item {
+            prev: HEAD,
+            next: HEAD,
+            symbol: 0,
+            weight: 0,
+            parent: None,
+            child_lo: None,
+        });
+
+        for (base, weights) in [0x00, 0x40, 0x80].into_iter().zip(weights) {
+            for (offset, &weight) in weights.iter().enumerate() {
+                tree.add_leaf(base + offset, u32::from(weight))?;
+            }
+        }
+
+        for symbol in [SYMBOL_END, SYMBOL_NEW] {
+            let item = tree.create_item(symbol, 1)?;
+            tree.insert_before(item, HEAD);
+        }
+
+        // Pair up the items from the lowest weight onwards, until only the root is left
+        let mut child_lo = tree.last();
+        while child_lo != HEAD {
+            let child_hi = tree.items[child_lo].prev;
+            if child_hi == HEAD {
+                break;
+            }
+
+            let weight = tree.items[child_hi].weight + tree.items[child_lo].weight;
+            let parent = tree.create_item(0, weight)?;
+            tree.insert_after(parent, tree.find_higher_or_equal(tree.last(), weight));
+
+            tree.items[child_lo].parent = Some(parent);
+            tree.items[child_hi].parent = Some(parent);
+            tree.items[parent].child_lo = Some(child_lo);
+
+            child_lo = tree.items[child_hi].prev;
+        }
+
+        Ok(tree)
+    }
+
+    fn last(&self) -> usize {
+        self.items[HEAD].prev
+    }
+
+    fn create_item(&mut self, symbol: usize, weight: u32) -> Result<usize, Error> {
+        if self.items.len() == MAX_ITEMS {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Huffman tree exceeds the amount of symbols",
+            ));
+        }
+
+        self.items.push(Item {
+            prev: UNLINKED,
+            next: UNLINKED,
+            symbol,
+            weight,
+            parent: None,
+            child_lo: None,
+        });
+        Ok(self.items.len() - 1)
+    }
+
+    fn add_leaf(&mut self, symbol: usize, weight: u32) -> Result<(), Error> {
+        if weight == 0 {
+            return Ok(());
+        }
+
+        let item = self.create_item(symbol, weight)?;
+        self.insert_after(item, self.find_higher_or_equal(self.last(), weight));
+        Ok(())
+    }
+
+    fn unlink(&mut self, item: usize) {
+        let Item { prev, next, .. } = self.items[item];
+        if prev != UNLINKED {
+            self.items[prev].next = next;
+            self.items[next].prev = prev;
+        }
+    }
+
+    fn insert_after(&mut self, item: usize, point: usize) {
+        self.unlink(item);
+
+        let next = self.items[point].next;
+        self.items[item].prev = point;
+        self.items[item].next = next;
+        self.items[next].prev = item;
+        self.items[point].next = item;
+    }
+
+    fn insert_before(&mut self, item: usize, point: usize) {
+        self.unlink(item);
+
+        let prev = self.items[point].prev;
+        self.items[item].prev = prev;
+        self.items[item].next = point;
+        self.items[prev].next = item;
+        self.items[point].prev = item;
+    }
+
+    /// Walks towards the root and returns the first item that weighs at least `weight`, or the list head.
+    fn find_higher_or_equal(&self, mut item: usize, weight: u32) -> usize {
+        while item != HEAD && self.items[item].weight < weight {
+            item = self.items[item].prev;
+        }
+
+        item
+    }
+
+    fn decode_symbol(&self, bits: &mut BitReader) -> Result<usize, Error> {
+        let mut item = self.items[HEAD].next;
+        while let Some(child_lo) = self.items[item].child_lo {
+            item = if bits.read_bit()? {
+                self.items[child_lo].prev
+            } else {
+                child_lo
+            };
+        }
+
+        Ok(self.items[item].symbol)
+    }
+
+    /// Splits the lowest weight leaf into itself and the new symbol.
+    fn insert_symbol(&mut self, symbol: usize) -> Result<(), Error> {
+        let last = self.last();
+
+        let child_hi = self.create_item(self.items[last].symbol, self.items[last].weight)?;
+        self.insert_before(child_hi, HEAD);
+        self.items[child_hi].parent = Some(last);
+
+        let child_lo = self.create_item(symbol, 0)?;
+        self.insert_before(child_lo, HEAD);
+        self.items[child_lo].parent = Some(last);
+
+        self.items[last].child_lo = Some(child_lo);
+
+        // Once to account for the split and once for the occurrence itself
+        self.increment_weight(child_lo);
+        self.increment_weight(child_lo);
+        Ok(())
+    }
+
+    /// Increments the weight of the item and its ancestors, swapping items to keep the list sorted.
+    fn increment_weight(&mut self, item: usize) {
+        let mut current = Some(item);
+        while let Some(item) = current {
+            self.items[item].weight += 1;
+
+            let higher = self.find_higher_or_equal(self.items[item].prev, self.items[item].weight);
+            let other = self.items[higher].next;
+            if other != item {
+                self.insert_after(other, item);
+                self.insert_after(item, higher);
+
+                let parent = self.items[item]
+                    .parent
+                    .expect("Only the root has no parent");
+                let other_parent = self.items[other]
+                    .parent
+                    .expect("Only the root has no parent");
+                let other_parent_lo = self.items[other_parent].child_lo;
+
+                if self.items[parent].child_lo == Some(item) {
+                    self.items[parent].child_lo = Some(other);
+                }
+                if other_parent_lo == Some(other) {
+                    self.items[other_parent].child_lo = Some(item);
+                }
+
+                self.items[item].parent = Some(other_parent);
+                self.items[other].parent = Some(parent);
+            }
+
+            current = self.items[item].parent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::decompress;
+
+    #[test]
+    fn unsupported_type() {
+        let err = decompress(&[0x02, 0xFF], &mut [0; 16]).unwrap_err();
+        assert!(err.to_string().contains("type 2"), "{}", err);
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(decompress(&[], &mut [0; 16]).is_err());
+        assert!(decompress(&[0x07], &mut [0; 16]).is_err());
+    }
+}
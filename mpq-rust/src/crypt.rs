@@ -169,7 +169,8 @@ pub fn decrypt(data: &mut [u8], mut seed: u32) {
     let mut it = 0;
     let mut ch;
 
-    while it < data.len() - 3 {
+    // trailing bytes that don't form a full dword are not encrypted
+    while it + 4 <= data.len() {
         seed2 = seed2.wrapping_add(CRYPT_TABLE[(0x400 + (seed & 0xff)) as usize]);
         ch = LittleEndian::read_u32(&data[it..]) ^ (seed.wrapping_add(seed2));
         seed = ((!seed << 0x15).wrapping_add(0x11111111)) | (seed >> 0x0b);
@@ -183,9 +184,29 @@ pub fn decrypt(data: &mut [u8], mut seed: u32) {
     }
 }
 
+/// The inverse of [`decrypt`], the seed is updated with the plain values.
+pub fn encrypt(data: &mut [u8], mut seed: u32) {
+    let mut seed2: u32 = 0xeeeeeeee;
+    let mut it = 0;
+    let mut ch;
+
+    while it + 4 <= data.len() {
+        seed2 = seed2.wrapping_add(CRYPT_TABLE[(0x400 + (seed & 0xff)) as usize]);
+        ch = LittleEndian::read_u32(&data[it..]);
+        LittleEndian::write_u32(&mut data[it..], ch ^ (seed.wrapping_add(seed2)));
+        seed = ((!seed << 0x15).wrapping_add(0x11111111)) | (seed >> 0x0b);
+        seed2 = ch
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+
+        it += 4;
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::hash_string;
+    use super::{decrypt, encrypt, hash_string};
 
     #[test]
     fn hash() {
@@ -195,4 +216,18 @@ mod test {
         assert_eq!(0xF4E6C69D, hash_string("arr\\units.dat", 0));
         assert_eq!(0xA26067F3, hash_string("unit\\neutral\\acritter.grp", 0));
     }
+
+    #[test]
+    fn encrypt_roundtrip() {
+        let plain: Vec<u8> = (0..31u8).collect();
+        let mut data = plain.clone();
+
+        encrypt(&mut data, 0xC3AF3770);
+        assert_ne!(plain[..28], data[..28]);
+        // trailing bytes that don't form a full dword are left as is
+        assert_eq!(plain[28..], data[28..]);
+
+        decrypt(&mut data, 0xC3AF3770);
+        assert_eq!(plain, data);
+    }
 }
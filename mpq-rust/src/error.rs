@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors of [`crate::File::read_verified`] and [`crate::Archive::list_files`]. Everything else reports [`io::Error`]s, which an `MpqError` converts
+/// into (with the original error as the payload), so `?` works in both directions.
+#[derive(Debug)]
+pub enum MpqError {
+    Io(io::Error),
+    /// The stored data of the sector doesn't match its Adler32 checksum.
+    SectorChecksumMismatch {
+        sector: u32,
+    },
+    /// The archive doesn't contain a `(listfile)`, so its file names are unknown. In contrast to an empty listfile,
+    /// the archive may still contain files.
+    MissingListfile,
+}
+
+impl fmt::Display for MpqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MpqError::Io(err) => write!(f, "{}", err),
+            MpqError::SectorChecksumMismatch { sector } => write!(f, "Checksum mismatch in sector {}", sector),
+            MpqError::MissingListfile => write!(f, "The archive doesn't contain a (listfile)"),
+        }
+    }
+}
+
+impl Error for MpqError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MpqError::Io(err) => Some(err),
+            MpqError::SectorChecksumMismatch { .. } | MpqError::MissingListfile => None,
+        }
+    }
+}
+
+impl From<io::Error> for MpqError {
+    fn from(err: io::Error) -> Self {
+        MpqError::Io(err)
+    }
+}
+
+impl From<MpqError> for io::Error {
+    fn from(err: MpqError) -> Self {
+        match err {
+            MpqError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
@@ -1,11 +1,15 @@
-//! A library for reading MPQ archives
+//! A library for reading and writing MPQ archives
 
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::unreadable_literal))]
 
 mod archive;
 mod chain;
-mod compression;
+pub mod compression;
 mod crypt;
+mod error;
 
-pub use crate::archive::{Archive, File};
+pub use crate::archive::{
+    Archive, ArchiveBuilder, Compression, File, FileHash, FileInfo, FileReader, FileStream, SignatureInfo,
+};
 pub use crate::chain::Chain;
+pub use crate::error::MpqError;
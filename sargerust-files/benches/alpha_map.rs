@@ -0,0 +1,34 @@
+//! Compares the word-wise 4-bit alpha map expansion against the naive per-nibble loop it replaced.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use sargerust_files::adt::alpha::{ALPHA_MAP_4BIT_SIZE, ALPHA_MAP_SIZE, expand_4bit_alpha_map};
+use std::hint::black_box;
+
+fn expand_naive(data: &[u8; ALPHA_MAP_4BIT_SIZE]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(ALPHA_MAP_SIZE);
+
+    for byte in data {
+        result.push(((byte & 0x0F) * 0x10) | (byte & 0x0F));
+        result.push(((byte >> 4) * 0x10) | (byte >> 4));
+    }
+
+    result
+}
+
+fn expand_4bit(c: &mut Criterion) {
+    let mut data = [0u8; ALPHA_MAP_4BIT_SIZE];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i * 37) as u8;
+    }
+
+    let mut group = c.benchmark_group("expand_4bit_alpha_map");
+    group.throughput(Throughput::Bytes(ALPHA_MAP_4BIT_SIZE as u64));
+    group.bench_function("naive", |b| b.iter(|| expand_naive(black_box(&data))));
+    group.bench_function("word", |b| {
+        b.iter(|| expand_4bit_alpha_map(black_box(&data)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, expand_4bit);
+criterion_main!(benches);
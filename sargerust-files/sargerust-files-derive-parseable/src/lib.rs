@@ -3,7 +3,7 @@ extern crate proc_macro2;
 use proc_macro_crate::{FoundCrate, crate_name};
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, quote_spanned};
-use syn::{Data, DeriveInput, Fields, Ident, parse_macro_input, spanned::Spanned};
+use syn::{Data, DeriveInput, Fields, Ident, PathArguments, Type, parse_macro_input, spanned::Spanned};
 
 #[proc_macro_derive(Parse)]
 pub fn derive_parseable(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -30,22 +30,7 @@ pub(crate) fn derive_parse_internal(input: DeriveInput) -> TokenStream {
             Fields::Named(ref fields) => {
                 let recurse = fields.named.iter().map(|f| {
                     let name = &f.ident;
-                    let ftype = &f.ty;
-                    // TODO: when ftype is generic (especially Vec<T>), we need to change the token to Vec::<T>
-                    // see e.g. https://blog.turbo.fish/proc-macro-simple-derive/
-                    // try to match ftype into Path(https://docs.rs/syn/1.0.109/syn/struct.TypePath.html)
-                    // get https://docs.rs/syn/1.0.109/syn/enum.PathArguments.html
-                    // something like:
-
-                    // let ftype = match &f.ty {
-                    //     Type::Path(path) => {
-                    //         match &path.path.segments.first()?.arguments {
-                    //             AngleBracketed(angle) => angle.args.first()
-                    //             _ => &f.ty
-                    //         }
-                    //     },
-                    //     _ => &f.ty
-                    // };
+                    let ftype = turbofish(&f.ty);
 
                     quote_spanned! {f.span()=>
                         #name: #ftype::parse(rdr)?,
@@ -69,11 +54,20 @@ pub(crate) fn derive_parse_internal(input: DeriveInput) -> TokenStream {
                 })
             }
         }
+    )
+}
 
-        impl #crate_name::common::reader::Parseable<Vec<#ident>> for Vec<#ident> {
-            fn parse<R: Read>(rdr: &mut R) -> Result<Vec<#ident>, #crate_name::ParserError> {
-                Ok(#crate_name::common::reader::read_chunk_array(rdr)?)
+/// Generic types like `Vec<T>` can't be used as an expression prefix (`Vec<T>::parse`), so this turns them into their
+/// turbofish form (`Vec::<T>::parse`), see https://blog.turbo.fish/proc-macro-simple-derive/
+fn turbofish(ty: &Type) -> Type {
+    let mut ty = ty.clone();
+    if let Type::Path(path) = &mut ty {
+        for segment in path.path.segments.iter_mut() {
+            if let PathArguments::AngleBracketed(arguments) = &mut segment.arguments {
+                arguments.colon2_token.get_or_insert_with(Default::default);
             }
         }
-    )
+    }
+
+    ty
 }
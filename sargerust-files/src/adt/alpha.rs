@@ -0,0 +1,80 @@
+use crate::ParserError;
+
+/// The size of an uncompressed 4-bit alpha map (64x64 nibbles) in bytes.
+pub const ALPHA_MAP_4BIT_SIZE: usize = 2048;
+/// The size of an 8-bit alpha map (64x64) in bytes, which is what all alpha maps are expanded to.
+pub const ALPHA_MAP_SIZE: usize = 4096;
+
+const LOW_NIBBLES: u64 = 0x0F0F_0F0F_0F0F_0F0F;
+
+/// Moves the 8 bytes of `value` into the low byte of 8 consecutive 16-bit lanes.
+#[inline(always)]
+fn spread_bytes(value: u64) -> u128 {
+    let mut spread = value as u128;
+    spread = (spread | (spread << 32)) & 0x0000_0000_FFFF_FFFF_0000_0000_FFFF_FFFF;
+    spread = (spread | (spread << 16)) & 0x0000_FFFF_0000_FFFF_0000_FFFF_0000_FFFF;
+    (spread | (spread << 8)) & 0x00FF_00FF_00FF_00FF_00FF_00FF_00FF_00FF
+}
+
+/// Expands 8 bytes (16 nibbles, low nibble first) into 16 bytes, without branching per pixel.
+#[inline(always)]
+fn expand_word(word: u64) -> u128 {
+    let nibbles = spread_bytes(word & LOW_NIBBLES) | (spread_bytes((word >> 4) & LOW_NIBBLES) << 8);
+    // Each byte is at most 0xF, so this is a carry-free multiplication by 0x11 (e.g. 0xA -> 0xAA)
+    nibbles | (nibbles << 4)
+}
+
+/// Expands an uncompressed 4-bit alpha map into an 8-bit one. Within a byte, the low nibble comes first.
+/// This runs for every layer of every MCNK, so it works on 8 bytes at a time.
+pub fn expand_4bit_alpha_map(data: &[u8; ALPHA_MAP_4BIT_SIZE]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(ALPHA_MAP_SIZE);
+
+    for chunk in data.chunks_exact(8) {
+        let word = u64::from_le_bytes(chunk.try_into().expect("chunks of 8 bytes"));
+        result.extend_from_slice(&expand_word(word).to_le_bytes());
+    }
+
+    result
+}
+
+/// Decompresses an RLE compressed alpha map (`SMLayerFlags::ALPHA_MAP_COMPRESSED`). Every run starts with a byte whose
+/// highest bit selects between filling (repeat the next byte) and copying (the next bytes verbatim), the lower 7 bits
+/// are the length of the run.
+pub fn decompress_alpha_map(data: &[u8]) -> Result<[u8; ALPHA_MAP_SIZE], ParserError> {
+    let truncated = || ParserError::FormatError {
+        reason: "Compressed alpha map is truncated",
+    };
+
+    let mut result = [0u8; ALPHA_MAP_SIZE];
+    let mut written = 0;
+    let mut position = 0;
+
+    while written < ALPHA_MAP_SIZE {
+        let header = *data.get(position).ok_or_else(truncated)?;
+        let count = ((header & 0x7F) as usize).min(ALPHA_MAP_SIZE - written);
+        let run = &mut result[written..written + count];
+        position += 1;
+
+        if header & 0x80 != 0 {
+            run.fill(*data.get(position).ok_or_else(truncated)?);
+            position += 1;
+        } else {
+            run.copy_from_slice(data.get(position..position + count).ok_or_else(truncated)?);
+            position += count;
+        }
+
+        written += count;
+    }
+
+    Ok(result)
+}
+
+/// Without `MCNKHeaderFlags::DO_NOT_FIX_ALPHA_MAP`, the 4-bit alpha maps are effectively 63x63: the last row and column
+/// repeat the previous ones.
+pub fn fix_alpha_map(map: &mut [u8; ALPHA_MAP_SIZE]) {
+    for row in map.chunks_exact_mut(64) {
+        row[63] = row[62];
+    }
+
+    map.copy_within(62 * 64..63 * 64, 63 * 64);
+}
@@ -1,3 +1,4 @@
+pub mod alpha;
 pub mod reader;
 pub mod types;
 
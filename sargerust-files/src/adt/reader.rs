@@ -7,7 +7,7 @@ use crate::adt::types::{
     MWIDChunk, MWMOChunk,
 };
 use crate::common::reader::{get_mandatory_chunk_by_name, get_optional_chunk_by_name};
-use crate::common::types::{IffChunk, MVerChunk};
+use crate::common::types::{FourCC, IffChunk, MVerChunk};
 
 pub struct ADTReader {}
 
@@ -17,7 +17,8 @@ impl ADTReader {
         let version_hdr = IffChunk::read_next_chunk(rdr)?;
         if !version_hdr.magic_str().eq("MVER") {
             return Err(ParserError::InvalidMagicValue {
-                magic: version_hdr.magic,
+                expected: FourCC::new(b"MVER"),
+                found: version_hdr.fourcc(),
             });
         }
 
@@ -42,6 +43,14 @@ impl ADTReader {
         };
         // No real error, only an EOF
 
+        // Starting with Cataclysm, ADTs are split into the root file (terrain only, without MCIN) and _tex0.adt/_obj0.adt
+        // files that contain the textures and models respectively. Both kinds lack MCIN, which is mandatory in Wrath.
+        if !chunk_list.iter().any(|chunk| chunk.is_magic("MCIN")) {
+            return Err(ParserError::FormatError {
+                reason: "split ADT (Cata+) not supported",
+            });
+        }
+
         let mhdr = get_mandatory_chunk_by_name::<MHDRChunk>(&chunk_list, "MHDR")?;
         let mcin = get_mandatory_chunk_by_name::<MCINChunk>(&chunk_list, "MCIN")?;
         let mtex = get_mandatory_chunk_by_name::<MTEXChunk>(&chunk_list, "MTEX")?;
@@ -1,6 +1,13 @@
+use crate::ParserError;
+use crate::adt::alpha::{ALPHA_MAP_4BIT_SIZE, ALPHA_MAP_SIZE, decompress_alpha_map, expand_4bit_alpha_map};
 use crate::adt::reader::ADTReader;
+#[cfg(feature = "wotlk")]
+use crate::adt::types::MH2OChunk;
+use crate::adt::types::{MCNKChunk, MCNKHeaderFlags, SMLayer, SMLayerFlags};
+use crate::common::reader::Parseable;
+use crate::wdt::types::{MPHDChunk, MPHDFlags};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 
 #[test]
 fn parse_gm_island() -> Result<(), anyhow::Error> {
@@ -10,3 +17,237 @@ fn parse_gm_island() -> Result<(), anyhow::Error> {
     let asset = ADTReader::parse_asset(&mut file)?;
     Ok(())
 }
+
+#[test]
+fn reject_split_adt() {
+    // A Cata+ root ADT: MVER 18 followed by MHDR, but no MCIN
+    let mut data = Vec::new();
+    data.extend_from_slice(b"REVM\x04\0\0\0\x12\0\0\0");
+    data.extend_from_slice(b"RDHM\x40\0\0\0");
+    data.extend_from_slice(&[0u8; 0x40]);
+
+    let result = ADTReader::parse_asset(&mut Cursor::new(data));
+    assert!(matches!(result, Err(ParserError::FormatError { .. })));
+}
+
+#[test]
+fn expand_4bit_alpha() {
+    let mut data = [0u8; ALPHA_MAP_4BIT_SIZE];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i * 37 + i / 256) as u8;
+    }
+
+    let expected: Vec<u8> = data
+        .iter()
+        .flat_map(|byte| [(byte & 0xF) * 0x11, (byte >> 4) * 0x11])
+        .collect();
+
+    let expanded = expand_4bit_alpha_map(&data);
+    assert_eq!(expanded.len(), ALPHA_MAP_SIZE);
+    assert_eq!(expanded, expected);
+}
+
+#[test]
+fn decompress_alpha() -> Result<(), anyhow::Error> {
+    // three literal bytes, then fill runs of 127 (the last one exceeding the map)
+    let mut data = vec![0x03, 1, 2, 3];
+    for run in 0..33u8 {
+        data.extend_from_slice(&[0xFF, run]);
+    }
+
+    let map = decompress_alpha_map(&data)?;
+    assert_eq!(map[..4], [1, 2, 3, 0]);
+    assert_eq!(map[3 + 127], 1);
+    assert_eq!(map[ALPHA_MAP_SIZE - 1], 32);
+
+    assert!(decompress_alpha_map(&data[..data.len() - 1]).is_err());
+    Ok(())
+}
+
+fn mcnk_with_mcal(flags: MCNKHeaderFlags, mcal: &[u8]) -> Result<MCNKChunk, anyhow::Error> {
+    let mut data = vec![0u8; 128];
+    data[0..4].copy_from_slice(&flags.bits().to_le_bytes());
+    data[36..40].copy_from_slice(&136u32.to_le_bytes());
+    data.extend_from_slice(b"LACM");
+    data.extend_from_slice(&(mcal.len() as u32).to_le_bytes());
+    data.extend_from_slice(mcal);
+
+    Ok(MCNKChunk::parse(&mut Cursor::new(data))?)
+}
+
+#[test]
+fn decode_alpha_maps() -> Result<(), anyhow::Error> {
+    let mphd = |flags: MPHDFlags| MPHDChunk {
+        flags,
+        something: 0,
+        unused: [0; 6],
+    };
+    let layer = |flags: SMLayerFlags, offset_in_mcal: u32| SMLayer {
+        textureId: 1,
+        flags: SMLayerFlags::USE_ALPHA_MAP | flags,
+        offset_in_mcal,
+        effectId: 0,
+    };
+
+    // 4-bit: every nibble is its column modulo 16 (even columns in the low nibble)
+    let packed = (0..ALPHA_MAP_4BIT_SIZE)
+        .map(|i| ((2 * i % 16) | (((2 * i + 1) % 16) << 4)) as u8)
+        .collect::<Vec<_>>();
+    let mut packed_bottom_right = packed.clone();
+    packed_bottom_right[ALPHA_MAP_4BIT_SIZE - 1] = 0xF0;
+
+    let chunk = mcnk_with_mcal(MCNKHeaderFlags::empty(), &packed_bottom_right)?;
+    let map = chunk.decode_alpha_map(&layer(SMLayerFlags::empty(), 0), &mphd(MPHDFlags::empty()))?;
+    assert_eq!(map[..3], [0x00, 0x11, 0x22]);
+    // the last column repeats the previous one, as does the last row
+    assert_eq!(map[63], map[62]);
+    assert_eq!(map[63 * 64..], map[62 * 64..63 * 64]);
+
+    let chunk = mcnk_with_mcal(MCNKHeaderFlags::DO_NOT_FIX_ALPHA_MAP, &packed_bottom_right)?;
+    let map = chunk.decode_alpha_map(&layer(SMLayerFlags::empty(), 0), &mphd(MPHDFlags::empty()))?;
+    assert_eq!(map[63], 0xFF);
+    assert_eq!(map[ALPHA_MAP_SIZE - 1], 0xFF);
+
+    // 8-bit, behind a 4 byte padding
+    let mut big = vec![0u8; 4];
+    big.extend((0..ALPHA_MAP_SIZE).map(|i| i as u8));
+    let chunk = mcnk_with_mcal(MCNKHeaderFlags::empty(), &big)?;
+    let map = chunk.decode_alpha_map(
+        &layer(SMLayerFlags::empty(), 4),
+        &mphd(MPHDFlags::ADT_HAS_BIG_ALPHA),
+    )?;
+    assert_eq!(map[..], big[4..]);
+    assert!(
+        chunk
+            .decode_alpha_map(
+                &layer(SMLayerFlags::empty(), 5),
+                &mphd(MPHDFlags::ADT_HAS_BIG_ALPHA)
+            )
+            .is_err()
+    );
+
+    // compressed: 64 rows of 64 times the row index
+    let compressed = (0..64u8).flat_map(|row| [0xC0, row]).collect::<Vec<_>>();
+    let chunk = mcnk_with_mcal(MCNKHeaderFlags::empty(), &compressed)?;
+    let map = chunk.decode_alpha_map(
+        &layer(SMLayerFlags::ALPHA_MAP_COMPRESSED, 0),
+        &mphd(MPHDFlags::ADT_HAS_BIG_ALPHA),
+    )?;
+    assert_eq!(map[64 * 10 + 7], 10);
+    assert_eq!(map[ALPHA_MAP_SIZE - 1], 63);
+    Ok(())
+}
+
+#[test]
+fn parse_mclq() -> Result<(), anyhow::Error> {
+    const LIQUID_SIZE: usize = 804;
+
+    let mut data = vec![0u8; 128];
+    data[0..4].copy_from_slice(&20u32.to_le_bytes()); // LQ_RIVER | LQ_MAGMA
+    data[96..100].copy_from_slice(&136u32.to_le_bytes());
+    data[100..104].copy_from_slice(&(8 + 2 * LIQUID_SIZE as u32).to_le_bytes());
+
+    // the size of the chunk itself is 0, as in the client files
+    data.extend_from_slice(b"QLCM\0\0\0\0");
+    for (height, uv) in [(10.0f32, [0u8; 4]), (20.0, [0x34, 0x12, 0x78, 0x56])] {
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&(height + 1.0).to_le_bytes());
+        for _ in 0..9 * 9 {
+            data.extend_from_slice(&uv);
+            data.extend_from_slice(&height.to_le_bytes());
+        }
+        let mut tiles = [0u8; 64];
+        tiles[9] = 0x0F;
+        data.extend_from_slice(&tiles);
+        data.extend_from_slice(&[0u8; 4 + 2 * 40]);
+    }
+
+    let chunk = MCNKChunk::parse(&mut Cursor::new(data))?;
+    let liquids = chunk.get_mclq()?.expect("MCLQ to be present");
+
+    assert_eq!(liquids.len(), 2);
+    assert_eq!(
+        liquids[0].liquid_type.bits(),
+        MCNKHeaderFlags::LQ_RIVER.bits()
+    );
+    assert_eq!(liquids[0].liquid.height_max, 11.0);
+    assert_eq!(liquids[0].liquid.verts.len(), 81);
+    assert!(liquids[0].liquid.renders_tile(0, 0));
+    assert!(!liquids[0].liquid.renders_tile(1, 1));
+
+    assert_eq!(
+        liquids[1].liquid_type.bits(),
+        MCNKHeaderFlags::LQ_MAGMA.bits()
+    );
+    assert_eq!(liquids[1].liquid.verts[80].height, 20.0);
+    assert_eq!(liquids[1].liquid.verts[0].magma_uv(), (0x1234, 0x5678));
+    Ok(())
+}
+
+/// An MH2O chunk where only the first MCNK has liquids: a river with 2x1 cells (the second one masked out) and a
+/// flat ocean.
+#[cfg(feature = "wotlk")]
+fn synthetic_mh2o() -> Vec<u8> {
+    const HEADERS_SIZE: u32 = 16 * 16 * 12;
+    const INSTANCE_SIZE: u32 = 24;
+    let exists_offset = HEADERS_SIZE + 2 * INSTANCE_SIZE;
+    let vertex_offset = exists_offset + 1;
+
+    let mut data = Vec::new();
+    for value in [HEADERS_SIZE, 2, 0] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.resize(HEADERS_SIZE as usize, 0);
+
+    let mut instance = |liquid_type: u16, format: u16, heights: [f32; 2], rect: [u8; 4], offsets: [u32; 2]| {
+        data.extend_from_slice(&liquid_type.to_le_bytes());
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&heights[0].to_le_bytes());
+        data.extend_from_slice(&heights[1].to_le_bytes());
+        data.extend_from_slice(&rect);
+        data.extend_from_slice(&offsets[0].to_le_bytes());
+        data.extend_from_slice(&offsets[1].to_le_bytes());
+    };
+    instance(
+        1,
+        0,
+        [1.0, 3.0],
+        [1, 2, 2, 1],
+        [exists_offset, vertex_offset],
+    );
+    instance(2, 2, [5.0, 5.0], [0, 0, 8, 8], [0, 0]);
+
+    data.push(0b01);
+    for height in [1.0f32, 2.0, 3.0, 1.5, 2.5, 3.0] {
+        data.extend_from_slice(&height.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+#[cfg(feature = "wotlk")]
+fn parse_mh2o() -> Result<(), anyhow::Error> {
+    let mh2o = MH2OChunk::parse(&mut Cursor::new(synthetic_mh2o()))?;
+    assert_eq!(mh2o.chunks.len(), 256);
+    assert!(mh2o.instances(&mh2o.chunks[1])?.is_empty());
+
+    let instances = mh2o.instances(&mh2o.chunks[0])?;
+    assert_eq!(instances.len(), 2);
+
+    let river = &instances[0];
+    assert_eq!(
+        (river.x_offset, river.y_offset, river.width, river.height),
+        (1, 2, 2, 1)
+    );
+    assert_eq!(mh2o.exists_mask(river)?, [true, false]);
+    assert_eq!(
+        mh2o.heights(river)?.expect("River heights"),
+        [1.0, 2.0, 3.0, 1.5, 2.5, 3.0]
+    );
+
+    let ocean = &instances[1];
+    assert_eq!(mh2o.exists_mask(ocean)?.len(), 64);
+    assert!(mh2o.heights(ocean)?.is_none());
+    assert_eq!(ocean.max_height_leve, 5.0);
+    Ok(())
+}
@@ -3,9 +3,12 @@
 #![allow(non_camel_case_types)]
 
 use crate::ParserError;
+use crate::adt::alpha::{
+    ALPHA_MAP_4BIT_SIZE, ALPHA_MAP_SIZE, decompress_alpha_map, expand_4bit_alpha_map, fix_alpha_map,
+};
 use crate::common::reader::{GenericStringList, Parseable, read_chunk_array};
-use crate::common::types::{C3Vector, CImVector, IffChunk};
-use crate::wdt::types::SMMapObjDef;
+use crate::common::types::{C3Vector, CImVector, FourCC, IffChunk};
+use crate::wdt::types::{MPHDChunk, MPHDFlags, SMMapObjDef};
 use bitflags::bitflags;
 use sargerust_files_derive_parseable::Parse;
 use std::collections::HashMap;
@@ -13,6 +16,7 @@ use std::io::{Cursor, Read};
 // https://wowdev.wiki/ADT/v18
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ADTAsset {
     pub mhdr: MHDRChunk,
     pub mcin: MCINChunk,
@@ -28,6 +32,7 @@ pub struct ADTAsset {
 }
 
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MHDRChunk {
     pub flags: u32,
     // from here on, offsets into chunks, allegedly the game uses only those as pointers
@@ -50,6 +55,7 @@ pub struct MHDRChunk {
 }
 
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMChunkInfo {
     pub offset: u32, // _absolute_ offset
     pub size: u32,
@@ -58,6 +64,7 @@ pub struct SMChunkInfo {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MCINChunk {
     pub chunk_info: Vec<SMChunkInfo>, // 16 * 16 elements
 }
@@ -71,6 +78,7 @@ impl Parseable<MCINChunk> for MCINChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MTEXChunk {
     pub filenames: Vec<String>,
 }
@@ -84,6 +92,7 @@ impl Parseable<MTEXChunk> for MTEXChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MMDXChunk {
     pub filenames: Vec<String>,
     pub offsets: HashMap<u32, usize>,
@@ -100,6 +109,7 @@ impl Parseable<MMDXChunk> for MMDXChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MMIDChunk {
     pub mmdx_offsets: Vec<u32>,
 }
@@ -114,6 +124,7 @@ impl Parseable<MMIDChunk> for MMIDChunk {
 
 // as opposed to in WDT, this seems to be an array
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MWMOChunk {
     pub filenames: Vec<String>,
     pub offsets: HashMap<u32, usize>,
@@ -130,6 +141,7 @@ impl Parseable<MWMOChunk> for MWMOChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MWIDChunk {
     pub mwmo_offsets: Vec<u32>,
 }
@@ -143,6 +155,7 @@ impl Parseable<MWIDChunk> for MWIDChunk {
 }
 
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMDoodadDef {
     pub nameId: u32, // MMID entry on which model to use
     pub uniqueId: u32,
@@ -153,6 +166,7 @@ pub struct SMDoodadDef {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MDDFChunk {
     pub doodadDefs: Vec<SMDoodadDef>,
 }
@@ -166,6 +180,7 @@ impl Parseable<MDDFChunk> for MDDFChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MODFChunk {
     pub mapObjDefs: Vec<SMMapObjDef>,
 }
@@ -180,6 +195,7 @@ impl Parseable<MODFChunk> for MODFChunk {
 
 #[cfg(feature = "wotlk")]
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMLiquidChunk {
     pub offset_instances: u32,  // SMLiquidInstance[layer_count] offset
     pub layer_count: u32,       // 0 if the chunk has no liquids, otherwise > 1, and then the other values become valid
@@ -188,6 +204,7 @@ pub struct SMLiquidChunk {
 
 #[cfg(feature = "wotlk")]
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct mh2o_chunk_attributes {
     pub fishable: u64, // 8x8 bit mask. Used for visibility?
     pub deep: u64,     // Fatigue Area
@@ -195,6 +212,7 @@ pub struct mh2o_chunk_attributes {
 
 #[cfg(feature = "wotlk")]
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMLiquidInstance {
     pub liquid_type: u16,          // foreign_key<uint16_t, &LiquidTypeRec::m_ID>
     pub liquid_vertex_format: u16, // This is gone after wrath for a database lookup.
@@ -208,23 +226,95 @@ pub struct SMLiquidInstance {
     pub offset_vertex_data: u32,
 }
 
+#[cfg(feature = "wotlk")]
+impl SMLiquidInstance {
+    /// The amount of vertices, (width + 1) * (height + 1)
+    pub fn vertex_count(&self) -> usize {
+        (self.width as usize + 1) * (self.height as usize + 1)
+    }
+}
+
 #[cfg(feature = "wotlk")]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// https://wowdev.wiki/ADT/v18#MH2O_chunk_(WotLK+) have fun
 pub struct MH2OChunk {
     pub chunks: Vec<SMLiquidChunk>, // 16x16 = 256 entries.
+    /// The whole chunk, all offsets are relative to its start.
+    pub(crate) data: Vec<u8>,
 }
 
+#[cfg(feature = "wotlk")]
 impl Parseable<MH2OChunk> for MH2OChunk {
     fn parse<R: Read>(rdr: &mut R) -> Result<MH2OChunk, ParserError> {
-        Ok(MH2OChunk {
-            chunks: read_chunk_array(rdr)?,
-        })
+        let mut data = Vec::new();
+        rdr.read_to_end(&mut data)?;
+
+        let mut header_rdr = Cursor::new(&data);
+        let chunks = (0..16 * 16)
+            .map(|_| SMLiquidChunk::parse(&mut header_rdr))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MH2OChunk { chunks, data })
+    }
+}
+
+#[cfg(feature = "wotlk")]
+impl MH2OChunk {
+    fn data_at(&self, offset: u32, length: usize) -> Result<&[u8], ParserError> {
+        let start = offset as usize;
+        self.data
+            .get(start..start + length)
+            .ok_or(ParserError::FormatError {
+                reason: "MH2O offset is out of bounds",
+            })
+    }
+
+    /// The liquid layers of the given chunk (one of [`MH2OChunk::chunks`]).
+    pub fn instances(&self, chunk: &SMLiquidChunk) -> Result<Vec<SMLiquidInstance>, ParserError> {
+        if chunk.layer_count == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut rdr = Cursor::new(self.data_at(chunk.offset_instances, chunk.layer_count as usize * 24)?);
+        (0..chunk.layer_count)
+            .map(|_| SMLiquidInstance::parse(&mut rdr))
+            .collect()
+    }
+
+    /// Which of the width * height cells of the instance contain liquid, row by row.
+    pub fn exists_mask(&self, instance: &SMLiquidInstance) -> Result<Vec<bool>, ParserError> {
+        let cell_count = instance.width as usize * instance.height as usize;
+        if instance.offset_exists_bitmap == 0 {
+            return Ok(vec![true; cell_count]);
+        }
+
+        let bitmap = self.data_at(instance.offset_exists_bitmap, cell_count.div_ceil(8))?;
+        Ok((0..cell_count)
+            .map(|cell| bitmap[cell / 8] & (1 << (cell % 8)) != 0)
+            .collect())
+    }
+
+    /// The heights of the (width + 1) * (height + 1) vertices of the instance, row by row. `None` for flat liquids
+    /// (usually oceans), which only have a depth or no vertex data at all and are at `max_height_level` instead.
+    pub fn heights(&self, instance: &SMLiquidInstance) -> Result<Option<Vec<f32>>, ParserError> {
+        // Format 2 is depth only
+        if instance.offset_vertex_data == 0 || instance.liquid_vertex_format == 2 {
+            return Ok(None);
+        }
+
+        let mut rdr = Cursor::new(self.data_at(instance.offset_vertex_data, instance.vertex_count() * 4)?);
+        (0..instance.vertex_count())
+            .map(|_| f32::parse(&mut rdr))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
     }
 }
 
 bitflags! {
     #[derive(Debug, Copy, Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
     pub struct MCNKHeaderFlags: u32 {
         const HAS_MCSH = 1 << 0;
         const IMPASS = 1 << 1;
@@ -247,6 +337,7 @@ impl Parseable<MCNKHeaderFlags> for MCNKHeaderFlags {
 // 256 individual MCNK chunks, row by row, starting from top-left (northwest).
 // The MCNK chunks have a large block of data that starts with a header, and then has sub-chunks of its own.
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// SMChunk
 pub struct MCNKChunkHeader {
     pub flags: MCNKHeaderFlags,
@@ -280,6 +371,7 @@ pub struct MCNKChunkHeader {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MCNKChunk {
     pub header: MCNKChunkHeader,
     pub(crate) sub_chunks: Vec<u8>,
@@ -299,80 +391,168 @@ impl Parseable<MCNKChunk> for MCNKChunk {
     }
 }
 
+/// The header offsets are relative to the start of the MCNK chunk, but [`MCNKChunk::sub_chunks`] starts right after
+/// the IFF chunk header (magic and size, 8 bytes) and the [`MCNKChunkHeader`] (128 bytes).
+const MCNK_SUB_CHUNK_BASE: u32 = 8 + 128;
+
 impl MCNKChunk {
-    pub fn get_mcvt(&self) -> Result<Option<MCVTSubChunk>, ParserError> {
-        if self.header.ofsHeight == 0 {
+    /// The sub chunk data starting at the given header offset.
+    fn sub_chunk_data(&self, ofs: u32) -> Result<&[u8], ParserError> {
+        let start = ofs
+            .checked_sub(MCNK_SUB_CHUNK_BASE)
+            .ok_or(ParserError::FormatError {
+                reason: "MCNK sub chunk offset points into the header",
+            })? as usize;
+
+        self.sub_chunks
+            .get(start..)
+            .ok_or(ParserError::FormatError {
+                reason: "MCNK sub chunk offset is out of bounds",
+            })
+    }
+
+    /// Reads the sub chunk at the given header offset (0 meaning absent) and validates its magic.
+    fn sub_chunk(&self, ofs: u32, magic: &str) -> Result<Option<IffChunk>, ParserError> {
+        if ofs == 0 {
             return Ok(None);
         }
 
-        let mut rdr = Cursor::new(&self.sub_chunks[(self.header.ofsHeight - 136) as usize..]);
+        let mut rdr = Cursor::new(self.sub_chunk_data(ofs)?);
         let iff = IffChunk::read_next_chunk(&mut rdr)?;
 
-        if !iff.is_magic("MCVT") {
-            return Err(ParserError::InvalidMagicValue { magic: iff.magic });
+        if !iff.is_magic(magic) {
+            return Err(ParserError::InvalidMagicValue {
+                expected: FourCC::new(magic.as_bytes().try_into().unwrap()),
+                found: iff.fourcc(),
+            });
         }
 
-        Ok(Some(read_chunk_array(&mut Cursor::new(&iff.data))?))
+        Ok(Some(iff))
+    }
+
+    pub fn get_mcvt(&self) -> Result<Option<MCVTSubChunk>, ParserError> {
+        self.sub_chunk(self.header.ofsHeight, "MCVT")?
+            .map(|iff| read_chunk_array(&mut Cursor::new(&iff.data)))
+            .transpose()
     }
 
     pub fn get_mccv(&self, mcnk: &MCNKChunkHeader) -> Result<Option<MCCVSubChunk>, ParserError> {
-        if self.header.ofsMCCV == 0 || !mcnk.flags.contains(MCNKHeaderFlags::HAS_MCCV) {
+        if !mcnk.flags.contains(MCNKHeaderFlags::HAS_MCCV) {
             return Ok(None);
         }
 
-        let mut rdr = Cursor::new(&self.sub_chunks[(self.header.ofsMCCV - 136) as usize..]);
-        let iff = IffChunk::read_next_chunk(&mut rdr)?;
-
-        if !iff.is_magic("MCCV") {
-            return Err(ParserError::InvalidMagicValue { magic: iff.magic });
-        }
-
-        Ok(Some(read_chunk_array(&mut Cursor::new(&iff.data))?))
+        self.sub_chunk(self.header.ofsMCCV, "MCCV")?
+            .map(|iff| read_chunk_array(&mut Cursor::new(&iff.data)))
+            .transpose()
     }
 
     pub fn get_mcnr(&self) -> Result<Option<MCNRSubChunk>, ParserError> {
-        if self.header.ofsNormal == 0 {
-            return Ok(None);
-        }
-
-        let mut rdr = Cursor::new(&self.sub_chunks[(self.header.ofsNormal - 136) as usize..]);
-        let iff = IffChunk::read_next_chunk(&mut rdr)?;
+        self.sub_chunk(self.header.ofsNormal, "MCNR")?
+            .map(|iff| read_chunk_array(&mut Cursor::new(&iff.data)))
+            .transpose()
+    }
 
-        if !iff.is_magic("MCNR") {
-            return Err(ParserError::InvalidMagicValue { magic: iff.magic });
-        }
+    pub fn get_mcly(&self) -> Result<Option<MCLYSubChunk>, ParserError> {
+        self.sub_chunk(self.header.ofsLayer, "MCLY")?
+            .map(|iff| read_chunk_array(&mut Cursor::new(&iff.data)))
+            .transpose()
+    }
 
-        Ok(Some(read_chunk_array(&mut Cursor::new(&iff.data))?))
+    pub fn get_mcal(&self) -> Result<Option<MCALSubChunk>, ParserError> {
+        Ok(self
+            .sub_chunk(self.header.ofsAlpha, "MCAL")?
+            .map(|iff| iff.data))
     }
 
-    pub fn get_mcly(&self) -> Result<Option<MCLYSubChunk>, ParserError> {
-        if self.header.ofsLayer == 0 {
-            return Ok(None);
+    /// The alpha map of the given layer as 64x64 bytes, row by row. Which of the formats (4-bit, 8-bit or compressed)
+    /// is used depends on the layer and the WDT.
+    pub fn decode_alpha_map(&self, layer: &SMLayer, mphd: &MPHDChunk) -> Result<[u8; ALPHA_MAP_SIZE], ParserError> {
+        let mcal = self.get_mcal()?.ok_or(ParserError::FormatError {
+            reason: "MCNK has no MCAL sub chunk",
+        })?;
+
+        let data = mcal
+            .get(layer.offset_in_mcal as usize..)
+            .ok_or(ParserError::FormatError {
+                reason: "Alpha map offset is out of bounds of MCAL",
+            })?;
+
+        if layer.flags.contains(SMLayerFlags::ALPHA_MAP_COMPRESSED) {
+            return decompress_alpha_map(data);
         }
 
-        let mut rdr = Cursor::new(&self.sub_chunks[(self.header.ofsLayer - 136) as usize..]);
-        let iff = IffChunk::read_next_chunk(&mut rdr)?;
+        let too_short = ParserError::FormatError {
+            reason: "Alpha map exceeds the MCAL sub chunk",
+        };
 
-        if !iff.is_magic("MCLY") {
-            return Err(ParserError::InvalidMagicValue { magic: iff.magic });
+        if mphd.flags.contains(MPHDFlags::ADT_HAS_BIG_ALPHA) {
+            return data
+                .get(..ALPHA_MAP_SIZE)
+                .ok_or(too_short)
+                .map(|map| map.try_into().expect("slice to be ALPHA_MAP_SIZE long"));
         }
 
-        Ok(Some(read_chunk_array(&mut Cursor::new(&iff.data))?))
+        let packed = data.get(..ALPHA_MAP_4BIT_SIZE).ok_or(too_short)?;
+        let mut map: [u8; ALPHA_MAP_SIZE] = expand_4bit_alpha_map(
+            packed
+                .try_into()
+                .expect("slice to be ALPHA_MAP_4BIT_SIZE long"),
+        )
+        .try_into()
+        .expect("expanded alpha map to be ALPHA_MAP_SIZE long");
+
+        if !self
+            .header
+            .flags
+            .contains(MCNKHeaderFlags::DO_NOT_FIX_ALPHA_MAP)
+        {
+            fix_alpha_map(&mut map);
+        }
+
+        Ok(map)
     }
 
-    pub fn get_mcal(&self) -> Result<Option<MCALSubChunk>, ParserError> {
-        if self.header.ofsAlpha == 0 {
+    /// The legacy liquids, one instance per `LQ_*` flag of the header (in the order of the flags). In contrast to the
+    /// other sub chunks, the size of MCLQ is only stored in the header (`sizeLiquid`, which includes the chunk header),
+    /// the size of the chunk itself is usually 0.
+    pub fn get_mclq(&self) -> Result<Option<MCLQSubChunk>, ParserError> {
+        if self.header.ofsLiquid == 0 || self.header.sizeLiquid <= 8 {
             return Ok(None);
         }
 
-        let mut rdr = Cursor::new(&self.sub_chunks[(self.header.ofsAlpha - 136) as usize..]);
-        let iff = IffChunk::read_next_chunk(&mut rdr)?;
-
-        if !iff.is_magic("MCAL") {
-            return Err(ParserError::InvalidMagicValue { magic: iff.magic });
+        let data = self
+            .sub_chunk_data(self.header.ofsLiquid)?
+            .get(..self.header.sizeLiquid as usize)
+            .ok_or(ParserError::FormatError {
+                reason: "MCLQ sub chunk exceeds the MCNK chunk",
+            })?;
+
+        let mut rdr = Cursor::new(data);
+        let magic = FourCC::from_iff_magic(u32::parse(&mut rdr)?);
+        if magic != FourCC::new(b"MCLQ") {
+            return Err(ParserError::InvalidMagicValue {
+                expected: FourCC::new(b"MCLQ"),
+                found: magic,
+            });
         }
-
-        Ok(Some(iff.data.clone()))
+        let _size = u32::parse(&mut rdr)?;
+
+        [
+            MCNKHeaderFlags::LQ_RIVER,
+            MCNKHeaderFlags::LQ_OCEAN,
+            MCNKHeaderFlags::LQ_MAGMA,
+            MCNKHeaderFlags::LQ_SLIME,
+        ]
+        .into_iter()
+        .filter(|liquid_type| self.header.flags.contains(*liquid_type))
+        .map(|liquid_type| {
+            Ok(MCLQLiquid {
+                liquid_type,
+                liquid: MCLQLiquidChunk::parse(&mut rdr)?,
+            })
+        })
+        .collect::<Result<Vec<_>, ParserError>>()
+        .map(Some)
     }
 
     pub fn get_index_low(row: u8, column: u8) -> u8 {
@@ -393,6 +573,7 @@ pub type MCCVSubChunk = Vec<CImVector>;
 
 // LK and before, this is more simple because it has no padding
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// 127 = 1, -127 = -1, _not_ normalized but almost.
 /// Nieriel recommends a recalculation of Z from X and Y, so that the vector is normalized.
 pub struct MCNREntry {
@@ -405,6 +586,8 @@ pub type MCNRSubChunk = Vec<MCNREntry>;
 
 bitflags! {
     #[derive(Debug, Copy, Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
     pub struct SMLayerFlags: u32 {
         // lowest 3 bits: animation rotation, next 3 bits: animation speed
         const ANIMATION_ENABLED = 1 << 6;
@@ -423,6 +606,7 @@ impl Parseable<SMLayerFlags> for SMLayerFlags {
 }
 
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMLayer {
     pub textureId: u32,
     pub flags: SMLayerFlags,
@@ -443,11 +627,107 @@ pub type MCSHSubChunk = Vec<u64>;
 /// alpha_map with 64x64 index, 4bit, 8bit or something completely else.
 pub type MCALSubChunk = Vec<u8>;
 
-#[cfg(not(feature = "wotlk"))] // <= TBC
-compile_error!("MCLQ Sub Chunk: Not implemented");
+#[derive(Debug, Clone, Copy, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// SLVert. The meaning of the first four bytes depends on the liquid: Water stores depth, flow0Pct, flow1Pct and a
+/// filler, oceans store depth, foam, wet and a filler (their height is unused), while magma and slime store texture
+/// coordinates instead, see [`SLVert::magma_uv`].
+pub struct SLVert {
+    pub depth: u8,
+    pub flow0Pct: u8,
+    pub flow1Pct: u8,
+    pub filler: u8,
+    pub height: f32,
+}
+
+impl SLVert {
+    /// The texture coordinates (s, t) of magma and slime vertices.
+    pub fn magma_uv(&self) -> (u16, u16) {
+        (
+            u16::from_le_bytes([self.depth, self.flow0Pct]),
+            u16::from_le_bytes([self.flow1Pct, self.filler]),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// SWFlowv
+pub struct SWFlowv {
+    pub sphere_center: C3Vector,
+    pub sphere_radius: f32,
+    pub dir: C3Vector,
+    pub velocity: f32,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// SMLiquidChunk of MCLQ (not to be confused with the MH2O header `SMLiquidChunk`), 804 bytes
+pub struct MCLQLiquidChunk {
+    pub height_min: f32,
+    pub height_max: f32,
+    /// 9x9 vertices, row by row
+    pub verts: Vec<SLVert>,
+    /// 8x8 tiles, the lower nibble is the liquid type (0x0F: not rendered), 0x40: deep (forced swimming),
+    /// 0x80: fatigue
+    pub tiles: [[u8; 8]; 8],
+    pub nFlowvs: u32,
+    /// Always 2 entries in the file, regardless of nFlowvs
+    pub flowvs: Vec<SWFlowv>,
+}
+
+impl MCLQLiquidChunk {
+    pub fn renders_tile(&self, row: usize, column: usize) -> bool {
+        self.tiles[row][column] & 0x0F != 0x0F
+    }
+}
+
+impl Parseable<MCLQLiquidChunk> for MCLQLiquidChunk {
+    fn parse<R: Read>(rdr: &mut R) -> Result<MCLQLiquidChunk, ParserError> {
+        let height_min = f32::parse(rdr)?;
+        let height_max = f32::parse(rdr)?;
+
+        let verts = (0..9 * 9)
+            .map(|_| SLVert::parse(rdr))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tiles = [[0u8; 8]; 8];
+        for row in &mut tiles {
+            rdr.read_exact(row)?;
+        }
+
+        let nFlowvs = u32::parse(rdr)?;
+        let flowvs = (0..2)
+            .map(|_| SWFlowv::parse(rdr))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MCLQLiquidChunk {
+            height_min,
+            height_max,
+            verts,
+            tiles,
+            nFlowvs,
+            flowvs,
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MCLQLiquid {
+    /// One of the `LQ_*` flags
+    pub liquid_type: MCNKHeaderFlags,
+    pub liquid: MCLQLiquidChunk,
+}
+
+/// Legacy liquids (pre WotLK, but also used in some WotLK ADTs), see [`MCNKChunk::get_mclq`]
+pub type MCLQSubChunk = Vec<MCLQLiquid>;
 
 #[cfg(not(feature = "wotlk"))] // <= TBC
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CWSoundEmitter {
     pub soundPointID: u32,
     pub soundNameID: u32,
@@ -471,6 +751,7 @@ pub struct CWSoundEmitter {
 #[cfg(feature = "wotlk")] // > TBC
 /// Apparently this is not well documented/researched
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CWSoundEmitter {
     pub entry_id: u32, // foreign_key<uint32_t, &SoundEntriesAdvancedRec::m_ID>
     pub position: C3Vector,
@@ -481,6 +762,7 @@ pub type MCSESubChunk = Vec<CWSoundEmitter>;
 
 #[cfg(any(feature = "wotlk", feature = "tbc"))] // >= TBC
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MFBOSubChunk {
     // not implemented yet
 }
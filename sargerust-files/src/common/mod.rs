@@ -1,2 +1,5 @@
 pub(crate) mod reader;
 pub mod types;
+
+#[cfg(test)]
+mod tests;
@@ -6,7 +6,7 @@ use std::io::Read;
 use byteorder::{LittleEndian, ReadBytesExt};
 
 use crate::ParserError;
-use crate::common::types::{C2Vector, C3Vector, C4Quaternion, CAaBox, CArgb, CImVector, IffChunk};
+use crate::common::types::{C2Vector, C3Vector, C4Quaternion, CAaBox, CArgb, CImVector, FourCC, IffChunk};
 
 pub(crate) trait Parseable<T> {
     fn parse<R: Read>(rdr: &mut R) -> Result<T, ParserError>;
@@ -99,6 +99,14 @@ impl Parseable<C4Quaternion> for C4Quaternion {
     }
 }
 
+impl Parseable<FourCC> for FourCC {
+    fn parse<R: Read>(rdr: &mut R) -> Result<FourCC, ParserError> {
+        let mut magic = [0u8; 4];
+        rdr.read_exact(&mut magic)?;
+        Ok(FourCC(magic))
+    }
+}
+
 impl Parseable<u8> for u8 {
     fn parse<R: Read>(rdr: &mut R) -> Result<u8, ParserError> {
         Ok(rdr.read_u8()?)
@@ -202,6 +210,14 @@ pub(crate) fn read_cstring<R: Read>(rdr: &mut R) -> Result<CString, ParserError>
     }
 }
 
+/// A `Vec<T>` consumes the rest of the reader (see [`read_chunk_array`]), so it can only be the last field of a
+/// `#[derive(Parse)]` struct.
+impl<T: Parseable<T>> Parseable<Vec<T>> for Vec<T> {
+    fn parse<R: Read>(rdr: &mut R) -> Result<Vec<T>, ParserError> {
+        read_chunk_array(rdr)
+    }
+}
+
 pub(crate) fn read_chunk_array<T: Parseable<T>, R: Read>(rdr: &mut R) -> Result<Vec<T>, ParserError> {
     let mut list = Vec::<T>::new();
     let mut element = T::parse(rdr);
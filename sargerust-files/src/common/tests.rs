@@ -0,0 +1,41 @@
+use std::io::{Cursor, Read};
+
+use sargerust_files_derive_parseable::Parse;
+
+use crate::common::reader::Parseable;
+use crate::common::types::{FourCC, IffChunk};
+
+#[test]
+fn fourcc_byte_orders() -> Result<(), anyhow::Error> {
+    let md20 = FourCC::new(b"MD20");
+    assert_eq!(FourCC::parse(&mut Cursor::new(b"MD20"))?, md20);
+    assert!(md20.matches_next(&mut Cursor::new(b"MD20"))?);
+    assert!(!md20.matches_next(&mut Cursor::new(b"02DM"))?);
+
+    // IFF chunk magics are stored reversed on disk
+    let chunk = IffChunk::read_next_chunk(&mut Cursor::new(b"REVM\x04\0\0\0\x12\0\0\0"))?;
+    assert_eq!(chunk.fourcc(), FourCC::new(b"MVER"));
+    assert_eq!(FourCC::new(b"MVER").to_iff_magic(), chunk.magic);
+
+    assert_eq!(md20.to_string(), "MD20");
+    assert_eq!(FourCC([b'M', 0, 0xFF, b'X']).to_string(), "M\\x00\\xffX");
+    Ok(())
+}
+
+#[derive(Debug, Parse)]
+struct CountedIndices {
+    count: u32,
+    indices: Vec<u16>,
+}
+
+#[test]
+fn derive_vec_field() -> Result<(), anyhow::Error> {
+    let parsed = CountedIndices::parse(&mut Cursor::new(b"\x02\0\0\0\x01\0\xff\xff"))?;
+    assert_eq!(parsed.count, 2);
+    assert_eq!(parsed.indices, [1, 0xFFFF]);
+
+    // a trailing partial element is dropped
+    let parsed = CountedIndices::parse(&mut Cursor::new(b"\0\0\0\0\x07"))?;
+    assert!(parsed.indices.is_empty());
+    Ok(())
+}
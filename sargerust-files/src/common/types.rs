@@ -6,6 +6,7 @@ use crate::ParserError;
 use crate::common::reader::Parseable;
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct C3Vector {
     pub x: f32,
     pub y: f32,
@@ -13,6 +14,7 @@ pub struct C3Vector {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct C2Vector {
     pub x: f32,
     pub y: f32,
@@ -20,6 +22,7 @@ pub struct C2Vector {
 
 // could also call this CBgra, but we keep consistency with WoWDevWiki
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CImVector {
     pub b: u8,
     pub g: u8,
@@ -28,12 +31,14 @@ pub struct CImVector {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CAaBox {
     pub min: C3Vector,
     pub max: C3Vector,
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CArgb {
     pub r: u8,
     pub g: u8,
@@ -42,6 +47,7 @@ pub struct CArgb {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct C4Quaternion {
     /// https://wowdev.wiki/WMO#MODD_chunk
     pub x: f32,
@@ -50,6 +56,51 @@ pub struct C4Quaternion {
     pub w: f32,
 }
 
+/// A four character code, as used for file and chunk magics. The bytes are stored in their readable order (e.g.
+/// `b"MVER"`), independent of how the respective format has laid them out on disk.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FourCC(pub [u8; 4]);
+
+impl FourCC {
+    pub const fn new(magic: &[u8; 4]) -> Self {
+        Self(*magic)
+    }
+
+    /// IFF based formats (ADT, WDT, WMO) store their chunk magics reversed on disk ("REVM"), so the magic that has
+    /// been read as little endian u32 has to be interpreted as big endian.
+    pub const fn from_iff_magic(magic: u32) -> Self {
+        Self(magic.to_be_bytes())
+    }
+
+    /// The inverse of [`FourCC::from_iff_magic`]
+    pub const fn to_iff_magic(self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+
+    /// Reads the next 4 bytes (in file order, as is the case for M2 and skin magics) and compares them to this
+    /// FourCC.
+    pub fn matches_next<R: Read>(&self, rdr: &mut R) -> Result<bool, ParserError> {
+        Ok(FourCC::parse(rdr)? == *self)
+    }
+}
+
+impl std::fmt::Display for FourCC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Invalid magics are not necessarily printable, so escape them instead of failing.
+        for byte in self.0 {
+            write!(f, "{}", std::ascii::escape_default(byte))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for FourCC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FourCC(\"{}\")", self)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct IffChunk {
     pub magic: u32,
@@ -59,9 +110,11 @@ pub(crate) struct IffChunk {
 
 impl IffChunk {
     pub fn magic_str(&self) -> String {
-        std::str::from_utf8(&self.magic.to_be_bytes()[..])
-            .unwrap_or_else(|_| panic!("Chunk Magic invalid utf8: {:X}", self.magic))
-            .to_owned()
+        self.fourcc().to_string()
+    }
+
+    pub fn fourcc(&self) -> FourCC {
+        FourCC::from_iff_magic(self.magic)
     }
 
     pub fn parse<T: Parseable<T>>(&self) -> Result<T, ParserError> {
@@ -81,11 +134,12 @@ impl IffChunk {
         // The reason of doing it this way is that we've discovered some chunks that had an invalid
         // magic (not valid utf-8). This is probably rather a parser issue, but also that way we
         // do u32 comparisons instead of converting everything to strings and comparing those.
-        self.magic == u32::from_be_bytes(magic.as_bytes().try_into().unwrap())
+        self.magic == FourCC::new(magic.as_bytes().try_into().unwrap()).to_iff_magic()
     }
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MVerChunk {
     pub version: u32,
 }
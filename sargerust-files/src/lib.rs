@@ -1,10 +1,11 @@
+use crate::common::types::FourCC;
 use thiserror::Error;
 
 // TODO: At some point, get rid of all the highlevel impl Parseables and use #[derive(Parseable)] or maybe #[derive(Deserializable)]
 #[derive(Error, Debug)]
 pub enum ParserError {
-    #[error("The file/chunk's magic value does not match the expectation {magic}")]
-    InvalidMagicValue { magic: u32 },
+    #[error("The file/chunk's magic value is invalid: expected {expected}, got {found}")]
+    InvalidMagicValue { expected: FourCC, found: FourCC },
 
     #[error("The file is violating the expected format, because: {reason}")]
     FormatError { reason: &'static str },
@@ -32,5 +33,6 @@ pub enum ParserError {
 pub mod adt;
 pub mod common;
 pub mod m2;
+pub mod wdl;
 pub mod wdt;
 pub mod wmo;
@@ -5,10 +5,11 @@
 #![allow(non_camel_case_types)]
 use crate::ParserError;
 use crate::common::reader::Parseable;
-use crate::common::types::CAaBox;
+use crate::common::types::{CAaBox, FourCC};
 use crate::m2::types::{
-    FOURCC_M2HEADER, FOURCC_M2SKIN, M2Array, M2Asset, M2SkinProfile, M2Texture, M2TextureFlags, M2TextureInternal,
-    M2TextureType, M2Vertex, Version,
+    FOURCC_M2HEADER, FOURCC_M2SKIN, M2Array, M2Asset, M2CompBone, M2Sequence, M2SequenceFlags, M2SkinProfile,
+    M2Texture, M2TextureFlags, M2TextureInternal, M2TextureTransform, M2TextureTransformInternal, M2TextureType,
+    M2Track, M2TrackHeader, M2Vertex, Version,
 };
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::ffi::CString;
@@ -29,9 +30,12 @@ impl M2Reader {
     }
 
     pub fn parse_asset<R: Read + Seek>(rdr: &mut R) -> Result<M2Asset, ParserError> {
-        let magic = rdr.read_u32::<LittleEndian>()?;
+        let magic = FourCC::parse(rdr)?;
         if magic != FOURCC_M2HEADER {
-            return Err(ParserError::InvalidMagicValue { magic });
+            return Err(ParserError::InvalidMagicValue {
+                expected: FOURCC_M2HEADER,
+                found: magic,
+            });
         }
 
         let version: Version = Version::parse(rdr)?;
@@ -102,6 +106,30 @@ impl M2Reader {
         // Start resolving arrays
         let name = M2Reader::resolve_array_string(rdr, &name_array)?;
         let verts: Vec<M2Vertex> = M2Reader::resolve_array(rdr, &vertices)?;
+        let sequences: Vec<M2Sequence> = M2Reader::resolve_array(rdr, &sequences)?;
+        let bones: Vec<M2CompBone> = M2Reader::resolve_array(rdr, &bones)?;
+
+        if bones
+            .iter()
+            .any(|bone| bone.parent().is_some_and(|parent| parent >= bones.len()))
+        {
+            return Err(ParserError::FormatError {
+                reason: "M2 bone references a parent that is out of range",
+            });
+        }
+
+        let global_loops: Vec<u32> = M2Reader::resolve_array(rdr, &global_loops)?;
+        let texture_transforms = M2Reader::resolve_array::<M2TextureTransformInternal, _>(rdr, &texture_transforms)?
+            .iter()
+            .map(|transform| {
+                Ok(M2TextureTransform {
+                    translation: M2Reader::resolve_track(rdr, &transform.translation, &sequences)?,
+                    rotation: M2Reader::resolve_track(rdr, &transform.rotation, &sequences)?,
+                    scaling: M2Reader::resolve_track(rdr, &transform.scaling, &sequences)?,
+                })
+            })
+            .collect::<Result<Vec<_>, ParserError>>()?;
+        let texture_transform_combos: Vec<i16> = M2Reader::resolve_array(rdr, &textureTransformCombos)?;
 
         let texs: Vec<M2TextureInternal> = M2Reader::resolve_array(rdr, &textures)?;
         let textures: Vec<M2Texture> = texs
@@ -118,19 +146,33 @@ impl M2Reader {
       magic,
       version,
       name,
+      sequences,
+      bones,
       vertices: verts,
       #[cfg(feature = "wotlk")] // > TBC
       num_skin_profiles,
-      textures
+      textures,
+      global_loops,
+      texture_transforms,
+      texture_transform_combos,
+      bounding_box,
+      bounding_sphere_radius,
+      collision_box,
+      collision_sphere_radius,
+      ribbon_emitters,
+      particle_emitters,
     })
     }
 
     pub fn parse_skin_profile<R: std::io::Read + std::io::Seek>(rdr: &mut R) -> Result<M2SkinProfile, ParserError> {
-        let magic = rdr.read_u32::<LittleEndian>()?;
+        let magic = FourCC::parse(rdr)?;
 
         #[cfg(feature = "wotlk")] // > TBC
         if magic != FOURCC_M2SKIN {
-            return Err(ParserError::InvalidMagicValue { magic });
+            return Err(ParserError::InvalidMagicValue {
+                expected: FOURCC_M2SKIN,
+                found: magic,
+            });
         }
 
         let vertices = M2Array::parse(rdr)?;
@@ -149,6 +191,69 @@ impl M2Reader {
         })
     }
 
+    /// Resolves the keyframes of a track. > TBC, sequences that don't have their keyframes in the .m2 file (but in a
+    /// separate .anim file) are left empty, unless the track is timed by a global loop.
+    fn resolve_track<T: Parseable<T>, R: Read + Seek>(
+        rdr: &mut R,
+        header: &M2TrackHeader,
+        sequences: &[M2Sequence],
+    ) -> Result<M2Track<T>, ParserError> {
+        #[cfg(feature = "wotlk")] // > TBC
+        let (timestamps, values) = {
+            let timestamp_arrays: Vec<M2Array> = M2Reader::resolve_array(rdr, &header.timestamps)?;
+            let value_arrays: Vec<M2Array> = M2Reader::resolve_array(rdr, &header.values)?;
+            if timestamp_arrays.len() != value_arrays.len() {
+                return Err(ParserError::FormatError {
+                    reason: "M2Track has a different number of timestamp and value lists",
+                });
+            }
+
+            let mut timestamps = Vec::with_capacity(timestamp_arrays.len());
+            let mut values = Vec::with_capacity(value_arrays.len());
+            for (idx, (timestamp_array, value_array)) in timestamp_arrays.iter().zip(&value_arrays).enumerate() {
+                let in_file = header.global_sequence >= 0
+                    || sequences.get(idx).is_none_or(|sequence| {
+                        sequence
+                            .flags
+                            .contains(M2SequenceFlags::PRIMARY_BONE_SEQUENCE)
+                    });
+
+                if in_file {
+                    timestamps.push(M2Reader::resolve_array(rdr, timestamp_array)?);
+                    values.push(M2Reader::resolve_array(rdr, value_array)?);
+                } else {
+                    timestamps.push(Vec::new());
+                    values.push(Vec::new());
+                }
+            }
+
+            (timestamps, values)
+        };
+
+        #[cfg(not(feature = "wotlk"))] // <= TBC
+        let (timestamps, values) = (
+            vec![M2Reader::resolve_array(rdr, &header.timestamps)?],
+            vec![M2Reader::resolve_array(rdr, &header.values)?],
+        );
+
+        if timestamps
+            .iter()
+            .zip(&values)
+            .any(|(timestamps, values): (&Vec<u32>, &Vec<T>)| timestamps.len() != values.len())
+        {
+            return Err(ParserError::FormatError {
+                reason: "M2Track has a different number of timestamps and values",
+            });
+        }
+
+        Ok(M2Track {
+            interpolation_type: header.interpolation_type,
+            global_sequence: header.global_sequence,
+            timestamps,
+            values,
+        })
+    }
+
     fn resolve_array<T: Parseable<T>, R: Read + Seek>(rdr: &mut R, array: &M2Array) -> Result<Vec<T>, ParserError> {
         let size = array.size as usize;
         if size > 0 {
@@ -1,4 +1,6 @@
 use std::fs::File;
+#[cfg(feature = "wotlk")]
+use std::io::Cursor;
 use std::io::{BufReader, BufWriter};
 
 use crate::m2::reader::M2Reader;
@@ -18,3 +20,183 @@ fn m2_parsing_and_obj_dumping() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[test]
+fn skin_validation() -> Result<(), anyhow::Error> {
+    let test_data = std::env::current_dir()?.join("test-data");
+
+    let mut file = BufReader::new(File::open(test_data.join("Chair01.m2"))?);
+    let asset = M2Reader::parse_asset(&mut file)?;
+
+    let mut skin_file = BufReader::new(File::open(test_data.join("Chair0100.skin"))?);
+    let mut skin = M2Reader::parse_skin_profile(&mut skin_file)?;
+    skin.validate(&asset)?;
+
+    // a skin of a different model would reference vertices that don't exist
+    skin.vertices.push(asset.vertices.len() as u16);
+    assert!(skin.validate(&asset).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn bounding_box() -> Result<(), anyhow::Error> {
+    let test_data = std::env::current_dir()?.join("test-data");
+
+    let mut file = BufReader::new(File::open(test_data.join("Chair01.m2"))?);
+    let asset = M2Reader::parse_asset(&mut file)?;
+
+    let bounds = asset.bounding_box();
+    assert!(bounds.min.x <= bounds.max.x && bounds.min.y <= bounds.max.y && bounds.min.z <= bounds.max.z);
+    assert!(asset.bounding_sphere_radius() > 0.0);
+
+    // every vertex has to be within the bounds
+    for vertex in &asset.vertices {
+        let pos = vertex.pos;
+        assert!(pos.x >= bounds.min.x - 0.01 && pos.x <= bounds.max.x + 0.01);
+        assert!(pos.y >= bounds.min.y - 0.01 && pos.y <= bounds.max.y + 0.01);
+        assert!(pos.z >= bounds.min.z - 0.01 && pos.z <= bounds.max.z + 0.01);
+    }
+
+    Ok(())
+}
+
+/// A WotLK M2 header (0x130 bytes) that only has sequences and bones, followed by their data.
+#[cfg(feature = "wotlk")]
+fn skeleton_m2(sequences: &[(u16, u32)], parents: &[i16]) -> Vec<u8> {
+    const HEADER_SIZE: u32 = 0x130;
+    const SEQUENCE_SIZE: u32 = 64;
+
+    let array = |size: usize, offset: u32| [(size as u32).to_le_bytes(), offset.to_le_bytes()].concat();
+
+    let mut data = b"MD20".to_vec();
+    data.extend_from_slice(&[8, 1, 0, 0]);
+    data.extend_from_slice(&[0; 8 + 4 + 8]); // name, global flags, global loops
+    data.extend(array(sequences.len(), HEADER_SIZE));
+    data.extend_from_slice(&[0; 8]);
+    data.extend(array(
+        parents.len(),
+        HEADER_SIZE + SEQUENCE_SIZE * sequences.len() as u32,
+    ));
+    data.resize(HEADER_SIZE as usize, 0);
+
+    for &(id, duration) in sequences {
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&[0; 2]);
+        data.extend_from_slice(&duration.to_le_bytes());
+        data.extend_from_slice(&[0; 52]);
+        data.extend_from_slice(&(-1i16).to_le_bytes());
+        data.extend_from_slice(&[0; 2]);
+    }
+
+    for &parent in parents {
+        data.extend_from_slice(&(-1i32).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&parent.to_le_bytes());
+        data.extend_from_slice(&[0; 2 + 4 + 3 * 20]);
+        data.extend_from_slice(&[0, 0, 0x80, 0x3f, 0, 0, 0, 0, 0, 0, 0, 0x40]); // pivot (1, 0, 2)
+    }
+
+    data
+}
+
+#[test]
+#[cfg(feature = "wotlk")]
+fn bones_and_sequences() -> Result<(), anyhow::Error> {
+    // Stand and Death, a root bone with two children and a grandchild
+    let data = skeleton_m2(&[(0, 1333), (1, 2000)], &[-1, 0, 0, 1]);
+    let asset = M2Reader::parse_asset(&mut Cursor::new(data))?;
+
+    assert_eq!(asset.bones.len(), 4);
+    assert_eq!(asset.root_bones().collect::<Vec<_>>(), [0]);
+    assert_eq!(asset.child_bones(0).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(asset.child_bones(1).collect::<Vec<_>>(), [3]);
+    assert_eq!(asset.bones[3].pivot.x, 1.0);
+    assert_eq!(asset.bones[3].pivot.z, 2.0);
+    assert!(!asset.bones[0].rotation.is_animated());
+
+    assert_eq!(
+        asset
+            .sequences
+            .iter()
+            .map(|sequence| (sequence.id, sequence.duration()))
+            .collect::<Vec<_>>(),
+        [(0, 1333), (1, 2000)]
+    );
+    assert_eq!(asset.sequences[0].variationNext, -1);
+
+    // a parent outside of the bone list
+    let data = skeleton_m2(&[], &[-1, 4]);
+    assert!(M2Reader::parse_asset(&mut Cursor::new(data)).is_err());
+    Ok(())
+}
+
+/// A WotLK M2 header with a global loop and one texture transform that scrolls along x within that loop.
+#[cfg(feature = "wotlk")]
+fn texture_transform_m2() -> Vec<u8> {
+    const HEADER_SIZE: u32 = 0x130;
+    const GLOBAL_LOOPS: u32 = HEADER_SIZE;
+    const COMBOS: u32 = GLOBAL_LOOPS + 4;
+    const TRANSFORMS: u32 = COMBOS + 4;
+    const TIMESTAMP_LISTS: u32 = TRANSFORMS + 3 * 20;
+    const VALUE_LISTS: u32 = TIMESTAMP_LISTS + 8;
+    const TIMESTAMPS: u32 = VALUE_LISTS + 8;
+    const VALUES: u32 = TIMESTAMPS + 2 * 4;
+
+    let array = |size: usize, offset: u32| [(size as u32).to_le_bytes(), offset.to_le_bytes()].concat();
+
+    let mut data = b"MD20".to_vec();
+    data.extend_from_slice(&[8, 1, 0, 0]);
+    data.extend_from_slice(&[0; 8 + 4]); // name, global flags
+    data.extend(array(1, GLOBAL_LOOPS));
+    data.resize(0x60, 0);
+    data.extend(array(1, TRANSFORMS));
+    data.resize(0x98, 0);
+    data.extend(array(1, COMBOS));
+    data.resize(HEADER_SIZE as usize, 0);
+
+    data.extend_from_slice(&1000u32.to_le_bytes());
+    data.extend_from_slice(&0i16.to_le_bytes());
+    data.extend_from_slice(&[0; 2]);
+
+    // translation (linear, global loop 0), rotation and scaling aren't animated
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&0i16.to_le_bytes());
+    data.extend(array(1, TIMESTAMP_LISTS));
+    data.extend(array(1, VALUE_LISTS));
+    for _ in 0..2 {
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&(-1i16).to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+    }
+
+    data.extend(array(2, TIMESTAMPS));
+    data.extend(array(2, VALUES));
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&1000u32.to_le_bytes());
+    for x in [0.0f32, 1.0] {
+        data.extend_from_slice(&x.to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+    }
+
+    data
+}
+
+#[test]
+#[cfg(feature = "wotlk")]
+fn texture_transforms() -> Result<(), anyhow::Error> {
+    let asset = M2Reader::parse_asset(&mut Cursor::new(texture_transform_m2()))?;
+
+    assert_eq!(asset.global_loops, [1000]);
+    assert_eq!(asset.texture_transform_combos, [0]);
+
+    let transform = asset.texture_transform(0).expect("combo 0 has a transform");
+    assert!(transform.translation.is_animated());
+    assert!(!transform.rotation.is_animated());
+    assert_eq!(transform.translation.global_sequence(), Some(0));
+    assert_eq!(transform.translation.timestamps, [vec![0, 1000]]);
+    assert_eq!(transform.translation.values[0][1].x, 1.0);
+
+    assert!(asset.texture_transform(1).is_none());
+    Ok(())
+}
@@ -1,19 +1,20 @@
 #![allow(non_snake_case)] // we use the exact wording from wowdev.wiki
 use crate::ParserError;
 use crate::common::reader::Parseable;
-use crate::common::types::{C2Vector, C3Vector};
+use crate::common::types::{C2Vector, C3Vector, C4Quaternion, CAaBox, FourCC};
 use crate::m2::reader::M2Reader;
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::io::{Read, Write};
 
-pub const FOURCC_M2HEADER: u32 = u32::from_le_bytes(*b"MD20");
+pub const FOURCC_M2HEADER: FourCC = FourCC::new(b"MD20");
 
 #[cfg(feature = "wotlk")] // >= WOTLK
-pub const FOURCC_M2SKIN: u32 = u32::from_le_bytes(*b"SKIN");
+pub const FOURCC_M2SKIN: FourCC = FourCC::new(b"SKIN");
 
 #[repr(C, packed)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct M2Array {
     pub size: u32,
     pub offset: u32, // relative to the chunk (legion+?) or the start of file.
@@ -28,15 +29,25 @@ impl Parseable<M2Array> for M2Array {
     }
 }
 
-#[allow(dead_code)] // At some point we will use it, bounding boxes etc.
-#[repr(C, packed)]
-struct M2Range {
-    minimum: u32,
-    maximum: u32,
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct M2Range {
+    pub minimum: u32,
+    pub maximum: u32,
+}
+
+impl Parseable<M2Range> for M2Range {
+    fn parse<R: Read>(rdr: &mut R) -> Result<M2Range, ParserError> {
+        Ok(M2Range {
+            minimum: rdr.read_u32::<LittleEndian>()?,
+            maximum: rdr.read_u32::<LittleEndian>()?,
+        })
+    }
 }
 
 #[repr(C, packed)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     pub major: u8, // always 1
     pub minor: u8, // classic: [0, 1], tbc: [4, 7], wotlk: 8
@@ -53,21 +64,93 @@ impl Parseable<Version> for Version {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct M2Asset {
-    pub magic: u32,
+    pub magic: FourCC,
     pub version: Version,
     pub name: String,
     // TODO: incomplete.
+    pub sequences: Vec<M2Sequence>,
+    pub bones: Vec<M2CompBone>,
     pub vertices: Vec<M2Vertex>,
     #[cfg(not(feature = "wotlk"))] // <= TBC
     pub skin_profiles: Vec<M2SkinProfile>,
     #[cfg(feature = "wotlk")] // > TBC
     pub num_skin_profiles: u32,
     pub textures: Vec<M2Texture>,
+    /// The durations (in ms) of the global loops, that tracks with a global sequence are timed by
+    pub global_loops: Vec<u32>,
+    pub texture_transforms: Vec<M2TextureTransform>,
+    /// Indexed by the batches, points into `texture_transforms` (-1 for none)
+    pub texture_transform_combos: Vec<i16>,
+    pub(crate) bounding_box: CAaBox,
+    pub(crate) bounding_sphere_radius: f32,
+    pub(crate) collision_box: CAaBox,
+    pub(crate) collision_sphere_radius: f32,
+    // Only the arrays are kept for now, the M2RibbonEmitter and M2Particle structs aren't parsed (yet).
+    pub(crate) ribbon_emitters: M2Array,
+    pub(crate) particle_emitters: M2Array,
 }
 
 impl M2Asset {
+    pub fn num_ribbon_emitters(&self) -> u32 {
+        self.ribbon_emitters.size
+    }
+
+    pub fn num_particle_emitters(&self) -> u32 {
+        self.particle_emitters.size
+    }
+
+    /// The bounds of the model's geometry (in model space), as stored in the header.
+    pub fn bounding_box(&self) -> CAaBox {
+        self.bounding_box
+    }
+
+    pub fn bounding_sphere_radius(&self) -> f32 {
+        self.bounding_sphere_radius
+    }
+
+    /// The bounds of the (simplified) collision geometry, which may be empty if the model has no collision.
+    pub fn collision_box(&self) -> CAaBox {
+        self.collision_box
+    }
+
+    pub fn collision_sphere_radius(&self) -> f32 {
+        self.collision_sphere_radius
+    }
+
+    /// Whether this model only consists of ribbon and/or particle emitters, without any geometry on its own.
+    /// Such models (e.g. fire or smoke emitters) cannot be rendered as a regular mesh.
+    pub fn is_emitter_only(&self) -> bool {
+        (self.num_ribbon_emitters() > 0 || self.num_particle_emitters() > 0) && self.vertices.is_empty()
+    }
+
+    /// The texture transform of the given combo (see [`M2Asset::texture_transform_combos`]), if any.
+    pub fn texture_transform(&self, combo: usize) -> Option<&M2TextureTransform> {
+        let index = usize::try_from(*self.texture_transform_combos.get(combo)?).ok()?;
+        self.texture_transforms.get(index)
+    }
+
+    /// The bones without a parent, i.e. the roots of the bind pose hierarchy.
+    pub fn root_bones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bones
+            .iter()
+            .enumerate()
+            .filter(|(_, bone)| bone.parent().is_none())
+            .map(|(index, _)| index)
+    }
+
+    /// The direct children of the given bone.
+    pub fn child_bones(&self, bone: usize) -> impl Iterator<Item = usize> + '_ {
+        self.bones
+            .iter()
+            .enumerate()
+            .filter(move |(_, child)| child.parent() == Some(bone))
+            .map(|(index, _)| index)
+    }
+
     pub fn dump_to_wavefront_obj<W: Write>(&self, w: &mut W, skin: &M2SkinProfile) -> Result<(), ParserError> {
+        skin.validate(self)?;
         write!(w, "o {}\n", &self.name)?;
         // g for groups/submeshes.
         for v in &skin.vertices {
@@ -93,6 +176,7 @@ impl M2Asset {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct M2Vertex {
     /// friendly reminder that WoW is right handed (Z Up)
     pub pos: C3Vector,
@@ -115,7 +199,249 @@ impl Parseable<M2Vertex> for M2Vertex {
     }
 }
 
+bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
+    pub struct M2SequenceFlags: u32 {
+        /// Sets 0x80 when loaded (M2Init)
+        const INIT = 0x1;
+        #[cfg(feature = "wotlk")]
+        /// The animation data is in the .m2 file, otherwise it's in a separate .anim file
+        const PRIMARY_BONE_SEQUENCE = 0x20;
+        /// This is an alias of another sequence (see [`M2Sequence::aliasNext`])
+        const IS_ALIAS = 0x40;
+        const BLENDED = 0x80;
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// An animation, `id` refers to AnimationData.dbc
+pub struct M2Sequence {
+    pub id: u16,
+    pub variationIndex: u16,
+    #[cfg(not(feature = "wotlk"))] // <= TBC
+    pub start_timestamp: u32,
+    #[cfg(not(feature = "wotlk"))] // <= TBC
+    pub end_timestamp: u32,
+    #[cfg(feature = "wotlk")] // > TBC
+    pub duration: u32,
+    pub movespeed: f32,
+    pub flags: M2SequenceFlags,
+    /// How often this variation is played, all variations of an animation sum up to 0x7FFF
+    pub frequency: i16,
+    pub replay: M2Range,
+    pub blendTime: u32,
+    pub bounds: CAaBox,
+    pub bounds_radius: f32,
+    /// The next variation of the same animation, -1 for none
+    pub variationNext: i16,
+    pub aliasNext: u16,
+}
+
+impl M2Sequence {
+    /// The length of the animation in milliseconds.
+    #[cfg(feature = "wotlk")] // > TBC
+    pub fn duration(&self) -> u32 {
+        self.duration
+    }
+
+    /// The length of the animation in milliseconds.
+    #[cfg(not(feature = "wotlk"))] // <= TBC
+    pub fn duration(&self) -> u32 {
+        self.end_timestamp.saturating_sub(self.start_timestamp)
+    }
+}
+
+impl Parseable<M2Sequence> for M2Sequence {
+    fn parse<R: Read>(rdr: &mut R) -> Result<M2Sequence, ParserError> {
+        let id = rdr.read_u16::<LittleEndian>()?;
+        let variationIndex = rdr.read_u16::<LittleEndian>()?;
+        #[cfg(not(feature = "wotlk"))] // <= TBC
+        let start_timestamp = rdr.read_u32::<LittleEndian>()?;
+        #[cfg(not(feature = "wotlk"))] // <= TBC
+        let end_timestamp = rdr.read_u32::<LittleEndian>()?;
+        #[cfg(feature = "wotlk")] // > TBC
+        let duration = rdr.read_u32::<LittleEndian>()?;
+        let movespeed = rdr.read_f32::<LittleEndian>()?;
+        let flags = M2SequenceFlags::from_bits_retain(rdr.read_u32::<LittleEndian>()?);
+        let frequency = rdr.read_i16::<LittleEndian>()?;
+        let _padding = rdr.read_u16::<LittleEndian>()?;
+
+        Ok(M2Sequence {
+            id,
+            variationIndex,
+            #[cfg(not(feature = "wotlk"))] // <= TBC
+            start_timestamp,
+            #[cfg(not(feature = "wotlk"))] // <= TBC
+            end_timestamp,
+            #[cfg(feature = "wotlk")] // > TBC
+            duration,
+            movespeed,
+            flags,
+            frequency,
+            replay: M2Range::parse(rdr)?,
+            blendTime: rdr.read_u32::<LittleEndian>()?,
+            bounds: CAaBox::parse(rdr)?,
+            bounds_radius: rdr.read_f32::<LittleEndian>()?,
+            variationNext: rdr.read_i16::<LittleEndian>()?,
+            aliasNext: rdr.read_u16::<LittleEndian>()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// The header of an M2Track, the keyframes themselves aren't resolved (yet).
+pub struct M2TrackHeader {
+    /// 0: none, 1: linear, 2: bezier, 3: hermite
+    pub interpolation_type: u16,
+    /// Index into the global loops, -1 if the track is timed by the sequences
+    pub global_sequence: i16,
+    #[cfg(not(feature = "wotlk"))] // <= TBC
+    pub(crate) interpolation_ranges: M2Array,
+    /// > TBC: One array of timestamps per sequence, <= TBC: all timestamps, split by the interpolation ranges
+    pub(crate) timestamps: M2Array,
+    pub(crate) values: M2Array,
+}
+
+impl M2TrackHeader {
+    pub fn is_animated(&self) -> bool {
+        self.timestamps.size > 0
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A M2Track whose keyframes have been resolved. > TBC, there's one list of timestamps (in ms) and values per
+/// sequence (or a single one for global sequences), <= TBC all keyframes are in one list. Sequences whose keyframes
+/// are in a separate .anim file are left empty.
+pub struct M2Track<T> {
+    /// 0: none, 1: linear, 2: bezier, 3: hermite
+    pub interpolation_type: u16,
+    /// Index into [`M2Asset::global_loops`], -1 if the track is timed by the sequences
+    pub global_sequence: i16,
+    pub timestamps: Vec<Vec<u32>>,
+    pub values: Vec<Vec<T>>,
+}
+
+impl<T> M2Track<T> {
+    pub fn is_animated(&self) -> bool {
+        self.timestamps
+            .iter()
+            .any(|timestamps| !timestamps.is_empty())
+    }
+
+    pub fn global_sequence(&self) -> Option<usize> {
+        usize::try_from(self.global_sequence).ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Animates the texture coordinates, e.g. to let water or fire scroll. Rotations are around (0.5, 0.5).
+pub struct M2TextureTransform {
+    pub translation: M2Track<C3Vector>,
+    pub rotation: M2Track<C4Quaternion>,
+    pub scaling: M2Track<C3Vector>,
+}
+
+#[derive(Debug)]
+/// The [`M2TextureTransform`] as stored in the file, before its tracks have been resolved.
+pub(crate) struct M2TextureTransformInternal {
+    pub translation: M2TrackHeader,
+    pub rotation: M2TrackHeader,
+    pub scaling: M2TrackHeader,
+}
+
+impl Parseable<M2TextureTransformInternal> for M2TextureTransformInternal {
+    fn parse<R: Read>(rdr: &mut R) -> Result<M2TextureTransformInternal, ParserError> {
+        Ok(M2TextureTransformInternal {
+            translation: M2TrackHeader::parse(rdr)?,
+            rotation: M2TrackHeader::parse(rdr)?,
+            scaling: M2TrackHeader::parse(rdr)?,
+        })
+    }
+}
+
+impl Parseable<M2TrackHeader> for M2TrackHeader {
+    fn parse<R: Read>(rdr: &mut R) -> Result<M2TrackHeader, ParserError> {
+        Ok(M2TrackHeader {
+            interpolation_type: rdr.read_u16::<LittleEndian>()?,
+            global_sequence: rdr.read_i16::<LittleEndian>()?,
+            #[cfg(not(feature = "wotlk"))] // <= TBC
+            interpolation_ranges: M2Array::parse(rdr)?,
+            timestamps: M2Array::parse(rdr)?,
+            values: M2Array::parse(rdr)?,
+        })
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
+    pub struct M2CompBoneFlags: u32 {
+        const IGNORE_PARENT_TRANSLATE = 0x1;
+        const IGNORE_PARENT_SCALE = 0x2;
+        const IGNORE_PARENT_ROTATION = 0x4;
+        const SPHERICAL_BILLBOARD = 0x8;
+        const CYLINDRICAL_BILLBOARD_LOCK_X = 0x10;
+        const CYLINDRICAL_BILLBOARD_LOCK_Y = 0x20;
+        const CYLINDRICAL_BILLBOARD_LOCK_Z = 0x40;
+        const TRANSFORMED = 0x200;
+        const KINEMATIC_BONE = 0x400;
+        const HELMET_ANIM_SCALED = 0x1000;
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct M2CompBone {
+    /// Index into the key bone lookup (e.g. 0: ArmL, 1: ArmR), -1 for ordinary bones
+    pub key_bone_id: i32,
+    pub flags: M2CompBoneFlags,
+    /// -1 for root bones
+    pub parent_bone: i16,
+    pub submesh_id: u16,
+    #[cfg(any(feature = "wotlk", feature = "tbc"))] // >= TBC
+    pub boneNameCRC: u32,
+    /// M2Track<C3Vector>
+    pub translation: M2TrackHeader,
+    /// M2Track<M2CompQuat> (> Vanilla: compressed into 4 i16)
+    pub rotation: M2TrackHeader,
+    /// M2Track<C3Vector>
+    pub scale: M2TrackHeader,
+    /// The position the bone rotates and scales around (in model space)
+    pub pivot: C3Vector,
+}
+
+impl M2CompBone {
+    pub fn parent(&self) -> Option<usize> {
+        usize::try_from(self.parent_bone).ok()
+    }
+}
+
+impl Parseable<M2CompBone> for M2CompBone {
+    fn parse<R: Read>(rdr: &mut R) -> Result<M2CompBone, ParserError> {
+        Ok(M2CompBone {
+            key_bone_id: rdr.read_i32::<LittleEndian>()?,
+            flags: M2CompBoneFlags::from_bits_retain(rdr.read_u32::<LittleEndian>()?),
+            parent_bone: rdr.read_i16::<LittleEndian>()?,
+            submesh_id: rdr.read_u16::<LittleEndian>()?,
+            #[cfg(any(feature = "wotlk", feature = "tbc"))] // >= TBC
+            boneNameCRC: rdr.read_u32::<LittleEndian>()?,
+            translation: M2TrackHeader::parse(rdr)?,
+            rotation: M2TrackHeader::parse(rdr)?,
+            scale: M2TrackHeader::parse(rdr)?,
+            pivot: C3Vector::parse(rdr)?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum M2TextureType {
     /// Texture given in filename
     None,
@@ -152,6 +478,8 @@ pub enum M2TextureType {
 
 bitflags! {
     #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
     pub struct M2TextureFlags: u32 {
         const WRAP_X = 0x1;
         const WRAP_Y = 0x2;
@@ -159,6 +487,7 @@ bitflags! {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct M2Texture {
     // TODO: better typing for type and flags.
     pub texture_type: M2TextureType,
@@ -209,9 +538,10 @@ impl Parseable<M2TextureInternal> for M2TextureInternal {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct M2SkinProfile {
     #[cfg(feature = "wotlk")] // >= WOTLK
-    pub magic: u32, // on tbc, this is just inside the main m2 file.
+    pub magic: FourCC, // on tbc, this is just inside the main m2 file.
     pub vertices: Vec<u16>,
     pub indices: Vec<u16>,
     // TODO: implement
@@ -221,7 +551,36 @@ pub struct M2SkinProfile {
     pub boneCountMax: u32,
 }
 
+impl M2SkinProfile {
+    /// Skin profiles are separate files, so a skin that doesn't belong to the given model (or is corrupt) would
+    /// reference vertices out of range. This checks that all indices are valid for the given model.
+    pub fn validate(&self, asset: &M2Asset) -> Result<(), ParserError> {
+        if self
+            .vertices
+            .iter()
+            .any(|&vertex| vertex as usize >= asset.vertices.len())
+        {
+            return Err(ParserError::FormatError {
+                reason: "Skin references vertices that are out of range for the M2",
+            });
+        }
+
+        if self
+            .indices
+            .iter()
+            .any(|&index| index as usize >= self.vertices.len())
+        {
+            return Err(ParserError::FormatError {
+                reason: "Skin indices are out of range of the skin vertices",
+            });
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct M2SkinSection {
     pub skinSectionId: u16,  // Mesh part ID
     pub Level: u16, // (level << 16) is added (|ed) to startTriangle and alike to avoid having to increase those fields to uint32s.
@@ -0,0 +1,5 @@
+pub mod reader;
+pub mod types;
+
+#[cfg(test)]
+mod tests;
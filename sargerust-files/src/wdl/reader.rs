@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::io::ErrorKind::UnexpectedEof;
+use std::io::{Cursor, Read};
+
+use crate::ParserError;
+use crate::common::types::{FourCC, IffChunk, MVerChunk};
+use crate::wdl::types::{MAHOChunk, MAOFChunk, MAREChunk, WDLAsset, WDLTile};
+
+pub struct WDLReader {}
+
+impl WDLReader {
+    pub fn parse_asset<R: Read>(rdr: &mut R) -> Result<WDLAsset, ParserError> {
+        // MAOF references the MARE chunks by their absolute file offset, so we need to know where each chunk starts.
+        let mut data = Vec::new();
+        rdr.read_to_end(&mut data)?;
+        let mut rdr = Cursor::new(data);
+
+        let version_hdr = IffChunk::read_next_chunk(&mut rdr)?;
+        if !version_hdr.magic_str().eq("MVER") {
+            return Err(ParserError::InvalidMagicValue {
+                expected: FourCC::new(b"MVER"),
+                found: version_hdr.fourcc(),
+            });
+        }
+
+        let mver = version_hdr.parse::<MVerChunk>()?;
+        if mver.version != 18 {
+            return Err(ParserError::FormatError {
+                reason: "Unknown MVER Version, expected 18",
+            });
+        }
+
+        let mut chunk_list = Vec::<(u64, IffChunk)>::new();
+        loop {
+            let position = rdr.position();
+            match IffChunk::read_next_chunk(&mut rdr) {
+                Ok(chunk) => chunk_list.push((position, chunk)),
+                // weird error handling because when EoF, we get that inside a parser error.
+                Err(ParserError::IOError(inner_error)) if inner_error.kind() == UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let maof_chunk = chunk_list
+            .iter()
+            .map(|(_, chunk)| chunk)
+            .find(|chunk| chunk.magic_str().eq("MAOF"))
+            .ok_or(ParserError::FormatError {
+                reason: "Missing mandatory MAOF chunk",
+            })?;
+
+        if maof_chunk.size != 64 * 64 * 4 {
+            return Err(ParserError::FormatError {
+                reason: "Invalid MAOF Chunk size",
+            });
+        }
+        let maof = maof_chunk.parse::<MAOFChunk>()?;
+
+        let chunk_indices = chunk_list
+            .iter()
+            .enumerate()
+            .map(|(index, (position, _))| (*position, index))
+            .collect::<HashMap<_, _>>();
+
+        let tiles = maof
+            .areaLowOffsets
+            .iter()
+            .map(|&offset| {
+                if offset == 0 {
+                    return Ok(None);
+                }
+
+                let index = *chunk_indices
+                    .get(&u64::from(offset))
+                    .ok_or(ParserError::FormatError {
+                        reason: "MAOF offset doesn't point to a chunk",
+                    })?;
+
+                let mare_chunk = &chunk_list[index].1;
+                if !mare_chunk.is_magic("MARE") {
+                    return Err(ParserError::InvalidMagicValue {
+                        expected: FourCC::new(b"MARE"),
+                        found: mare_chunk.fourcc(),
+                    });
+                }
+
+                // The holes directly follow the heights, if present at all.
+                let maho = chunk_list
+                    .get(index + 1)
+                    .map(|(_, chunk)| chunk)
+                    .filter(|chunk| chunk.is_magic("MAHO"))
+                    .map(|chunk| chunk.parse::<MAHOChunk>())
+                    .transpose()?;
+
+                Ok(Some(WDLTile {
+                    mare: mare_chunk.parse::<MAREChunk>()?,
+                    maho,
+                }))
+            })
+            .collect::<Result<Vec<_>, ParserError>>()?;
+
+        Ok(WDLAsset { maof, tiles })
+    }
+}
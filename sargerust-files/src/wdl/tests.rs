@@ -0,0 +1,79 @@
+use std::io::Cursor;
+
+use crate::ParserError;
+use crate::wdl::reader::WDLReader;
+
+fn chunk(magic: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = magic.iter().rev().copied().collect::<Vec<_>>();
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+    chunk
+}
+
+fn heights(count: i16) -> Vec<u8> {
+    (0..count)
+        .flat_map(|height| (height - 100).to_le_bytes())
+        .collect()
+}
+
+#[test]
+fn parse_tiles() -> Result<(), anyhow::Error> {
+    let mver = chunk(b"MVER", &18u32.to_le_bytes());
+    let maof_size = 8 + 64 * 64 * 4;
+    let mare_offset = (mver.len() + maof_size) as u32;
+
+    // tiles (1, 2) with holes and (3, 0) without
+    let mut offsets = vec![0u32; 64 * 64];
+    offsets[2 * 64 + 1] = mare_offset;
+    let mare = chunk(b"MARE", &[heights(17 * 17), heights(16 * 16)].concat());
+    let mut holes = [0u8; 32];
+    holes[2 * 5] = 1 << 3;
+    let maho = chunk(b"MAHO", &holes);
+    offsets[3] = mare_offset + (mare.len() + maho.len()) as u32;
+
+    let mut data = mver;
+    data.extend(chunk(
+        b"MAOF",
+        &offsets
+            .iter()
+            .flat_map(|offset| offset.to_le_bytes())
+            .collect::<Vec<_>>(),
+    ));
+    data.extend(&mare);
+    data.extend(maho);
+    data.extend(&mare);
+
+    let asset = WDLReader::parse_asset(&mut Cursor::new(data))?;
+    assert_eq!(asset.tiles.len(), 64 * 64);
+    assert_eq!(
+        asset
+            .existing_tiles()
+            .map(|(x, y, _)| (x, y))
+            .collect::<Vec<_>>(),
+        [(3, 0), (1, 2)]
+    );
+
+    let tile = asset.tile(1, 2).expect("Tile (1, 2)");
+    assert_eq!(tile.mare.outer_height(0, 0), -100);
+    assert_eq!(tile.mare.outer_height(16, 16), 17 * 17 - 101);
+    assert_eq!(tile.mare.inner_height(1, 0), 16 - 100);
+    let maho = tile.maho.expect("Holes of tile (1, 2)");
+    assert!(maho.is_hole(5, 3));
+    assert!(!maho.is_hole(5, 4));
+
+    assert!(asset.tile(3, 0).expect("Tile (3, 0)").maho.is_none());
+    assert!(asset.tile(0, 0).is_none());
+    Ok(())
+}
+
+#[test]
+fn reject_dangling_offset() {
+    let mut offsets = vec![0u8; 64 * 64 * 4];
+    offsets[0] = 0x42;
+
+    let mut data = chunk(b"MVER", &18u32.to_le_bytes());
+    data.extend(chunk(b"MAOF", &offsets));
+
+    let result = WDLReader::parse_asset(&mut Cursor::new(data));
+    assert!(matches!(result, Err(ParserError::FormatError { .. })));
+}
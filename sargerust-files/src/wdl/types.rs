@@ -0,0 +1,98 @@
+#![allow(non_snake_case)] // we use the exact wording from wowdev.wiki
+use crate::ParserError;
+use crate::common::reader::Parseable;
+use sargerust_files_derive_parseable::Parse;
+use std::io::Read;
+// https://wowdev.wiki/WDL
+
+pub struct WDLAsset {
+    pub maof: MAOFChunk,
+    /// 64 * 64 entries in the order of [`MAOFChunk::areaLowOffsets`], `None` for tiles without terrain
+    pub tiles: Vec<Option<WDLTile>>,
+}
+
+#[derive(Debug, Parse)]
+/// SMAreaLowOffsets: absolute file offsets of the MARE chunks, 0 for tiles without terrain.
+pub struct MAOFChunk {
+    pub areaLowOffsets: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WDLTile {
+    pub mare: MAREChunk,
+    pub maho: Option<MAHOChunk>,
+}
+
+#[derive(Debug, Clone)]
+/// The heights of a tile, at the corners (outer, 17x17) and centers (inner, 16x16) of a regular grid, row by row.
+pub struct MAREChunk {
+    pub outer: Vec<i16>,
+    pub inner: Vec<i16>,
+}
+
+impl MAREChunk {
+    pub fn outer_height(&self, row: usize, column: usize) -> i16 {
+        assert!(row < 17 && column < 17);
+        self.outer[row * 17 + column]
+    }
+
+    pub fn inner_height(&self, row: usize, column: usize) -> i16 {
+        assert!(row < 16 && column < 16);
+        self.inner[row * 16 + column]
+    }
+}
+
+impl Parseable<MAREChunk> for MAREChunk {
+    fn parse<R: Read>(rdr: &mut R) -> Result<MAREChunk, ParserError> {
+        let outer = (0..17 * 17)
+            .map(|_| i16::parse(rdr))
+            .collect::<Result<Vec<_>, _>>()?;
+        let inner = (0..16 * 16)
+            .map(|_| i16::parse(rdr))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MAREChunk { outer, inner })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+/// One 16 bit mask per row of the 16x16 grid, a set bit marks a hole.
+pub struct MAHOChunk {
+    pub holes: [u16; 16],
+}
+
+impl MAHOChunk {
+    pub fn is_hole(&self, row: usize, column: usize) -> bool {
+        assert!(row < 16 && column < 16);
+        self.holes[row] & (1 << column) != 0
+    }
+}
+
+impl Parseable<MAHOChunk> for MAHOChunk {
+    fn parse<R: Read>(rdr: &mut R) -> Result<MAHOChunk, ParserError> {
+        let mut holes = [0u16; 16];
+        for row in &mut holes {
+            *row = u16::parse(rdr)?;
+        }
+
+        Ok(MAHOChunk { holes })
+    }
+}
+
+impl WDLAsset {
+    pub fn tile(&self, chunk_x: u8, chunk_y: u8) -> Option<&WDLTile> {
+        let range = 0..64;
+        assert!(range.contains(&chunk_x));
+        assert!(range.contains(&chunk_y));
+
+        self.tiles[64usize * chunk_y as usize + chunk_x as usize].as_ref()
+    }
+
+    /// All tiles that have low resolution terrain, as (x, y, tile).
+    pub fn existing_tiles(&self) -> impl Iterator<Item = (u8, u8, &WDLTile)> + '_ {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tile)| Some(((index % 64) as u8, (index / 64) as u8, tile.as_ref()?)))
+    }
+}
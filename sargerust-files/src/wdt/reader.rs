@@ -2,7 +2,7 @@ use std::io::ErrorKind::UnexpectedEof;
 use std::io::Read;
 
 use crate::ParserError;
-use crate::common::types::{IffChunk, MVerChunk};
+use crate::common::types::{FourCC, IffChunk, MVerChunk};
 use crate::wdt::types::{MPHDChunk, MWMOChunk, MainChunk, SMMapObjDef, WDTAsset};
 
 pub struct WDTReader {}
@@ -13,7 +13,8 @@ impl WDTReader {
         let version_hdr = IffChunk::read_next_chunk(rdr)?;
         if !version_hdr.magic_str().eq("MVER") {
             return Err(ParserError::InvalidMagicValue {
-                magic: version_hdr.magic,
+                expected: FourCC::new(b"MVER"),
+                found: version_hdr.fourcc(),
             });
         }
 
@@ -14,3 +14,20 @@ fn simple_parse() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[test]
+fn global_wmo() -> Result<(), anyhow::Error> {
+    let test_data = std::env::current_dir()?.join("test-data");
+
+    // The tram has no terrain, only a single WMO
+    let mut file = BufReader::new(File::open(
+        test_data.join("World_Maps_DeeprunTram_DeeprunTram.wdt"),
+    )?);
+    let asset = WDTReader::parse_asset(&mut file)?;
+
+    let (filename, _) = asset.global_wmo().expect("Global WMO");
+    assert!(filename.to_lowercase().ends_with(".wmo"));
+    assert_eq!(asset.existing_tiles().count(), 0);
+
+    Ok(())
+}
@@ -117,6 +117,7 @@ impl From<&IffChunk> for MWMOChunk {
 }
 
 #[derive(Debug, Copy, Clone, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMMapObjDef {
     pub nameId: u32,
     pub uniqueId: u32,
@@ -138,4 +139,24 @@ impl WDTAsset {
 
         self.main.map_area_info[64usize * chunk_y as usize + chunk_x as usize].flags != 0
     }
+
+    /// All tiles that have an ADT, as (x, y) in the order of [`WDTAsset::has_chunk`].
+    pub fn existing_tiles(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.main
+            .map_area_info
+            .iter()
+            .enumerate()
+            .filter(|(_, info)| info.flags != 0)
+            .map(|(index, _)| ((index % 64) as u8, (index / 64) as u8))
+    }
+
+    /// Maps without terrain (e.g. most instances) consist of a single global WMO instead of ADTs. Returns its
+    /// filename and placement.
+    pub fn global_wmo(&self) -> Option<(&str, &SMMapObjDef)> {
+        if !self.mphd.flags.contains(MPHDFlags::WDT_USES_GLOBAL_MAP_OBJ) {
+            return None;
+        }
+
+        Some((&self.mwmo.as_ref()?.filename, self.modf.as_ref()?))
+    }
 }
@@ -3,7 +3,7 @@ use std::io::{Cursor, Read, Seek, SeekFrom};
 
 use crate::ParserError;
 use crate::common::reader::Parseable;
-use crate::common::types::{IffChunk, MVerChunk};
+use crate::common::types::{FourCC, IffChunk, MVerChunk};
 use crate::wmo::types::{
     MFOGChunk, MOBAChunk, MOBNChunk, MOBRChunk, MOCVChunk, MODDChunk, MODNChunk, MODRChunk, MODSChunk, MOGIChunk,
     MOGNChunk, MOGPChunk, MOHDChunk, MOLRChunk, MOLTChunk, MOMTChunk, MONRChunk, MOPYChunk, MOSBChunk, MOTVChunk,
@@ -18,7 +18,8 @@ impl WMOReader {
         let version_hdr = IffChunk::read_next_chunk(rdr)?;
         if !version_hdr.magic_str().eq("MVER") {
             return Err(ParserError::InvalidMagicValue {
-                magic: version_hdr.magic,
+                expected: FourCC::new(b"MVER"),
+                found: version_hdr.fourcc(),
             });
         }
 
@@ -96,7 +97,8 @@ impl WMOReader {
         let version_hdr = IffChunk::read_next_chunk(rdr)?;
         if !version_hdr.magic_str().eq("MVER") {
             return Err(ParserError::InvalidMagicValue {
-                magic: version_hdr.magic,
+                expected: FourCC::new(b"MVER"),
+                found: version_hdr.fourcc(),
             });
         }
 
@@ -24,3 +24,25 @@ fn parse_group() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[test]
+fn parse_group_bsp() -> Result<(), anyhow::Error> {
+    let test_data = std::env::current_dir()?.join("test-data");
+    let mut file = BufReader::new(File::open(
+        test_data.join("World_wmo_Dungeon_AZ_Subway_Subway_000.wmo"),
+    )?);
+    let group_asset = WMOReader::parse_group(&mut file)?;
+
+    let mobn = group_asset.mobn.expect("MOBN chunk");
+    let mobr = group_asset.mobr.expect("MOBR chunk");
+    assert!(mobn.nodeList.len() > 1);
+
+    for node in mobn.nodeList.iter().filter(|node| node.is_leaf()) {
+        let face_range = node.faceStart as usize..node.faceStart as usize + node.nFaces as usize;
+        for &face in &mobr.nodeFaceIndices[face_range] {
+            assert!((face as usize) < group_asset.mopy.polyList.len());
+        }
+    }
+
+    Ok(())
+}
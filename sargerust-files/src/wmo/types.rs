@@ -10,12 +10,13 @@ use num_enum::FromPrimitive;
 use sargerust_files_derive_parseable::Parse;
 
 use crate::ParserError;
-use crate::common::reader::{GenericStringList, Parseable, read_chunk_array, read_cstring};
+use crate::common::reader::{GenericStringList, Parseable, read_cstring};
 use crate::common::types::{C2Vector, C3Vector, C4Quaternion, CAaBox, CArgb, CImVector, MVerChunk};
 
 // https://wowdev.wiki/WMO
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WMORootAsset {
     pub mver: MVerChunk,
     pub mohd: MOHDChunk,
@@ -33,6 +34,7 @@ pub struct WMORootAsset {
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Also known as SMOHeader
 pub struct MOHDChunk {
     pub nTextures: u32,
@@ -69,6 +71,7 @@ impl Parseable<MOHDChunk> for MOHDChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOTXChunk {
     pub textureNameList: Vec<String>,
     pub offsets: HashMap<u32, usize>,
@@ -85,6 +88,7 @@ impl Parseable<MOTXChunk> for MOTXChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMOMaterial {
     pub flags: u32,
     pub shader: u32,
@@ -127,20 +131,14 @@ impl Parseable<SMOMaterial> for SMOMaterial {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOMTChunk {
     pub materialList: Vec<SMOMaterial>,
 }
 
-impl Parseable<MOMTChunk> for MOMTChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOMTChunk, ParserError> {
-        Ok(MOMTChunk {
-            materialList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOGNChunk {
     pub groupNameList: Vec<String>,
     pub offset_lookup: HashMap<u32, usize>,
@@ -157,6 +155,7 @@ impl Parseable<MOGNChunk> for MOGNChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMOGroupInfo {
     pub flags: u32,
     pub bounding_box: CAaBox,
@@ -173,20 +172,14 @@ impl Parseable<SMOGroupInfo> for SMOGroupInfo {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOGIChunk {
     pub groupInfoList: Vec<SMOGroupInfo>,
 }
 
-impl Parseable<MOGIChunk> for MOGIChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOGIChunk, ParserError> {
-        Ok(MOGIChunk {
-            groupInfoList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOSBChunk {
     pub skyboxName: String,
 }
@@ -216,6 +209,7 @@ impl Parseable<MOSBChunk> for MOSBChunk {
 */
 #[repr(u8)]
 #[derive(FromPrimitive, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SMOLightLightType {
     OMNI_LGT = 0,
     SPOT_LGT = 1,
@@ -226,6 +220,7 @@ pub enum SMOLightLightType {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMOLight {
     pub lightType: SMOLightLightType,
     pub useAtten: u8,
@@ -258,20 +253,14 @@ impl Parseable<SMOLight> for SMOLight {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOLTChunk {
     pub lightList: Vec<SMOLight>,
 }
 
-impl Parseable<MOLTChunk> for MOLTChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOLTChunk, ParserError> {
-        Ok(MOLTChunk {
-            lightList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMODoodadSet {
     pub name: String,
     pub startIndex: u32,
@@ -296,20 +285,14 @@ impl Parseable<SMODoodadSet> for SMODoodadSet {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MODSChunk {
     pub doodadSetList: Vec<SMODoodadSet>,
 }
 
-impl Parseable<MODSChunk> for MODSChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MODSChunk, ParserError> {
-        Ok(MODSChunk {
-            doodadSetList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MODNChunk {
     pub doodadNameList: Vec<String>,
     pub doodadNameListLookup: HashMap<u32, usize>,
@@ -326,6 +309,7 @@ impl Parseable<MODNChunk> for MODNChunk {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// https://wowdev.wiki/WMO#MODD_chunk
 pub struct SMODoodadDef {
     pub nameIndex: u32, // actually, u24
@@ -356,20 +340,14 @@ impl Parseable<SMODoodadDef> for SMODoodadDef {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MODDChunk {
     pub doodadDefList: Vec<SMODoodadDef>,
 }
 
-impl Parseable<MODDChunk> for MODDChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MODDChunk, ParserError> {
-        Ok(MODDChunk {
-            doodadDefList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMOFog {
     pub flags: u32,
     pub pos: C3Vector,
@@ -401,22 +379,16 @@ impl Parseable<SMOFog> for SMOFog {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MFOGChunk {
     pub fogList: Vec<SMOFog>,
 }
 
-impl Parseable<MFOGChunk> for MFOGChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MFOGChunk, ParserError> {
-        Ok(MFOGChunk {
-            fogList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 // WMO group file
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WMOGroupAsset {
     pub mver: MVerChunk,
     pub mogp: MOGPChunk,
@@ -434,6 +406,7 @@ pub struct WMOGroupAsset {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOGPChunk {
     pub groupName: u32,            // offset into MOGN
     pub descriptiveGroupName: u32, // offset into MOGN
@@ -475,77 +448,60 @@ impl Parseable<MOGPChunk> for MOGPChunk {
 }
 
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMOPoly {
-    pub flags: u8,       // TODO: kind of important
+    pub flags: u8,
     pub material_id: u8, // index into MOMT, 0xFF for collision faces.
 }
 
-#[derive(Debug)]
-pub struct MOPYChunk {
-    pub polyList: Vec<SMOPoly>,
-}
+impl SMOPoly {
+    pub const F_NOCAMCOLLIDE: u8 = 0x02;
+    pub const F_DETAIL: u8 = 0x04;
+    pub const F_COLLISION: u8 = 0x08;
+    pub const F_RENDER: u8 = 0x20;
 
-impl Parseable<MOPYChunk> for MOPYChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOPYChunk, ParserError> {
-        Ok(MOPYChunk {
-            polyList: Vec::<SMOPoly>::parse(rdr)?,
-        })
+    pub fn is_render_face(&self) -> bool {
+        self.flags & Self::F_RENDER != 0 && self.flags & Self::F_DETAIL == 0
+    }
+
+    /// Whether the face blocks movement, which includes invisible collision-only faces (material 0xFF).
+    pub fn is_collidable(&self) -> bool {
+        self.flags & Self::F_COLLISION != 0 || self.is_render_face()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MOPYChunk {
+    pub polyList: Vec<SMOPoly>,
+}
+
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOVIChunk {
     pub indices: Vec<u16>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOVTChunk {
     pub vertexList: Vec<C3Vector>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MONRChunk {
     pub normalList: Vec<C3Vector>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOTVChunk {
     pub textureVertexList: Vec<C2Vector>,
 }
 
-impl Parseable<MOVIChunk> for MOVIChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOVIChunk, ParserError> {
-        Ok(MOVIChunk {
-            indices: read_chunk_array(rdr)?,
-        })
-    }
-}
-
-impl Parseable<MOVTChunk> for MOVTChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOVTChunk, ParserError> {
-        Ok(MOVTChunk {
-            vertexList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
-impl Parseable<MONRChunk> for MONRChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MONRChunk, ParserError> {
-        Ok(MONRChunk {
-            normalList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
-impl Parseable<MOTVChunk> for MOTVChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOTVChunk, ParserError> {
-        Ok(MOTVChunk {
-            textureVertexList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SMOBatch {
     // bounding box for culling.
     pub bx: u16,
@@ -562,46 +518,26 @@ pub struct SMOBatch {
     pub material_id: u8, // Index in MOMT
 }
 
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOBAChunk {
     pub batchList: Vec<SMOBatch>,
 }
 
-impl Parseable<MOBAChunk> for MOBAChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOBAChunk, ParserError> {
-        Ok(MOBAChunk {
-            batchList: Vec::<SMOBatch>::parse(rdr)?,
-        })
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MOLRChunk {
     pub lightRefList: Vec<u16>,
 }
 
-impl Parseable<MOLRChunk> for MOLRChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOLRChunk, ParserError> {
-        Ok(MOLRChunk {
-            lightRefList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MODRChunk {
     pub doodadRefList: Vec<u16>,
 }
 
-impl Parseable<MODRChunk> for MODRChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MODRChunk, ParserError> {
-        Ok(MODRChunk {
-            doodadRefList: read_chunk_array(rdr)?,
-        })
-    }
-}
-
 #[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CAaBspNode {
     pub flags: u16,
     pub negChild: i16,
@@ -611,30 +547,28 @@ pub struct CAaBspNode {
     pub planeDist: f32,
 }
 
-pub type MOBNChunk = CAaBspNode;
+impl CAaBspNode {
+    pub const FLAG_LEAF: u16 = 0x4;
 
-#[derive(Debug)]
-pub struct MOBRChunk {
-    pub nodeFaceIndices: Vec<u16>,
+    pub fn is_leaf(&self) -> bool {
+        self.flags & Self::FLAG_LEAF != 0
+    }
 }
 
-impl Parseable<MOBRChunk> for MOBRChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOBRChunk, ParserError> {
-        Ok(MOBRChunk {
-            nodeFaceIndices: read_chunk_array(rdr)?,
-        })
-    }
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MOBNChunk {
+    pub nodeList: Vec<CAaBspNode>,
 }
 
-#[derive(Debug)]
-pub struct MOCVChunk {
-    pub colorVertexList: Vec<CImVector>,
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MOBRChunk {
+    pub nodeFaceIndices: Vec<u16>,
 }
 
-impl Parseable<MOCVChunk> for MOCVChunk {
-    fn parse<R: Read>(rdr: &mut R) -> Result<MOCVChunk, ParserError> {
-        Ok(MOCVChunk {
-            colorVertexList: read_chunk_array(rdr)?,
-        })
-    }
+#[derive(Debug, Parse)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MOCVChunk {
+    pub colorVertexList: Vec<CImVector>,
 }
@@ -9,7 +9,9 @@
 // TODO: Why don't we required the 16 byte padding here?
 struct GpuTerrainData {
     base_texture: u32,
-    additional_layers: array<u32, 6>,
+    layers: array<u32, 4>,
+    // one channel per layer
+    alpha_map: u32,
     flags: u32,
 }
 
@@ -79,7 +81,7 @@ fn vs_main(@builtin(instance_index) instance_index: u32, @builtin(vertex_index)
 
 @fragment
 fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
-    var material = materials[vs_out.material]; // needs to be var, otherwise accessing additional_layers[i] won't work.
+    var material = materials[vs_out.material]; // needs to be var, otherwise accessing layers[i] won't work.
     if (material.base_texture == 0u) {
         return vec4<f32>(0.5, 0.0, 0.0, 1.0);
     }
@@ -97,28 +99,29 @@ fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
     let tex_z = textureSample(base_tex, nearest_sampler, vs_out.vertex_relative.xy * tex_scale);
     var albedo_sum = tex_x * blend_weights.x + tex_y * blend_weights.y + tex_z * blend_weights.z;
 
-    for (var i = 0; i < 3; i++) {
-        let tex_index = material.additional_layers[2 * i];
-        let alpha_index = material.additional_layers[2 * i + 1];
+    var alphas = vec4(0.0);
+    if (material.alpha_map != 0u) {
+        alphas = textureSample(textures[material.alpha_map - 1u], primary_sampler, vs_out.vertex_relative.zx);
+    }
 
-        if (tex_index != 0 && alpha_index == 0) {
-            return vec4(1.0, 0.0, 0.0, 1.0);
+    for (var i = 0; i < 4; i++) {
+        let tex_index = material.layers[i];
+        if (tex_index == 0u) {
+            continue;
         }
 
-        if (tex_index == 0u || alpha_index == 0u) {
-            continue; // TODO: continue or break?
+        if (material.alpha_map == 0u) {
+            return vec4(1.0, 0.0, 0.0, 1.0);
         }
 
         let albedo_tex = textures[tex_index - 1u];
-        let alpha_tex = textures[alpha_index - 1u];
 
         let tex_x = textureSample(albedo_tex, nearest_sampler, vs_out.vertex_relative.zy * tex_scale);
         let tex_y = textureSample(albedo_tex, nearest_sampler, vs_out.vertex_relative.zx * tex_scale);
         let tex_z = textureSample(albedo_tex, nearest_sampler, vs_out.vertex_relative.xy * tex_scale);
         let albedo = tex_x * blend_weights.x + tex_y * blend_weights.y + tex_z * blend_weights.z;
 
-        let alpha = textureSample(alpha_tex, primary_sampler, vs_out.vertex_relative.zx).r;
-        albedo_sum = mix(albedo_sum, albedo, alpha);
+        albedo_sum = mix(albedo_sum, albedo, alphas[i]);
     }
 
     return vec4(albedo_sum.xyz, 1.0);
@@ -9,6 +9,7 @@
 struct GpuUnitsData{
     texture_layers: array<u32, 3>,
     flags: u32,
+    uv_transform: mat3x3<f32>,
 }
 
 // whole frame uniform bind group
@@ -70,7 +71,7 @@ fn vs_main(@builtin(instance_index) instance_index: u32, @builtin(vertex_index)
 fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
     var material = materials[vs_out.material]; // needs to be var, otherwise accessing additional_layers[i] won't work.
 
-    let coords = vs_out.coords0;
+    let coords = (material.uv_transform * vec3<f32>(vs_out.coords0, 1.0)).xy;
     let uvdx = dpdx(coords);
     let uvdy = dpdy(coords);
 
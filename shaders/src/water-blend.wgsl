@@ -0,0 +1,57 @@
+{{include "rend3-routine/structures.wgsl"}}
+{{include "rend3-routine/structures_object.wgsl"}}
+{{include "rend3-routine/material.wgsl"}}
+
+struct GpuWaterData {
+    color: vec4<f32>,
+}
+
+// whole frame uniform bind group
+@group(0) @binding(0)
+var primary_sampler: sampler;
+@group(0) @binding(1)
+var nearest_sampler: sampler;
+
+// per material bind group
+@group(1) @binding(0)
+var<storage> object_buffer: array<Object>;
+@group(1) @binding(1)
+var<storage> vertex_buffer: array<u32>;
+@group(1) @binding(2)
+var<storage> per_camera_uniform: PerCameraUniform;
+@group(1) @binding(3)
+var<storage> materials: array<GpuWaterData>;
+
+// texture bind group
+@group(2) @binding(0)
+var textures: binding_array<texture_2d<f32>>;
+
+{{
+    vertex_fetch
+    object_buffer
+    position
+}}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) material: u32,
+}
+
+@vertex
+fn vs_main(@builtin(instance_index) instance_index: u32, @builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let indices = Indices(instance_index, vertex_index);
+    let data = object_buffer[indices.object];
+    let vs_in = get_vertices(indices);
+
+    let model_view_proj = per_camera_uniform.view_proj * data.transform;
+
+    var vs_out: VertexOutput;
+    vs_out.material = data.material_index;
+    vs_out.position = model_view_proj * vec4<f32>(vs_in.position, 1.0);
+    return vs_out;
+}
+
+@fragment
+fn fs_main(vs_out: VertexOutput) -> @location(0) vec4<f32> {
+    return materials[vs_out.material].color;
+}
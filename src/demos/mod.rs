@@ -1,8 +1,12 @@
+use crate::io;
 use crate::io::common::loader::RawAssetLoader;
 use crate::io::mpq::loader::MPQLoader;
 use crate::rendering;
+use crate::rendering::common::camera;
+use crate::rendering::common::camera::FieldOfView;
 use crate::rendering::common::coordinate_systems;
 use crate::rendering::common::highlevel_types::PlacedDoodad;
+use crate::rendering::common::movement::{MovementConfig, MovementInput};
 use crate::rendering::common::special_types::TerrainTextureLayer;
 use crate::rendering::common::types::{AlbedoType, Material, Mesh, MeshWithLod};
 use crate::rendering::importer::adt_importer::ADTImporter;
@@ -10,6 +14,7 @@ use crate::rendering::importer::m2_importer::M2Importer;
 use crate::rendering::loader::blp_loader::BLPLoader;
 use crate::rendering::loader::m2_loader::{LoadedM2, M2Loader};
 use crate::rendering::loader::wmo_loader::WMOLoader;
+use anyhow::Context;
 use glam::{Affine3A, DVec2, Mat4, Vec3, Vec3A};
 use image_blp::BlpImage;
 use itertools::Itertools;
@@ -41,7 +46,7 @@ struct DemoApplication {
 pub fn render<'a, W>(
     placed_doodads: Vec<PlacedDoodad>,
     wmos: W,
-    textures: HashMap<String, BlpImage>,
+    textures: HashMap<String, Arc<BlpImage>>,
     terrain_chunk: Vec<(Vec3, Mesh, Vec<TerrainTextureLayer>)>,
     camera_location: Vec3A,
 ) where
@@ -121,7 +126,12 @@ pub fn render<'a, W>(
 
     let mut object_list = Vec::new(); // we need to prevent object handles from getting dropped.
 
-    rendering::add_placed_doodads(&placed_doodads, &renderer, &mut object_list);
+    rendering::add_placed_doodads(
+        &placed_doodads,
+        camera_location,
+        &renderer,
+        &mut object_list,
+    );
     rendering::add_wmo_groups(wmos, &textures, &renderer, &mut object_list);
     rendering::add_terrain_chunks(&terrain_chunk, &renderer, &mut object_list);
 
@@ -132,10 +142,11 @@ pub fn render<'a, W>(
 
     // Set camera's location
     renderer.set_camera_data(rend3::types::Camera {
-        projection: rend3::types::CameraProjection::Perspective {
-            vfov: 90.0,
-            near: 0.1,
-        },
+        projection: rend3::types::CameraProjection::Raw(camera::projection(
+            FieldOfView::default(),
+            camera::aspect_ratio(glam::UVec2::new(window_size.width, window_size.height)),
+            None,
+        )),
         view,
     });
 
@@ -152,6 +163,8 @@ pub fn render<'a, W>(
     });
 
     let mut resolution = glam::UVec2::new(window_size.width, window_size.height);
+    let movement = MovementConfig::default();
+    let mut timestamp_last_frame = Instant::now();
 
     event_loop
         .run(move |event, _| match event {
@@ -191,35 +204,32 @@ pub fn render<'a, W>(
                 let right: Vec3A = rotation.x_axis;
                 let up: Vec3A = rotation.z_axis;
 
-                if *app.scancode_status.get(&17u32).unwrap_or(&false) {
-                    // W
-                    app.camera_location += forward * 30.0 / 60.0 * 5.0; // fake delta time
-                }
-                if *app.scancode_status.get(&31u32).unwrap_or(&false) {
-                    // S
-                    app.camera_location -= forward * 30.0 / 60.0; // fake delta time
-                }
-                if *app.scancode_status.get(&30u32).unwrap_or(&false) {
-                    // A
-                    app.camera_location -= right * 20.0 / 60.0;
-                }
-                if *app.scancode_status.get(&32u32).unwrap_or(&false) {
-                    // D
-                    app.camera_location += right * 20.0 / 60.0;
-                }
-                if *app.scancode_status.get(&42u32).unwrap_or(&false) {
-                    // LSHIFT
-                    app.camera_location += up * 20.0 / 60.0;
+                let now = Instant::now();
+                let delta_time = (now - timestamp_last_frame).as_secs_f32();
+                timestamp_last_frame = now;
+
+                let pressed = |scancode: u32| *app.scancode_status.get(&scancode).unwrap_or(&false);
+                let input = MovementInput {
+                    forward: pressed(17),  // W
+                    backward: pressed(31), // S
+                    left: pressed(30),     // A
+                    right: pressed(32),    // D
+                    up: pressed(42),       // LSHIFT
+                    down: pressed(29),     // LCTRL
+                };
+                let turn_right = pressed(57421);
+                let turn_left = pressed(57419);
+
+                app.camera_location += movement
+                    .fly
+                    .displacement(&input, forward, right, up, delta_time);
+
+                // The demo yaw is in units of PI
+                if turn_right {
+                    app.camera_yaw += movement.turn_rate / PI * delta_time;
                 }
-                if *app.scancode_status.get(&29u32).unwrap_or(&false) {
-                    app.camera_location -= up * 20.0 / 60.0;
-                }
-                if *app.scancode_status.get(&57421u32).unwrap_or(&false) {
-                    // arrow right
-                    app.camera_yaw += 1.0 / 60.0;
-                }
-                if *app.scancode_status.get(&57419u32).unwrap_or(&false) {
-                    app.camera_yaw -= 1.0 / 60.0;
+                if turn_left {
+                    app.camera_yaw -= movement.turn_rate / PI * delta_time;
                 }
 
                 // technically, we could also invert the view rotation (remember this is not the cams matrix, but the _view_ matrix, so how do you transform
@@ -235,10 +245,11 @@ pub fn render<'a, W>(
 
                 // Set camera's location
                 renderer.set_camera_data(rend3::types::Camera {
-                    projection: rend3::types::CameraProjection::Perspective {
-                        vfov: 90.0,
-                        near: 0.1,
-                    },
+                    projection: rend3::types::CameraProjection::Raw(camera::projection(
+                        FieldOfView::default(),
+                        camera::aspect_ratio(resolution),
+                        None,
+                    )),
                     view,
                 });
 
@@ -342,7 +353,7 @@ pub fn main_simple_m2(loader: &MPQLoader) -> Result<(), anyhow::Error> {
         loader.load_raw_owned(skin_path).unwrap(),
     ))?;
     let blp_opt = BLPLoader::load_blp_from_ldr(loader, tex_path);
-    let imported_mesh = M2Importer::create_mesh(&m2, &skin);
+    let imported_mesh = M2Importer::create_mesh(&m2, &skin)?;
     let mat = M2Importer::create_material(&blp_opt);
 
     let dad = PlacedDoodad {
@@ -351,6 +362,9 @@ pub fn main_simple_m2(loader: &MPQLoader) -> Result<(), anyhow::Error> {
             mesh: imported_mesh,
             material: mat,
             blp_opt,
+            is_emitter_only: m2.is_emitter_only(),
+            lods: None,
+            bounding_box: M2Importer::create_bounding_box(&m2),
         }),
     };
 
@@ -396,11 +410,20 @@ pub fn main_simple_wmo(loader: &MPQLoader) -> Result<(), anyhow::Error> {
     let dooads = loaded
         .doodads
         .iter()
-        // Resolve references
-        .map(|dad| PlacedDoodad {
-            transform: dad.transform,
-            m2: load_m2_doodad(loader, &mut m2_cache, &dad.m2_ref),
-        })
+        // Resolve references, doodads that fail to load are skipped
+        .filter_map(
+            |dad| match load_m2_doodad(loader, &mut m2_cache, &dad.m2_ref) {
+                Ok(m2) => Some(PlacedDoodad {
+                    transform: dad.transform,
+                    m2,
+                }),
+                Err(err) => {
+                    warn!("{:#}", err);
+                    None
+                }
+            },
+        )
+        .filter(|dad| !dad.m2.is_emitter_only)
         .collect_vec();
 
     let group_list = loaded.loaded_groups;
@@ -492,7 +515,7 @@ fn handle_adt(
     adt: &ADTAsset,
     m2_cache: &mut HashMap<String, Arc<LoadedM2>>,
     render_list: &mut Vec<PlacedDoodad>,
-    texture_map: &mut HashMap<String, BlpImage>,
+    texture_map: &mut HashMap<String, Arc<BlpImage>>,
     wmos: &mut Vec<(Affine3A, Vec<(MeshWithLod, Vec<Material>)>)>,
 ) -> Result<Vec<(Vec3, Mesh, Vec<TerrainTextureLayer>)>, anyhow::Error> {
     for wmo_ref in adt.modf.mapObjDefs.iter() {
@@ -507,7 +530,7 @@ fn handle_adt(
             continue; // TODO: Temporary performance optimization
         }
 
-        let loaded = WMOLoader::load(loader, name)?;
+        let loaded = WMOLoader::load_with_set(loader, name, wmo_ref.doodadSet)?;
         // TODO: currently, only WMO makes use of texture names, M2s load their textures in load_m2_doodad (when the doodad becomes placeable).
         let textures = loaded
             .loaded_groups
@@ -531,16 +554,27 @@ fn handle_adt(
             // NOTE: Here we loose the relationship between DAD and wmo, that is required for parenting.
             // Since rend3 does not have a scenegraph, we "fake" the parenting for now.
             // Also we need to resolve m2 references.
+            let m2 = match load_m2_doodad(loader, m2_cache, &dad.m2_ref) {
+                Ok(m2) => m2,
+                Err(err) => {
+                    warn!("{:#}", err);
+                    continue;
+                }
+            };
+            if m2.is_emitter_only {
+                continue;
+            }
+
             render_list.push(PlacedDoodad {
                 transform: transform * dad.transform,
-                m2: load_m2_doodad(loader, m2_cache, &dad.m2_ref),
+                m2,
             });
         }
 
         wmos.push((transform, loaded.loaded_groups));
     }
 
-    // TODO: deduplicate with collect doodads (at least the emitter and m2 name replacement)
+    // TODO: deduplicate with collect doodads (at least the m2 name replacement)
     for dad_ref in &adt.mddf.doodadDefs {
         let name = &adt.mmdx.filenames[*adt
             .mmdx
@@ -549,16 +583,19 @@ fn handle_adt(
             .unwrap()];
         trace!("M2 {} has been referenced from ADT", name);
 
-        // fix name: currently it ends with .mdx but we need .m2
-        let name = name
-            .to_lowercase()
-            .replace(".mdx", ".m2")
-            .replace(".mdl", ".m2");
-        if name.to_lowercase().contains("emitter") {
+        let name = io::normalize_model_path(name);
+
+        let entry = match load_m2_doodad(loader, m2_cache, &name) {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("{:#}", err);
+                continue;
+            }
+        };
+        if entry.is_emitter_only {
             continue;
         }
 
-        let entry = load_m2_doodad(loader, m2_cache, &name);
         render_list.push(PlacedDoodad {
             transform: crate::transform_for_doodad_ref(dad_ref),
             m2: entry,
@@ -574,19 +611,28 @@ fn handle_adt(
             unused: [0, 0, 0, 0, 0, 0],
         };
 
-        terrain_chunk.push(ADTImporter::create_mesh(mcnk, false, &adt.mtex, &mphd)?);
+        terrain_chunk.push(ADTImporter::create_mesh(
+            mcnk, false, true, &adt.mtex, &mphd,
+        )?);
     }
 
     Ok(terrain_chunk)
 }
 
-#[allow(deprecated)] // only for the demo, we load m2s in non-graph style, which is easier for us
-fn load_m2_doodad(loader: &MPQLoader, m2_cache: &mut HashMap<String, Arc<LoadedM2>>, name: &str) -> Arc<LoadedM2> {
+// only for the demo, we load m2s in non-graph style, which is easier for us
+fn load_m2_doodad(
+    loader: &MPQLoader,
+    m2_cache: &mut HashMap<String, Arc<LoadedM2>>,
+    name: &str,
+) -> Result<Arc<LoadedM2>, anyhow::Error> {
     // Caching M2s is unavoidable in some way, especially when loading multiple chunks in parallel.
     // Otherwise, m2s could be loaded multiple times, but the important thing is to deduplicate
     // them before sending them to the render thread. Share meshes and textures!
-    let entry = m2_cache
-        .entry(name.to_string())
-        .or_insert_with(|| Arc::new(M2Loader::load_no_lod(loader, name)));
-    entry.clone()
+    if let Some(entry) = m2_cache.get(name) {
+        return Ok(entry.clone());
+    }
+
+    let entry = Arc::new(M2Loader::load_with_lods(loader, name).with_context(|| format!("Cannot load {}", name))?);
+    m2_cache.insert(name.to_string(), entry.clone());
+    Ok(entry)
 }
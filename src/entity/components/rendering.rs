@@ -1,15 +1,26 @@
 use crate::rendering::asset_graph::nodes::adt_node::{IRTexture, M2Node};
-use rend3::types::ObjectHandle;
+use crate::rendering::rend3_backend::material::units::units_material::UnitsMaterial;
+use rend3::types::{MaterialHandle, ObjectHandle};
+use std::collections::HashSet;
 use std::sync::{Arc, RwLock};
 
 #[derive(Default, Debug, Clone)]
 pub enum RenderableSource {
     #[default]
     DebugCube,
-    M2(Arc<M2Node>, Vec<Arc<RwLock<Option<IRTexture>>>>),
+    /// The model, its dynamic textures and the geosets to show (see
+    /// [`crate::rendering::importer::m2_importer::M2Importer::is_geoset_visible`]), all
+    /// geosets are shown without a whitelist.
+    M2(
+        Arc<M2Node>,
+        Vec<Arc<RwLock<Option<IRTexture>>>>,
+        Option<HashSet<u16>>,
+    ),
 }
 #[derive(Default, Debug, Clone)]
 pub struct Renderable {
     pub handle: Option<ObjectHandle>,
     pub source: RenderableSource,
+    /// Materials with a texture animation need to be updated every frame
+    pub animated_material: Option<(MaterialHandle, UnitsMaterial)>,
 }
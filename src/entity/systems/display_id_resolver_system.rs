@@ -1,53 +1,63 @@
 use crate::entity::components::rendering::{Renderable, RenderableSource};
 use crate::entity::components::units::UnitDisplayId;
 use crate::game::application::GameApplication;
-use crate::io::common::loader::RawAssetLoader;
+use crate::io;
+use crate::io::dbc;
 use crate::io::mpq::loader::MPQLoader;
 use crate::rendering::asset_graph::m2_generator::M2Generator;
 use crate::rendering::asset_graph::nodes::adt_node::{IRTexture, M2Node};
 use crate::rendering::asset_graph::resolver::Resolver;
 use hecs::Without;
 use itertools::Itertools;
-use log::{info, warn};
+use log::{error, info, warn};
 use sargerust_files::m2::types::M2TextureType;
-use std::io::Cursor;
+use std::collections::HashSet;
+use std::ops::Deref;
 use std::sync::{Arc, RwLock};
+use wow_dbc::Indexable;
 use wow_dbc::wrath_tables::creature_display_info::CreatureDisplayInfo;
 use wow_dbc::wrath_tables::creature_model_data::CreatureModelData;
-use wow_dbc::{DbcTable, Indexable};
 
 pub struct DisplayIdResolverSystem {
-    creature_display_info: CreatureDisplayInfo,
-    creature_model_data: CreatureModelData,
+    /// None if either of the tables couldn't be loaded, then no units are displayed.
+    tables: Option<(CreatureDisplayInfo, CreatureModelData)>,
     m2_resolver: Resolver<M2Generator, M2Node>,
     tex_resolver: Resolver<M2Generator, RwLock<Option<IRTexture>>>,
 }
 
 impl DisplayIdResolverSystem {
     pub fn new(mpq_loader: Arc<MPQLoader>) -> Self {
-        let cdi_buf = mpq_loader
-            .load_raw_owned("DBFilesClient\\CreatureDisplayInfo.dbc")
-            .expect("Failed to load CreatureDisplayInfo.dbc");
-
-        let cmi_buf = mpq_loader
-            .load_raw_owned("DBFilesClient\\CreatureModelData.dbc")
-            .expect("Failed to load CreatureModelData.dbc");
-
-        let creature_display_info =
-            CreatureDisplayInfo::read(&mut Cursor::new(cdi_buf)).expect("Failed to parse Creature Display Info");
-
-        let creature_model_data =
-            CreatureModelData::read(&mut Cursor::new(cmi_buf)).expect("Failed to parse Creature Model Info");
+        let tables = dbc::load_dbc(mpq_loader.deref(), "DBFilesClient\\CreatureDisplayInfo.dbc")
+            .and_then(|creature_display_info| {
+                let creature_model_data = dbc::load_dbc(mpq_loader.deref(), "DBFilesClient\\CreatureModelData.dbc")?;
+                Ok((creature_display_info, creature_model_data))
+            })
+            .inspect_err(|err| error!("{}, units won't be displayed", err))
+            .ok();
 
         Self {
-            creature_display_info,
-            creature_model_data,
+            tables,
             m2_resolver: Resolver::new(M2Generator::new(mpq_loader.clone())),
             tex_resolver: Resolver::new(M2Generator::new(mpq_loader.clone())),
         }
     }
 
+    /// CreatureGeosetData packs the variant of the geoset groups 1 to 8 into one nibble each, e.g. 0x21 shows the
+    /// geosets 101 and 202. Groups that aren't specified show their first variant.
+    fn geoset_whitelist(creature_geoset_data: i32) -> HashSet<u16> {
+        (0..8)
+            .map(|group| {
+                let variant = (creature_geoset_data as u32 >> (group * 4)) & 0xF;
+                ((group + 1) * 100 + variant.max(1)) as u16
+            })
+            .collect()
+    }
+
     pub fn update(&self, app: &GameApplication) {
+        let Some((display_info_table, model_data_table)) = &self.tables else {
+            return;
+        };
+
         let mut write = app
             .entity_tracker
             .world()
@@ -58,15 +68,12 @@ impl DisplayIdResolverSystem {
 
         for (entity, display_id) in write.query_mut::<Without<&UnitDisplayId, &Renderable>>() {
             // Freshly added entities
-            let Some(creature_display_info) = self.creature_display_info.get(display_id.0) else {
+            let Some(creature_display_info) = display_info_table.get(display_id.0) else {
                 warn!("No CreatureDisplayInfo for DisplayId {}", display_id.0);
                 continue;
             };
 
-            let Some(creature_model_data) = self
-                .creature_model_data
-                .get(creature_display_info.model_id.id)
-            else {
+            let Some(creature_model_data) = model_data_table.get(creature_display_info.model_id.id) else {
                 warn!(
                     "No CreatureModelData for ModelId {}",
                     creature_display_info.model_id.id
@@ -74,20 +81,26 @@ impl DisplayIdResolverSystem {
                 continue;
             };
 
-            // fix name: currently it ends with .mdx, but we need .m2
-            let name = creature_model_data
-                .model_name
-                .to_lowercase()
-                .replace(".mdx", ".m2")
-                .replace(".mdl", ".m2");
+            let name = io::normalize_model_path(&creature_model_data.model_name);
 
             let base_path = name.split_at(name.rfind('\\').expect("No \\ in name")).0;
 
-            let result = self.m2_resolver.resolve(name.clone());
+            let result = match self.m2_resolver.resolve(name.clone()) {
+                Ok(result) => result,
+                Err(err) => {
+                    warn!(
+                        "Cannot load the model of DisplayId {}: {:#}",
+                        display_id.0, err
+                    );
+                    continue;
+                }
+            };
 
             for reference in &result.tex_reference {
-                let resolve = self.tex_resolver.resolve(reference.reference_str.clone());
-                *reference.reference.write().expect("Write Lock") = Some(resolve);
+                match self.tex_resolver.resolve(reference.reference_str.clone()) {
+                    Ok(resolve) => *reference.reference.write().expect("Write Lock") = Some(resolve),
+                    Err(err) => warn!("Skipping Texture {}: {:#}", reference.reference_str, err),
+                }
             }
 
             let resolved_dynamic_textures = result
@@ -106,20 +119,28 @@ impl DisplayIdResolverSystem {
 
                     let tex_name = format!("{}\\{}.blp", base_path, tex_file_name);
 
-                    Some(self.tex_resolver.resolve(tex_name.clone()))
+                    self.tex_resolver
+                        .resolve(tex_name.clone())
+                        .inspect_err(|err| warn!("Skipping Texture {}: {:#}", tex_name, err))
+                        .ok()
                 })
                 .collect_vec();
 
-            new_renderables.push((entity, (result, resolved_dynamic_textures)));
+            let geoset_whitelist = Self::geoset_whitelist(creature_display_info.creature_geoset_data);
+            new_renderables.push((
+                entity,
+                (result, resolved_dynamic_textures, geoset_whitelist),
+            ));
         }
 
-        for (entity, (arc, dynamic_textures)) in new_renderables {
+        for (entity, (arc, dynamic_textures, geoset_whitelist)) in new_renderables {
             write
                 .insert_one(
                     entity,
                     Renderable {
                         handle: None,
-                        source: RenderableSource::M2(arc, dynamic_textures),
+                        source: RenderableSource::M2(arc, dynamic_textures, Some(geoset_whitelist)),
+                        animated_material: None,
                     },
                 )
                 .expect("Insert Renderable");
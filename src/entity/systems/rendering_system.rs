@@ -2,15 +2,22 @@ use crate::entity::components::objects::{TmpLocation, TmpOrientation};
 use crate::entity::components::rendering::{Renderable, RenderableSource};
 use crate::game::application::GameApplication;
 use crate::rendering::application::RenderingApplication;
+use crate::rendering::asset_graph::nodes::adt_node::M2Node;
 use crate::rendering::common::coordinate_systems::{adt_to_blender_rot, adt_to_blender_unaligned};
+use crate::rendering::importer::m2_importer::M2Importer;
+use crate::rendering::rend3_backend::Rend3BackendConverter;
 use crate::rendering::rend3_backend::gpu_loaders;
+use crate::rendering::rend3_backend::gpu_loaders::TextureState;
 use crate::rendering::rend3_backend::material::units::units_material::UnitsMaterial;
 use glam::{Mat4, Quat, Vec4};
 use itertools::Itertools;
+use log::warn;
 use rend3::Renderer;
 use rend3::types::{MaterialHandle, MeshHandle, Object, ObjectMeshKind, Texture2DHandle};
 use rend3_routine::pbr::{AlbedoComponent, PbrMaterial, Transparency};
+use std::collections::HashSet;
 use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Instant;
 
 // cube_example from rend3.
 fn vertex(pos: [f32; 3]) -> glam::Vec3 {
@@ -68,12 +75,15 @@ fn create_debug_mesh() -> rend3::types::Mesh {
 
 pub struct RenderingSystem {
     debug_object: OnceLock<(MeshHandle, MaterialHandle)>,
+    /// Drives the texture animations
+    clock: Instant,
 }
 
 impl RenderingSystem {
     pub fn new() -> Self {
         Self {
             debug_object: OnceLock::new(),
+            clock: Instant::now(),
         }
     }
 
@@ -93,14 +103,51 @@ impl RenderingSystem {
         })
     }
 
-    pub fn update(&self, app: &GameApplication) {
-        let renderer = app.renderer.get().expect("Renderer not initialized");
+    fn animate_material(&self, renderer: &Arc<Renderer>, renderable: &Renderable) {
+        let (RenderableSource::M2(m2, ..), Some((handle, material))) =
+            (&renderable.source, &renderable.animated_material)
+        else {
+            return;
+        };
+
+        let Some(animation) = &m2.texture_animation else {
+            return;
+        };
+
+        let uv_transform = animation.uv_transform(self.clock.elapsed().as_millis() as u64);
+        renderer.update_material(
+            handle,
+            UnitsMaterial {
+                uv_transform,
+                ..material.clone()
+            },
+        );
+    }
+
+    /// Geoset variants differ per entity, so the filtered mesh can't be shared through the node.
+    fn load_geoset_mesh(renderer: &Arc<Renderer>, m2: &M2Node, geoset_whitelist: &HashSet<u16>) -> MeshHandle {
+        let mesh_lock = m2.mesh.read().expect("Mesh Read Lock");
+        let Some(mesh) = mesh_lock.data() else {
+            drop(mesh_lock);
+            warn!("M2 mesh has been hollowed, showing all geosets");
+            return gpu_loaders::gpu_load_mesh(renderer, &m2.mesh);
+        };
+
+        let filtered = M2Importer::filter_geosets(mesh, &m2.geosets, Some(geoset_whitelist));
+        let render_mesh = Rend3BackendConverter::create_mesh_from_ir(&filtered)
+            .unwrap_or_else(|err| panic!("Mesh building failed: {}", err));
+        renderer
+            .add_mesh(render_mesh)
+            .expect("Mesh creation successful")
+    }
 
+    pub fn update(&self, app: &GameApplication, renderer: &Arc<Renderer>) {
         // TODO: Think about the whole hecs threading. We should probably enqueue changes and batch do them in a big write lock?
         //  that way, many threads can perform reading instead of permanently waiting for the one writing thread. And once all
         //  calculations are done, commit things. On the other hand, updates are single threaded currently.
         //  ALSO: hecs claims that mutable access is better. But is it better than not having to lock the world?
 
+        let base_mip_level = app.settings.texture_quality.base_mip_level();
         let mut write = app
             .entity_tracker
             .world()
@@ -118,6 +165,7 @@ impl RenderingSystem {
 
             if let Some(handle) = &renderable.handle {
                 renderer.set_object_transform(handle, transform);
+                self.animate_material(renderer, renderable);
             } else {
                 let object = match &renderable.source {
                     RenderableSource::DebugCube => {
@@ -134,34 +182,37 @@ impl RenderingSystem {
 
                     // TODO: RenderingApplication:are_all_textures_loaded -> Also support gradually loading dynamic
                     //  entities. or at least not adding them until they are ready. Like just "continue".
-                    RenderableSource::M2(m2, dynamic_textures) => {
+                    RenderableSource::M2(m2, dynamic_textures, geoset_whitelist) => {
                         if !RenderingApplication::are_all_textures_loaded(&m2.tex_reference) {
                             continue; // Try the entity again later.
                         }
 
-                        if dynamic_textures
-                            .iter()
-                            .any(|tex| tex.read().expect("Texture read lock").is_none())
-                        {
-                            continue; // Try the entity again later.
-                        }
-
-                        let mesh_handle = gpu_loaders::gpu_load_mesh(renderer, &m2.mesh);
+                        let mesh_handle = match geoset_whitelist {
+                            Some(whitelist) => Self::load_geoset_mesh(renderer, m2, whitelist),
+                            None => gpu_loaders::gpu_load_mesh(renderer, &m2.mesh),
+                        };
 
                         // TODO: A sense of order (as static and dynamic textures could be interleaved), also could they
                         //  then exceed 3? i.e. are there fully equipped dynamic textures still having static ones?
 
                         let material = {
-                            let mut textures = dynamic_textures
-                                .iter()
-                                .map(|tex| gpu_loaders::gpu_load_texture(renderer, &RwLock::new(Some(tex.clone()))))
-                                .chain(
-                                    m2.tex_reference
-                                        .iter()
-                                        .map(|tex| gpu_loaders::gpu_load_texture(renderer, &tex.reference)),
-                                )
-                                .take(3)
-                                .collect_vec();
+                            let mut textures =
+                                dynamic_textures
+                                    .iter()
+                                    .map(|tex| {
+                                        gpu_loaders::gpu_load_texture(
+                                            renderer,
+                                            &RwLock::new(Some(tex.clone())),
+                                            base_mip_level,
+                                        )
+                                    })
+                                    .chain(m2.tex_reference.iter().map(|tex| {
+                                        gpu_loaders::gpu_load_texture(renderer, &tex.reference, base_mip_level)
+                                    }))
+                                    // Textures that failed to load are left empty, they can't be resolved anymore.
+                                    .map(TextureState::handle)
+                                    .take(3)
+                                    .collect_vec();
 
                             for _ in textures.len()..3 {
                                 textures.push(None);
@@ -171,10 +222,16 @@ impl RenderingSystem {
                                 .try_into()
                                 .expect("should match the array length since we call take(3)");
 
-                            UnitsMaterial { texture_layers }
+                            UnitsMaterial {
+                                texture_layers,
+                                ..Default::default()
+                            }
                         };
 
-                        let material_handle = renderer.add_material(material);
+                        let material_handle = renderer.add_material(material.clone());
+                        if m2.texture_animation.is_some() {
+                            renderable.animated_material = Some((material_handle.clone(), material));
+                        }
 
                         Object {
                             mesh_kind: ObjectMeshKind::Static(mesh_handle),
@@ -23,6 +23,10 @@ impl Systems {
     pub fn update(&self, app: &GameApplication, delta_time: f32) {
         self.spline_walker_system.update(app, delta_time);
         self.display_id_resolver_system.update(app);
-        self.rendering_system.update(app);
+
+        // GPU-dependent systems only start once the renderer is available.
+        if let Some(renderer) = app.renderer() {
+            self.rendering_system.update(app, &renderer);
+        }
     }
 }
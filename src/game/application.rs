@@ -1,17 +1,20 @@
 use rend3::Renderer;
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::Receiver;
-use std::sync::{Arc, OnceLock, Weak};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, Weak};
 
 use crate::entity::entity_tracker::EntityTracker;
 use crate::entity::systems::systems::Systems;
 use crate::game::game_state::GameState;
+use crate::game::settings::Settings;
 use crate::io::mpq::loader::MPQLoader;
+use crate::networking::NetworkError;
 use crate::networking::application::NetworkApplication;
 use crate::rendering::application::RenderingApplication;
+use log::error;
 use winit::dpi::LogicalSize;
+use wow_world_messages::wrath::Vector3d;
 use wow_world_messages::wrath::opcodes::ServerOpcodeMessage;
-use wow_world_messages::wrath::{Map, Vector3d};
 
 pub enum GameOperationMode {
     Standalone,
@@ -22,14 +25,17 @@ pub struct GameApplication {
     pub mpq_loader: Arc<MPQLoader>,
     pub game_state: Arc<GameState>,
     pub close_requested: AtomicBool,
-    pub renderer: OnceLock<Arc<Renderer>>,
+    renderer: OnceLock<Arc<Renderer>>,
+    /// Signalled once the renderer has been set, see [`GameApplication::wait_for_renderer`]
+    renderer_ready: (Mutex<()>, Condvar),
     pub network: Option<NetworkApplication>,
     pub entity_tracker: EntityTracker,
+    pub settings: Settings,
     systems: Systems,
     weak_self: Weak<GameApplication>,
 }
 
-const WINDOW_TITLE: &str = concat!(
+pub const WINDOW_TITLE: &str = concat!(
     "Sargerust: Wrath of the Rust King (",
     env!("VERGEN_GIT_BRANCH"),
     "/",
@@ -38,17 +44,29 @@ const WINDOW_TITLE: &str = concat!(
 );
 
 impl GameApplication {
-    pub fn new(weak_self: &Weak<GameApplication>, mpq_loader: MPQLoader) -> Self {
+    pub fn new(weak_self: &Weak<GameApplication>, mpq_loader: MPQLoader, settings: Settings) -> Self {
+        mpq_loader.preload(&[
+            "DBFilesClient\\Map.dbc",
+            "DBFilesClient\\CreatureDisplayInfo.dbc",
+            "DBFilesClient\\CreatureModelData.dbc",
+        ]);
+
         let mpq_loader_arc = Arc::new(mpq_loader);
         Self {
             mpq_loader: mpq_loader_arc.clone(),
             weak_self: weak_self.clone(),
-            game_state: Arc::new(GameState::new(weak_self.clone(), mpq_loader_arc.clone())),
+            game_state: Arc::new(GameState::new(
+                weak_self.clone(),
+                mpq_loader_arc.clone(),
+                &settings,
+            )),
             close_requested: AtomicBool::new(false),
             renderer: OnceLock::new(),
+            renderer_ready: (Mutex::new(()), Condvar::new()),
             entity_tracker: EntityTracker::new(),
             network: None,
             systems: Systems::new(weak_self.clone(), mpq_loader_arc.clone()),
+            settings,
         }
     }
 
@@ -57,10 +75,10 @@ impl GameApplication {
         address: &str,
         username: &str,
         password: &str,
-    ) -> Receiver<Box<ServerOpcodeMessage>> {
-        let (network, receiver) = NetworkApplication::connect(address, username, password);
+    ) -> Result<Receiver<Box<ServerOpcodeMessage>>, NetworkError> {
+        let (network, receiver) = NetworkApplication::connect(address, username, password)?;
         self.network = Some(network);
-        receiver
+        Ok(receiver)
     }
 
     /// Run the game application. This will block until the window is closed and take care of
@@ -87,9 +105,9 @@ impl GameApplication {
         let render_app = RenderingApplication::new(self.weak_self.clone());
 
         if standalone {
-            // TODO: Derive standalone *and* otherwise the map from the launch args.
-            self.game_state.change_map(
-                Map::EasternKingdoms,
+            // TODO: Derive the position from the launch args as well.
+            let result = self.game_state.change_map_from_string(
+                &self.settings.map,
                 Vector3d {
                     x: -8924.0,
                     y: -117.0,
@@ -97,6 +115,11 @@ impl GameApplication {
                 },
                 0.0,
             );
+
+            if let Err(err) = result {
+                error!("{:#}", err);
+                return;
+            }
         }
 
         rend3_framework::start(render_app, wnd); // This blocks until the window is closed
@@ -108,6 +131,39 @@ impl GameApplication {
         }
     }
 
+    /// The renderer only becomes available once the window has been created (see [`RenderingApplication`]'s setup),
+    /// so anything that needs to create GPU resources has to cope with None before that.
+    pub fn renderer(&self) -> Option<Arc<Renderer>> {
+        self.renderer.get().cloned()
+    }
+
+    /// Blocks the calling thread until the renderer is available. Must not be called from the render thread before
+    /// setup, as that would dead-lock.
+    pub fn wait_for_renderer(&self) -> Arc<Renderer> {
+        let (lock, condvar) = &self.renderer_ready;
+        let guard = lock.lock().expect("Renderer Ready Lock poisoned");
+        let _guard = condvar
+            .wait_while(guard, |_| self.renderer.get().is_none())
+            .expect("Renderer Ready Lock poisoned");
+
+        self.renderer
+            .get()
+            .expect("Renderer to be set once signalled")
+            .clone()
+    }
+
+    /// Called exactly once by the rendering application, from then on all GPU-dependent systems run.
+    pub fn set_renderer(&self, renderer: Arc<Renderer>) {
+        let (lock, condvar) = &self.renderer_ready;
+        let _guard = lock.lock().expect("Renderer Ready Lock poisoned");
+
+        if self.renderer.set(renderer).is_err() {
+            panic!("Setting the renderer on Application failed: already initialized");
+        }
+
+        condvar.notify_all();
+    }
+
     pub fn logic_update(&self, delta_time: f32) {
         self.systems.update(self, delta_time);
     }
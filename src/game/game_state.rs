@@ -1,15 +1,19 @@
 use crate::game::application::GameApplication;
 use crate::game::map_manager::MapManager;
-use crate::io::common::loader::RawAssetLoader;
+use crate::game::settings::Settings;
+use crate::io::dbc;
 use crate::io::mpq::loader::MPQLoader;
 use crate::networking::utils::net_vector3d_to_glam;
 use crate::physics::physics_state::PhysicsState;
+use crate::rendering::asset_graph::graphviz;
+use anyhow::anyhow;
 use glam::{Vec3, Vec3A};
-use log::debug;
-use std::io::Cursor;
+use itertools::Itertools;
+use log::{error, info};
 use std::ops::Deref;
 use std::sync::{Arc, RwLock, Weak};
 use wow_dbc::DbcTable;
+use wow_dbc::wrath_tables::map::MapRow;
 use wow_world_messages::wrath::{Map, Vector3d};
 
 /// This is _the_ shared state that is accessed by multiple threads
@@ -21,18 +25,31 @@ pub struct GameState {
     pub player_location: RwLock<Vec3A>,
     pub player_orientation: RwLock<f32>,
     pub physics_state: Arc<RwLock<PhysicsState>>,
-    map_dbc: wow_dbc::wrath_tables::map::Map,
+    /// None if Map.dbc couldn't be loaded, then no map can be entered.
+    map_dbc: Option<wow_dbc::wrath_tables::map::Map>,
+    /// See [`Settings::locale`]
+    locale: String,
 }
 
 impl GameState {
-    pub fn new(app: Weak<GameApplication>, mpq_loader: Arc<MPQLoader>) -> Self {
+    pub fn new(app: Weak<GameApplication>, mpq_loader: Arc<MPQLoader>, settings: &Settings) -> Self {
         Self {
-            map_manager: Arc::new(RwLock::new(MapManager::new(mpq_loader.clone()))),
+            map_manager: Arc::new(RwLock::new(MapManager::new(
+                app.clone(),
+                mpq_loader.clone(),
+                settings.loader_threads,
+                settings.view_distance,
+                settings.unload_distance(),
+                settings.mesh_memory_budget,
+            ))),
             player_location: RwLock::new(Vec3A::new(0.0, 0.0, 0.0)),
             player_orientation: RwLock::new(0.0),
             physics_state: Arc::new(RwLock::new(PhysicsState::new(app.clone()))),
             app,
-            map_dbc: Self::read_map(mpq_loader.deref()),
+            map_dbc: dbc::load_dbc(mpq_loader.deref(), "DBFilesClient\\Map.dbc")
+                .inspect_err(|err| error!("{}, no map can be entered", err))
+                .ok(),
+            locale: settings.locale.clone(),
         }
     }
 
@@ -40,27 +57,127 @@ impl GameState {
         self.app.upgrade().expect("Weak Pointer expired")
     }
 
-    fn read_map(mpq_loader: &MPQLoader) -> wow_dbc::wrath_tables::map::Map {
-        let map_buf = mpq_loader
-            .load_raw_owned("DBFilesClient\\Map.dbc")
-            .expect("Failed to load Map.dbc");
+    /// Dumps the asset graph of all currently loaded tiles in the Graphviz DOT format, see [`graphviz`].
+    pub fn dump_asset_graph(&self) -> String {
+        let map_manager = self.map_manager.read().expect("Map Manager Read Lock");
+        graphviz::tile_graph_to_dot(&map_manager.tile_graph)
+    }
+
+    /// Resets everything that belongs to the previous map: its tiles, the physics colliders and the resolver caches.
+    /// Has to be called before [`GameState::change_map`] whenever the server (re-)places the player into the world.
+    pub fn on_map_changed(&self) {
+        // Locks are taken one after another, never at the same time
+        self.map_manager
+            .write()
+            .expect("Map Manager Write Lock")
+            .clear();
 
-        wow_dbc::wrath_tables::map::Map::read(&mut Cursor::new(map_buf)).expect("Failed to parse Map.dbc")
+        self.physics_state
+            .write()
+            .expect("Physics State Write Lock")
+            .clear_map();
     }
 
-    /// Called when first entering the world and whenever the map changes (teleport, portal)
-    pub fn change_map(&self, map: Map, position: Vector3d, orientation: f32) {
-        let map_row = self
+    /// Resolves a map by its internal name (the directory in Map.dbc, case insensitive), its name in the configured
+    /// locale or by its id, e.g. "Azeroth", "Eastern Kingdoms" or "0". If there is no such map, the error lists the
+    /// closest matching names.
+    pub fn resolve_map(&self, name: &str) -> Result<&MapRow, anyhow::Error> {
+        let name = name.trim();
+        // Every map name contains the empty string, so there is nothing to suggest
+        if name.is_empty() {
+            return Err(anyhow!("No map name given"));
+        }
+
+        let rows = self
             .map_dbc
-            .rows()
+            .as_ref()
+            .ok_or_else(|| anyhow!("Map.dbc is not available"))?
+            .rows();
+
+        if let Some(row) = rows
+            .iter()
+            .find(|row| row.directory.eq_ignore_ascii_case(name))
+            .or_else(|| {
+                rows.iter()
+                    .find(|row| dbc::localized_string(&row.map_name_lang, &self.locale).eq_ignore_ascii_case(name))
+            })
+            .or_else(|| {
+                let id = name.parse::<i32>().ok()?;
+                rows.iter().find(|row| row.id.id == id)
+            })
+        {
+            return Ok(row);
+        }
+
+        let lowercase = name.to_ascii_lowercase();
+        let max_distance = (lowercase.len() / 3).max(2);
+        let suggestions = rows
             .iter()
-            .find(|row| row.id.id as u32 == map.as_int())
-            .unwrap_or_else(|| panic!("Undefined Map {}", map));
+            .map(|row| {
+                let directory = row.directory.to_ascii_lowercase();
+                // Partial names (e.g. "Northrend" for "NorthrendBG") are as good as a typo
+                let distance = if directory.contains(&lowercase) || lowercase.contains(&directory) {
+                    1
+                } else {
+                    edit_distance(&lowercase, &directory)
+                };
+                (distance, row)
+            })
+            .filter(|(distance, _)| *distance <= max_distance)
+            .sorted_by_key(|(distance, row)| (*distance, row.id.id))
+            .take(5)
+            .map(|(_, row)| {
+                format!(
+                    "{} ({}, \"{}\")",
+                    row.directory,
+                    row.id.id,
+                    dbc::localized_string(&row.map_name_lang, &self.locale)
+                )
+            })
+            .collect_vec();
+
+        if suggestions.is_empty() {
+            Err(anyhow!("Unknown map \"{}\"", name))
+        } else {
+            Err(anyhow!(
+                "Unknown map \"{}\", did you mean: {}",
+                name,
+                suggestions.join(", ")
+            ))
+        }
+    }
+
+    /// Like [`GameState::change_map`], but the map is resolved from its name or id first, see
+    /// [`GameState::resolve_map`]. Nothing is loaded if the map can't be resolved.
+    pub fn change_map_from_string(
+        &self,
+        name: &str,
+        position: Vector3d,
+        orientation: f32,
+    ) -> Result<(), anyhow::Error> {
+        let id = self.resolve_map(name)?.id.id;
+        let map = Map::try_from(id as u32).map_err(|_| anyhow!("Map {} is not supported", id))?;
+        self.change_map(map, position, orientation);
+        Ok(())
+    }
+
+    /// Called when first entering the world and whenever the map changes (teleport, portal)
+    pub fn change_map(&self, map: Map, position: Vector3d, orientation: f32) {
+        let Some(map_row) = self.map_dbc.as_ref().and_then(|map_dbc| {
+            map_dbc
+                .rows()
+                .iter()
+                .find(|row| row.id.id as u32 == map.as_int())
+        }) else {
+            error!("Can't switch to map {}, it's not defined in Map.dbc", map);
+            return;
+        };
 
-        // TODO: Somehow handle locales
-        debug!(
-            "Switching to map {} (\"{}\", {})",
-            map, map_row.map_name_lang.de_de, map_row.directory
+        info!(
+            "Entering {} ({}, {})",
+            dbc::localized_string(&map_row.map_name_lang, &self.locale),
+            map,
+            map_row.directory
         );
 
         // It's important to set the player location before loading the map for the first time,
@@ -85,3 +202,22 @@ impl GameState {
         );
     }
 }
+
+/// The Levenshtein distance, i.e. the number of single character edits to get from one string to the other. Bytes are
+/// compared as they are, so callers have to lowercase both strings to ignore the casing.
+pub(super) fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut previous = (0..=b.len()).collect_vec();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.bytes().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
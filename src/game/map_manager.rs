@@ -1,13 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
 use std::ops::DerefMut;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock, Weak};
 use std::time::Instant;
 
-use glam::{Vec3, Vec3A};
+use glam::{Vec2, Vec3, Vec3A};
 use itertools::Itertools;
-use log::{error, info, trace, warn};
+use log::{debug, error, info, trace, warn};
 use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::{broadcast, watch};
 use tokio::task::JoinSet;
 
 use sargerust_files::adt::reader::ADTReader;
@@ -15,6 +16,8 @@ use sargerust_files::adt::types::ADTAsset;
 use sargerust_files::wdt::reader::WDTReader;
 use sargerust_files::wdt::types::{MPHDChunk, SMMapObjDef, WDTAsset};
 
+use crate::game::application::GameApplication;
+use crate::io;
 use crate::io::common::loader::RawAssetLoader;
 use crate::io::mpq::loader::MPQLoader;
 use crate::rendering::asset_graph::m2_generator::M2Generator;
@@ -24,13 +27,54 @@ use crate::rendering::asset_graph::nodes::adt_node::{
 };
 use crate::rendering::asset_graph::resolver::Resolver;
 use crate::rendering::common::coordinate_systems;
+use crate::rendering::common::coordinate_systems::TILE_SIZE;
 use crate::rendering::common::special_types::TerrainTextureLayerRend3;
 use crate::rendering::importer::adt_importer::ADTImporter;
+use crate::rendering::importer::water_importer::WaterImporter;
 use crate::{transform_for_doodad_ref, transform_for_wmo_ref};
 
+/// The key of the global WMO in the tile graph of maps without terrain. As those maps don't have any tiles, it
+/// can't collide with a real tile.
+const GLOBAL_WMO_TILE: (u8, u8) = (0, 0);
+
+/// The progress of loading tiles and their assets, accumulated over the lifetime of the [`MapManager`].
+/// Loading is done when the completed counts match the requested counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub tiles_requested: u32,
+    pub tiles_completed: u32,
+    pub textures_requested: u32,
+    pub textures_resolved: u32,
+}
+
+impl LoadProgress {
+    pub fn is_done(&self) -> bool {
+        self.tiles_completed == self.tiles_requested && self.textures_resolved == self.textures_requested
+    }
+}
+
+/// Emitted whenever a tile has been added to or removed from the tile graph, see [`MapManager::subscribe_tiles`].
+#[derive(Debug, Clone)]
+pub enum TileEvent {
+    Loaded((u8, u8), Arc<ADTNode>),
+    Unloaded((u8, u8)),
+}
+
 pub struct MapManager {
+    app: Weak<GameApplication>,
     runtime: Runtime,
+    progress: Arc<watch::Sender<LoadProgress>>,
+    tile_events: broadcast::Sender<TileEvent>,
     mpq_loader: Arc<MPQLoader>,
+    view_distance: f32,
+    unload_distance: f32,
+    mesh_memory_budget: Option<usize>,
+    /// The WMO groups that are currently being resolved, see [`MapManager::resolve_visible_groups`]
+    pending_groups: Arc<Mutex<HashSet<String>>>,
+    /// The WMO groups that couldn't be resolved, so that they aren't retried on every camera update.
+    failed_groups: Arc<Mutex<HashSet<String>>>,
+    /// Tiles of the current map that couldn't be loaded, so that they aren't retried on every camera update.
+    failed_tiles: HashSet<(u8, u8)>,
     pub current_map: Option<(String, WDTAsset)>,
     pub tile_graph: HashMap<(u8, u8), Arc<ADTNode>>,
     pub m2_resolver: Arc<Resolver<M2Generator, M2Node>>,
@@ -40,9 +84,29 @@ pub struct MapManager {
 }
 
 impl MapManager {
-    pub fn new(mpq_loader: Arc<MPQLoader>) -> Self {
+    /// `loader_threads` bounds both the async workers and the blocking threads that the resolvers run on.
+    /// Tiles are streamed in when they are within `view_distance` of the camera and unloaded again when they are
+    /// further away than `unload_distance`. Uploaded meshes are hollowed once the IR of all meshes exceeds
+    /// `mesh_memory_budget` bytes.
+    pub fn new(
+        app: Weak<GameApplication>,
+        mpq_loader: Arc<MPQLoader>,
+        loader_threads: usize,
+        view_distance: f32,
+        unload_distance: f32,
+        mesh_memory_budget: Option<usize>,
+    ) -> Self {
         Self {
+            app,
             mpq_loader: mpq_loader.clone(),
+            view_distance,
+            unload_distance,
+            mesh_memory_budget,
+            pending_groups: Default::default(),
+            failed_groups: Default::default(),
+            failed_tiles: HashSet::new(),
+            progress: Arc::new(watch::channel(LoadProgress::default()).0),
+            tile_events: broadcast::channel(256).0,
             current_map: None,
             tile_graph: HashMap::new(),
             // TODO: work on sharing the M2Generator.
@@ -51,64 +115,360 @@ impl MapManager {
             wmo_resolver: Arc::new(Resolver::new(M2Generator::new(mpq_loader.clone()))),
             wmo_group_resolver: Arc::new(Resolver::new(M2Generator::new(mpq_loader.clone()))),
             runtime: Builder::new_multi_thread()
+                .worker_threads(loader_threads)
+                .max_blocking_threads(loader_threads)
+                .thread_name("sargerust-loader")
                 .build()
                 .expect("Tokio Runtime to be built"),
         }
     }
 
-    pub fn update_camera(&mut self, position: Vec3A) {
+    /// Subscribes to the loading progress, e.g. to display a loading indicator.
+    pub fn subscribe_progress(&self) -> watch::Receiver<LoadProgress> {
+        self.progress.subscribe()
+    }
+
+    /// Subscribes to tiles being loaded and unloaded, so that the tile graph doesn't need to be diffed. Only changes
+    /// after subscribing are received, so subscribers should take the current tile graph as their starting point.
+    /// The events hold strong references to the tiles, so receivers need to keep up to not delay the unloading.
+    pub fn subscribe_tiles(&self) -> broadcast::Receiver<TileEvent> {
+        self.tile_events.subscribe()
+    }
+
+    fn insert_tile(&mut self, coords: (u8, u8), graph: ADTNode) {
+        let graph = Arc::new(graph);
+        self.tile_graph.insert(coords, graph.clone());
+        // Fails if nobody is subscribed, which is fine.
+        let _ = self.tile_events.send(TileEvent::Loaded(coords, graph));
+    }
+
+    fn remove_tile(&mut self, coords: (u8, u8)) {
+        if self.tile_graph.remove(&coords).is_some() {
+            let _ = self.tile_events.send(TileEvent::Unloaded(coords));
+        }
+    }
+
+    fn remove_all_tiles(&mut self) {
+        for coords in self.tile_graph.keys().copied().collect_vec() {
+            self.remove_tile(coords);
+        }
+    }
+
+    /// The orientation is the server's, see [`MapManager::prioritize_tiles`].
+    pub fn update_camera(&mut self, position: Vec3A, orientation: f32) {
         if self.current_map.is_none() {
             return;
         }
 
-        let coords = coordinate_systems::adt_world_to_tiles(position.into());
-        if self.tile_graph.contains_key(&coords) {
+        self.relieve_memory_pressure();
+
+        // Maps that consist of a single WMO don't have any tiles to stream in, it only needs to be loaded again
+        // after a reload.
+        let global_wmo = self
+            .current_map
+            .as_ref()
+            .and_then(|(_, wdt)| wdt.global_wmo())
+            .map(|(name, map_obj_def)| (name.to_owned(), *map_obj_def));
+        if let Some((name, map_obj_def)) = global_wmo {
+            if self.tile_graph.is_empty() {
+                self.load_global_wmo(name, map_obj_def);
+            }
+            self.resolve_visible_groups(position);
             return;
         }
 
-        // TODO: unloading and proper range based checks once the API around here stabilizesd
-        self.try_load_chunk(&coords);
+        self.unload_far_tiles(position);
+        self.load_tiles_around(position, Some(orientation));
+        self.resolve_visible_groups(position);
+    }
+
+    /// Loads all tiles within the view distance of the position that aren't loaded yet. Tiles that don't exist,
+    /// e.g. at the edges of the map, are skipped (see [`WDTAsset::has_chunk`]). With an orientation, the tiles in
+    /// front of the player are loaded first, see [`MapManager::prioritize_tiles`].
+    fn load_tiles_around(&mut self, position: Vec3A, orientation: Option<f32>) {
+        let center = coordinate_systems::adt_world_to_tiles(position.into());
+        let radius = (self.view_distance / TILE_SIZE).ceil() as i32;
+
+        let mut candidates = vec![];
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                let (x, y) = (center.0 as i32 + dx, center.1 as i32 + dy);
+                if !(0..64).contains(&x) || !(0..64).contains(&y) {
+                    continue;
+                }
+
+                let coords = (x as u8, y as u8);
+                if self.tile_graph.contains_key(&coords)
+                    || self.failed_tiles.contains(&coords)
+                    || !Self::is_tile_within(position, coords, self.view_distance)
+                {
+                    continue;
+                }
+
+                candidates.push(coords);
+            }
+        }
+
+        if let Some(orientation) = orientation {
+            Self::prioritize_tiles(&mut candidates, position, orientation);
+        }
+
+        for coords in candidates {
+            self.try_load_chunk(&coords);
+        }
+    }
+
+    /// Foveated preloading: Orders the tiles by how much they are in front of the player, so that the horizon in the
+    /// view direction streams in first and the tiles behind the player come last. The player's own tile always comes
+    /// first. The orientation is the server's, i.e. 0 faces +x (north) and it grows counter-clockwise.
+    pub(super) fn prioritize_tiles(tiles: &mut [(u8, u8)], position: Vec3A, orientation: f32) {
+        let player_tile = coordinate_systems::adt_world_to_tiles(position.into());
+        let view = Vec2::from_angle(orientation);
+        let priority = |coords: &(u8, u8)| {
+            if *coords == player_tile {
+                return f32::INFINITY;
+            }
+
+            // Tiles extend towards negative world coordinates, see adt_world_to_tiles
+            let tile_center = coordinate_systems::adt_tiles_to_world(coords.0, coords.1)
+                - Vec3A::new(TILE_SIZE / 2.0, TILE_SIZE / 2.0, 0.0);
+            view.dot((tile_center - position).truncate().normalize_or_zero())
+        };
+
+        tiles.sort_by(|a, b| priority(b).total_cmp(&priority(a)));
+    }
+
+    /// WMO groups are resolved lazily, only once they may be visible from the camera (see
+    /// [`WMONode::is_group_visible`]), so that large WMOs don't load all of their interiors at once.
+    fn resolve_visible_groups(&self, position: Vec3A) {
+        let camera = coordinate_systems::adt_to_blender(position);
+
+        for wmo_ref in self.tile_graph.values().flat_map(|graph| &graph.wmos) {
+            let Some(wmo) = wmo_ref
+                .reference
+                .reference
+                .read()
+                .expect("WMO Read Lock")
+                .clone()
+            else {
+                continue;
+            };
+
+            let local_camera = wmo_ref.to_local(camera);
+            for (idx, sub_group) in wmo.subgroups.iter().enumerate() {
+                if sub_group
+                    .reference
+                    .read()
+                    .expect("Sub group Read Lock")
+                    .is_some()
+                    || !wmo.is_group_visible(idx, local_camera, self.view_distance)
+                {
+                    continue;
+                }
+
+                if self
+                    .failed_groups
+                    .lock()
+                    .expect("Failed groups lock")
+                    .contains(&sub_group.reference_str)
+                    || !self
+                        .pending_groups
+                        .lock()
+                        .expect("Pending groups lock")
+                        .insert(sub_group.reference_str.clone())
+                {
+                    continue; // Failed before or already being resolved
+                }
+
+                let resolver = self.wmo_group_resolver.clone();
+                let pending_groups = self.pending_groups.clone();
+                let failed_groups = self.failed_groups.clone();
+                let sub_group_cloned = sub_group.clone();
+                let app = self.app.clone();
+                self.runtime.spawn_blocking(move || {
+                    Self::wait_for_renderer(&app);
+                    let group_result = match resolver.resolve(sub_group_cloned.reference_str.to_string()) {
+                        Ok(group_result) => group_result,
+                        Err(err) => {
+                            error!(
+                                "Skipping WMO Group {}: {:#}",
+                                sub_group_cloned.reference_str, err
+                            );
+                            failed_groups
+                                .lock()
+                                .expect("Failed groups lock")
+                                .insert(sub_group_cloned.reference_str.clone());
+                            pending_groups
+                                .lock()
+                                .expect("Pending groups lock")
+                                .remove(&sub_group_cloned.reference_str);
+                            return;
+                        }
+                    };
+
+                    let mut write_lock_group = sub_group_cloned
+                        .reference
+                        .write()
+                        .expect("Write lock on sub group reference");
+
+                    *write_lock_group.deref_mut() = Some(group_result);
+                    pending_groups
+                        .lock()
+                        .expect("Pending groups lock")
+                        .remove(&sub_group_cloned.reference_str);
+                });
+            }
+        }
+    }
+
+    /// Node hollowing: Frees the IR of all meshes that have been uploaded already, as soon as the meshes exceed the
+    /// memory budget. Meshes that haven't been uploaded yet are counted as well, but can't be hollowed.
+    fn relieve_memory_pressure(&self) {
+        let Some(budget) = self.mesh_memory_budget else {
+            return;
+        };
+
+        let ir_size = self.m2_resolver.ir_size() + self.wmo_group_resolver.ir_size();
+        if ir_size <= budget {
+            return;
+        }
+
+        let freed = self.m2_resolver.hollow_all() + self.wmo_group_resolver.hollow_all();
+        if freed > 0 {
+            debug!(
+                "Mesh IR exceeded the budget ({} of {} MiB), hollowing freed {} MiB",
+                ir_size / (1024 * 1024),
+                budget / (1024 * 1024),
+                freed / (1024 * 1024)
+            );
+        }
+    }
+
+    /// Whether any point of the tile is within the distance of the position (ignoring the height).
+    fn is_tile_within(position: Vec3A, coords: (u8, u8), distance: f32) -> bool {
+        // Tiles extend towards negative world coordinates, see adt_world_to_tiles
+        let max = coordinate_systems::adt_tiles_to_world(coords.0, coords.1);
+        let min = max - Vec3A::new(TILE_SIZE, TILE_SIZE, 0.0);
+        let closest = position.clamp(min, max);
+        closest.truncate().distance(position.truncate()) <= distance
+    }
+
+    /// Tile pruning: Dropping a tile cascades down to the GPU handles of all its assets, except for those that are
+    /// still referenced by other tiles, e.g. WMOs that span multiple tiles (see [`MapManager::try_find_wmo_ref`]) or
+    /// models and textures that have been deduplicated by the resolvers.
+    fn unload_far_tiles(&mut self, position: Vec3A) {
+        let far_tiles = self
+            .tile_graph
+            .keys()
+            .filter(|&&coords| !Self::is_tile_within(position, coords, self.unload_distance))
+            .copied()
+            .collect_vec();
+
+        if far_tiles.is_empty() {
+            return;
+        }
+
+        for coords in far_tiles {
+            trace!("Unloading tile {:?}", coords);
+            self.remove_tile(coords);
+        }
+
+        // Tasks that are still running may keep some nodes alive, those will be evicted with the next unload.
+        self.m2_resolver.evict_expired();
+        self.tex_resolver.evict_expired();
+        self.wmo_resolver.evict_expired();
+        self.wmo_group_resolver.evict_expired();
     }
 
     // TODO: I am not sure if the whole preloading shouldn't be the responsibility of the render thread and if we as src\game should at best care about building the graph.
-    pub fn preload_map(
-        &mut self,
-        map: String,
-        position: Vec3,
-        _orientation: f32, /* TODO: use for foveated preloading */
-    ) {
+    pub fn preload_map(&mut self, map: String, position: Vec3, orientation: f32) {
         let now = Instant::now();
         info!("Loading map {} @ {}", map, position);
-        let wdt_buf = self
+        // Like with failed tiles, the player stays in an empty world instead of crashing the game.
+        let Some(wdt_buf) = self
             .mpq_loader
             .as_ref()
-            .load_raw_owned(&format!("world\\maps\\{}\\{}.wdt", map, map));
-        let wdt =
-            WDTReader::parse_asset(&mut Cursor::new(wdt_buf.expect("Cannot load map wdt"))).expect("Error parsing WDT");
+            .load_raw_shared(&format!("world\\maps\\{}\\{}.wdt", map, map))
+        else {
+            error!(
+                "Cannot load map {}, its WDT is missing from the archives",
+                map
+            );
+            return;
+        };
 
+        let wdt = match WDTReader::parse_asset(&mut Cursor::new(wdt_buf)) {
+            Ok(wdt) => wdt,
+            Err(err) => {
+                error!("Cannot parse the WDT of map {}: {}", map, err);
+                return;
+            }
+        };
+
+        self.failed_tiles.clear();
         let chunk_coords_pos = coordinate_systems::adt_world_to_tiles(position);
         // TODO: We expect the result to be (row, column), but for some reason, it seems to be (column, row)
 
-        for x in /*-1i8..2*/ 0..1 {
-            for y in /*-1i8..2*/ 0..1 {
-                let chunk_coords = (
-                    (chunk_coords_pos.0 as i8 + x) as u8,
-                    (chunk_coords_pos.1 as i8 + y) as u8,
-                );
+        if let Some((name, map_obj_def)) = wdt.global_wmo() {
+            self.load_global_wmo(name.to_owned(), *map_obj_def);
+            self.current_map = Some((map, wdt));
+            warn!("Loading took {}ms", now.elapsed().as_millis());
+            return;
+        }
 
-                if wdt.has_chunk(chunk_coords.1, chunk_coords.0) {
-                    self.load_chunk(&map, &chunk_coords, &wdt.mphd);
-                } else {
-                    error!("We load into the world on unmapped terrain?!");
-                }
-            }
+        if !wdt.has_chunk(chunk_coords_pos.1, chunk_coords_pos.0) {
+            error!("We load into the world on unmapped terrain?!");
         }
 
+        // The surrounding tiles are loaded right away, so that the world doesn't end at the player's tile border.
         self.current_map = Some((map, wdt));
+        self.load_tiles_around(position.into(), Some(orientation));
         warn!("Loading took {}ms", now.elapsed().as_millis());
         // ADT file is map_x_y.adt. I think x are rows and ys are columns.
     }
 
+    /// Unloads the current map and all of its tiles, so that nothing of it bleeds into the next map.
+    pub fn clear(&mut self) {
+        self.current_map = None;
+        self.failed_tiles.clear();
+        self.failed_groups
+            .lock()
+            .expect("Failed groups lock")
+            .clear();
+        // Groups that are still being resolved belong to the dropped tiles.
+        self.pending_groups
+            .lock()
+            .expect("Pending groups lock")
+            .clear();
+        self.remove_all_tiles();
+
+        // Tasks that are still running may keep some nodes alive, those will be evicted on the next map change.
+        self.m2_resolver.evict_expired();
+        self.tex_resolver.evict_expired();
+        self.wmo_resolver.evict_expired();
+        self.wmo_group_resolver.evict_expired();
+    }
+
+    /// Drops all tiles and forgets all cached assets, but stays on the current map, so that the tiles in view are
+    /// loaded (and imported) again on the next camera update.
+    pub fn reload(&mut self) {
+        self.remove_all_tiles();
+        self.failed_tiles.clear();
+        self.failed_groups
+            .lock()
+            .expect("Failed groups lock")
+            .clear();
+        // Groups that are still being resolved belong to the dropped tiles.
+        self.pending_groups
+            .lock()
+            .expect("Pending groups lock")
+            .clear();
+        self.m2_resolver.clear();
+        self.tex_resolver.clear();
+        self.wmo_resolver.clear();
+        self.wmo_group_resolver.clear();
+    }
+
     fn try_load_chunk(&mut self, coords: &(u8, u8)) -> bool {
         if let Some((map, wdt)) = self.current_map.as_ref() {
             let mphd = wdt.mphd;
@@ -121,15 +481,75 @@ impl MapManager {
         false
     }
     fn load_chunk(&mut self, map: &String, chunk_coords: &(u8, u8), mphd: &MPHDChunk) {
-        let adt_buf = self.mpq_loader.as_ref().load_raw_owned(&format!(
+        if self.failed_tiles.contains(chunk_coords) {
+            return;
+        }
+
+        let adt_path = format!(
             "world\\maps\\{}\\{}_{}_{}.adt",
             map, map, chunk_coords.1, chunk_coords.0
-        ));
-        let adt =
-            ADTReader::parse_asset(&mut Cursor::new(adt_buf.expect("Cannot load map adt"))).expect("Error parsing ADT");
+        );
+
+        // Missing tiles and data of a later expansion (e.g. split ADTs, which are detected by the reader) are skipped
+        // instead of crashing.
+        if self.mpq_loader.resolve_archive(&adt_path).is_none() {
+            error!(
+                "Cannot load tile {}_{}_{}, it is listed in the WDT but missing from the archives",
+                map, chunk_coords.1, chunk_coords.0
+            );
+            self.failed_tiles.insert(*chunk_coords);
+            return;
+        }
+
+        let adt = match self.mpq_loader.load_parsed(&adt_path, |buf| {
+            ADTReader::parse_asset(&mut Cursor::new(buf))
+        }) {
+            Ok(adt) => adt,
+            Err(err) => {
+                error!(
+                    "Cannot parse tile {}_{}_{}, is the data from a later expansion? {}",
+                    map, chunk_coords.1, chunk_coords.0, err
+                );
+                self.failed_tiles.insert(*chunk_coords);
+                return;
+            }
+        };
+
+        let graph = match self.handle_adt_lazy(&adt, mphd) {
+            Ok(graph) => graph,
+            Err(err) => {
+                error!(
+                    "Cannot load tile {}_{}_{}: {}",
+                    map, chunk_coords.1, chunk_coords.0, err
+                );
+                self.failed_tiles.insert(*chunk_coords);
+                return;
+            }
+        };
+
+        self.progress
+            .send_modify(|progress| progress.tiles_requested += 1);
         trace!("Loaded tile {}_{}_{}", map, chunk_coords.1, chunk_coords.0);
-        let graph = self.handle_adt_lazy(&adt, mphd).unwrap();
-        self.tile_graph.insert(*chunk_coords, Arc::new(graph));
+        self.insert_tile(*chunk_coords, graph);
+    }
+
+    /// Loads the WMO of a map without terrain. It's treated like a tile without terrain and doodads (see
+    /// [`GLOBAL_WMO_TILE`]), so that the renderer doesn't need to distinguish both kinds of maps.
+    fn load_global_wmo(&mut self, name: String, map_obj_def: SMMapObjDef) {
+        self.progress
+            .send_modify(|progress| progress.tiles_requested += 1);
+        trace!("Loading global WMO {}", name);
+
+        let transform = transform_for_wmo_ref(&map_obj_def);
+        let wmos = vec![Arc::new(WMOReference::new(map_obj_def, transform, name))];
+        self.resolve_references(JoinSet::new(), &wmos, &[]);
+
+        let graph = ADTNode {
+            terrain: vec![],
+            doodads: vec![],
+            wmos,
+        };
+        self.insert_tile(GLOBAL_WMO_TILE, graph);
     }
 
     fn handle_adt_lazy(&self, adt: &ADTAsset, mphd: &MPHDChunk) -> Result<ADTNode, anyhow::Error> {
@@ -144,16 +564,7 @@ impl MapManager {
                 .unwrap()];
             //trace!("M2 {} has been referenced from ADT", name);
 
-            // fix name: currently it ends with .mdx, but we need .m2
-            let name = name
-                .to_lowercase()
-                .replace(".mdx", ".m2")
-                .replace(".mdl", ".m2");
-
-            // TODO: this (and the string replace) could also happen on consumer level, where the ADTNode is built
-            if name.to_lowercase().contains("emitter") {
-                continue;
-            }
+            let name = io::normalize_model_path(name);
 
             direct_doodad_refs.push(Arc::new(DoodadReference::new(
                 transform_for_doodad_ref(dad_ref).into(),
@@ -169,15 +580,18 @@ impl MapManager {
                 .unwrap()];
             //trace!("WMO {} has been referenced from ADT", name);
 
-            if name.ends_with("STORMWIND.WMO") {
-                continue; // TODO: Temporary performance optimization
+            if wmos
+                .iter()
+                .any(|wmo| Self::is_same_wmo_ref(wmo, &wmo_ref, name))
+            {
+                continue;
             }
 
+            // WMOs that span multiple tiles are referenced by each of them. Tiles are loaded one after another, so the
+            // reference of an already loaded neighbour is part of the tile graph.
             if let Some(wmo_reference) = self.try_find_wmo_ref(&wmo_ref, name) {
                 wmos.push(wmo_reference);
             } else {
-                // TODO: There's a race condition from this line until this method terminates. And
-                //  it even fails to find WMORefs already present in wmos, which is kinda a file fault anyway.
                 let transform = transform_for_wmo_ref(&wmo_ref);
                 wmos.push(Arc::new(WMOReference::new(
                     wmo_ref,
@@ -191,7 +605,23 @@ impl MapManager {
 
         let mut terrain_chunk = vec![];
         for mcnk in &adt.mcnks {
-            let mesh = ADTImporter::create_mesh(mcnk, false, &adt.mtex, mphd)?;
+            let mesh = ADTImporter::create_mesh(mcnk, false, true, &adt.mtex, mphd)?;
+            let water_mesh = adt
+                .mh2o
+                .as_ref()
+                .map(|mh2o| WaterImporter::create_mesh(mh2o, mcnk))
+                .transpose()?
+                .flatten();
+            let liquid = adt
+                .mh2o
+                .as_ref()
+                .map(|mh2o| WaterImporter::create_surface(mh2o, mcnk))
+                .transpose()?
+                .flatten();
+
+            let bounds = water_mesh.as_ref().map_or(mesh.1.bounding_box(), |water| {
+                mesh.1.bounding_box().union(&water.bounding_box())
+            });
 
             let texture_layers = mesh
                 .2
@@ -208,8 +638,14 @@ impl MapManager {
             let tile = TerrainTile {
                 position: mesh.0.into(),
                 mesh: RwLock::new(mesh.1.into()),
+                heights: mcnk.get_mcvt()?.unwrap_or_default(),
                 object_handle: RwLock::new(None),
+                material_handle: RwLock::new(None),
                 texture_layers,
+                water_mesh: water_mesh.map(|mesh| RwLock::new(mesh.into())),
+                water_object_handle: RwLock::new(None),
+                liquid,
+                bounds,
             };
 
             // TODO: This is a bit sketchy, why do we need to kick this off manually. Also think about the JoinSet again, this isn't exactly lazy then.
@@ -223,12 +659,26 @@ impl MapManager {
                 &mut set,
                 self.tex_resolver.clone(),
                 references,
+                self.progress.clone(),
+                self.app.clone(),
             );
             terrain_chunk.push(tile);
         }
 
+        self.resolve_references(set, &wmos, &direct_doodad_refs);
+
+        Ok(ADTNode {
+            terrain: terrain_chunk,
+            doodads: direct_doodad_refs,
+            wmos,
+        })
+    }
+
+    /// Resolves the WMOs (including their groups and doodads) and the doodads, without blocking. Once everything,
+    /// including what has already been spawned on the set, has been resolved, the tile is counted as completed.
+    fn resolve_references(&self, mut set: JoinSet<()>, wmos: &[Arc<WMOReference>], doodads: &[Arc<DoodadReference>]) {
         // TODO: Resolving should be a matter of the rendering app, not this code here? But then their code relies on things being preloaded?
-        for wmo in &wmos {
+        for wmo in wmos {
             if wmo
                 .reference
                 .reference
@@ -241,35 +691,26 @@ impl MapManager {
                 continue;
             }
 
-            let result = self
+            let result = match self
                 .wmo_resolver
-                .resolve(wmo.reference.reference_str.clone());
-
-            // TODO: should we resolve WMO Groups right here or rather after all WMOs are resolved? Technically groups could even be lazily resolved?
-            for sub_group in &result.subgroups {
-                let resolver = self.wmo_group_resolver.clone();
-                let sub_group_cloned = sub_group.clone();
-                set.spawn_blocking_on(
-                    move || {
-                        let group_result = resolver.resolve(sub_group_cloned.reference_str.to_string());
-
-                        let mut write_lock_group = sub_group_cloned
-                            .reference
-                            .write()
-                            .expect("Write lock on sub group reference");
-
-                        *write_lock_group.deref_mut() = Some(group_result);
-                    },
-                    self.runtime.handle(),
-                );
-            }
+                .resolve(wmo.reference.reference_str.clone())
+            {
+                Ok(result) => result,
+                Err(err) => {
+                    error!("Skipping WMO {}: {:#}", wmo.reference.reference_str, err);
+                    continue;
+                }
+            };
 
+            // WMO Groups are resolved lazily, see resolve_visible_groups
             // TODO: optimize. Since all materials and textures reside on the WMO level, they are loaded, even when the subgroup that needs them isn't.
             Self::resolve_tex_reference(
                 self.runtime.handle(),
                 &mut set,
                 self.tex_resolver.clone(),
                 result.tex_references.clone(),
+                self.progress.clone(),
+                self.app.clone(),
             );
 
             // Contrary to the previous use case description, we kick of wmo doodad loading before
@@ -283,7 +724,10 @@ impl MapManager {
             // having a RwLock<HashMap<_>>,summed thread wait time has gone down from 270s to 205s.
             // the tex_resolver is the most occupied resolver, which is no wonder since most textures
             // also stem from common.mpq thus blocking on loading as well.
-            for dad in &result.doodads {
+            for dad in result
+                .active_doodad_sets(wmo.map_obj_def.doodadSet)
+                .flatten()
+            {
                 let m2_resolver = self.m2_resolver.clone();
                 let tex_resolver = self.tex_resolver.clone();
 
@@ -293,6 +737,8 @@ impl MapManager {
                     dad.clone(),
                     m2_resolver,
                     tex_resolver,
+                    self.progress.clone(),
+                    self.app.clone(),
                 );
             }
 
@@ -305,7 +751,7 @@ impl MapManager {
             *write_lock.deref_mut() = Some(result);
         }
 
-        for dad in &direct_doodad_refs {
+        for dad in doodads {
             let m2_resolver = self.m2_resolver.clone();
             let tex_resolver = self.tex_resolver.clone();
 
@@ -315,52 +761,79 @@ impl MapManager {
                 dad.clone(),
                 m2_resolver,
                 tex_resolver,
+                self.progress.clone(),
+                self.app.clone(),
             );
         }
 
-        // We need to poll the JoinSet
-        self.runtime.spawn_blocking(move || {
-            while let Some(result) = pollster::block_on(set.join_next()) {
-                result.expect("Loading to be successful");
+        // We need to poll the JoinSet. This must not happen on a blocking thread, because with bounded blocking
+        // threads, the poller would starve the very resolvers it is waiting for.
+        let progress = self.progress.clone();
+        self.runtime.spawn(async move {
+            while let Some(result) = set.join_next().await {
+                if let Err(err) = result {
+                    error!("Resolving the assets of a tile panicked: {}", err);
+                }
             }
+
+            progress.send_modify(|progress| progress.tiles_completed += 1);
         });
+    }
 
-        Ok(ADTNode {
-            terrain: terrain_chunk,
-            doodads: direct_doodad_refs,
-            wmos,
-        })
+    /// The resolved IR is only uploaded once the renderer exists, so the resolvers wait for it instead of competing
+    /// with the window creation for the loader threads. Only ever call this on the blocking threads of the runtime.
+    fn wait_for_renderer(app: &Weak<GameApplication>) {
+        if let Some(app) = app.upgrade() {
+            app.wait_for_renderer();
+        }
     }
 
     fn try_find_wmo_ref(&self, needle: &SMMapObjDef, needle_str: &str) -> Option<Arc<WMOReference>> {
         self.tile_graph
             .values()
             .find_map(|graph| {
-                graph.wmos.iter().find(|wmo| {
-                    wmo.map_obj_def.uniqueId == needle.uniqueId && wmo.reference.reference_str.eq(needle_str)
-                })
+                graph
+                    .wmos
+                    .iter()
+                    .find(|wmo| Self::is_same_wmo_ref(wmo, needle, needle_str))
             })
             .cloned()
     }
 
+    fn is_same_wmo_ref(wmo: &WMOReference, needle: &SMMapObjDef, needle_str: &str) -> bool {
+        wmo.map_obj_def.uniqueId == needle.uniqueId && wmo.reference.reference_str.eq(needle_str)
+    }
+
     fn spawn_doodad_resolvers(
         handle: &Handle,
         set: &mut JoinSet<()>,
         dad: Arc<DoodadReference>,
         m2_resolver: Arc<Resolver<M2Generator, M2Node>>,
         tex_resolver: Arc<Resolver<M2Generator, RwLock<Option<IRTexture>>>>,
+        progress: Arc<watch::Sender<LoadProgress>>,
+        app: Weak<GameApplication>,
     ) {
         let handle_clone = handle.clone();
         set.spawn_on(
             async move {
                 let reference_str = dad.reference.reference_str.clone();
+                let app_clone = app.clone();
                 let result = handle_clone
                     .spawn_blocking(move || {
+                        Self::wait_for_renderer(&app_clone);
                         // TODO: PERF: get rid of duplicated references here, make DoodadReference#reference an Arc and cache them at least adt wide.
                         m2_resolver.resolve(reference_str)
                     })
                     .await
                     .unwrap();
+
+                let result = match result {
+                    Ok(result) => result,
+                    Err(err) => {
+                        error!("Skipping Doodad {}: {:#}", dad.reference.reference_str, err);
+                        return;
+                    }
+                };
                 let mut new_set = JoinSet::new();
                 // TODO: currently, we use join sets rarely, especially for those non-failing (error returning) operations, there's no reason to really join.
                 Self::resolve_tex_reference(
@@ -368,6 +841,8 @@ impl MapManager {
                     &mut new_set,
                     tex_resolver,
                     result.tex_reference.clone(),
+                    progress,
+                    app,
                 );
 
                 handle_clone
@@ -395,19 +870,34 @@ impl MapManager {
         set: &mut JoinSet<()>,
         tex_resolver: Arc<Resolver<M2Generator, RwLock<Option<IRTexture>>>>,
         references: Vec<Arc<IRTextureReference>>,
+        progress: Arc<watch::Sender<LoadProgress>>,
+        app: Weak<GameApplication>,
     ) {
+        progress.send_modify(|progress| progress.textures_requested += references.len() as u32);
+
         for tex_reference in references {
             let resolver = tex_resolver.clone();
+            let progress = progress.clone();
+            let app = app.clone();
             set.spawn_blocking_on(
                 move || {
-                    let result_tex = resolver.resolve(tex_reference.reference_str.clone());
-
-                    let mut ref_wlock = tex_reference
-                        .reference
-                        .write()
-                        .expect("texture reference write lock");
-
-                    *ref_wlock.deref_mut() = Some(result_tex);
+                    Self::wait_for_renderer(&app);
+                    match resolver.resolve(tex_reference.reference_str.clone()) {
+                        Ok(result_tex) => {
+                            let mut ref_wlock = tex_reference
+                                .reference
+                                .write()
+                                .expect("texture reference write lock");
+
+                            *ref_wlock.deref_mut() = Some(result_tex);
+                        }
+                        Err(err) => error!(
+                            "Skipping Texture {}: {:#}",
+                            tex_reference.reference_str, err
+                        ),
+                    }
+
+                    progress.send_modify(|progress| progress.textures_resolved += 1);
                 },
                 handle,
             );
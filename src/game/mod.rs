@@ -2,3 +2,7 @@ pub mod application;
 pub mod game_state;
 pub mod map_manager;
 pub mod packet_handlers;
+pub mod settings;
+
+#[cfg(test)]
+mod tests;
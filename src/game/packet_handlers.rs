@@ -41,11 +41,17 @@ impl PacketHandlers {
                 ServerOpcodeMessage::SMSG_LOGIN_VERIFY_WORLD(pkt) => {
                     // pkt.as_int() and then manual DBC logic at some point, to support custom maps.
 
-                    self.app()
-                        .game_state
-                        .change_map(pkt.map, pkt.position, pkt.orientation);
+                    // This is also sent after reconnecting, so there may be a previous map.
+                    let game_state = self.app().game_state.clone();
+                    game_state.on_map_changed();
+                    game_state.change_map(pkt.map, pkt.position, pkt.orientation);
                     // here, we would probably want to call into the GameApplication again.
                 }
+                ServerOpcodeMessage::SMSG_NEW_WORLD(pkt) => {
+                    let game_state = self.app().game_state.clone();
+                    game_state.on_map_changed();
+                    game_state.change_map(pkt.map, pkt.position, pkt.orientation);
+                }
                 ServerOpcodeMessage::SMSG_MONSTER_MOVE(_) => (),
                 ServerOpcodeMessage::SMSG_MOTD(pkt) => {
                     for motd in &pkt.motds {
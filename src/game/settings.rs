@@ -0,0 +1,404 @@
+use anyhow::{Context, anyhow};
+use glam::Vec3A;
+use itertools::Itertools;
+use log::LevelFilter;
+use rend3::types::{PresentMode, SampleCount};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use crate::io::dbc;
+use crate::rendering::common::camera::{CameraPose, FieldOfView};
+use crate::rendering::common::coordinate_systems::TILE_SIZE;
+
+/// The subsystems that can be configured with `--log-filter`, and the modules that they consist of. The most specific
+/// module wins, so e.g. the loader modules are only affected by "render" if "loader" isn't configured as well.
+pub const LOG_SUBSYSTEMS: &[(&str, &[&str])] = &[
+    (
+        "loader",
+        &[
+            "sargerust::io",
+            "sargerust::game::map_manager",
+            "sargerust::rendering::asset_graph",
+            "sargerust::rendering::importer",
+            "sargerust::rendering::loader",
+        ],
+    ),
+    ("physics", &["sargerust::physics", "rapier3d"]),
+    (
+        "render",
+        &[
+            "sargerust::rendering",
+            "rend3",
+            "rend3_routine",
+            "rend3_framework",
+            "wgpu_core",
+            "wgpu_hal",
+        ],
+    ),
+    (
+        "net",
+        &[
+            "sargerust::networking",
+            "sargerust::game::packet_handlers",
+            "wow_login_messages",
+            "wow_world_messages",
+        ],
+    ),
+];
+
+/// Printed alongside invalid command line arguments.
+pub const USAGE: &str = "Usage: sargerust [options]
+
+Options:
+    --loader-threads <count>
+    --view-distance <yards>
+    --unload-distance <yards>
+    --fov <degrees> | --hfov <degrees>
+    --present-mode {auto,vsync,mailbox,immediate}
+    --msaa {1,4}
+    --texture-quality {full,half,quarter}
+    --map <name or id>
+    --camera x,y,z,yaw,pitch | --look-at x,y,z,target_x,target_y,target_z
+    --list-dependencies <map>
+    --verify-map <map>
+    --asset-cache-dir <path>
+    --asset-cache-size <MiB>
+    --mesh-memory-budget <MiB>
+    --locale <locale>
+    --log-filter <subsystem=level,...>";
+
+/// Reduces the resolution (and VRAM usage) of all textures by skipping their largest mip levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextureQuality {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+}
+
+impl TextureQuality {
+    /// The mip level that textures are uploaded from, if they have that many levels.
+    pub fn base_mip_level(self) -> u8 {
+        match self {
+            TextureQuality::Full => 0,
+            TextureQuality::Half => 1,
+            TextureQuality::Quarter => 2,
+        }
+    }
+}
+
+/// Settings that can be changed by passing command line arguments, e.g. `sargerust --loader-threads 4`
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The number of threads that are used to load assets in the background. Loading saturates all of them, so
+    /// leaving cores free keeps the game responsive while a map is loading.
+    pub loader_threads: usize,
+    /// The distance (in yards) up to which the world is rendered and tiles are streamed in.
+    pub view_distance: f32,
+    /// Set by `--unload-distance`, the distance (in yards) beyond which tiles are unloaded again. Defaults to half a
+    /// tile more than the view distance, so that tiles don't get loaded and unloaded repeatedly at the border.
+    pub unload_distance: Option<f32>,
+    /// Set by `--present-mode {auto,vsync,mailbox,immediate}`, the latter two disable vsync (e.g. for benchmarking),
+    /// but not every surface supports them.
+    pub present_mode: PresentMode,
+    /// Set by `--msaa {1,4}`, the number of samples per pixel. The renderer falls back to 1, if the adapter doesn't
+    /// support multisampling.
+    pub msaa: SampleCount,
+    /// Set by `--texture-quality {full,half,quarter}`.
+    pub texture_quality: TextureQuality,
+    /// Set by either `--fov` (vertical) or `--hfov` (horizontal), in degrees.
+    pub fov: FieldOfView,
+    /// The map that is loaded in standalone mode, either by its name (e.g. "Azeroth" or "Eastern Kingdoms") or its id,
+    /// see [`crate::game::game_state::GameState::resolve_map`].
+    pub map: String,
+    /// Set by either `--camera x,y,z,yaw,pitch` (degrees) or `--look-at x,y,z,target_x,target_y,target_z`, the camera
+    /// is then placed there whenever a map has been loaded.
+    pub camera: Option<CameraPose>,
+    /// When set, the files that the given map references are printed instead of starting the game.
+    pub list_dependencies: Option<String>,
+    /// When set, the sector checksums of all files that the given map references are verified instead of starting
+    /// the game.
+    pub verify_map: Option<String>,
+    /// When set, parsed assets are cached in this directory, so that later launches don't have to parse them again.
+    pub asset_cache_dir: Option<PathBuf>,
+    /// Set by `--asset-cache-size <MiB>`, the size (in bytes) that the asset cache may occupy on disk, before the least
+    /// recently used assets are removed. Defaults to 1 GiB.
+    pub asset_cache_size: u64,
+    /// Set by `--mesh-memory-budget <MiB>`, the RAM (in bytes) that the M2 and WMO meshes may occupy, before those that
+    /// have been uploaded to the GPU are hollowed, see [`crate::rendering::asset_graph`].
+    pub mesh_memory_budget: Option<usize>,
+    /// Set by `--locale deDE`, the locale whose strings are read from the DBCs, see [`crate::io::dbc::LOCALES`].
+    pub locale: String,
+    /// Set by `--log-filter loader=warn,physics=debug`, the log level per subsystem (see [`LOG_SUBSYSTEMS`]).
+    pub log_filter: Vec<(&'static str, LevelFilter)>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let cpus = std::thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(2);
+
+        Self {
+            loader_threads: (cpus - 1).max(1),
+            view_distance: TILE_SIZE,
+            unload_distance: None,
+            fov: FieldOfView::default(),
+            present_mode: PresentMode::AutoVsync,
+            msaa: SampleCount::One,
+            texture_quality: TextureQuality::default(),
+            map: "Azeroth".to_string(),
+            camera: None,
+            list_dependencies: None,
+            verify_map: None,
+            asset_cache_dir: None,
+            asset_cache_size: 1024 * 1024 * 1024,
+            mesh_memory_budget: None,
+            locale: "enUS".to_string(),
+            log_filter: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_args() -> Result<Self, anyhow::Error> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, anyhow::Error> {
+        let mut settings = Settings::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--loader-threads" => {
+                    settings.loader_threads = Self::parse_value::<usize, _>(&arg, &mut args)?;
+                    if settings.loader_threads == 0 {
+                        return Err(anyhow!("--loader-threads needs at least one thread"));
+                    }
+                }
+                "--view-distance" => {
+                    settings.view_distance = Self::parse_value::<f32, _>(&arg, &mut args)?;
+                    if !settings.view_distance.is_finite() || settings.view_distance <= 0.0 {
+                        return Err(anyhow!("--view-distance needs to be positive"));
+                    }
+                }
+                "--unload-distance" => {
+                    let distance = Self::parse_value::<f32, _>(&arg, &mut args)?;
+                    if !distance.is_finite() || distance <= 0.0 {
+                        return Err(anyhow!("--unload-distance needs to be positive"));
+                    }
+                    settings.unload_distance = Some(distance);
+                }
+                "--fov" => {
+                    settings.fov = FieldOfView::Vertical(Self::parse_fov(&arg, &mut args)?);
+                }
+                "--hfov" => {
+                    settings.fov = FieldOfView::Horizontal(Self::parse_fov(&arg, &mut args)?);
+                }
+                "--present-mode" => {
+                    let value = Self::parse_value::<String, _>(&arg, &mut args)?;
+                    settings.present_mode = match value.as_str() {
+                        "auto" => PresentMode::AutoVsync,
+                        "vsync" => PresentMode::Fifo,
+                        "mailbox" => PresentMode::Mailbox,
+                        "immediate" => PresentMode::Immediate,
+                        _ => {
+                            return Err(anyhow!(
+                                "Invalid value \"{}\" for --present-mode, expected auto, vsync, mailbox or immediate",
+                                value
+                            ));
+                        }
+                    };
+                }
+                "--msaa" => {
+                    let value = Self::parse_value::<String, _>(&arg, &mut args)?;
+                    settings.msaa = match value.as_str() {
+                        "1" => SampleCount::One,
+                        "4" => SampleCount::Four,
+                        _ => {
+                            return Err(anyhow!(
+                                "Invalid value \"{}\" for --msaa, expected 1 or 4",
+                                value
+                            ));
+                        }
+                    };
+                }
+                "--texture-quality" => {
+                    let value = Self::parse_value::<String, _>(&arg, &mut args)?;
+                    settings.texture_quality = match value.as_str() {
+                        "full" => TextureQuality::Full,
+                        "half" => TextureQuality::Half,
+                        "quarter" => TextureQuality::Quarter,
+                        _ => {
+                            return Err(anyhow!(
+                                "Invalid value \"{}\" for --texture-quality, expected full, half or quarter",
+                                value
+                            ));
+                        }
+                    };
+                }
+                "--map" => {
+                    settings.map = Self::parse_value::<String, _>(&arg, &mut args)?;
+                }
+                "--camera" => {
+                    let [x, y, z, yaw, pitch] = Self::parse_floats(&arg, &mut args)?;
+                    settings.camera = Some(CameraPose::Angles {
+                        position: Vec3A::new(x, y, z),
+                        yaw: yaw.to_radians(),
+                        pitch: pitch.to_radians(),
+                    });
+                }
+                "--look-at" => {
+                    let [x, y, z, target_x, target_y, target_z] = Self::parse_floats(&arg, &mut args)?;
+                    settings.camera = Some(CameraPose::LookAt {
+                        position: Vec3A::new(x, y, z),
+                        target: Vec3A::new(target_x, target_y, target_z),
+                    });
+                }
+                "--list-dependencies" => {
+                    settings.list_dependencies = Some(Self::parse_value::<String, _>(&arg, &mut args)?);
+                }
+                "--verify-map" => {
+                    settings.verify_map = Some(Self::parse_value::<String, _>(&arg, &mut args)?);
+                }
+                "--asset-cache-dir" => {
+                    settings.asset_cache_dir = Some(Self::parse_value::<PathBuf, _>(&arg, &mut args)?);
+                }
+                "--asset-cache-size" => {
+                    let mib = Self::parse_value::<u64, _>(&arg, &mut args)?;
+                    settings.asset_cache_size = mib * 1024 * 1024;
+                }
+                "--mesh-memory-budget" => {
+                    let mib = Self::parse_value::<usize, _>(&arg, &mut args)?;
+                    settings.mesh_memory_budget = Some(mib * 1024 * 1024);
+                }
+                "--locale" => {
+                    let value = Self::parse_value::<String, _>(&arg, &mut args)?;
+                    if dbc::locale_index(&value).is_none() {
+                        return Err(anyhow!(
+                            "Unknown locale \"{}\" for --locale, expected one of: {}",
+                            value,
+                            dbc::LOCALES.join(", ")
+                        ));
+                    }
+                    settings.locale = value;
+                }
+                "--log-filter" => {
+                    let value = Self::parse_value::<String, _>(&arg, &mut args)?;
+                    settings.log_filter = Self::parse_log_filter(&value)?;
+                }
+                _ => return Err(anyhow!("Unknown argument {}", arg)),
+            }
+        }
+
+        if settings
+            .unload_distance
+            .is_some_and(|distance| distance < settings.view_distance)
+        {
+            return Err(anyhow!(
+                "--unload-distance must not be smaller than the view distance"
+            ));
+        }
+
+        Ok(settings)
+    }
+
+    /// See [`Settings::unload_distance`]
+    pub fn unload_distance(&self) -> f32 {
+        self.unload_distance
+            .unwrap_or(self.view_distance + TILE_SIZE * 0.5)
+    }
+
+    fn parse_fov<I: Iterator<Item = String>>(arg: &str, args: &mut I) -> Result<f32, anyhow::Error> {
+        let degrees = Self::parse_value::<f32, _>(arg, args)?;
+        if !(degrees > 0.0 && degrees < 180.0) {
+            return Err(anyhow!("{} needs to be between 0 and 180 degrees", arg));
+        }
+
+        Ok(degrees)
+    }
+
+    /// Parses a comma separated list of exactly N finite numbers, optionally in parentheses, e.g. `(1.5,-2,+3e2)`.
+    pub(super) fn parse_floats<const N: usize, I: Iterator<Item = String>>(
+        arg: &str,
+        args: &mut I,
+    ) -> Result<[f32; N], anyhow::Error> {
+        let value = Self::parse_value::<String, _>(arg, args)?;
+        let trimmed = value.trim();
+        let list = trimmed
+            .strip_prefix('(')
+            .and_then(|list| list.strip_suffix(')'))
+            .unwrap_or(trimmed);
+
+        let floats = list
+            .split(',')
+            .enumerate()
+            .map(|(component, float)| {
+                float
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|float| float.is_finite())
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Invalid number \"{}\" for component {} of {}",
+                            float.trim(),
+                            component + 1,
+                            arg
+                        )
+                    })
+            })
+            .collect::<Result<Vec<f32>, anyhow::Error>>()?;
+
+        floats
+            .try_into()
+            .map_err(|floats: Vec<f32>| anyhow!("{} expects {} numbers, got {}", arg, N, floats.len()))
+    }
+
+    fn parse_log_filter(value: &str) -> Result<Vec<(&'static str, LevelFilter)>, anyhow::Error> {
+        value
+            .split(',')
+            .filter(|directive| !directive.is_empty())
+            .map(|directive| {
+                let (subsystem, level) = directive.split_once('=').ok_or_else(|| {
+                    anyhow!(
+                        "Expected subsystem=level for --log-filter, got \"{}\"",
+                        directive
+                    )
+                })?;
+
+                let (subsystem, _) = LOG_SUBSYSTEMS
+                    .iter()
+                    .find(|(name, _)| *name == subsystem)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Unknown subsystem \"{}\" for --log-filter, expected one of: {}",
+                            subsystem,
+                            LOG_SUBSYSTEMS.iter().map(|(name, _)| name).join(", ")
+                        )
+                    })?;
+
+                let level = level
+                    .parse::<LevelFilter>()
+                    .with_context(|| format!("Invalid log level \"{}\" for --log-filter", level))?;
+
+                Ok((*subsystem, level))
+            })
+            .collect()
+    }
+
+    fn parse_value<T: std::str::FromStr, I: Iterator<Item = String>>(
+        arg: &str,
+        args: &mut I,
+    ) -> Result<T, anyhow::Error>
+    where
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for {}", arg))?;
+        value
+            .parse::<T>()
+            .with_context(|| format!("Invalid value \"{}\" for {}", value, arg))
+    }
+}
@@ -0,0 +1,76 @@
+use crate::game::game_state::edit_distance;
+use crate::game::map_manager::MapManager;
+use crate::game::settings::Settings;
+use crate::rendering::common::coordinate_systems::TILE_SIZE;
+use glam::Vec3A;
+use std::f32::consts::PI;
+
+/// The center of tile (32, 32), tile (31, 32) is in +x (north) of it and (33, 32) in -x.
+const POSITION: Vec3A = Vec3A::new(-TILE_SIZE / 2.0, -TILE_SIZE / 2.0, 0.0);
+
+#[test]
+fn tiles_in_front_are_loaded_first() {
+    let mut tiles = [(33, 32), (32, 33), (31, 32), (32, 32)];
+    MapManager::prioritize_tiles(&mut tiles, POSITION, 0.0);
+    assert_eq!(tiles, [(32, 32), (31, 32), (32, 33), (33, 32)]);
+
+    MapManager::prioritize_tiles(&mut tiles, POSITION, PI);
+    assert_eq!(tiles, [(32, 32), (33, 32), (32, 33), (31, 32)]);
+}
+
+fn parse_floats<const N: usize>(value: &str) -> Result<[f32; N], anyhow::Error> {
+    Settings::parse_floats("--look-at", &mut std::iter::once(value.to_string()))
+}
+
+#[test]
+fn parse_floats_in_parentheses() {
+    assert_eq!(parse_floats::<3>(" (1.5, 2,3) ").unwrap(), [1.5, 2.0, 3.0]);
+    assert_eq!(parse_floats::<3>("1.5,2,3").unwrap(), [1.5, 2.0, 3.0]);
+}
+
+#[test]
+fn parse_floats_with_sign_and_exponent() {
+    assert_eq!(parse_floats::<3>("+1,-2,-0.5").unwrap(), [1.0, -2.0, -0.5]);
+    assert_eq!(
+        parse_floats::<3>("1e3,-2.5E-1,+1e0").unwrap(),
+        [1000.0, -0.25, 1.0]
+    );
+}
+
+#[test]
+fn parse_floats_names_the_invalid_component() {
+    let err = parse_floats::<3>("(1,abc,0)").unwrap_err().to_string();
+    assert!(err.contains("component 2"), "{}", err);
+    assert!(err.contains("\"abc\""), "{}", err);
+
+    // Infinite values are rejected as well
+    let err = parse_floats::<3>("1,2,inf").unwrap_err().to_string();
+    assert!(err.contains("component 3"), "{}", err);
+}
+
+#[test]
+fn parse_floats_expects_all_components() {
+    let err = parse_floats::<3>("1,2").unwrap_err().to_string();
+    assert!(err.contains("expects 3 numbers, got 2"), "{}", err);
+    assert!(parse_floats::<3>("1,2,3,4").is_err());
+    assert!(Settings::parse_floats::<3, _>("--look-at", &mut std::iter::empty()).is_err());
+}
+
+#[test]
+fn edit_distance_counts_single_edits() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("", "azeroth"), 7);
+    assert_eq!(edit_distance("azeroth", ""), 7);
+    assert_eq!(edit_distance("azeroth", "azeroth"), 0);
+    // Substitution, insertion and deletion
+    assert_eq!(edit_distance("azeroth", "azerath"), 1);
+    assert_eq!(edit_distance("azeroth", "azerroth"), 1);
+    assert_eq!(edit_distance("azeroth", "azroth"), 1);
+    assert_eq!(edit_distance("kalimdor", "kalimdro"), 2);
+}
+
+#[test]
+fn edit_distance_is_case_sensitive() {
+    assert_eq!(edit_distance("Azeroth", "azeroth"), 1);
+    assert_eq!(edit_distance("NORTHREND", "northrend"), 9);
+}
@@ -0,0 +1,212 @@
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use log::{debug, trace, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Bump this whenever the serialized form of a cached asset changes without the crate version changing, e.g. while
+/// working on the parsers.
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// Pruning removes entries until the cache is this fraction of its maximum size, so that it doesn't have to prune
+/// again after every write.
+const PRUNE_TARGET: f64 = 0.75;
+
+/// Identifies the contents of a file without reading it, see [`mpq::Archive::file_info`]. Archives without
+/// `(attributes)` have no CRC, the position and size within the archive still change whenever a patch replaces the
+/// file, though.
+#[derive(Debug, Hash)]
+pub struct AssetKey<'a> {
+    pub archive: &'a str,
+    pub path: &'a str,
+    pub offset: u64,
+    pub packed_size: u32,
+    pub size: u32,
+    pub crc: Option<u32>,
+}
+
+/// Precedes every entry, entries of another format or crate version are discarded.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct EntryHeader {
+    format_version: u32,
+    crate_version: String,
+    /// Guards against hash collisions of the file name.
+    key: String,
+}
+
+impl EntryHeader {
+    fn new(key: &AssetKey) -> Self {
+        Self {
+            format_version: CACHE_FORMAT_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            key: format!("{:?}", key),
+        }
+    }
+}
+
+/// Stores parsed assets on disk, so that subsequent launches can skip reading and parsing them. Entries are keyed by
+/// where the file is stored (see [`AssetKey`]), so files that changed (e.g. due to a new patch archive) miss the cache
+/// instead of yielding stale data. The key hash isn't stable across compiler versions, which only results in cache
+/// misses. Once the cache exceeds its maximum size, the least recently used entries are removed.
+pub struct AssetCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// The size of all entries, as far as this process knows.
+    bytes: AtomicU64,
+    prune_lock: Mutex<()>,
+}
+
+impl AssetCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Result<Self, std::io::Error> {
+        fs::create_dir_all(&dir)?;
+        let cache = Self {
+            dir,
+            max_bytes,
+            bytes: AtomicU64::new(0),
+            prune_lock: Mutex::new(()),
+        };
+
+        cache.prune();
+        Ok(cache)
+    }
+
+    fn entry_path(&self, key: &AssetKey) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.bin", hasher.finish()))
+    }
+
+    /// Returns the cached asset for the given file or loads and parses (and caches) it otherwise. Failing to read or
+    /// write the cache is not an error, the asset is just loaded instead.
+    pub fn get_or_load<T, E, F>(&self, key: &AssetKey, load: F) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T, E>,
+    {
+        let entry = self.entry_path(key);
+        let header = EntryHeader::new(key);
+
+        if let Ok(buf) = fs::read(&entry) {
+            match Self::deserialize_entry(&buf, &header) {
+                Ok(Some(asset)) => {
+                    trace!("Loading {} from the asset cache", key.path);
+                    // Entries are pruned by their modification time, so hits keep them alive.
+                    if let Err(err) = fs::File::options()
+                        .write(true)
+                        .open(&entry)
+                        .and_then(|file| file.set_modified(SystemTime::now()))
+                    {
+                        trace!(
+                            "Cannot touch the asset cache entry of {}: {}",
+                            key.path, err
+                        );
+                    }
+                    return Ok(asset);
+                }
+                Ok(None) => debug!("Discarding the outdated asset cache entry of {}", key.path),
+                Err(err) => warn!("Discarding the asset cache entry of {}: {}", key.path, err),
+            }
+        }
+
+        let asset = load()?;
+        let serialized = bincode::serialize(&header).and_then(|mut buf| {
+            bincode::serialize_into(&mut buf, &asset)?;
+            Ok(buf)
+        });
+
+        match serialized {
+            Ok(buf) => {
+                let len = buf.len() as u64;
+                match fs::write(&entry, buf) {
+                    Ok(()) => {
+                        if self.bytes.fetch_add(len, Ordering::Relaxed) + len > self.max_bytes {
+                            self.prune();
+                        }
+                    }
+                    Err(err) => warn!(
+                        "Cannot write the asset cache entry of {}: {}",
+                        key.path, err
+                    ),
+                }
+            }
+            Err(err) => warn!("Cannot serialize {} for the asset cache: {}", key.path, err),
+        }
+
+        Ok(asset)
+    }
+
+    /// None if the entry has been written by another format or crate version, or for another key.
+    fn deserialize_entry<T: DeserializeOwned>(buf: &[u8], expected: &EntryHeader) -> Result<Option<T>, bincode::Error> {
+        let mut reader = buf;
+        let header: EntryHeader = bincode::deserialize_from(&mut reader)?;
+        if header != *expected {
+            return Ok(None);
+        }
+
+        bincode::deserialize_from(reader).map(Some)
+    }
+
+    /// Removes the least recently used entries, once the cache exceeds its maximum size.
+    fn prune(&self) {
+        // Another thread is pruning already.
+        let Ok(_guard) = self.prune_lock.try_lock() else {
+            return;
+        };
+
+        let mut entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| {
+                    let metadata = entry
+                        .metadata()
+                        .ok()
+                        .filter(|metadata| metadata.is_file())?;
+                    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    Some((entry.path(), metadata.len(), modified))
+                })
+                .collect::<Vec<_>>(),
+            Err(err) => {
+                warn!("Cannot enumerate the asset cache: {}", err);
+                return;
+            }
+        };
+
+        let mut bytes: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if bytes > self.max_bytes {
+            let target = (self.max_bytes as f64 * PRUNE_TARGET) as u64;
+            entries.sort_by_key(|(_, _, modified)| *modified);
+
+            let mut removed = 0;
+            for (path, len, _) in entries {
+                if bytes <= target {
+                    break;
+                }
+
+                match fs::remove_file(&path) {
+                    Ok(()) => {
+                        bytes -= len;
+                        removed += 1;
+                    }
+                    Err(err) => warn!(
+                        "Cannot remove the asset cache entry {}: {}",
+                        path.display(),
+                        err
+                    ),
+                }
+            }
+
+            debug!(
+                "Pruned {} asset cache entries, {} MiB remaining",
+                removed,
+                bytes / (1024 * 1024)
+            );
+        }
+
+        self.bytes.store(bytes, Ordering::Relaxed);
+    }
+}
@@ -1,10 +1,16 @@
+use std::sync::Arc;
+
 pub trait AssetLoader<T> {
     fn load(&self, path: &str) -> T;
 }
 
 pub trait RawAssetLoader {
-    fn load_raw(&self, path: &str) -> &[u8];
+    /// Caching implementations hand out the cached buffer itself, so prefer this over
+    /// [`RawAssetLoader::load_raw_owned`] whenever the buffer is only read.
+    fn load_raw_shared(&self, path: &str) -> Option<Arc<[u8]>>; // TODO: Result!
 
-    /// in case of a caching implementation, this may need to clone the whole buffer!
-    fn load_raw_owned(&self, path: &str) -> Option<Vec<u8>>; // TODO: Result!
+    /// Copies the whole buffer, only use this when the buffer needs to be owned.
+    fn load_raw_owned(&self, path: &str) -> Option<Vec<u8>> {
+        self.load_raw_shared(path).map(|buf| buf.to_vec())
+    }
 }
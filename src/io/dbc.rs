@@ -0,0 +1,84 @@
+use crate::io::common::loader::RawAssetLoader;
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+use wow_dbc::{DbcError, DbcTable, ExtendedLocalizedString};
+
+#[derive(Debug)]
+pub enum DbcLoadError {
+    /// The file isn't contained in any of the loaded archives.
+    NotFound {
+        path: String,
+    },
+    ParseFailed {
+        path: String,
+        error: DbcError,
+    },
+}
+
+impl Display for DbcLoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbcLoadError::NotFound { path } => write!(f, "{} could not be found", path),
+            DbcLoadError::ParseFailed { path, error } => write!(f, "Failed to parse {}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for DbcLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DbcLoadError::NotFound { .. } => None,
+            DbcLoadError::ParseFailed { error, .. } => Some(error),
+        }
+    }
+}
+
+/// Loads and parses a DBC, e.g. `load_dbc::<Map>(loader, "DBFilesClient\\Map.dbc")`. Callers decide whether a
+/// missing table is fatal, so that the client can also run against partial data dumps.
+pub fn load_dbc<T: DbcTable>(loader: &impl RawAssetLoader, path: &str) -> Result<T, DbcLoadError> {
+    let buf = loader
+        .load_raw_shared(path)
+        .ok_or_else(|| DbcLoadError::NotFound {
+            path: path.to_string(),
+        })?;
+
+    T::read(&mut Cursor::new(buf)).map_err(|error| DbcLoadError::ParseFailed {
+        path: path.to_string(),
+        error,
+    })
+}
+
+/// The locale codes (as used in the MPQ names, e.g. `locale-deDE.MPQ`) in the order of the columns of an
+/// [`ExtendedLocalizedString`]. enGB installs share the enUS column.
+pub const LOCALES: [&str; 9] = [
+    "enUS", "koKR", "frFR", "deDE", "zhCN", "zhTW", "esES", "esMX", "ruRU",
+];
+
+/// The column of the given locale code (case insensitive), None if the locale is unknown.
+pub fn locale_index(locale: &str) -> Option<usize> {
+    if locale.eq_ignore_ascii_case("enGB") {
+        return Some(0);
+    }
+
+    LOCALES
+        .iter()
+        .position(|candidate| candidate.eq_ignore_ascii_case(locale))
+}
+
+/// Resolves the string for the given locale code, falling back to enUS if the locale is unknown or the DBC doesn't
+/// contain a translation for it (DBCs only ever contain the strings of the locale that they have been shipped with).
+pub fn localized_string<'a>(ext: &'a ExtendedLocalizedString, locale: &str) -> &'a str {
+    let value = match locale_index(locale) {
+        Some(1) => &ext.ko_kr,
+        Some(2) => &ext.fr_fr,
+        Some(3) => &ext.de_de,
+        Some(4) => &ext.en_cn,
+        Some(5) => &ext.en_tw,
+        Some(6) => &ext.es_es,
+        Some(7) => &ext.es_mx,
+        Some(8) => &ext.ru_ru,
+        _ => &ext.en_gb,
+    };
+
+    if value.is_empty() { &ext.en_gb } else { value }
+}
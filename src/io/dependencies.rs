@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use anyhow::{Context, anyhow};
+use sargerust_files::adt::reader::ADTReader;
+use sargerust_files::m2::reader::M2Reader;
+use sargerust_files::m2::types::M2TextureType;
+use sargerust_files::wdt::reader::WDTReader;
+use sargerust_files::wmo::reader::WMOReader;
+
+use crate::io;
+use crate::io::common::loader::RawAssetLoader;
+use crate::rendering::loader::m2_loader::M2Loader;
+use crate::rendering::loader::wmo_loader::WMOLoader;
+
+/// Collects the (lowercase) path of every file that the given map references: The WDT, all of its ADTs, their
+/// textures, M2s (including skins and textures) and WMOs (including groups, textures and doodads).
+/// In contrast to the asset graph, nothing is imported, so this can be used for prefetching or packaging.
+/// Files that are referenced but can't be found are still part of the result, they just can't be traversed further.
+pub fn collect_dependencies<L: RawAssetLoader>(loader: &L, map_name: &str) -> Result<HashSet<String>, anyhow::Error> {
+    let mut collector = DependencyCollector {
+        loader,
+        files: HashSet::new(),
+    };
+
+    let wdt_path = format!("world\\maps\\{}\\{}.wdt", map_name, map_name);
+    let wdt_buf = collector
+        .visit(&wdt_path)
+        .ok_or_else(|| anyhow!("Cannot load {}", wdt_path))?;
+    let wdt = WDTReader::parse_asset(&mut Cursor::new(wdt_buf))?;
+
+    // Terrain maps have an empty MWMO chunk, only maps that are flagged accordingly consist of a global WMO
+    if let Some((wmo, _)) = wdt.global_wmo() {
+        collector.wmo(wmo)?;
+    }
+
+    for (x, y) in wdt.existing_tiles() {
+        collector.adt(&format!(
+            "world\\maps\\{}\\{}_{}_{}.adt",
+            map_name, map_name, x, y
+        ))?;
+    }
+
+    Ok(collector.files)
+}
+
+struct DependencyCollector<'a, L: RawAssetLoader> {
+    loader: &'a L,
+    files: HashSet<String>,
+}
+
+impl<L: RawAssetLoader> DependencyCollector<'_, L> {
+    /// Records the file and returns true if it hasn't been recorded before.
+    fn record(&mut self, path: &str) -> bool {
+        self.files.insert(path.to_ascii_lowercase())
+    }
+
+    /// Records the file and loads it, unless it has already been visited (or is missing).
+    fn visit(&mut self, path: &str) -> Option<Arc<[u8]>> {
+        if !self.record(path) {
+            return None;
+        }
+
+        self.loader.load_raw_shared(path)
+    }
+
+    fn adt(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        let Some(buf) = self.visit(path) else {
+            return Ok(());
+        };
+
+        let adt = ADTReader::parse_asset(&mut Cursor::new(buf)).with_context(|| format!("Parsing {}", path))?;
+
+        for texture in &adt.mtex.filenames {
+            self.record(texture);
+        }
+
+        for m2 in &adt.mmdx.filenames {
+            self.m2(&io::normalize_model_path(m2))?;
+        }
+
+        for wmo in &adt.mwmo.filenames {
+            self.wmo(wmo)?;
+        }
+
+        Ok(())
+    }
+
+    fn m2(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        let Some(buf) = self.visit(path) else {
+            return Ok(());
+        };
+
+        let m2 = M2Reader::parse_asset(&mut Cursor::new(buf)).with_context(|| format!("Parsing {}", path))?;
+
+        // In theory, we could investigate the number of LoD Levels, but we only ever load "0"
+        self.record(&M2Loader::skin_path(path, 0));
+
+        for texture in &m2.textures {
+            // other texture types are resolved at runtime (e.g. creature skins)
+            if texture.texture_type == M2TextureType::None && !texture.filename.is_empty() {
+                self.record(&texture.filename);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn wmo(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        let Some(buf) = self.visit(path) else {
+            return Ok(());
+        };
+
+        let wmo = WMOReader::parse_root(&mut Cursor::new(buf)).with_context(|| format!("Parsing {}", path))?;
+
+        let path_upper = path.to_uppercase();
+        let group_path = path_upper.trim_end_matches(".WMO");
+        for x in 0..wmo.mohd.nGroups {
+            self.record(&format!("{}_{:0>3}.wmo", group_path, x));
+        }
+
+        for texture in &wmo.motx.textureNameList {
+            if !texture.is_empty() {
+                self.record(texture);
+            }
+        }
+
+        for doodad in WMOLoader::collect_dooads_for_wmo_root(&wmo) {
+            self.m2(&doodad.m2_ref)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,2 +1,23 @@
+pub mod asset_cache;
 pub mod common;
+pub mod dbc;
+pub mod dependencies;
 pub mod mpq;
+
+#[cfg(test)]
+mod tests;
+
+/// Model references (MMDX, CreatureModelData) still use the extensions of the alpha client (`.mdx`, `.mdl`), while the
+/// archives only contain `.m2` files. Only the extension is rewritten, names that already end in `.m2` are kept as is.
+/// Model paths are lowercased, as they are also used as keys for the resolvers and to derive the skin profile names.
+pub fn normalize_model_path(path: &str) -> String {
+    let lowercase = path.to_lowercase();
+    if let Some(stem) = lowercase
+        .strip_suffix(".mdx")
+        .or_else(|| lowercase.strip_suffix(".mdl"))
+    {
+        return format!("{}.m2", stem);
+    }
+
+    lowercase
+}
@@ -1,21 +1,30 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fs;
-use std::io::Cursor;
-use std::ops::DerefMut;
+use std::io::{Cursor, Read};
+use std::num::NonZeroUsize;
 use std::path::Path;
-use std::sync::RwLock;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicUsize};
 
+use anyhow::{Context, anyhow};
 use itertools::Itertools;
-use log::{trace, warn};
+use log::{error, trace, warn};
 
-use mpq::Archive;
+use mpq::{Archive, FileHash};
+use quick_cache::Weighter;
+use quick_cache::sync::Cache;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 
+use crate::io::asset_cache::{AssetCache, AssetKey};
 use crate::io::common::loader::RawAssetLoader;
+use crate::rendering::loader::blp_loader::BlpCache;
 
 pub fn read_mpq_file_into_owned(archive: &mut Archive, file_name: &str) -> Result<Vec<u8>, std::io::Error> {
     let file = archive.open_file(file_name)?;
-    let mut buf: Vec<u8> = vec![0; file.size() as usize];
-    file.read(archive, &mut buf)?;
+    let mut buf: Vec<u8> = Vec::with_capacity(file.size() as usize);
+    file.reader(archive).read_to_end(&mut buf)?;
     Ok(buf)
 }
 
@@ -23,11 +32,32 @@ pub fn read_mpq_file_into_cursor(archive: &mut Archive, file_name: &str) -> Resu
     read_mpq_file_into_owned(archive, file_name).map(Cursor::new)
 }
 
+/// The size (in bytes) of the files that are kept in memory, so that assets that are shared between tiles (textures,
+/// doodads) don't have to be decompressed again.
+const FILE_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+/// Only used to size the cache up front, the files are bounded by [`FILE_CACHE_BYTES`].
+const FILE_CACHE_ESTIMATED_FILES: usize = 2048;
+
+/// Weighs the cached files by their size.
+#[derive(Clone)]
+struct FileSizeWeighter;
+
+impl Weighter<String, Arc<[u8]>> for FileSizeWeighter {
+    fn weight(&self, _key: &String, val: &Arc<[u8]>) -> u64 {
+        val.len() as u64
+    }
+}
+
 pub struct MPQLoader {
-    prioritized_archives: Vec<(String, RwLock<Archive>)>,
-    #[allow(unused)]
-    // Will become used once MPQLoader is concurrent (because then we construct new readers from the data_folder and the archive name)
-    data_folder: String,
+    /// Files are read through [`Archive::read_file`], which doesn't need exclusive access to the archive.
+    prioritized_archives: Vec<(String, Archive)>,
+    /// Keyed by the normalized path, see [`MPQLoader::normalize_path`].
+    file_cache: Cache<String, Arc<[u8]>, FileSizeWeighter>,
+    /// Set by `--asset-cache-dir`, see [`MPQLoader::load_parsed`]
+    asset_cache: Option<AssetCache>,
+    /// The CRCs of the files in each archive (see [`Archive::file_crcs`]), only read when there is an asset cache.
+    file_crcs: Vec<Vec<u32>>,
+    blp_cache: BlpCache,
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
@@ -83,20 +113,172 @@ impl MPQLoader {
             .map(|(filename, entry)| {
                 (
                     filename,
-                    RwLock::new(
-                        Archive::open(entry.path())
-                            .unwrap_or_else(|_| panic!("Failed to load MPQ {}", entry.path().to_str().unwrap())),
-                    ),
+                    Archive::open(entry.path())
+                        .unwrap_or_else(|_| panic!("Failed to load MPQ {}", entry.path().to_str().unwrap())),
                 )
             })
             .collect_vec();
 
         MPQLoader {
             prioritized_archives,
-            data_folder: data_folder.into(),
+            file_cache: Cache::with_weighter(
+                FILE_CACHE_ESTIMATED_FILES,
+                FILE_CACHE_BYTES,
+                FileSizeWeighter,
+            ),
+            asset_cache: None,
+            file_crcs: Vec::new(),
+            blp_cache: BlpCache::default(),
         }
     }
 
+    /// The parsed BLPs of this loader's archives, see [`crate::rendering::loader::blp_loader::BLPLoader`]
+    pub fn blp_cache(&self) -> &BlpCache {
+        &self.blp_cache
+    }
+
+    /// MPQ lookups ignore the casing and treat slashes as backslashes, so every path is normalized to the uppercase,
+    /// backslash separated form, e.g. `WORLD\MAPS\AZEROTH\AZEROTH.WDT`, before it is resolved or used as cache key.
+    /// Callers may pass paths in any casing and with either separator.
+    pub fn normalize_path(path: &str) -> String {
+        path.replace('/', "\\").to_ascii_uppercase()
+    }
+
+    pub fn with_asset_cache(mut self, asset_cache: AssetCache) -> Self {
+        self.file_crcs = self
+            .prioritized_archives
+            .iter()
+            .map(|(name, archive)| {
+                archive.file_crcs().unwrap_or_else(|err| {
+                    warn!("Cannot read the attributes of {}: {}", name, err);
+                    Vec::new()
+                })
+            })
+            .collect();
+        self.asset_cache = Some(asset_cache);
+        self
+    }
+
+    /// Loads the given file and parses it. With an asset cache, the parsed asset of a previous launch is used
+    /// instead, if the file didn't change in the meantime. Then the file isn't even read from the archive.
+    pub fn load_parsed<T, E, F>(&self, path: &str, parse: F) -> Result<T, anyhow::Error>
+    where
+        T: Serialize + DeserializeOwned,
+        E: std::error::Error + Send + Sync + 'static,
+        F: FnOnce(&[u8]) -> Result<T, E>,
+    {
+        let load = || -> Result<T, anyhow::Error> {
+            let raw = self
+                .load_raw_shared(path)
+                .ok_or_else(|| anyhow!("Cannot load {}", path))?;
+            Ok(parse(&raw)?)
+        };
+
+        let Some(asset_cache) = &self.asset_cache else {
+            return load();
+        };
+
+        let path = &Self::normalize_path(path);
+        let hash = FileHash::new(path);
+        let Some((index, info)) = self
+            .prioritized_archives
+            .iter()
+            .enumerate()
+            .find_map(|(index, (_, archive))| Some((index, archive.file_info(&hash)?)))
+        else {
+            return Err(anyhow!("Cannot load {}", path));
+        };
+
+        let key = AssetKey {
+            archive: &self.prioritized_archives[index].0,
+            path,
+            offset: info.offset,
+            packed_size: info.packed_size,
+            size: info.unpacked_size,
+            crc: self.file_crcs[index].get(info.block_index).copied(),
+        };
+        asset_cache.get_or_load(&key, load)
+    }
+
+    /// Loads the given files into the cache concurrently, so that the files needed right after startup (e.g. DBCs)
+    /// don't have to be read sequentially from the archives once they are first requested. At most one thread per
+    /// core is used, independent of the amount of files.
+    pub fn preload(&self, paths: &[&str]) {
+        let next = AtomicUsize::new(0);
+        let workers = std::thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(paths.len());
+
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while let Some(path) = paths.get(next.fetch_add(1, atomic::Ordering::Relaxed)) {
+                        if self.load_raw_shared(path).is_none() {
+                            warn!("Could not preload {}", path);
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    /// The index of the archive that provides the file, i.e. the archive with the highest priority that contains it.
+    /// The name is only hashed once, instead of once per archive.
+    pub fn resolve_archive(&self, path: &str) -> Option<usize> {
+        let hash = FileHash::new(&Self::normalize_path(path));
+        self.prioritized_archives
+            .iter()
+            .position(|(_, archive)| archive.contains_hash(&hash))
+    }
+
+    /// Loads a batch of files, each distinct (normalized) path is only resolved and read once.
+    pub fn load_many(&self, paths: &[&str]) -> Vec<Option<Arc<[u8]>>> {
+        let mut loaded: HashMap<String, Option<Arc<[u8]>>> = HashMap::new();
+
+        paths
+            .iter()
+            .map(|path| {
+                loaded
+                    .entry(Self::normalize_path(path))
+                    .or_insert_with_key(|key| self.load_raw_shared(key))
+                    .clone()
+            })
+            .collect()
+    }
+
+    /// Expects a normalized path, see [`MPQLoader::normalize_path`].
+    fn load_uncached(&self, path: &str) -> Option<Vec<u8>> {
+        // the very bad API design of the mpq crate currently loads the file as soon as we try to open it.
+        let Some(index) = self.resolve_archive(path) else {
+            warn!("Could not locate {}!", path);
+            return None;
+        };
+
+        let (name, archive) = &self.prioritized_archives[index];
+        trace!("Loading {} from {}", path, name);
+        // Corrupt or unsupported data (e.g. LZMA compressed sectors) only fails this file
+        archive
+            .read_file(path)
+            .inspect_err(|err| error!("Could not read {} from {}: {}", path, name, err))
+            .ok()
+    }
+
+    /// Reads the file while validating its sector checksums, for integrity checks. Files without checksums pass.
+    pub fn verify_file(&self, path: &str) -> Result<(), anyhow::Error> {
+        let path = &Self::normalize_path(path);
+        let index = self
+            .resolve_archive(path)
+            .ok_or_else(|| anyhow!("Could not locate {}", path))?;
+        let (name, archive) = &self.prioritized_archives[index];
+
+        let mut archive = archive.try_clone()?;
+        let file = archive.open_file(path)?;
+        let mut buf: Vec<u8> = vec![0; file.size() as usize];
+        file.read_verified(&mut archive, &mut buf)
+            .with_context(|| format!("Verifying {} in {}", path, name))?;
+        Ok(())
+    }
+
     // TODO: understand locales (e.g. deDE) and their order/priority.
     fn sorting_order(a: &String, b: &String) -> Ordering {
         let type_a = MPQLoader::extract_mpq_type(a);
@@ -163,39 +345,15 @@ impl MPQLoader {
 }
 
 impl RawAssetLoader for MPQLoader {
-    fn load_raw(&self, _path: &str) -> &[u8] {
-        //&self.load_raw_owned(path)
-        todo!()
-    }
-
-    fn load_raw_owned(&self, path: &str) -> Option<Vec<u8>> {
-        // the very bad API design of the mpq crate currently loads the file as soon as we try to open it.
-        let opt = self
-            .prioritized_archives
-            .iter()
-            .map(|(name, archive)| {
-                let exists = archive
-                    .read()
-                    .map(|ar| ar.contains_file(path))
-                    .unwrap_or(false);
-                (name, archive, exists)
-            })
-            .find(|(_, _, exists)| *exists)
-            .map(|(name, archive, _)| (name, archive));
-
-        if opt.is_none() {
-            warn!("Could not locate {}!", path);
+    fn load_raw_shared(&self, path: &str) -> Option<Arc<[u8]>> {
+        let key = Self::normalize_path(path);
+        if let Some(buf) = self.file_cache.get(&key) {
+            trace!("Loading {} from cache", path);
+            return Some(buf);
         }
 
-        opt.map(|(name, archive_guard)| {
-            trace!("Loading {} from {}", path, name);
-            let mut guard = archive_guard.write().unwrap();
-            let archive = guard.deref_mut();
-            let file = archive.open_file(path).unwrap();
-            let mut buf: Vec<u8> = vec![0; file.size() as usize];
-            file.read(archive, &mut buf)
-                .expect("I/O Error. TODO: Error handling");
-            buf
-        })
+        let buf: Arc<[u8]> = self.load_uncached(&key)?.into();
+        self.file_cache.insert(key, buf.clone());
+        Some(buf)
     }
 }
@@ -1 +1,4 @@
 pub mod loader;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+use crate::io;
+use crate::io::common::loader::RawAssetLoader;
+use crate::io::mpq::loader::MPQLoader;
+use mpq::{ArchiveBuilder, Compression};
+
+#[test]
+fn normalize_path() {
+    assert_eq!(
+        MPQLoader::normalize_path("world/maps/Azeroth/Azeroth.wdt"),
+        "WORLD\\MAPS\\AZEROTH\\AZEROTH.WDT"
+    );
+    assert_eq!(
+        MPQLoader::normalize_path("World\\Maps\\AZEROTH\\AZEROTH.WDT"),
+        "WORLD\\MAPS\\AZEROTH\\AZEROTH.WDT"
+    );
+}
+
+#[test]
+fn paths_resolve_regardless_of_casing_and_separators() {
+    let data_folder = std::env::temp_dir().join(format!("sargerust-normalize-{}", std::process::id()));
+    std::fs::create_dir_all(&data_folder).unwrap();
+
+    let mut builder = ArchiveBuilder::new();
+    builder.add_file(
+        "World\\Maps\\Azeroth\\Azeroth.wdt",
+        b"wdt",
+        Compression::None,
+    );
+    builder
+        .finalize(data_folder.join("common.MPQ"))
+        .expect("Archive to be written");
+
+    let loader = MPQLoader::new(data_folder.to_str().unwrap());
+    let lowercase = loader.load_raw_owned("world/maps/Azeroth/Azeroth.wdt");
+    let uppercase = loader.load_raw_owned("World\\Maps\\AZEROTH\\AZEROTH.WDT");
+    std::fs::remove_dir_all(&data_folder).unwrap();
+
+    assert_eq!(lowercase.as_deref(), Some(b"wdt".as_slice()));
+    assert_eq!(lowercase, uppercase);
+}
+
+#[test]
+fn normalize_model_path() {
+    assert_eq!(
+        io::normalize_model_path("World\\Generic\\Tree.MDX"),
+        "world\\generic\\tree.m2"
+    );
+    assert_eq!(
+        io::normalize_model_path("Creature\\Talbuk\\Talbuk.mdl"),
+        "creature\\talbuk\\talbuk.m2"
+    );
+    assert_eq!(
+        io::normalize_model_path("creature\\talbuk\\talbuk.m2"),
+        "creature\\talbuk\\talbuk.m2"
+    );
+    // Only the extension is rewritten
+    assert_eq!(
+        io::normalize_model_path("world\\foo.mdx\\bar.mdx"),
+        "world\\foo.mdx\\bar.m2"
+    );
+}
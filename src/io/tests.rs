@@ -0,0 +1,240 @@
+use crate::io::common::loader::RawAssetLoader;
+use crate::io::dbc;
+use crate::io::dependencies::collect_dependencies;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use wow_dbc::ExtendedLocalizedString;
+
+/// Serves the files from memory, regardless of the casing of their path.
+#[derive(Default)]
+struct MemoryLoader {
+    files: HashMap<String, Arc<[u8]>>,
+}
+
+impl MemoryLoader {
+    fn add(&mut self, path: &str, data: Vec<u8>) {
+        self.files.insert(path.to_ascii_lowercase(), data.into());
+    }
+}
+
+impl RawAssetLoader for MemoryLoader {
+    fn load_raw_shared(&self, path: &str) -> Option<Arc<[u8]>> {
+        self.files.get(&path.to_ascii_lowercase()).cloned()
+    }
+}
+
+fn chunk(magic: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    // IFF magics are stored reversed
+    let mut buf = magic.iter().rev().copied().collect::<Vec<u8>>();
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+    buf
+}
+
+fn string_list(strings: &[&str]) -> Vec<u8> {
+    strings
+        .iter()
+        .flat_map(|string| string.bytes().chain([0]))
+        .collect()
+}
+
+/// A WDT that either has the ADT at (1, 2) and an empty MWMO, like the terrain maps of Wrath, or a global WMO.
+fn wdt(global_wmo: Option<&str>) -> Vec<u8> {
+    let mut mphd = [0u8; 32];
+    let mut main = vec![0u8; 64 * 64 * 8];
+    if global_wmo.is_none() {
+        main[(2 * 64 + 1) * 8] = 1;
+    } else {
+        mphd[0] = 1; // WDT_USES_GLOBAL_MAP_OBJ
+    }
+
+    let mut data = chunk(b"MVER", &18u32.to_le_bytes());
+    data.extend(chunk(b"MPHD", &mphd));
+    data.extend(chunk(b"MAIN", &main));
+    data.extend(chunk(
+        b"MWMO",
+        &global_wmo
+            .map(|wmo| string_list(&[wmo]))
+            .unwrap_or_default(),
+    ));
+    if global_wmo.is_some() {
+        data.extend(chunk(b"MODF", &[0; 64]));
+    }
+
+    data
+}
+
+fn adt(textures: &[&str], m2s: &[&str], wmos: &[&str]) -> Vec<u8> {
+    let mut adt = chunk(b"MVER", &18u32.to_le_bytes());
+    adt.extend(chunk(b"MHDR", &[0; 0x40]));
+    adt.extend(chunk(b"MCIN", &[0; 16 * 16 * 16]));
+    adt.extend(chunk(b"MTEX", &string_list(textures)));
+    adt.extend(chunk(b"MMDX", &string_list(m2s)));
+    adt.extend(chunk(b"MMID", &[]));
+    adt.extend(chunk(b"MWMO", &string_list(wmos)));
+    for magic in [b"MWID", b"MDDF", b"MODF"] {
+        adt.extend(chunk(magic, &[]));
+    }
+
+    adt
+}
+
+/// A WMO root with one doodad set that contains a single doodad.
+fn wmo_root(groups: u32, textures: &[&str], doodad: &str) -> Vec<u8> {
+    let mut mohd = [0u8; 64];
+    mohd[4..8].copy_from_slice(&groups.to_le_bytes());
+
+    let mut mods = vec![0u8; 0x14]; // name
+    mods.extend_from_slice(&0u32.to_le_bytes());
+    mods.extend_from_slice(&1u32.to_le_bytes());
+    mods.extend_from_slice(&[0; 4]);
+
+    let mut data = chunk(b"MVER", &17u32.to_le_bytes());
+    data.extend(chunk(b"MOHD", &mohd));
+    data.extend(chunk(b"MOTX", &string_list(textures)));
+    for magic in [b"MOMT", b"MOGN", b"MOGI", b"MOLT"] {
+        data.extend(chunk(magic, &[]));
+    }
+    data.extend(chunk(b"MODS", &mods));
+    data.extend(chunk(b"MODN", &string_list(&[doodad])));
+    data.extend(chunk(b"MODD", &[0; 40])); // name offset 0, position, orientation, scale and color
+    data.extend(chunk(b"MFOG", &[]));
+    data
+}
+
+/// A WotLK M2 header (0x130 bytes) that only has textures, given as their type and filename.
+fn m2(textures: &[(u32, &str)]) -> Vec<u8> {
+    const HEADER_SIZE: u32 = 0x130;
+    const TEXTURE_SIZE: u32 = 16;
+
+    let array = |size: usize, offset: u32| [(size as u32).to_le_bytes(), offset.to_le_bytes()].concat();
+
+    let mut data = b"MD20".to_vec();
+    data.extend_from_slice(&[8, 1, 0, 0]);
+    data.resize(0x50, 0);
+    data.extend(array(textures.len(), HEADER_SIZE));
+    data.resize(HEADER_SIZE as usize, 0);
+
+    let mut filename_offset = HEADER_SIZE + TEXTURE_SIZE * textures.len() as u32;
+    for &(texture_type, filename) in textures {
+        let size = if filename.is_empty() {
+            0
+        } else {
+            filename.len() + 1
+        };
+        data.extend_from_slice(&texture_type.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend(array(size, filename_offset));
+        filename_offset += size as u32;
+    }
+
+    for &(_, filename) in textures {
+        if !filename.is_empty() {
+            data.extend(string_list(&[filename]));
+        }
+    }
+
+    data
+}
+
+fn paths(paths: &[&str]) -> HashSet<String> {
+    paths.iter().map(|path| path.to_string()).collect()
+}
+
+#[test]
+fn terrain_map_dependencies() -> Result<(), anyhow::Error> {
+    let mut loader = MemoryLoader::default();
+    loader.add("World\\Maps\\Terrain\\Terrain.wdt", wdt(None));
+    loader.add(
+        "World\\Maps\\Terrain\\Terrain_1_2.adt",
+        adt(
+            &["Tileset\\Grass.blp"],
+            &["World\\Tree.mdx"],
+            &["World\\House.wmo"],
+        ),
+    );
+    // The creature skin is resolved at runtime
+    loader.add(
+        "World\\Tree.m2",
+        m2(&[(0, "World\\Tree.blp"), (11, ""), (0, "")]),
+    );
+    loader.add(
+        "World\\House.wmo",
+        wmo_root(2, &["World\\Wall.blp"], "World\\Chair.mdx"),
+    );
+    // The chair is missing, so it can't be traversed, but is still part of the result
+
+    assert_eq!(
+        collect_dependencies(&loader, "Terrain")?,
+        paths(&[
+            "world\\maps\\terrain\\terrain.wdt",
+            "world\\maps\\terrain\\terrain_1_2.adt",
+            "tileset\\grass.blp",
+            "world\\tree.m2",
+            "world\\tree00.skin",
+            "world\\tree.blp",
+            "world\\house.wmo",
+            "world\\house_000.wmo",
+            "world\\house_001.wmo",
+            "world\\wall.blp",
+            "world\\chair.m2",
+        ])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn global_wmo_map_dependencies() -> Result<(), anyhow::Error> {
+    let mut loader = MemoryLoader::default();
+    loader.add(
+        "World\\Maps\\Instance\\Instance.wdt",
+        wdt(Some("World\\Dungeon.wmo")),
+    );
+    loader.add("World\\Dungeon.wmo", wmo_root(1, &[], "World\\Torch.m2"));
+    loader.add("World\\Torch.m2", m2(&[(0, "World\\Torch.blp")]));
+
+    assert_eq!(
+        collect_dependencies(&loader, "Instance")?,
+        paths(&[
+            "world\\maps\\instance\\instance.wdt",
+            "world\\dungeon.wmo",
+            "world\\dungeon_000.wmo",
+            "world\\torch.m2",
+            "world\\torch00.skin",
+            "world\\torch.blp",
+        ])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn missing_wdt() {
+    assert!(collect_dependencies(&MemoryLoader::default(), "Missing").is_err());
+}
+
+#[test]
+fn locale_index() {
+    assert_eq!(dbc::locale_index("deDE"), Some(3));
+    assert_eq!(dbc::locale_index("dede"), Some(3));
+    // enGB installs use the enUS column
+    assert_eq!(dbc::locale_index("enGB"), Some(0));
+    assert_eq!(dbc::locale_index("enUS"), Some(0));
+    assert_eq!(dbc::locale_index("xxXX"), None);
+}
+
+#[test]
+fn localized_string() {
+    let name = ExtendedLocalizedString {
+        en_gb: "Eastern Kingdoms".to_string(),
+        de_de: "Östliche Königreiche".to_string(),
+        ..Default::default()
+    };
+
+    assert_eq!(dbc::localized_string(&name, "deDE"), "Östliche Königreiche");
+    assert_eq!(dbc::localized_string(&name, "enGB"), "Eastern Kingdoms");
+    // There is no French translation in the DBC
+    assert_eq!(dbc::localized_string(&name, "frFR"), "Eastern Kingdoms");
+    assert_eq!(dbc::localized_string(&name, "xxXX"), "Eastern Kingdoms");
+}
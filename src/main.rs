@@ -1,18 +1,23 @@
 #![feature(iter_array_chunks)]
 
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use glam::{Affine3A, EulerRot, Quat, Vec3};
 use image_blp::BlpImage;
 use image_blp::convert::blp_to_image;
-use image_blp::parser::parse_blp_with_externals;
+use itertools::Itertools;
+use log::{LevelFilter, error};
 use mpq::Archive;
 use rendering::common::coordinate_systems::TILE_SIZE;
 use sargerust_files::adt::types::SMDoodadDef;
 use sargerust_files::wdt::types::SMMapObjDef;
 
 use crate::game::application::GameApplication;
+use crate::game::settings::{LOG_SUBSYSTEMS, Settings, USAGE};
+use crate::io::asset_cache::AssetCache;
 use crate::io::mpq::loader::MPQLoader;
+use crate::rendering::loader::blp_loader::BLPLoader;
 
 mod demos;
 pub mod entity;
@@ -33,13 +38,50 @@ enum DemoMode {
 
 fn main() {
     let mode = DemoMode::NoDemo(true);
-    env_logger::init();
+    let settings = match Settings::from_args() {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{:#}", err);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    init_logger(&settings);
 
     // TODO: perspectively, this folder will be a CLI argument
     let data_folder = std::env::current_dir()
         .expect("Can't read current working directory!")
         .join("_data");
-    let mpq_loader = MPQLoader::new(data_folder.to_string_lossy().as_ref());
+    let mut mpq_loader = MPQLoader::new(data_folder.to_string_lossy().as_ref());
+    if let Some(asset_cache_dir) = &settings.asset_cache_dir {
+        let asset_cache = AssetCache::new(asset_cache_dir.clone(), settings.asset_cache_size)
+            .expect("Failed to create the asset cache directory");
+        mpq_loader = mpq_loader.with_asset_cache(asset_cache);
+    }
+
+    if let Some(map_name) = &settings.list_dependencies {
+        let dependencies = collect_dependencies_or_exit(&mpq_loader, map_name);
+        for path in dependencies.iter().sorted() {
+            println!("{}", path);
+        }
+        return;
+    }
+
+    if let Some(map_name) = &settings.verify_map {
+        let dependencies = collect_dependencies_or_exit(&mpq_loader, map_name);
+        let mut failures = 0;
+        for path in dependencies.iter().sorted() {
+            if let Err(err) = mpq_loader.verify_file(path) {
+                eprintln!("{:#}", err);
+                failures += 1;
+            }
+        }
+        println!("Verified {} files, {} failed", dependencies.len(), failures);
+        if failures > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
 
     match mode {
         DemoMode::M2 => demos::main_simple_m2(&mpq_loader).unwrap(),
@@ -49,17 +91,20 @@ fn main() {
         DemoMode::NoDemo(standalone) => {
             let mut receiver = None;
             let app = Arc::new_cyclic(|weak| {
-                let mut app = GameApplication::new(weak, mpq_loader);
+                let mut app = GameApplication::new(weak, mpq_loader, settings);
                 if !standalone {
                     receiver = Some(app.connect_to_realm("127.0.0.1:3724", "user", "user"));
                 }
                 app
             });
 
-            let operation_mode = if standalone {
-                game::application::GameOperationMode::Standalone
-            } else {
-                game::application::GameOperationMode::Networked(receiver.unwrap())
+            let operation_mode = match receiver {
+                None => game::application::GameOperationMode::Standalone,
+                Some(Ok(receiver)) => game::application::GameOperationMode::Networked(receiver),
+                Some(Err(err)) => {
+                    error!("{}", err);
+                    std::process::exit(1);
+                }
             };
 
             app.run(operation_mode);
@@ -67,6 +112,44 @@ fn main() {
     }
 }
 
+/// Used by the command line tools, which can't do anything without the files of the map.
+fn collect_dependencies_or_exit(mpq_loader: &MPQLoader, map_name: &str) -> HashSet<String> {
+    match io::dependencies::collect_dependencies(mpq_loader, map_name) {
+        Ok(dependencies) => dependencies,
+        Err(err) => {
+            eprintln!(
+                "Failed to collect the dependencies of map {}: {:#}",
+                map_name, err
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn init_logger(settings: &Settings) {
+    let mut builder = env_logger::Builder::from_default_env();
+
+    // Without RUST_LOG, only our own info messages are shown, without the per-asset traces and dependency chatter
+    if std::env::var_os("RUST_LOG").is_none() {
+        builder
+            .filter_level(LevelFilter::Warn)
+            .filter_module("sargerust", LevelFilter::Info);
+    }
+
+    for (subsystem, level) in &settings.log_filter {
+        let (_, modules) = LOG_SUBSYSTEMS
+            .iter()
+            .find(|(name, _)| name == subsystem)
+            .expect("Subsystem to be validated when parsing the settings");
+
+        for module in *modules {
+            builder.filter_module(module, *level);
+        }
+    }
+
+    builder.init();
+}
+
 fn transform_for_doodad_ref(dad_ref: &SMDoodadDef) -> Affine3A {
     let scale = Vec3::new(
         dad_ref.scale as f32 / 1024.0,
@@ -128,33 +211,18 @@ fn debug_dump_blp(archive: &mut Archive, file_name: &str) {
 #[allow(unused)]
 fn debug_dump_mpq_filelist(data_dir: &str, mpq_name: &str) {
     let mut archive = Archive::open(format!("{}\\{}", data_dir, mpq_name)).unwrap();
-    let buf = io::mpq::loader::read_mpq_file_into_owned(&mut archive, "(listfile)").unwrap();
-    std::fs::write(format!("./{}.txt", mpq_name), buf).unwrap();
+    let files = archive.list_files().unwrap();
+    std::fs::write(format!("./{}.txt", mpq_name), files.join("\n")).unwrap();
 }
 
 fn load_blp_from_mpq(archive: &mut Archive, file_name: &str) -> Option<BlpImage> {
-    // TODO: The blp crate has bad error handling, as it doesn't mix with anyhow::Error.
-    // furthermore, the built in error types stem from nom, that we don't have as dependency.
-
-    // load_blp uses the fs to load mip maps next to it.
-    // we don't want to extract blps into temporary files, though, so we use the other API
-    // and there, we either don't support BLP0 Mipmaps or we properly implement the callback at some time
-
     let owned_file = io::mpq::loader::read_mpq_file_into_owned(archive, file_name);
     if owned_file.is_err() {
         dbg!(owned_file.unwrap_err());
         return None;
     }
 
-    let root_input = owned_file.unwrap();
-    let image = parse_blp_with_externals(&root_input, |_i| {
-        // This could also be no_mipmaps from the image-blp parser crate.
-        panic!("Loading of BLP Mip Maps is unsupported. File {}", file_name)
-    });
-
-    if image.is_err() {
-        dbg!(image.unwrap_err());
-        return None;
-    }
-    Some(image.unwrap().1)
+    BLPLoader::parse_blp(file_name, &owned_file.unwrap(), |path| {
+        io::mpq::loader::read_mpq_file_into_owned(archive, path).ok()
+    })
 }
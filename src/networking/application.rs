@@ -1,7 +1,7 @@
 use crate::game::application::GameApplication;
 use crate::game::packet_handlers::PacketHandlers;
-use crate::networking::auth;
 use crate::networking::world::WorldServer;
+use crate::networking::{NetworkError, auth};
 use log::trace;
 use std::net::TcpStream;
 use std::sync::mpsc::{Receiver, Sender, channel};
@@ -23,24 +23,34 @@ impl NetworkApplication {
         address: &str,
         username: &str,
         password: &str,
-    ) -> (NetworkApplication, Receiver<Box<ServerOpcodeMessage>>) {
-        let (session_key, realms) = Self::logon_realm(address, username, password);
-        trace!("Choosing realm {}", &realms[0].name);
+    ) -> Result<(NetworkApplication, Receiver<Box<ServerOpcodeMessage>>), NetworkError> {
+        let (session_key, realms) = Self::logon_realm(address, username, password)?;
+        let realm = realms
+            .first()
+            .ok_or_else(|| NetworkError::Protocol("The realm list is empty".to_string()))?;
+        trace!("Choosing realm {}", &realm.name);
 
         let (sender, receiver) = channel();
 
-        (
+        Ok((
             Self {
-                world_server: NetworkApplication::connect_to_world_server(sender, username, &realms[0], session_key),
+                world_server: NetworkApplication::connect_to_world_server(sender, username, realm, session_key)?,
             },
             receiver,
-        )
+        ))
     }
 
-    fn logon_realm(address: &str, username: &str, password: &str) -> ([u8; SESSION_KEY_LENGTH as usize], Vec<Realm>) {
-        let mut auth_server = TcpStream::connect(address).expect("Connecting to the Server succeeds");
-        let (key, realm_msg) = auth::auth(&mut auth_server, username, password);
-        (key, realm_msg.realms)
+    fn logon_realm(
+        address: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<([u8; SESSION_KEY_LENGTH as usize], Vec<Realm>), NetworkError> {
+        let mut auth_server = TcpStream::connect(address).map_err(|error| NetworkError::Connect {
+            address: address.to_string(),
+            error,
+        })?;
+        let (key, realm_msg) = auth::auth(&mut auth_server, username, password)?;
+        Ok((key, realm_msg.realms))
     }
 
     fn connect_to_world_server(
@@ -48,20 +58,22 @@ impl NetworkApplication {
         username: &str,
         realm: &Realm,
         session_key: [u8; SESSION_KEY_LENGTH as usize],
-    ) -> Arc<WorldServer> {
+    ) -> Result<Arc<WorldServer>, NetworkError> {
         let server_id = realm.realm_id; // TODO: inline
-        let world_server_stream = TcpStream::connect(&realm.address).unwrap();
+        let world_server_stream = TcpStream::connect(&realm.address).map_err(|error| NetworkError::Connect {
+            address: realm.address.clone(),
+            error,
+        })?;
 
         // Got the realm, have been connecting to the world server
-        let s = expect_server_message::<SMSG_AUTH_CHALLENGE, _>(&mut &world_server_stream).unwrap();
+        let s = expect_server_message::<SMSG_AUTH_CHALLENGE, _>(&mut &world_server_stream)
+            .map_err(|err| NetworkError::protocol("Reading the world server's auth challenge", err))?;
 
         let seed = ProofSeed::new();
         let seed_value = seed.seed();
-        let (client_proof, crypto) = seed.into_client_header_crypto(
-            &NormalizedString::new(username).unwrap(),
-            session_key,
-            s.server_seed,
-        );
+        // The username has already been normalized successfully during the logon
+        let username = NormalizedString::new(username).expect("Username to be valid after the logon");
+        let (client_proof, crypto) = seed.into_client_header_crypto(&username, session_key, s.server_seed);
 
         // Caution, crypto implements Copy and then encryption breaks! Do not access encrypt/decrypt here, use the world server.
         let (encrypter, decrypter) = crypto.split();
@@ -77,7 +89,7 @@ impl NetworkApplication {
             client_build: 12340,
             login_server_id: server_id as u32,
             // The trick is that we need to uppercase the account name
-            username: NormalizedString::new(username).unwrap().to_string(),
+            username: username.to_string(),
             client_seed: seed_value,
             client_proof,
             addon_info: vec![],
@@ -88,9 +100,9 @@ impl NetworkApplication {
             dos_response: 0,
         }
         .write_unencrypted_client(&mut &world_server_stream)
-        .unwrap();
+        .map_err(|err| NetworkError::protocol("Sending the auth session", err))?;
 
-        Arc::new_cyclic(|weak| {
+        Ok(Arc::new_cyclic(|weak| {
             WorldServer::new(
                 weak.clone(),
                 world_server_stream,
@@ -98,7 +110,7 @@ impl NetworkApplication {
                 decrypter,
                 packet_handler_sender,
             )
-        })
+        }))
     }
 
     fn spawn_packet_handler_thread(
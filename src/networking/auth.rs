@@ -1,3 +1,4 @@
+use crate::networking::NetworkError;
 use std::net::{Ipv4Addr, TcpStream};
 use wow_login_messages::Message;
 use wow_login_messages::all::{CMD_AUTH_LOGON_CHALLENGE_Client, Locale, Os, Platform, ProtocolVersion, Version};
@@ -14,7 +15,7 @@ pub fn auth(
     mut auth_server: &mut TcpStream,
     username: &str,
     password: &str,
-) -> ([u8; SESSION_KEY_LENGTH as usize], CMD_REALM_LIST_Server) {
+) -> Result<([u8; SESSION_KEY_LENGTH as usize], CMD_REALM_LIST_Server), NetworkError> {
     CMD_AUTH_LOGON_CHALLENGE_Client {
         protocol_version: ProtocolVersion::Eight, // We are pretending to be 1.12
         version: Version {
@@ -31,9 +32,10 @@ pub fn auth(
         account_name: username.to_string(),     //
     }
     .write(&mut auth_server)
-    .unwrap();
+    .map_err(|err| NetworkError::protocol("Sending the logon challenge", err))?;
 
-    let s = expect_server_message::<CMD_AUTH_LOGON_CHALLENGE_Server, _>(&mut auth_server).unwrap();
+    let s = expect_server_message::<CMD_AUTH_LOGON_CHALLENGE_Server, _>(&mut auth_server)
+        .map_err(|err| NetworkError::protocol("Reading the logon challenge", err))?;
 
     let c = if let CMD_AUTH_LOGON_CHALLENGE_Server::Success {
         generator,
@@ -44,19 +46,27 @@ pub fn auth(
     } = s
     {
         let generator = generator[0];
-        let large_safe_prime = large_safe_prime.try_into().unwrap();
-        let server_public_key = PublicKey::from_le_bytes(server_public_key).unwrap();
+        let large_safe_prime = large_safe_prime
+            .try_into()
+            .map_err(|prime: Vec<u8>| NetworkError::protocol("Invalid large safe prime length", prime.len()))?;
+        let server_public_key = PublicKey::from_le_bytes(server_public_key)
+            .map_err(|err| NetworkError::protocol("Invalid server public key", err))?;
 
         SrpClientChallenge::new(
-            NormalizedString::new(username).unwrap(),
-            NormalizedString::new(password).unwrap(),
+            NormalizedString::new(username)
+                .map_err(|err| NetworkError::Authentication(format!("Invalid username: {}", err)))?,
+            NormalizedString::new(password)
+                .map_err(|err| NetworkError::Authentication(format!("Invalid password: {}", err)))?,
             generator,
             large_safe_prime,
             server_public_key,
             salt,
         )
     } else {
-        panic!()
+        return Err(NetworkError::Authentication(format!(
+            "The server rejected the logon challenge ({:?})",
+            s
+        )));
     };
 
     CMD_AUTH_LOGON_PROOF_Client {
@@ -67,18 +77,27 @@ pub fn auth(
         security_flag: CMD_AUTH_LOGON_PROOF_Client_SecurityFlag::empty(),
     }
     .write(&mut auth_server)
-    .unwrap();
+    .map_err(|err| NetworkError::protocol("Sending the logon proof", err))?;
 
-    let s = expect_server_message::<CMD_AUTH_LOGON_PROOF_Server, _>(&mut auth_server).unwrap();
+    let s = expect_server_message::<CMD_AUTH_LOGON_PROOF_Server, _>(&mut auth_server)
+        .map_err(|err| NetworkError::protocol("Reading the logon proof", err))?;
     let c = if let CMD_AUTH_LOGON_PROOF_Server::Success { server_proof, .. } = s {
-        c.verify_server_proof(server_proof).unwrap()
+        c.verify_server_proof(server_proof)
+            .map_err(|err| NetworkError::Authentication(format!("The server proof doesn't match: {}", err)))?
     } else {
-        panic!()
+        // The server answers a wrong password with a failed proof, as only then it can tell.
+        return Err(NetworkError::Authentication(format!(
+            "The server rejected the logon proof ({:?})",
+            s
+        )));
     };
 
-    CMD_REALM_LIST_Client {}.write(&mut auth_server).unwrap();
+    CMD_REALM_LIST_Client {}
+        .write(&mut auth_server)
+        .map_err(|err| NetworkError::protocol("Requesting the realm list", err))?;
 
-    let realms = expect_server_message::<CMD_REALM_LIST_Server, _>(&mut auth_server).unwrap();
+    let realms = expect_server_message::<CMD_REALM_LIST_Server, _>(&mut auth_server)
+        .map_err(|err| NetworkError::protocol("Reading the realm list", err))?;
 
-    (*c.session_key(), realms)
+    Ok((*c.session_key(), realms))
 }
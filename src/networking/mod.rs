@@ -1,3 +1,4 @@
+use std::fmt::{Display, Formatter};
 use wow_srp::wrath_header::ClientDecrypterHalf;
 
 pub mod application;
@@ -6,6 +7,48 @@ pub mod movement_tracker;
 pub mod utils;
 pub mod world;
 
+#[cfg(test)]
+mod tests;
+
+/// Errors while logging into the realm, before the networking threads are spawned.
+#[derive(Debug)]
+pub enum NetworkError {
+    /// The TCP connection couldn't be established, i.e. the server is down or the address is wrong.
+    Connect {
+        address: String,
+        error: std::io::Error,
+    },
+    /// The SRP handshake failed, e.g. because of an unknown account or a wrong password.
+    Authentication(String),
+    /// The server sent something unexpected or closed the connection during the handshake.
+    Protocol(String),
+}
+
+impl NetworkError {
+    fn protocol(context: &str, error: impl Display) -> Self {
+        NetworkError::Protocol(format!("{}: {}", context, error))
+    }
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkError::Connect { address, error } => write!(f, "Could not connect to {}: {}", address, error),
+            NetworkError::Authentication(reason) => write!(f, "Authentication failed: {}", reason),
+            NetworkError::Protocol(reason) => write!(f, "Unexpected response from the server: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetworkError::Connect { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
 pub fn skip_encrypted<R: std::io::Read>(
     mut r: R,
     d: &mut ClientDecrypterHalf,
@@ -1,16 +1,31 @@
 use crate::networking::world::WorldServer;
 use crate::physics::character_movement_information::CharacterMovementInformation;
+use crate::physics::physics_state::PhysicsState;
 use crate::rendering::common::coordinate_systems;
 use glam::{Quat, Vec3};
 use std::f32::consts::PI;
 use std::sync::Weak;
 use std::time::Instant;
 use wow_world_messages::wrath::{
-    MSG_MOVE_HEARTBEAT, MSG_MOVE_START_BACKWARD, MSG_MOVE_START_FORWARD, MSG_MOVE_START_STRAFE_LEFT,
-    MSG_MOVE_START_STRAFE_RIGHT, MSG_MOVE_START_TURN_LEFT, MSG_MOVE_START_TURN_RIGHT, MSG_MOVE_STOP, MovementFlags,
-    MovementInfo, MovementInfo_MovementFlags, Vector3d,
+    MSG_MOVE_FALL_LAND, MSG_MOVE_HEARTBEAT, MSG_MOVE_JUMP, MSG_MOVE_START_BACKWARD, MSG_MOVE_START_FORWARD,
+    MSG_MOVE_START_STRAFE_LEFT, MSG_MOVE_START_STRAFE_RIGHT, MSG_MOVE_START_TURN_LEFT, MSG_MOVE_START_TURN_RIGHT,
+    MSG_MOVE_STOP, MovementFlags, MovementInfo, MovementInfo_MovementFlags, MovementInfo_MovementFlags_Falling,
+    Vector3d,
 };
 
+/// The CMSG MOVE packets that the [`MovementTracker`] sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MovementMessage {
+    Jump,
+    FallLand,
+    Stop,
+    StartForward,
+    StartBackward,
+    StartStrafeLeft,
+    StartStrafeRight,
+    Heartbeat,
+}
+
 /// The Movement Tracker is the struct responsible for sending the CMSG MOVE packets for the current player.
 /// It has nothing to do with tracking movement of other entities!
 pub struct MovementTracker {
@@ -18,41 +33,144 @@ pub struct MovementTracker {
     last_movement_info: MovementInfo,
     last_orientation: f32,
     last_heartbeat: Instant,
+    /// How the current jump or fall started, None while on the ground.
+    fall: Option<MovementInfo_MovementFlags_Falling>,
 }
 
 impl MovementTracker {
+    /// Relatively large to prevent physics drift from causing a movement
+    const EPSILON: f32 = 1.0e-3;
+    /// Walking down a slope may briefly lose contact with the ground, which shouldn't count as falling.
+    const FALL_DELAY: f32 = 0.1;
+
     pub fn new(world_server: Weak<WorldServer>) -> Self {
         Self {
             world_server,
             last_movement_info: MovementInfo::default(),
             last_orientation: 0.0,
             last_heartbeat: Instant::now(),
+            fall: None,
         }
     }
 
     pub fn track_movement(&mut self, movement_info: CharacterMovementInformation) {
+        let world = self
+            .world_server
+            .upgrade()
+            .expect("World Server to outlive Movement Tracker");
+
+        let Some((message, info)) = self.next_message(movement_info, world.get_timestamp()) else {
+            return;
+        };
+
+        let guid = *world.player_guid.get().expect("Player Guid is already set");
+        match message {
+            MovementMessage::Jump => world.send_encrypted(MSG_MOVE_JUMP { guid, info }),
+            MovementMessage::FallLand => world.send_encrypted(MSG_MOVE_FALL_LAND { guid, info }),
+            MovementMessage::Stop => world.send_encrypted(MSG_MOVE_STOP { guid, info }),
+            MovementMessage::StartForward => world.send_encrypted(MSG_MOVE_START_FORWARD { guid, info }),
+            MovementMessage::StartBackward => world.send_encrypted(MSG_MOVE_START_BACKWARD { guid, info }),
+            MovementMessage::StartStrafeLeft => world.send_encrypted(MSG_MOVE_START_STRAFE_LEFT { guid, info }),
+            MovementMessage::StartStrafeRight => world.send_encrypted(MSG_MOVE_START_STRAFE_RIGHT { guid, info }),
+            MovementMessage::Heartbeat => world.send_encrypted(MSG_MOVE_HEARTBEAT { guid, info }),
+        }
+        .expect("Sending message to be successful");
+    }
+
+    /// Decides which message (if any) has to be sent for the movement of this update, without sending it.
+    pub(super) fn next_message(
+        &mut self,
+        movement_info: CharacterMovementInformation,
+        timestamp: u32,
+    ) -> Option<(MovementMessage, MovementInfo)> {
         let counter_rotation = Quat::from_rotation_z(PI - movement_info.orientation);
         let delta_unrotated =
             counter_rotation * coordinate_systems::adt_to_blender(movement_info.delta_movement.into());
 
-        self._track_movement(
+        let descending = !movement_info.in_water && delta_unrotated.z < -Self::EPSILON;
+        if movement_info.jumped {
+            self.fall = Some(Self::build_falling(
+                &movement_info,
+                PhysicsState::JUMP_VELOCITY,
+            ));
+        } else if self.fall.is_none() && movement_info.fall_time >= Self::FALL_DELAY && descending {
+            // Walked off an edge
+            self.fall = Some(Self::build_falling(&movement_info, 0.0));
+        }
+
+        self._next_message(
             delta_unrotated.into(),
             movement_info.absolute_position,
             movement_info.orientation,
-        );
+            movement_info.in_water,
+            movement_info.jumped,
+            movement_info.fall_time,
+            movement_info.landed,
+            timestamp,
+        )
     }
 
-    fn _track_movement(&mut self, delta_unrotated: Vec3, absolute_position: Vec3, orientation: f32) {
-        let world = self
-            .world_server
-            .upgrade()
-            .expect("World Server to outlive Movement Tracker");
+    /// The direction is the one of the horizontal movement (or the orientation when jumping straight up), as the
+    /// server extrapolates the fall from it.
+    fn build_falling(movement_info: &CharacterMovementInformation, z_speed: f32) -> MovementInfo_MovementFlags_Falling {
+        let horizontal_velocity = movement_info.velocity.truncate();
+        let xy_speed = horizontal_velocity.length();
+        let angle = if xy_speed > Self::EPSILON {
+            horizontal_velocity.y.atan2(horizontal_velocity.x)
+        } else {
+            movement_info.orientation
+        };
 
-        let player_guid = world.player_guid.get().expect("Player Guid is already set");
-        let timestamp = world.get_timestamp();
+        MovementInfo_MovementFlags_Falling {
+            z_speed,
+            cos_angle: angle.cos(),
+            sin_angle: angle.sin(),
+            xy_speed,
+        }
+    }
 
-        let info = Self::build_movement_info(delta_unrotated, absolute_position, orientation, timestamp);
-        let info_clone = info.clone();
+    #[allow(clippy::too_many_arguments)]
+    fn _next_message(
+        &mut self,
+        delta_unrotated: Vec3,
+        absolute_position: Vec3,
+        orientation: f32,
+        in_water: bool,
+        jumped: bool,
+        fall_time: f32,
+        landed: bool,
+        timestamp: u32,
+    ) -> Option<(MovementMessage, MovementInfo)> {
+        if fall_time <= 0.0 || landed {
+            // Landed or e.g. dived into water
+            let was_falling = self.fall.take().is_some();
+            if landed && was_falling {
+                // The landing isn't falling anymore, only the fall_time is left for the fall damage.
+                let info = Self::build_movement_info(
+                    delta_unrotated,
+                    absolute_position,
+                    orientation,
+                    in_water,
+                    None,
+                    fall_time,
+                    timestamp,
+                );
+                self.last_heartbeat = Instant::now();
+                self.last_movement_info = info.clone();
+                return Some((MovementMessage::FallLand, info));
+            }
+        }
+
+        let info = Self::build_movement_info(
+            delta_unrotated,
+            absolute_position,
+            orientation,
+            in_water,
+            self.fall,
+            fall_time,
+            timestamp,
+        );
+        let last_movement_info = std::mem::replace(&mut self.last_movement_info, info.clone());
 
         // TODO: integrate into the following if-else branch. It has been commented out for the time being.
         // if orientation != self.last_orientation {
@@ -75,81 +193,58 @@ impl MovementTracker {
         //     self.last_orientation = orientation;
         // }
 
-        if info.flags.is_empty() {
-            if Self::is_moving(&self.last_movement_info.flags) {
-                // We've been moving, so stop.
-                let msg = MSG_MOVE_STOP {
-                    guid: *player_guid,
-                    info,
-                };
-                world
-                    .send_encrypted(msg)
-                    .expect("Sending message to be successful");
-                self.last_heartbeat = Instant::now();
-            } // else: do nothing, we're standing still.
-        } else if self.last_movement_info.flags != info.flags {
-            self.last_heartbeat = Instant::now();
+        let message = if jumped {
+            MovementMessage::Jump
+        } else if !Self::is_moving(&info.flags) && info.flags.get_falling().is_none() {
+            if !Self::is_moving(&last_movement_info.flags) {
+                // We're standing still.
+                return None;
+            }
+
+            // We've been moving, so stop.
+            MovementMessage::Stop
+        } else if last_movement_info.flags != info.flags {
             if info.flags.get_forward() {
-                world
-                    .send_encrypted(MSG_MOVE_START_FORWARD {
-                        guid: *player_guid,
-                        info,
-                    })
-                    .expect("Sending message to be successful");
+                MovementMessage::StartForward
             } else if info.flags.get_backward() {
-                world
-                    .send_encrypted(MSG_MOVE_START_BACKWARD {
-                        guid: *player_guid,
-                        info,
-                    })
-                    .expect("Sending message to be successful");
+                MovementMessage::StartBackward
             } else if info.flags.get_strafe_left() {
-                world
-                    .send_encrypted(MSG_MOVE_START_STRAFE_LEFT {
-                        guid: *player_guid,
-                        info,
-                    })
-                    .expect("Sending message to be successful");
+                MovementMessage::StartStrafeLeft
             } else if info.flags.get_strafe_right() {
-                world
-                    .send_encrypted(MSG_MOVE_START_STRAFE_RIGHT {
-                        guid: *player_guid,
-                        info,
-                    })
-                    .expect("Sending message to be successful");
-            }
-        } else {
-            // TODO: this currently fires a ByteBufferException sometimes when parsing apparently.
-            if self.last_heartbeat.elapsed().as_millis() >= 500 {
-                world
-                    .send_encrypted(MSG_MOVE_HEARTBEAT {
-                        guid: *player_guid,
-                        info,
-                    })
-                    .expect("Sending message to be successful");
+                MovementMessage::StartStrafeRight
+            } else {
                 self.last_heartbeat = Instant::now();
+                return None;
             }
-        }
+        } else if self.last_heartbeat.elapsed().as_millis() >= 500 {
+            // TODO: this currently fires a ByteBufferException sometimes when parsing apparently.
+            MovementMessage::Heartbeat
+        } else {
+            return None;
+        };
 
-        self.last_movement_info = info_clone;
+        self.last_heartbeat = Instant::now();
+        Some((message, info))
     }
 
     fn build_movement_info(
         delta_unrotated: Vec3,
         absolute_position: Vec3,
         orientation: f32,
+        in_water: bool,
+        fall: Option<MovementInfo_MovementFlags_Falling>,
+        fall_time: f32,
         timestamp: u32,
     ) -> MovementInfo {
-        let inner_flags = Self::build_movement_flags(delta_unrotated);
+        let inner_flags = Self::build_movement_flags(delta_unrotated, in_water);
+        let mut flags = MovementInfo_MovementFlags::new(inner_flags.as_int(), None, None, None, None);
+        if let Some(fall) = fall {
+            // The falling flag requires its data
+            flags = flags.set_falling(fall);
+        }
 
         MovementInfo {
-            flags: MovementInfo_MovementFlags::new(
-                inner_flags.as_int(),
-                None, // TODO:
-                None,
-                None,
-                None,
-            ),
+            flags,
             timestamp,
             position: Vector3d {
                 x: absolute_position.x,
@@ -157,27 +252,24 @@ impl MovementTracker {
                 z: absolute_position.z,
             },
             orientation,
-            fall_time: 0.0, // TODO
+            // in milliseconds
+            fall_time: fall_time * 1000.0,
         }
     }
 
     /// Converts the actual movement input into MovementFlags.
     /// delta_unrotated means it's in the characters local space (blender space?)
-    fn build_movement_flags(delta_unrotated: Vec3) -> MovementFlags {
-        const EPSILON: f32 = 1.0e-3; // relatively large to prevent physics drift from causing a movement
-        if delta_unrotated.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
-            return MovementFlags::new(MovementFlags::NONE);
-        }
-
+    /// The falling flag isn't part of it, as it requires the data of the jump or fall.
+    fn build_movement_flags(delta_unrotated: Vec3, in_water: bool) -> MovementFlags {
         let mut flags = MovementFlags::new(0);
-
-        if delta_unrotated.z.is_sign_negative() && delta_unrotated.z.abs() > EPSILON {
-            flags.set_falling();
-            // TODO: this implies setting fall_time at the very least but also a few more flags on MovementInfo
-            return flags; // No chance to walk or do anything else.
+        if in_water {
+            // Sinking towards the swim depth isn't falling
+            flags.set_swimming();
+        } else if delta_unrotated.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+            return MovementFlags::new(MovementFlags::NONE);
         }
 
-        if delta_unrotated.x.abs() > EPSILON {
+        if delta_unrotated.x.abs() > Self::EPSILON {
             if delta_unrotated.x.is_sign_negative() {
                 flags.set_strafe_left();
             } else {
@@ -185,7 +277,7 @@ impl MovementTracker {
             }
         }
 
-        if delta_unrotated.y.abs() > EPSILON {
+        if delta_unrotated.y.abs() > Self::EPSILON {
             if delta_unrotated.y.is_sign_negative() {
                 flags.set_backward();
             } else {
@@ -0,0 +1,62 @@
+use crate::networking::movement_tracker::{MovementMessage, MovementTracker};
+use crate::physics::character_movement_information::CharacterMovementInformation;
+use crate::physics::physics_state::PhysicsState;
+use glam::Vec3;
+use std::sync::Weak;
+
+const TIMESTEP: f32 = 1.0 / 60.0;
+
+/// A character that only moves vertically, by `delta_z` during this update.
+fn movement(delta_z: f32, jumped: bool, fall_time: f32, landed: bool) -> CharacterMovementInformation {
+    let delta_movement = Vec3::new(0.0, 0.0, delta_z);
+    CharacterMovementInformation {
+        delta_movement,
+        velocity: delta_movement / TIMESTEP,
+        absolute_position: Vec3::new(100.0, 200.0, 50.0),
+        orientation: 0.0,
+        in_water: false,
+        jumped,
+        fall_time,
+        landed,
+    }
+}
+
+#[test]
+fn jump_until_landing() {
+    let mut tracker = MovementTracker::new(Weak::new());
+
+    let (message, info) = tracker
+        .next_message(movement(0.13, true, TIMESTEP, false), 1)
+        .expect("Jump to be sent");
+    assert_eq!(message, MovementMessage::Jump);
+    assert_eq!(
+        info.flags.get_falling().map(|fall| fall.z_speed),
+        Some(PhysicsState::JUMP_VELOCITY)
+    );
+
+    // Neither the ascent nor the descent change the flags
+    assert!(
+        tracker
+            .next_message(movement(0.1, false, 2.0 * TIMESTEP, false), 2)
+            .is_none()
+    );
+    assert!(
+        tracker
+            .next_message(movement(-0.1, false, 3.0 * TIMESTEP, false), 3)
+            .is_none()
+    );
+
+    let (message, info) = tracker
+        .next_message(movement(-0.05, false, 0.5, true), 4)
+        .expect("Landing to be sent");
+    assert_eq!(message, MovementMessage::FallLand);
+    assert!(info.flags.get_falling().is_none());
+    assert_eq!(info.fall_time, 500.0);
+
+    // Standing still on the ground again
+    assert!(
+        tracker
+            .next_message(movement(0.0, false, 0.0, false), 5)
+            .is_none()
+    );
+}
@@ -2,6 +2,17 @@ use glam::Vec3;
 
 pub struct CharacterMovementInformation {
     pub delta_movement: Vec3,
+    /// The delta_movement in yards per second.
+    pub velocity: Vec3,
     pub absolute_position: Vec3,
     pub orientation: f32,
+    /// Whether the character is swimming, i.e. below a liquid surface.
+    pub in_water: bool,
+    /// Whether a jump has been started during this update.
+    pub jumped: bool,
+    /// For how long the character has been jumping or falling in seconds, 0 on the ground. On landing, this is the
+    /// duration of the whole fall.
+    pub fall_time: f32,
+    /// Whether the character touched the ground again during this update.
+    pub landed: bool,
 }
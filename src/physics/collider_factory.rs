@@ -1,19 +1,24 @@
 use crate::physics::physics_simulator::PhysicsSimulator;
 use crate::physics::terrain_tile_colliders::{DoodadColliderEntry, TerrainTileColliders};
+use crate::rendering::asset_graph::m2_generator::M2Generator;
 use crate::rendering::asset_graph::nodes::adt_node::{
     ADTNode, DoodadReference, IRMesh, M2Node, NodeReference, TerrainTile, WMOGroupNode, WMONode, WMOReference,
 };
+use crate::rendering::asset_graph::resolver::Resolver;
 use crate::rendering::common::coordinate_systems;
 use crate::rendering::common::mesh_merger::MeshMerger;
 use crate::rendering::common::types::Mesh;
-use glam::{Affine3A, Quat, Vec3};
+use glam::{Affine3A, Quat, Vec3, Vec3A};
 use itertools::Itertools;
-use log::trace;
-use nalgebra::Isometry3;
+use log::{debug, trace, warn};
+use nalgebra::{DMatrix, Isometry3};
 use rapier3d::dynamics::RigidBodyHandle;
 use rapier3d::geometry::{Collider, ColliderBuilder, ColliderHandle, MeshConverter};
+use std::f32::consts::FRAC_PI_2;
 use std::ops::Deref;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, RwLock, Weak};
+use std::time::Instant;
 
 pub enum ColliderFactory {}
 
@@ -23,6 +28,7 @@ impl ColliderFactory {
         simulator: &mut PhysicsSimulator,
         handle: RigidBodyHandle,
         adt: &Arc<ADTNode>,
+        m2_resolver: &Resolver<M2Generator, M2Node>,
     ) {
         let weak = Arc::downgrade(adt);
         Self::process_terrain_heightmap(adt_nodes, simulator, handle, adt, &weak);
@@ -36,7 +42,47 @@ impl ColliderFactory {
             .doodad_colliders
             .clone();
 
-        Self::process_doodads(simulator, handle, &adt.doodads, &doodad_colliders, None);
+        Self::process_doodads(
+            simulator,
+            handle,
+            &adt.doodads,
+            &doodad_colliders,
+            None,
+            m2_resolver,
+        );
+    }
+
+    /// Builds a heightfield collider from the MCVT heights of a terrain chunk, which are relative to `position`.
+    /// The heightfield has twice the resolution of the outer grid, so that the inner vertices are part of it and the
+    /// points in between are interpolated along the outer edges. As heightfield cells are always split along the same
+    /// diagonal, half of those quarter cells are triangulated differently than the terrain mesh, which is close enough.
+    pub fn terrain_heightfield(position: Vec3A, heights: &[f32]) -> Collider {
+        const CELLS: usize = 16;
+        let outer = |row: usize, column: usize| heights[17 * row + column];
+        let inner = |row: usize, column: usize| heights[17 * row + column + 9];
+
+        // Parry's heightfields are y-up and span [-0.5, 0.5] in x and z, rows go along z and columns along x.
+        // Rotating them by 90° around x maps their z to our -y, so the terrain columns (growing in -y) are the
+        // heightfield rows and the terrain rows (growing in -x) are the heightfield columns in reverse.
+        let heightfield = DMatrix::from_fn(CELLS + 1, CELLS + 1, |half_column, reversed_half_row| {
+            let half_row = CELLS - reversed_half_row;
+            let (row, column) = (half_row / 2, half_column / 2);
+            match (half_row % 2, half_column % 2) {
+                (0, 0) => outer(row, column),
+                (1, 1) => inner(row, column),
+                (0, _) => (outer(row, column) + outer(row, column + 1)) / 2.0,
+                _ => (outer(row, column) + outer(row + 1, column)) / 2.0,
+            }
+        });
+
+        let size = coordinate_systems::GRID_SIZE * 8.0;
+        let translation = Vec3::from(position) - Vec3::new(size / 2.0, size / 2.0, 0.0);
+        ColliderBuilder::heightfield(heightfield, Vec3::new(size, 1.0, size).into())
+            .position(Isometry3::from((
+                translation,
+                Quat::from_rotation_x(FRAC_PI_2),
+            )))
+            .build()
     }
 
     fn process_terrain_heightmap(
@@ -47,7 +93,13 @@ impl ColliderFactory {
         weak: &Weak<ADTNode>,
     ) {
         if !adt_nodes.iter().any(|entry| entry.0.ptr_eq(weak)) {
-            let colliders = adt.terrain.iter().map(|terrain| terrain.into()).collect();
+            let now = Instant::now();
+            let colliders = adt.terrain.iter().map(Collider::from).collect_vec();
+            debug!(
+                "Building {} terrain colliders took {}µs",
+                colliders.len(),
+                now.elapsed().as_micros()
+            );
             let collider_handles = simulator.insert_colliders(colliders, handle);
             adt_nodes.push((weak.clone(), TerrainTileColliders::new(collider_handles)));
         }
@@ -62,6 +114,8 @@ impl ColliderFactory {
         simulator: &mut PhysicsSimulator,
         handle: RigidBodyHandle,
         adt: &ADTNode,
+        m2_resolver: &Resolver<M2Generator, M2Node>,
+        wmo_group_resolver: &Resolver<M2Generator, WMOGroupNode>,
     ) {
         for wmo_ref in &adt.wmos {
             let resolved_wmo = wmo_ref.reference.reference.read().expect("poisoned lock");
@@ -82,8 +136,23 @@ impl ColliderFactory {
                     .1
                     .clone();
 
-                Self::process_wmo_groups(simulator, handle, wmo_ref, &wmo.subgroups, &colliders);
-                Self::process_wmo_doodads(wmo_doodads, simulator, handle, &weak_wmo, wmo_ref, wmo);
+                Self::process_wmo_groups(
+                    simulator,
+                    handle,
+                    wmo_ref,
+                    &wmo.subgroups,
+                    &colliders,
+                    wmo_group_resolver,
+                );
+                Self::process_wmo_doodads(
+                    wmo_doodads,
+                    simulator,
+                    handle,
+                    &weak_wmo,
+                    wmo_ref,
+                    wmo,
+                    m2_resolver,
+                );
             }
         }
     }
@@ -94,6 +163,7 @@ impl ColliderFactory {
         wmo_ref: &WMOReference,
         groups: &Vec<Arc<NodeReference<WMOGroupNode>>>,
         colliders: &RwLock<Vec<(Weak<WMOGroupNode>, ColliderHandle)>>,
+        wmo_group_resolver: &Resolver<M2Generator, WMOGroupNode>,
     ) {
         let (scale, rotation, translation) = wmo_ref.transform.to_scale_rotation_translation();
         for group_reference in groups {
@@ -118,13 +188,21 @@ impl ColliderFactory {
                     group_reference.reference_str
                 );
 
-                let mesh_batches = group
-                    .mesh_batches
-                    .iter()
-                    // TODO: Get rid of that clone
-                    .map(|mesh_lock| mesh_lock.read().expect("poisoned read lock").data.clone())
-                    .collect_vec();
-                let mut mesh = MeshMerger::merge_meshes_index_only(&mesh_batches);
+                let mut mesh = match &group.collision_mesh {
+                    // Groups can consist of non-collidable faces only, trimeshes can't be empty, though.
+                    Some(collision_mesh) if collision_mesh.index_buffer.is_empty() => continue,
+                    Some(collision_mesh) => collision_mesh.clone(),
+                    None => match Self::merge_render_batches(group_reference, group, wmo_group_resolver) {
+                        Ok(mesh) => mesh,
+                        Err(err) => {
+                            warn!(
+                                "Skipping the collider of WMO Group {}: {}",
+                                group_reference.reference_str, err
+                            );
+                            continue;
+                        }
+                    },
+                };
 
                 // TODO: Validate that the coordinate systems are matching, but since we are rotating the mesh
                 //  afterwards, I think for now mesh and scale are in the same coordinate system
@@ -153,6 +231,19 @@ impl ColliderFactory {
         }
     }
 
+    /// Fallback for groups without a BSP tree: Use the render geometry as collider, which lacks the collision-only
+    /// faces and contains detail faces that shouldn't collide.
+    fn merge_render_batches(
+        group_reference: &NodeReference<WMOGroupNode>,
+        group: &WMOGroupNode,
+        wmo_group_resolver: &Resolver<M2Generator, WMOGroupNode>,
+    ) -> Result<Mesh, anyhow::Error> {
+        // TODO: Get rid of that clone
+        let mesh_batches = wmo_group_resolver.collider_meshes(&group_reference.reference_str, group);
+        group.collider_pending.store(false, Ordering::Release);
+        Ok(MeshMerger::merge_meshes_index_only(&mesh_batches?))
+    }
+
     fn process_wmo_doodads(
         wmo_doodads: &mut Vec<(Weak<WMONode>, Arc<RwLock<Vec<DoodadColliderEntry>>>)>,
         simulator: &mut PhysicsSimulator,
@@ -160,6 +251,7 @@ impl ColliderFactory {
         weak_wmo: &Weak<WMONode>,
         wmo_ref: &WMOReference,
         wmo: &WMONode,
+        m2_resolver: &Resolver<M2Generator, M2Node>,
     ) {
         if !wmo_doodads.iter_mut().any(|entry| entry.0.ptr_eq(weak_wmo)) {
             // No collider for that wmo yet, but we have resolved the reference, so we can submit collision handles.
@@ -173,21 +265,25 @@ impl ColliderFactory {
             .1
             .clone();
 
-        Self::process_doodads(
-            simulator,
-            handle,
-            &wmo.doodads,
-            &colliders,
-            Some(wmo_ref.transform),
-        );
+        for doodads in wmo.active_doodad_sets(wmo_ref.map_obj_def.doodadSet) {
+            Self::process_doodads(
+                simulator,
+                handle,
+                doodads,
+                &colliders,
+                Some(wmo_ref.transform),
+                m2_resolver,
+            );
+        }
     }
 
     fn process_doodads(
         simulator: &mut PhysicsSimulator,
         handle: RigidBodyHandle,
-        doodads: &Vec<Arc<DoodadReference>>,
+        doodads: &[Arc<DoodadReference>],
         doodad_colliders: &RwLock<Vec<DoodadColliderEntry>>,
         parent_transform: Option<Affine3A>,
+        m2_resolver: &Resolver<M2Generator, M2Node>,
     ) {
         // TODO: A lot of those doodads probably shouldn't be collidable (lilypad, jugs, wall shield)
         for doodad in doodads {
@@ -198,6 +294,10 @@ impl ColliderFactory {
                 continue;
             };
 
+            if dad.is_emitter_only {
+                continue;
+            }
+
             {
                 let has_collider_for_key = doodad_colliders
                     .read()
@@ -227,7 +327,18 @@ impl ColliderFactory {
                 doodad.reference.reference_str, doodad_translation
             );
 
-            let mut mesh = dad.deref().mesh.read().expect("Mesh RLock").data.clone();
+            let meshes = m2_resolver.collider_meshes(&doodad.reference.reference_str, dad);
+            dad.collider_pending.store(false, Ordering::Release);
+            let mut mesh = match meshes {
+                Ok(mut meshes) => meshes.pop().expect("M2 nodes have exactly one mesh"),
+                Err(err) => {
+                    warn!(
+                        "Skipping the collider of Doodad {}: {}",
+                        doodad.reference.reference_str, err
+                    );
+                    continue;
+                }
+            };
             // TODO: Validate that the coordinate systems are matching, but since we are rotating the mesh
             //  afterwards, I think for now mesh and scale are in the same coordinate system
             MeshMerger::mesh_scale_position(&mut mesh, scale);
@@ -259,9 +370,7 @@ impl ColliderFactory {
 // TODO: We have differing implementations of From<T> for Collider. Some set the position, some don't
 impl From<&TerrainTile> for Collider {
     fn from(value: &TerrainTile) -> Self {
-        let mut collider: Collider = value.mesh.read().expect("Mesh RLock").deref().into();
-        collider.set_position(Into::<Vec3>::into(value.position).into());
-        collider
+        ColliderFactory::terrain_heightfield(value.position, &value.heights)
     }
 }
 
@@ -273,7 +382,10 @@ impl From<&M2Node> for Collider {
 
 impl From<&IRMesh> for Collider {
     fn from(value: &IRMesh) -> Self {
-        (&value.data).into()
+        value
+            .data()
+            .expect("Cannot build a collider of a hollowed mesh")
+            .into()
     }
 }
 
@@ -5,3 +5,6 @@ pub mod terrain_tile_colliders;
 
 pub mod character_movement_information;
 pub mod collider_factory;
+
+#[cfg(test)]
+mod tests;
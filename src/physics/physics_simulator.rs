@@ -108,6 +108,29 @@ impl PhysicsSimulator {
             .set_translation(translation.into());
     }
 
+    /// The normal of the first collider below the center of the given collider, up to `max_distance` away.
+    pub fn ground_normal(&self, collider_handle: ColliderHandle, max_distance: f32) -> Option<Vec3> {
+        let collider = self
+            .collider_set
+            .get(collider_handle)
+            .expect("Collider Handle to be valid");
+
+        let ray = Ray::new(
+            Point::from(*collider.translation()),
+            vector![0.0, 0.0, -1.0],
+        );
+        self.queries
+            .cast_ray_and_get_normal(
+                &self.rigid_body_set,
+                &self.collider_set,
+                &ray,
+                max_distance,
+                true,
+                QueryFilter::default().exclude_collider(collider_handle),
+            )
+            .map(|(_, intersection)| intersection.normal.into())
+    }
+
     pub fn move_character(
         &mut self,
         controller: &KinematicCharacterController,
@@ -1,15 +1,20 @@
-use std::ops::{Deref, DerefMut};
+use std::collections::HashMap;
+use std::ops::DerefMut;
 use std::sync::{Arc, OnceLock, RwLock, Weak};
 
 use crate::game::application::GameApplication;
+use crate::game::map_manager::{MapManager, TileEvent};
 use crate::physics::character_movement_information::CharacterMovementInformation;
 use crate::physics::collider_factory::ColliderFactory;
 use crate::physics::physics_simulator::PhysicsSimulator;
 use crate::physics::terrain_tile_colliders::{DoodadColliderEntry, TerrainTileColliders};
 use crate::rendering::asset_graph::nodes::adt_node::{ADTNode, WMOGroupNode, WMONode};
 use glam::{Vec3, Vec3A};
+use log::warn;
 use rapier3d::control::KinematicCharacterController;
 use rapier3d::prelude::*;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::TryRecvError;
 
 pub struct PhysicsState {
     app: Weak<GameApplication>,
@@ -17,26 +22,54 @@ pub struct PhysicsState {
     rigid_body_handle: OnceLock<RigidBodyHandle>,
     character_controller: KinematicCharacterController,
     character_controller_collider: Option<ColliderHandle>, // TODO: We could get rid of the Option and just create a collider at (0, 0, 0), we'll teleport it every frame anyway.
+    /// Subscribed on the first [`PhysicsState::delta_map`], as the map manager isn't reachable before.
+    tile_events: Option<broadcast::Receiver<TileEvent>>,
+    /// The tiles of the map manager, kept up to date by the `tile_events`.
+    tiles: HashMap<(u8, u8), Weak<ADTNode>>,
     adt_nodes: Vec<(Weak<ADTNode>, TerrainTileColliders)>,
     wmo_doodads: Vec<(Weak<WMONode>, Arc<RwLock<Vec<DoodadColliderEntry>>>)>,
     wmo_colliders: Vec<(
         Weak<WMONode>,
         Arc<RwLock<Vec<(Weak<WMOGroupNode>, ColliderHandle)>>>,
     )>,
-    time_since_airborne: f32,
+    /// For how long the character has been jumping or falling, including the ascent of a jump.
+    fall_time: f32,
+    /// Whether the character stood on the ground after the last update, see [`PhysicsState::may_jump`].
+    grounded: bool,
+    /// The vertical velocity of the current jump or fall in yards per second, positive upwards.
+    vertical_velocity: f32,
 }
 
 impl PhysicsState {
+    /// How far below the liquid surface the character floats, so that the head stays above the surface.
+    const SWIM_DEPTH: f32 = 1.5;
+    /// The fraction of the distance to the swim depth that is covered per second, which damps the bobbing.
+    const BUOYANCY_DAMPING: f32 = 3.0;
+    /// Swimming is slower than walking.
+    const SWIM_SPEED_FACTOR: f32 = 0.6;
+    /// The initial upwards velocity of a jump, in yards per second, like the client's.
+    pub const JUMP_VELOCITY: f32 = 7.96;
+    /// The client's gravity in yards per second², it decelerates jumps and accelerates falls alike.
+    const GRAVITY: f32 = 19.29;
+    /// The steepest slope that the character stands on without sliding down, and thus may jump from.
+    pub(super) const WALKABLE_SLOPE_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+    const CAPSULE_HALF_HEIGHT: f32 = 1.0;
+    const CAPSULE_RADIUS: f32 = 0.5;
+    /// How far below the capsule the ground is looked for, when jumping.
+    const GROUND_PROBE_DISTANCE: f32 = 0.25;
+
     pub fn new(app: Weak<GameApplication>) -> Self {
         Self {
             app,
             physics_simulator: PhysicsSimulator::default(),
+            tile_events: None,
+            tiles: HashMap::new(),
             adt_nodes: vec![],
             rigid_body_handle: OnceLock::new(),
             character_controller: KinematicCharacterController {
                 up: Vector::z_axis(),
                 max_slope_climb_angle: std::f32::consts::FRAC_PI_2,
-                min_slope_slide_angle: std::f32::consts::FRAC_PI_4,
+                min_slope_slide_angle: Self::WALKABLE_SLOPE_ANGLE,
                 normal_nudge_factor: 5.0e-2, // was e-4. bigger value -> faster ascent, too large: jitter
                 slide: true,
                 //snap_to_ground: Some(CharacterLength::Relative(1.0)),
@@ -50,7 +83,9 @@ impl PhysicsState {
             character_controller_collider: None,
             wmo_doodads: vec![],
             wmo_colliders: vec![],
-            time_since_airborne: 0.0,
+            fall_time: 0.0,
+            grounded: false,
+            vertical_velocity: 0.0,
         }
     }
 
@@ -58,7 +93,7 @@ impl PhysicsState {
         self.app.upgrade().expect("Weak Pointer expired")
     }
 
-    pub fn update_fixed(&mut self, movement_relative: Vec3) -> CharacterMovementInformation {
+    pub fn update_fixed(&mut self, movement_relative: Vec3, jump: bool) -> CharacterMovementInformation {
         if self.character_controller_collider.is_none() {
             self.character_controller_collider = Some(self.create_character_collider());
         }
@@ -69,28 +104,46 @@ impl PhysicsState {
             .expect("has to be constructed already");
 
         self.delta_map();
-        let char = self.update_character(collider, movement_relative, false, timestep);
+        let player_location = *self
+            .app()
+            .game_state
+            .player_location
+            .read()
+            .expect("player read lock");
+        let liquid_height = self.liquid_height_at(player_location);
+        let char = self.update_character(
+            collider,
+            movement_relative,
+            jump,
+            false,
+            liquid_height,
+            timestep,
+        );
         self.physics_simulator.step();
         char
     }
 
-    // TODO: Implement notifications via https://docs.rs/tokio/latest/tokio/sync/broadcast/index.html
-
-    // TODO: Collider with heightfield at low res or rather meshes? Mesh would have the benefit of
-    //  already being in adt/whatever space. In the end, it should be a heightfield for performance reasons, though.
     pub fn delta_map(&mut self) {
-        // Find changed (i.e. added or removed) tiles. Currently, we don't go after interior changes.
         let app = self.app();
         let mm_lock = app.game_state.clone().map_manager.clone();
         let mm = mm_lock.read().expect("Read Lock on Map Manager");
         let handle = self.terrain_rb();
 
-        for adt in mm.tile_graph.values() {
+        self.receive_tile_events(&mm);
+
+        // Doodads and WMO groups are resolved lazily, so the loaded tiles are checked for new ones every time.
+        let tiles = self
+            .tiles
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect::<Vec<_>>();
+        for adt in &tiles {
             ColliderFactory::process_terrain_tiles(
                 &mut self.adt_nodes,
                 &mut self.physics_simulator,
                 handle,
                 adt,
+                &mm.m2_resolver,
             );
 
             ColliderFactory::process_wmos(
@@ -99,27 +152,129 @@ impl PhysicsState {
                 &mut self.physics_simulator,
                 handle,
                 adt,
+                &mm.m2_resolver,
+                &mm.wmo_group_resolver,
             );
         }
 
-        for (weak, tile_colliders) in self.adt_nodes.deref() {
-            if weak.upgrade().is_none() {
-                for &collider in &tile_colliders.terrain_colliders {
-                    self.physics_simulator.drop_collider(collider, false);
-                }
+        // Tiles that have been unloaded (and WMOs that aren't referenced by any loaded tile anymore)
+        // TODO: Technically some of the doodad weaks of living nodes may be dead, it's rather hypothetical, though.
+        let simulator = &mut self.physics_simulator;
+        self.adt_nodes.retain(|(weak, tile_colliders)| {
+            let alive = weak.strong_count() > 0;
+            if !alive {
+                Self::drop_tile_colliders(simulator, tile_colliders);
+            }
+            alive
+        });
 
-                let doodad_colliders = tile_colliders
-                    .doodad_colliders
-                    .read()
-                    .expect("poisoned lock");
+        self.wmo_doodads.retain(|(weak, doodad_colliders)| {
+            let alive = weak.strong_count() > 0;
+            if !alive {
+                Self::drop_doodad_colliders(simulator, doodad_colliders);
+            }
+            alive
+        });
+
+        self.wmo_colliders.retain(|(weak, group_colliders)| {
+            let alive = weak.strong_count() > 0;
+            if !alive {
+                Self::drop_group_colliders(simulator, group_colliders);
+            }
+            alive
+        });
+    }
 
-                for collider in doodad_colliders.iter() {
-                    self.physics_simulator
-                        .drop_collider(collider.collider_handle, false);
+    /// Applies the tiles that have been loaded or unloaded since the last call. When subscribing for the first time or
+    /// when too many events have been missed, the tiles are taken from the tile graph instead.
+    fn receive_tile_events(&mut self, mm: &MapManager) {
+        let Some(receiver) = self.tile_events.as_mut() else {
+            self.tile_events = Some(mm.subscribe_tiles());
+            self.tiles = Self::tiles_of(mm);
+            return;
+        };
+
+        loop {
+            match receiver.try_recv() {
+                Ok(TileEvent::Loaded(coords, adt)) => {
+                    self.tiles.insert(coords, Arc::downgrade(&adt));
+                }
+                Ok(TileEvent::Unloaded(coords)) => {
+                    self.tiles.remove(&coords);
                 }
+                Err(TryRecvError::Lagged(missed)) => {
+                    // The remaining events are applied on top, they are older than the tile graph, but end up at
+                    // the same state.
+                    warn!(
+                        "Missed {} tile events, taking the tile graph instead",
+                        missed
+                    );
+                    self.tiles = Self::tiles_of(mm);
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+    }
+
+    fn tiles_of(mm: &MapManager) -> HashMap<(u8, u8), Weak<ADTNode>> {
+        mm.tile_graph
+            .iter()
+            .map(|(&coords, adt)| (coords, Arc::downgrade(adt)))
+            .collect()
+    }
+
+    /// The height of the liquid surface (e.g. a lake) at the given position in ADT space, if there is any.
+    fn liquid_height_at(&self, position: Vec3A) -> Option<f32> {
+        let app = self.app();
+        let mm = app
+            .game_state
+            .map_manager
+            .read()
+            .expect("Read Lock on Map Manager");
+
+        mm.tile_graph
+            .values()
+            .flat_map(|adt| &adt.terrain)
+            .find_map(|tile| tile.liquid_height_at(position))
+    }
+
+    /// Drops the colliders of all tiles, WMOs and doodads, independent of whether their nodes are still alive.
+    pub fn clear_map(&mut self) {
+        self.tiles.clear();
+
+        for (_, tile_colliders) in self.adt_nodes.drain(..) {
+            Self::drop_tile_colliders(&mut self.physics_simulator, &tile_colliders);
+        }
+
+        for (_, doodad_colliders) in self.wmo_doodads.drain(..) {
+            Self::drop_doodad_colliders(&mut self.physics_simulator, &doodad_colliders);
+        }
 
-                // TODO: Drop all other colliders and remove entries.
-            } // TODO: Else - technically some of the weaks below may be none, it's rather hypothetical, though.
+        for (_, group_colliders) in self.wmo_colliders.drain(..) {
+            Self::drop_group_colliders(&mut self.physics_simulator, &group_colliders);
+        }
+    }
+
+    fn drop_tile_colliders(simulator: &mut PhysicsSimulator, tile_colliders: &TerrainTileColliders) {
+        for &collider in &tile_colliders.terrain_colliders {
+            simulator.drop_collider(collider, false);
+        }
+
+        Self::drop_doodad_colliders(simulator, &tile_colliders.doodad_colliders);
+    }
+
+    fn drop_doodad_colliders(simulator: &mut PhysicsSimulator, doodad_colliders: &RwLock<Vec<DoodadColliderEntry>>) {
+        for doodad in doodad_colliders.read().expect("poisoned lock").iter() {
+            simulator.drop_collider(doodad.collider_handle, false);
+        }
+    }
+
+    fn drop_group_colliders(
+        simulator: &mut PhysicsSimulator,
+        group_colliders: &RwLock<Vec<(Weak<WMOGroupNode>, ColliderHandle)>>,
+    ) {
+        for (_, collider) in group_colliders.read().expect("poisoned lock").iter() {
+            simulator.drop_collider(*collider, false);
         }
     }
 
@@ -142,17 +297,30 @@ impl PhysicsState {
         };
         pos_vec3.z += 1.0; // compare this in update_character for the reasoning.
 
-        let coll = ColliderBuilder::capsule_z(1.0, 0.5)
+        let coll = ColliderBuilder::capsule_z(Self::CAPSULE_HALF_HEIGHT, Self::CAPSULE_RADIUS)
             .position(pos_vec3.into())
             .build();
         self.physics_simulator.insert_collider(coll)
     }
 
+    /// Jumps are only possible from walkable ground, so steep slopes and walls can't be climbed by jumping.
+    pub(super) fn may_jump(grounded: bool, ground_normal: Option<Vec3>) -> bool {
+        grounded && ground_normal.is_some_and(|normal| normal.angle_between(Vec3::Z) <= Self::WALKABLE_SLOPE_ANGLE)
+    }
+
+    /// Applies the gravity to the vertical velocity and returns the resulting vertical movement of this update.
+    fn integrate_gravity(&mut self, timestep: f32) -> f32 {
+        self.vertical_velocity -= Self::GRAVITY * timestep;
+        self.vertical_velocity * timestep
+    }
+
     pub fn update_character(
         &mut self,
         collider: ColliderHandle,
         movement_relative: Vec3,
+        jump: bool,
         flying: bool,
+        liquid_height: Option<f32>,
         timestep: f32,
     ) -> CharacterMovementInformation {
         // I think rapier does not care about the collider at all, all that is important is that it's a shape with a position
@@ -166,12 +334,46 @@ impl PhysicsState {
             .into()
         };
 
+        // Below a liquid surface, gravity is replaced by a damped buoyancy that keeps the character near the surface
+        let in_water = !flying && liquid_height.is_some_and(|height| pos.z < height);
+        let movement_relative = match liquid_height {
+            Some(height) if in_water => {
+                let buoyancy = (height - Self::SWIM_DEPTH - pos.z) * (Self::BUOYANCY_DAMPING * timestep).min(1.0);
+                movement_relative * Self::SWIM_SPEED_FACTOR + Vec3::new(0.0, 0.0, buoyancy)
+            }
+            _ => movement_relative,
+        };
+
         // I think this is because of the capsule shape and considering the physics position to be the center?
         pos.z += 2.0;
 
         // Update the collider first
         self.physics_simulator.teleport_collider(collider, pos);
 
+        let jumped = jump
+            && !flying
+            && !in_water
+            && Self::may_jump(
+                self.grounded,
+                self.physics_simulator.ground_normal(
+                    collider,
+                    Self::CAPSULE_HALF_HEIGHT + Self::CAPSULE_RADIUS + Self::GROUND_PROBE_DISTANCE,
+                ),
+            );
+        if jumped {
+            self.vertical_velocity = Self::JUMP_VELOCITY;
+        } else if flying || in_water {
+            self.vertical_velocity = 0.0;
+        }
+
+        let ascending = self.vertical_velocity > 0.0;
+        let jump_lift = if ascending {
+            self.integrate_gravity(timestep)
+        } else {
+            0.0
+        };
+        let movement_relative = movement_relative + Vec3::new(0.0, 0.0, jump_lift);
+
         // TODO: when not flying, we should also null z-axis forces in movement_relative, but we keep it for debugging at the moment.
 
         let mut movement = self.physics_simulator.move_character(
@@ -181,26 +383,42 @@ impl PhysicsState {
             movement_relative,
         );
 
-        if !flying && !movement.grounded {
-            self.time_since_airborne += timestep;
+        if ascending {
+            // Hitting a ceiling ends the ascent, the fall starts from there
+            if jump_lift > 0.0 && movement.translation.z < jump_lift * 0.5 {
+                self.vertical_velocity = 0.0;
+            }
+            self.fall_time += timestep;
+        } else if !flying && !in_water && !movement.grounded {
+            self.fall_time += timestep;
 
             let sliding_movement = movement.translation;
             self.physics_simulator
                 .teleport_collider(collider, pos + Vec3::from(sliding_movement)); // apply the previous movement
 
-            if self.time_since_airborne >= 4.0 * timestep {
-                let gravity_velocity = -9.81 * self.time_since_airborne * 0.5; /* TODO: Find the actual gravity that is good for the game-sense */
+            // Walking down a slope may briefly lose the ground, which doesn't start a fall yet
+            if self.fall_time >= 4.0 * timestep {
+                let fall = self.integrate_gravity(timestep);
                 movement = self.physics_simulator.move_character(
                     &self.character_controller,
                     collider,
                     50.0,
-                    Vec3::new(0.0, 0.0, gravity_velocity * timestep),
+                    Vec3::new(0.0, 0.0, fall),
                 );
 
                 movement.translation += sliding_movement;
             }
-        } else {
-            self.time_since_airborne = 0.0;
+        }
+
+        // Holding the jump key doesn't restart the jump while still ascending.
+        self.grounded = !ascending && movement.grounded;
+
+        let airborne = !flying && !in_water && (ascending || !movement.grounded);
+        let landed = !airborne && movement.grounded && self.fall_time > 0.0;
+        let fall_time = self.fall_time;
+        if !airborne {
+            self.fall_time = 0.0;
+            self.vertical_velocity = 0.0;
         }
 
         // TODO: actually, the absolute position is a bit too high, causing flying. Is this the capsule offset?
@@ -232,6 +450,11 @@ impl PhysicsState {
             absolute_position: absolute_position.into(),
             orientation,
             delta_movement: transl.into(),
+            velocity: (transl / timestep).into(),
+            in_water,
+            jumped,
+            fall_time,
+            landed,
         }
     }
 }
@@ -0,0 +1,92 @@
+use crate::physics::collider_factory::ColliderFactory;
+use crate::physics::physics_simulator::PhysicsSimulator;
+use crate::physics::physics_state::PhysicsState;
+use crate::rendering::common::coordinate_systems::GRID_SIZE;
+use glam::{Vec3, Vec3A};
+use rapier3d::geometry::Ray;
+use rapier3d::parry::query::RayCast;
+use rapier3d::prelude::{ColliderBuilder, vector};
+
+const RAY_START: f32 = 1000.0;
+
+/// The absolute height of the heightfield at the given position, by casting a ray from above.
+fn height_at(collider: &rapier3d::geometry::Collider, x: f32, y: f32) -> Option<f32> {
+    let ray = Ray::new([x, y, RAY_START].into(), [0.0, 0.0, -1.0].into());
+    collider
+        .shape()
+        .cast_ray(collider.position(), &ray, 2.0 * RAY_START, true)
+        .map(|time_of_impact| RAY_START - time_of_impact)
+}
+
+#[test]
+fn terrain_heightfield_matches_the_terrain_vertices() {
+    let heights = (0..145)
+        .map(|i| (i as f32 * 0.5).sin() * 10.0)
+        .collect::<Vec<f32>>();
+    let position = Vec3A::new(100.0, 200.0, 50.0);
+    let collider = ColliderFactory::terrain_heightfield(position, &heights);
+
+    // Rows grow in -x, columns in -y, like the terrain mesh. Stay off the borders to not miss the heightfield.
+    for row in 1..8 {
+        for column in 1..8 {
+            let x = position.x - GRID_SIZE * row as f32;
+            let y = position.y - GRID_SIZE * column as f32;
+            let expected = position.z + heights[17 * row + column];
+            let height = height_at(&collider, x, y).expect("Outer vertex within the heightfield");
+            assert!((height - expected).abs() < 1e-3, "{height} != {expected}");
+        }
+    }
+
+    for row in 0..8 {
+        for column in 0..8 {
+            let x = position.x - GRID_SIZE * (row as f32 + 0.5);
+            let y = position.y - GRID_SIZE * (column as f32 + 0.5);
+            let expected = position.z + heights[17 * row + column + 9];
+            let height = height_at(&collider, x, y).expect("Inner vertex within the heightfield");
+            assert!((height - expected).abs() < 1e-3, "{height} != {expected}");
+        }
+    }
+
+    assert!(height_at(&collider, position.x + 1.0, position.y - 1.0).is_none());
+    assert!(height_at(&collider, position.x - 1.0, position.y + 1.0).is_none());
+}
+
+/// The ground normal below a character capsule that floats right above a plane, which is tilted by `slope` degrees.
+fn ground_normal_on_slope(slope: f32) -> Option<Vec3> {
+    let mut simulator = PhysicsSimulator::default();
+    simulator.insert_collider(
+        ColliderBuilder::cuboid(50.0, 50.0, 0.5)
+            .rotation(vector![slope.to_radians(), 0.0, 0.0])
+            .build(),
+    );
+
+    // The capsule's center is 1.5 yards above its bottom
+    let surface = 0.5 / slope.to_radians().cos();
+    let character = simulator.insert_collider(
+        ColliderBuilder::capsule_z(1.0, 0.5)
+            .translation(vector![0.0, 0.0, surface + 1.6])
+            .build(),
+    );
+
+    // Updates the query pipeline
+    simulator.step();
+    simulator.ground_normal(character, 1.75)
+}
+
+#[test]
+fn jump_needs_ground() {
+    assert!(PhysicsState::may_jump(true, Some(Vec3::Z)));
+    assert!(!PhysicsState::may_jump(false, Some(Vec3::Z)));
+    // Nothing below the character
+    assert!(!PhysicsState::may_jump(true, None));
+}
+
+#[test]
+fn jump_needs_walkable_slope() {
+    let gentle = ground_normal_on_slope(30.0).expect("Slope below the character");
+    assert!(PhysicsState::may_jump(true, Some(gentle)));
+
+    let steep = ground_normal_on_slope(60.0).expect("Slope below the character");
+    assert!((steep.angle_between(Vec3::Z) - 60f32.to_radians()).abs() < 1e-3);
+    assert!(!PhysicsState::may_jump(true, Some(steep)));
+}
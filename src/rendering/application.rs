@@ -1,29 +1,42 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::f32::consts::PI;
+use std::future::Future;
 use std::hash::BuildHasher;
 use std::ops::DerefMut;
+use std::pin::Pin;
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex, RwLock, Weak};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use winit::event::Event;
 
-use crate::game::application::GameApplication;
-use crate::rendering::asset_graph::nodes::adt_node::{ADTNode, DoodadReference, IRMaterial, IRTextureReference};
+use crate::game::application::{GameApplication, WINDOW_TITLE};
+use crate::game::map_manager::LoadProgress;
+use crate::rendering::asset_graph::nodes::adt_node::{
+    ADTNode, DoodadReference, IRMaterial, IRTextureReference, M2Node, TerrainTile,
+};
+use crate::rendering::common::camera;
 use crate::rendering::common::coordinate_systems;
-use crate::rendering::common::types::{AlbedoType, Material, TransparencyType};
-use crate::rendering::rend3_backend::material::terrain::terrain_material::TerrainMaterial;
+use crate::rendering::common::frame_stats::FrameStats;
+use crate::rendering::common::frustum::Frustum;
+use crate::rendering::common::movement::{MovementConfig, MovementInput};
+use crate::rendering::common::types::{AlbedoType, BoundingBox, Material, TransparencyType};
+use crate::rendering::rend3_backend::gpu_loaders::TextureState;
+use crate::rendering::rend3_backend::material::terrain::terrain_material::{MAX_ALPHA_LAYERS, TerrainMaterial};
 use crate::rendering::rend3_backend::material::terrain::terrain_routine::TerrainRoutine;
 use crate::rendering::rend3_backend::material::units::units_routine::UnitsRoutine;
+use crate::rendering::rend3_backend::material::water::water_material::WaterMaterial;
+use crate::rendering::rend3_backend::material::water::water_routine::WaterRoutine;
+use crate::rendering::rend3_backend::screenshot::ScreenshotTarget;
 use crate::rendering::rend3_backend::{Rend3BackendConverter, gpu_loaders};
-use glam::{Mat4, UVec2, Vec3A, Vec4};
+use glam::{Affine3A, Mat4, UVec2, Vec3A, Vec4};
 use itertools::Itertools;
-use log::{trace, warn};
-use rend3::graph::RenderGraph;
+use log::{error, info, trace, warn};
+use rend3::graph::{RenderGraph, RenderTargetHandle};
 use rend3::types::{
     Camera, CameraProjection, Handedness, MaterialHandle, PresentMode, SampleCount, Texture, Texture2DHandle,
 };
 use rend3::util::typedefs::FastHashMap;
-use rend3::{Renderer, ShaderPreProcessor};
+use rend3::{InstanceAdapterDevice, Renderer, ShaderPreProcessor};
 use rend3_framework::{EventContext, Grabber, RedrawContext, SetupContext};
 use rend3_routine::base::{
     BaseRenderGraph, BaseRenderGraphInputs, BaseRenderGraphIntermediateState, BaseRenderGraphRoutines,
@@ -32,8 +45,10 @@ use rend3_routine::base::{
 use rend3_routine::common::CameraSpecifier;
 use rend3_routine::forward::ForwardRoutineArgs;
 use rend3_routine::{clear, forward};
+use tokio::sync::watch;
 use winit::event::{ElementState, KeyEvent, WindowEvent};
 use winit::platform::scancode::PhysicalKeyExtScancode;
+use winit::window::Window;
 
 // #[derive(Debug)] // TODO: Ensure Grabber implements Display
 pub struct RenderingApplication {
@@ -50,14 +65,49 @@ pub struct RenderingApplication {
     current_map: Option<String>,
     tile_graph: HashMap<(u8, u8), Arc<ADTNode>>,
     missing_texture_material: Option<MaterialHandle>,
+    /// The equivalent of missing_texture_material for custom materials that need texture handles, e.g. terrain.
+    missing_texture: Option<Texture2DHandle>,
     texture_still_loading_material: Option<MaterialHandle>,
+    /// Shared by all liquid surfaces
+    water_material: Option<MaterialHandle>,
     fly_cam: bool,
+    movement: MovementConfig,
+    load_progress: Option<watch::Receiver<LoadProgress>>,
+    /// The last progress received from [`RenderingApplication::load_progress`]
+    last_progress: LoadProgress,
+    /// Toggled by F8, shows the frame statistics and object counts in the window title
+    show_stats: bool,
+    /// Whether the current window title contains the statistics, to remove them once they get disabled
+    title_has_stats: bool,
+    frame_stats: FrameStats,
+    /// The validated [`crate::game::settings::Settings::msaa`], see [`RenderingApplication::setup`]
+    sample_count: SampleCount,
+    /// Kept from [`rend3_framework::App::create_iad`] to query the supported texture format features
+    adapter: Option<Arc<wgpu::Adapter>>,
+    /// Set by F12, the next frame is then additionally rendered into a [`ScreenshotTarget`]
+    screenshot_requested: bool,
+    /// Set by F10, all tiles and their assets are then discarded and loaded again with the next update
+    reload_requested: bool,
+    /// Debug toggles (F5 - F7) per asset category. Hidden categories have their objects dropped and aren't loaded.
+    show_terrain: bool,
+    show_wmos: bool,
+    show_doodads: bool,
+    /// The view frustum of the current frame. Objects outside of it are not added to the renderer, see
+    /// [`RenderingApplication::cull_objects`].
+    frustum: Option<Frustum>,
+    /// Drives the texture animations, see [`RenderingApplication::animate_textures`]
+    animation_clock: Instant,
 
     terrain_routine: Option<Mutex<TerrainRoutine>>,
     units_routine: Option<Mutex<UnitsRoutine>>,
+    water_routine: Option<Mutex<WaterRoutine>>,
 }
 
 impl RenderingApplication {
+    /// The formats of the multisampled targets of rend3_routine's base render graph, which all passes render into
+    const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
     pub fn new(app: Weak<GameApplication>) -> Self {
         Self {
             app,
@@ -70,10 +120,28 @@ impl RenderingApplication {
             current_map: None,
             tile_graph: HashMap::new(),
             missing_texture_material: None,
+            missing_texture: None,
             texture_still_loading_material: None,
+            water_material: None,
             fly_cam: false,
+            movement: MovementConfig::default(),
+            load_progress: None,
+            last_progress: LoadProgress::default(),
+            show_stats: false,
+            title_has_stats: false,
+            frame_stats: FrameStats::default(),
+            sample_count: SampleCount::One,
+            adapter: None,
+            screenshot_requested: false,
+            reload_requested: false,
+            show_terrain: true,
+            show_wmos: true,
+            show_doodads: true,
+            frustum: None,
+            animation_clock: Instant::now(),
             terrain_routine: None,
             units_routine: None,
+            water_routine: None,
         }
     }
 
@@ -81,11 +149,59 @@ impl RenderingApplication {
         self.app.upgrade().expect("Weak Pointer expired")
     }
 
-    fn run_updates(&mut self, renderer: &Arc<Renderer>, delta_time: f32, delta_movement: Vec3A) {
+    /// Places the camera at the exact position (in blender coordinates) and orientation (yaw and pitch in radians, see
+    /// [`camera::CameraPose`]). This switches to the fly cam, as the camera would follow the player otherwise.
+    pub fn set_camera(&mut self, position: Vec3A, yaw: f32, pitch: f32) {
+        self.fly_cam = true;
+        self.camera_location = position;
+        self.camera_yaw = yaw;
+        // Internally, the pitch is stored in units of PI
+        self.camera_pitch = pitch / PI;
+    }
+
+    /// The view matrix, that is the transform from world space (blender coordinates) to the camera.
+    fn view_matrix(&self) -> Mat4 {
+        // technically, we could also invert the view rotation (remember this is not the cams matrix, but the _view_ matrix, so how do you transform
+        // the world to get to the screen (i.e. 0, 0). Hence we also need to invert the camera_location. Inverting the rotation isn't a deal though,
+        // as we can just control the input angles.
+
+        //let view = Mat4::from_euler(glam::EulerRot::XYZ, -self.camera_pitch + 0.5 * PI, -self.camera_yaw, 0.0);
+        let view = Mat4::from_euler(
+            glam::EulerRot::XYZ,
+            (-0.5 - self.camera_pitch) * PI,
+            0.0 /* roll */ * PI,
+            self.camera_yaw,
+        );
+        view * Mat4::from_translation((-self.camera_location).into())
+    }
+
+    fn projection_matrix(&self, resolution: UVec2) -> Mat4 {
+        // rend3's perspective projection has no far plane, so we build the projection ourselves, in order to not
+        // render anything beyond the view distance that tiles are streamed in with.
+        let app = self.app();
+        camera::projection(
+            app.settings.fov,
+            camera::aspect_ratio(resolution),
+            Some(app.settings.view_distance),
+        )
+    }
+
+    fn run_updates(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        delta_time: f32,
+        delta_movement: Vec3A,
+        jump: bool,
+        resolution: UVec2,
+    ) {
         if self.missing_texture_material.is_none() {
             self.init_missing_texture_material(renderer);
         }
 
+        if self.water_material.is_none() {
+            self.water_material = Some(renderer.add_material(WaterMaterial::default()));
+        }
+
         let app = self.app();
 
         // TODO: A lot of the things that are done here, are game logic and should belong to the game application (e.g. physics)
@@ -107,7 +223,10 @@ impl RenderingApplication {
                 .clone()
                 .write()
                 .expect("Write lock on physics state")
-                .update_fixed(coordinate_systems::blender_to_adt(delta_movement).into());
+                .update_fixed(
+                    coordinate_systems::blender_to_adt(delta_movement).into(),
+                    jump,
+                );
 
             let duration_physics = (Instant::now() - pre_physics).as_millis();
             if duration_physics > 6 {
@@ -134,15 +253,25 @@ impl RenderingApplication {
             }
         }
 
+        let reload = std::mem::take(&mut self.reload_requested);
+        if reload {
+            info!("Reloading all assets");
+            mm_lock.write().expect("Write lock on map manager").reload();
+        }
+
         {
             let mm = mm_lock.read().expect("Read Lock on Map Manager");
-            if mm.current_map.is_some() != self.current_map.is_some() /* initial load or unload */ ||
-                (mm.current_map.is_some() && &mm.current_map.as_ref().unwrap().0 != self.current_map.as_ref().unwrap())
-            {
-                trace!("Map has changed, discarding everything");
+            let map_changed = mm.current_map.is_some() != self.current_map.is_some() /* initial load or unload */ ||
+                (mm.current_map.is_some() && &mm.current_map.as_ref().unwrap().0 != self.current_map.as_ref().unwrap());
+
+            if map_changed || reload {
+                trace!("Map has changed or is reloaded, discarding everything");
                 self.tile_graph.clear();
-                self.current_map = Some(mm.current_map.as_ref().unwrap().0.clone());
+                // The map may also have been unloaded while waiting for the next one (see GameState::on_map_changed)
+                self.current_map = mm.current_map.as_ref().map(|(map, _)| map.clone());
+            }
 
+            if map_changed {
                 // TODO: This needs to be more sophisticated, in general it sucks that we just can't call from the packet handler into RenderApplication
                 self.camera_location = coordinate_systems::adt_to_blender(
                     *app.game_state
@@ -157,8 +286,19 @@ impl RenderingApplication {
                         .player_orientation
                         .read()
                         .expect("Read Lock on Player Orientation");
+
+                if let Some(pose) = app.settings.camera {
+                    let (yaw, pitch) = pose.yaw_pitch();
+                    self.set_camera(pose.position(), yaw, pitch);
+                }
             }
 
+            // The camera has its final location for this frame now.
+            self.frustum = Some(Frustum::from_view_projection(
+                self.projection_matrix(resolution) * self.view_matrix(),
+            ));
+            self.cull_objects();
+
             let added_tiles = mm
                 .tile_graph
                 .iter()
@@ -185,13 +325,18 @@ impl RenderingApplication {
                 // currently, we only update doodads
                 self.update_tile_graph(renderer, *key, value);
             }
+
+            self.animate_textures(renderer);
         }
 
         // a) We need to drop mm first and b) this should happen after the camera_location has been initially set
         mm_lock
             .write()
             .expect("Write lock on map manager")
-            .update_camera(coordinate_systems::blender_to_adt(self.camera_location));
+            .update_camera(
+                coordinate_systems::blender_to_adt(self.camera_location),
+                PI - self.camera_yaw,
+            );
     }
 
     fn init_missing_texture_material(&mut self, renderer: &Arc<Renderer>) {
@@ -201,9 +346,24 @@ impl RenderingApplication {
             transparency: TransparencyType::Opaque,
         };
 
-        let render_mat = Rend3BackendConverter::create_material_from_ir(&mat, None);
+        let render_mat =
+            Rend3BackendConverter::create_material_from_ir(&mat, None).expect("Plain colors need no texture");
         self.missing_texture_material = Some(renderer.add_material(render_mat));
 
+        let missing_texture = Texture {
+            label: Some("Missing Texture".to_string()),
+            data: vec![56, 255, 0, 255], // neon/lime green
+            format: rend3::types::TextureFormat::Rgba8UnormSrgb,
+            size: UVec2::new(1, 1),
+            mip_count: rend3::types::MipmapCount::ONE,
+            mip_source: rend3::types::MipmapSource::Uploaded,
+        };
+        self.missing_texture = Some(
+            renderer
+                .add_texture_2d(missing_texture)
+                .expect("Texture creation successful"),
+        );
+
         let mat_loading = Material {
             is_unlit: true,
             albedo: AlbedoType::Value(Vec4::new(0.4, 0.4, 0.4, 1.0)),
@@ -211,7 +371,7 @@ impl RenderingApplication {
         };
 
         self.texture_still_loading_material = Some(renderer.add_material(
-            Rend3BackendConverter::create_material_from_ir(&mat_loading, None),
+            Rend3BackendConverter::create_material_from_ir(&mat_loading, None).expect("Plain colors need no texture"),
         ))
     }
 
@@ -243,21 +403,36 @@ impl RenderingApplication {
                     .clone()
             };
 
-            self.load_doodads(renderer, &wmo.doodads, Some(wmo_ref.transform.into()));
+            for doodads in wmo.active_doodad_sets(wmo_ref.map_obj_def.doodadSet) {
+                self.load_doodads(renderer, doodads, Some(wmo_ref.transform.into()));
+            }
+
+            if !self.show_wmos {
+                continue;
+            }
+
             let all_tex_loaded = Self::are_all_textures_loaded(&wmo.tex_references);
 
             if !all_tex_loaded {
                 continue; // TODO: implement delay loading of textures
             }
 
-            for material in &wmo.materials {
-                let missing = self
-                    .missing_texture_material
-                    .as_ref()
-                    .expect("Missing Texture Material to be initialized already")
-                    .clone();
-                Self::load_material(missing, renderer, material, &wmo.tex_references);
-            }
+            let base_mip_level = self.app().settings.texture_quality.base_mip_level();
+            let material_handles = wmo
+                .materials
+                .iter()
+                .map(|material| {
+                    let (missing, loading) = self.fallback_materials();
+                    Self::load_material(
+                        missing,
+                        loading,
+                        renderer,
+                        material,
+                        &wmo.tex_references,
+                        base_mip_level,
+                    )
+                })
+                .collect_vec();
 
             if wmo_ref.obj_handles.read().expect("Obj Handles").is_empty() {
                 // First load, we'll be so kind and preallocate
@@ -272,7 +447,13 @@ impl RenderingApplication {
                 }
             }
 
+            let local_camera = wmo_ref.to_local(self.camera_location);
+            let view_distance = self.app().settings.view_distance;
             for (subgroup_id, subgroup_ref) in wmo.subgroups.iter().enumerate() {
+                if !wmo.is_group_visible(subgroup_id, local_camera, view_distance) {
+                    continue;
+                }
+
                 {
                     let handles_lock = wmo_ref.obj_handles.read().expect("Obj Handles");
                     let wmoref_rlock = handles_lock[subgroup_id]
@@ -299,23 +480,18 @@ impl RenderingApplication {
                         .clone()
                 };
 
+                if !self.is_in_frustum(&subgroup.bounding_box.transformed(&wmo_ref.transform)) {
+                    continue;
+                }
+
                 let mut object_handles = Vec::with_capacity(subgroup.mesh_batches.len());
 
-                // TODO: probably we should merge all batches into one object
+                // Batches have already been merged per material, see WMOGroupImporter::load_wmo_group
                 for (idx, batch) in subgroup.mesh_batches.iter().enumerate() {
                     let mat_id = subgroup.material_ids[idx];
 
-                    // TODO: This may still fail async, we haven't ensured that all required materials (and especially their textures) are resolved.
                     let material_handle = if mat_id != 0xFF {
-                        let mat_rw = wmo.materials[mat_id as usize]
-                            .read()
-                            .expect("Material read lock");
-
-                        mat_rw
-                            .handle
-                            .as_ref()
-                            .expect("Material to be loaded (right above)")
-                            .clone()
+                        material_handles[mat_id as usize].clone()
                     } else {
                         // TODO: this is not exactly correct, we should probably have a "no mat" material.
                         //  and especially for WMO Groups, they probably have a default material anyway
@@ -347,6 +523,10 @@ impl RenderingApplication {
     }
 
     fn load_terrain_chunks(&self, renderer: &Arc<Renderer>, graph: &Arc<ADTNode>) {
+        if !self.show_terrain {
+            return;
+        }
+
         for tile in &graph.terrain {
             {
                 let rlock = tile.object_handle.read().expect("Object Handle Read Lock");
@@ -355,94 +535,169 @@ impl RenderingApplication {
                 }
             }
 
-            let loaded_texture_layers = tile
-                .texture_layers
-                .iter()
-                .map(|layer| {
-                    let base_layer =
-                        gpu_loaders::gpu_load_texture(renderer, &layer.base_texture_ref.reference).unwrap();
-
-                    let alpha_layer = layer.alpha_map_ref.as_ref().map(|alpha_ref| {
-                        // TODO: Since this code is completely ugly anyway, we can also right away take the write lock instead of checking for previous success.
-                        //  the whole principle and "API" will probably need a good overhaul anyway.
-
-                        let mut wlock = alpha_ref.write().expect("Alpha Handle Write Lock");
-
-                        let alpha_tex = Texture {
-                            label: Some(format!("Alpha Layer Terrain {}", tile.position)),
-                            data: wlock.data.clone(),
-                            format: rend3::types::TextureFormat::R8Unorm,
-                            size: UVec2::new(64, 64),
-                            mip_count: rend3::types::MipmapCount::ONE,
-                            mip_source: rend3::types::MipmapSource::Uploaded,
-                        };
-
-                        let alpha_handle = renderer
-                            .add_texture_2d(alpha_tex)
-                            .expect("Texture creation successful");
-
-                        wlock.handle = Some(alpha_handle.clone());
-                        alpha_handle
-                    });
-
-                    (base_layer, alpha_layer)
-                })
-                .collect_vec();
+            if !self.is_in_frustum(&tile.world_bounds()) {
+                continue;
+            }
+
+            let Some(material_handle) = self.terrain_material(renderer, tile) else {
+                continue;
+            };
 
             let mut wlock = tile
                 .object_handle
                 .write()
                 .expect("Object Handle Write Lock");
 
-            assert!(
-                !loaded_texture_layers.is_empty(),
-                "At least one texture layer has to be present"
-            );
+            let mesh_handle = gpu_loaders::gpu_load_mesh(renderer, &tile.mesh);
 
-            let base_texture = loaded_texture_layers[0].0.clone();
-            let mut additional_layers = [const { None }; 6];
+            let object = rend3::types::Object {
+                mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
+                material: material_handle,
+                transform: tile.transform(),
+            };
 
-            for (idx, (base, alpha_opt)) in loaded_texture_layers.iter().skip(1).enumerate() {
-                if idx > 2 {
-                    warn!("Terrain: Skipping texture layer {}, only 4 supported", idx);
-                    break;
-                }
+            *wlock.deref_mut() = Some(renderer.add_object(object));
 
-                if let Some(alpha) = alpha_opt {
-                    additional_layers[2 * idx] = Some(base.clone());
-                    additional_layers[2 * idx + 1] = Some(alpha.clone());
-                } else {
-                    warn!("Terrain: Skipping texture layer {}, missing alpha map", idx);
-                }
+            if let Some(water_mesh) = &tile.water_mesh {
+                let water_object = rend3::types::Object {
+                    mesh_kind: rend3::types::ObjectMeshKind::Static(gpu_loaders::gpu_load_mesh(renderer, water_mesh)),
+                    material: self
+                        .water_material
+                        .as_ref()
+                        .expect("Water Material to be initialized already")
+                        .clone(),
+                    transform: tile.transform(),
+                };
+
+                *tile
+                    .water_object_handle
+                    .write()
+                    .expect("Water Object Handle Write Lock") = Some(renderer.add_object(water_object));
             }
+        }
+    }
 
-            let material = TerrainMaterial {
-                base_texture,
-                additional_layers,
-            };
-            let material_handle = renderer.add_material(material);
-            let mesh_handle = gpu_loaders::gpu_load_mesh(renderer, &tile.mesh);
+    /// The terrain material of the tile, which packs the alpha maps of all layers into a single texture. It's created
+    /// once and kept on the tile, so that culling the tile doesn't upload it again. None while the base textures are
+    /// still loading.
+    fn terrain_material(&self, renderer: &Arc<Renderer>, tile: &TerrainTile) -> Option<MaterialHandle> {
+        if let Some(handle) = tile
+            .material_handle
+            .read()
+            .expect("Material Handle Read Lock")
+            .as_ref()
+        {
+            return Some(handle.clone());
+        }
 
-            let object = rend3::types::Object {
-                mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
-                material: material_handle.clone(),
-                transform: coordinate_systems::adt_to_blender_transform(Vec3A::new(
-                    tile.position.x,
-                    tile.position.y,
-                    tile.position.z,
-                )),
+        let base_mip_level = self.app().settings.texture_quality.base_mip_level();
+        let base_layers = tile
+            .texture_layers
+            .iter()
+            .map(|layer| gpu_loaders::gpu_load_texture(renderer, &layer.base_texture_ref.reference, base_mip_level))
+            .collect_vec();
+
+        if base_layers
+            .iter()
+            .any(|state| matches!(state, TextureState::Loading))
+        {
+            return None; // Try again, once all textures have been resolved.
+        }
+
+        let base_textures = base_layers
+            .into_iter()
+            .map(|base_state| {
+                base_state.handle().unwrap_or_else(|| {
+                    self.missing_texture
+                        .as_ref()
+                        .expect("Missing Texture to be initialized already")
+                        .clone()
+                })
+            })
+            .collect_vec();
+
+        assert!(
+            !base_textures.is_empty(),
+            "At least one texture layer has to be present"
+        );
+
+        if tile.texture_layers.len() > MAX_ALPHA_LAYERS + 1 {
+            warn!(
+                "Terrain: Skipping {} texture layers, only {} are supported",
+                tile.texture_layers.len() - (MAX_ALPHA_LAYERS + 1),
+                MAX_ALPHA_LAYERS + 1
+            );
+        }
+
+        // The alpha maps of all layers are packed into the channels of a single texture.
+        let mut layers = [const { None }; MAX_ALPHA_LAYERS];
+        let mut alpha_data = vec![0u8; 64 * 64 * 4];
+        let mut alpha_layers = vec![];
+
+        for (idx, (layer, base)) in tile
+            .texture_layers
+            .iter()
+            .zip(&base_textures)
+            .skip(1)
+            .take(MAX_ALPHA_LAYERS)
+            .enumerate()
+        {
+            let Some(alpha_ref) = layer.alpha_map_ref.as_ref() else {
+                warn!(
+                    "Terrain: Skipping texture layer {}, missing alpha map",
+                    idx + 1
+                );
+                continue;
             };
 
-            *wlock.deref_mut() = Some(renderer.add_object(object));
+            let alpha = alpha_ref.read().expect("Alpha Handle Read Lock");
+            for (pixel, value) in alpha_data.chunks_exact_mut(4).zip(&alpha.data) {
+                pixel[idx] = *value;
+            }
+
+            layers[idx] = Some(base.clone());
+            alpha_layers.push(alpha_ref);
         }
+
+        let alpha_map = (!alpha_layers.is_empty()).then(|| {
+            let alpha_tex = Texture {
+                label: Some(format!("Alpha Layers Terrain {}", tile.position)),
+                data: alpha_data,
+                format: rend3::types::TextureFormat::Rgba8Unorm,
+                size: UVec2::new(64, 64),
+                mip_count: rend3::types::MipmapCount::ONE,
+                mip_source: rend3::types::MipmapSource::Uploaded,
+            };
+
+            let alpha_handle = renderer
+                .add_texture_2d(alpha_tex)
+                .expect("Texture creation successful");
+
+            for alpha_ref in alpha_layers {
+                alpha_ref.write().expect("Alpha Handle Write Lock").handle = Some(alpha_handle.clone());
+            }
+
+            alpha_handle
+        });
+
+        let material = TerrainMaterial {
+            base_texture: base_textures[0].clone(),
+            layers,
+            alpha_map,
+        };
+        let material_handle = renderer.add_material(material);
+        *tile
+            .material_handle
+            .write()
+            .expect("Material Handle Write Lock") = Some(material_handle.clone());
+        Some(material_handle)
     }
 
-    fn load_doodads(
-        &self,
-        renderer: &Arc<Renderer>,
-        doodads: &Vec<Arc<DoodadReference>>,
-        parent_transform: Option<Mat4>,
-    ) {
+    fn load_doodads(&self, renderer: &Arc<Renderer>, doodads: &[Arc<DoodadReference>], parent_transform: Option<Mat4>) {
+        if !self.show_doodads {
+            return;
+        }
+
         for doodad in doodads {
             // TODO: we need a better logic to express the desire to actually render something, because then we can explicitly load to the gpu
 
@@ -461,6 +716,16 @@ impl RenderingApplication {
                 m2_rlock.as_ref().expect("previous is_none check.").clone()
             };
 
+            if m2.is_emitter_only {
+                // TODO: Particle and ribbon emitters aren't supported yet, there's nothing to render.
+                doodad.renderer_is_complete.store(true, Ordering::SeqCst);
+                continue;
+            }
+
+            if !self.is_in_frustum(&Self::doodad_bounds(&m2, doodad, parent_transform)) {
+                continue;
+            }
+
             let all_tex_loaded = Self::are_all_textures_loaded(&m2.tex_reference);
             let has_object_handle = { doodad.renderer_object_handle.blocking_read().is_some() };
 
@@ -470,12 +735,16 @@ impl RenderingApplication {
             }
 
             let material_handle = if all_tex_loaded {
-                let missing = self
-                    .missing_texture_material
-                    .as_ref()
-                    .expect("Missing Texture Material to be initialized already")
-                    .clone();
-                Self::load_material(missing, renderer, &m2.material, &m2.tex_reference)
+                let (missing, loading) = self.fallback_materials();
+                let base_mip_level = self.app().settings.texture_quality.base_mip_level();
+                Self::load_material(
+                    missing,
+                    loading,
+                    renderer,
+                    &m2.material,
+                    &m2.tex_reference,
+                    base_mip_level,
+                )
             } else {
                 self.texture_still_loading_material
                     .as_ref()
@@ -483,8 +752,6 @@ impl RenderingApplication {
                     .clone()
             };
 
-            // TODO: handle the absence of the tex_reference. Currently this will render the missing texture style, but I guess when we _know_ the texture is not ready yet, we should load an albedo grey material.
-
             let mesh_handle = gpu_loaders::gpu_load_mesh(renderer, &m2.mesh);
             let object = rend3::types::Object {
                 mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
@@ -501,6 +768,391 @@ impl RenderingApplication {
         }
     }
 
+    /// Shows the loading progress in the window title, as long as there's something loading, as well as the frame
+    /// statistics, if enabled.
+    fn update_window_title(&mut self, window: &Window, frame_time: Duration) {
+        let receiver = self.load_progress.get_or_insert_with(|| {
+            self.app
+                .upgrade()
+                .expect("Weak Pointer expired")
+                .game_state
+                .map_manager
+                .read()
+                .expect("Map Manager Read Lock")
+                .subscribe_progress()
+        });
+
+        let progress_changed = receiver.has_changed().unwrap_or(false);
+        if progress_changed {
+            self.last_progress = *receiver.borrow_and_update();
+        }
+
+        let stats_changed = self.frame_stats.record_frame(frame_time) && self.show_stats;
+        if !progress_changed && !stats_changed && self.title_has_stats == self.show_stats {
+            return;
+        }
+
+        let progress = self.last_progress;
+        let mut title = WINDOW_TITLE.to_string();
+
+        if !progress.is_done() {
+            title += &format!(
+                " - Loading: {}/{} tiles, {}/{} textures",
+                progress.tiles_completed,
+                progress.tiles_requested,
+                progress.textures_resolved,
+                progress.textures_requested
+            );
+        }
+
+        if self.show_stats {
+            let stats = self.frame_stats;
+            let pending = progress
+                .tiles_requested
+                .saturating_sub(progress.tiles_completed)
+                + progress
+                    .textures_requested
+                    .saturating_sub(progress.textures_resolved);
+
+            title += &format!(
+                " - {:.0} FPS, {:.1} ms (max {:.1} ms), {} tiles, {} objects, {} pending",
+                stats.fps,
+                stats.average_frame_time.as_secs_f32() * 1000.0,
+                stats.max_frame_time.as_secs_f32() * 1000.0,
+                self.tile_graph.len(),
+                self.count_objects(),
+                pending
+            );
+        }
+
+        window.set_title(&title);
+        self.title_has_stats = self.show_stats;
+    }
+
+    /// Counts the objects that have been added to the renderer for the currently loaded tiles.
+    fn count_objects(&self) -> usize {
+        // Only peeks at the doodad handles, so that the count never blocks rendering.
+        let doodad_objects = |doodads: &[Arc<DoodadReference>]| {
+            doodads
+                .iter()
+                .filter(|doodad| {
+                    doodad
+                        .renderer_object_handle
+                        .try_read()
+                        .is_ok_and(|handle| handle.is_some())
+                })
+                .count()
+        };
+
+        let mut count = 0;
+        for graph in self.tile_graph.values() {
+            count += graph
+                .terrain
+                .iter()
+                .filter(|tile| {
+                    tile.object_handle
+                        .read()
+                        .expect("Object Handle Read Lock")
+                        .is_some()
+                })
+                .count();
+
+            count += graph
+                .terrain
+                .iter()
+                .filter(|tile| {
+                    tile.water_object_handle
+                        .read()
+                        .expect("Water Object Handle Read Lock")
+                        .is_some()
+                })
+                .count();
+
+            count += doodad_objects(&graph.doodads);
+
+            for wmo_ref in &graph.wmos {
+                count += wmo_ref
+                    .obj_handles
+                    .read()
+                    .expect("Obj Handles")
+                    .iter()
+                    .map(|handles| handles.read().expect("Subgroup Obj Handle Read Lock").len())
+                    .sum::<usize>();
+
+                if let Some(wmo) = wmo_ref
+                    .reference
+                    .reference
+                    .read()
+                    .expect("WMO Read Lock")
+                    .as_ref()
+                {
+                    count += wmo
+                        .active_doodad_sets(wmo_ref.map_obj_def.doodadSet)
+                        .map(doodad_objects)
+                        .sum::<usize>();
+                }
+            }
+        }
+
+        count
+    }
+
+    /// Drops the objects of all categories that have been hidden. They are re-created by the regular loading, once
+    /// they are shown again.
+    fn apply_visibility(&self) {
+        for graph in self.tile_graph.values() {
+            if !self.show_terrain {
+                for tile in &graph.terrain {
+                    *tile
+                        .object_handle
+                        .write()
+                        .expect("Object Handle Write Lock") = None;
+                    *tile
+                        .water_object_handle
+                        .write()
+                        .expect("Water Object Handle Write Lock") = None;
+                }
+            }
+
+            if !self.show_doodads {
+                Self::drop_doodad_objects(&graph.doodads);
+            }
+
+            for wmo_ref in &graph.wmos {
+                if !self.show_wmos {
+                    for subgroup_handles in wmo_ref.obj_handles.read().expect("Obj Handles").iter() {
+                        subgroup_handles
+                            .write()
+                            .expect("Subgroup Obj Handle Write Lock")
+                            .clear();
+                    }
+                }
+
+                if !self.show_doodads {
+                    if let Some(wmo) = wmo_ref
+                        .reference
+                        .reference
+                        .read()
+                        .expect("WMO Read Lock")
+                        .as_ref()
+                    {
+                        for doodads in &wmo.doodad_sets {
+                            Self::drop_doodad_objects(doodads);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Falls back to no multisampling, if the adapter can't multisample the HDR color and depth targets. WebGPU
+    /// guarantees 4x multisampling for those formats, but downlevel adapters may lack it.
+    fn supported_sample_count(adapter: Option<&wgpu::Adapter>, requested: SampleCount) -> SampleCount {
+        if requested == SampleCount::One {
+            return requested;
+        }
+
+        let Some(adapter) = adapter else {
+            warn!(
+                "Cannot validate {}x MSAA without an adapter, falling back to no multisampling",
+                requested as u8
+            );
+            return SampleCount::One;
+        };
+
+        let supported = [Self::HDR_FORMAT, Self::DEPTH_FORMAT].iter().all(|format| {
+            adapter
+                .get_texture_format_features(*format)
+                .flags
+                .sample_count_supported(requested as u32)
+        });
+
+        if supported {
+            info!("Using {}x MSAA", requested as u8);
+            requested
+        } else {
+            warn!(
+                "The adapter doesn't support {}x MSAA, falling back to no multisampling",
+                requested as u8
+            );
+            SampleCount::One
+        }
+    }
+
+    /// Whether the world space box is (partially) inside of the view frustum. Before the first frustum has been
+    /// calculated, everything is considered visible.
+    fn is_in_frustum(&self, bounds: &BoundingBox) -> bool {
+        self.frustum
+            .as_ref()
+            .is_none_or(|frustum| frustum.intersects(bounds))
+    }
+
+    fn doodad_bounds(m2: &M2Node, doodad: &DoodadReference, parent_transform: Option<Mat4>) -> BoundingBox {
+        let transform = parent_transform.unwrap_or(Mat4::IDENTITY) * doodad.transform;
+        m2.bounding_box.transformed(&Affine3A::from_mat4(transform))
+    }
+
+    /// Drops the objects that have left the view frustum. Like with [`RenderingApplication::apply_visibility`], they
+    /// are re-created by the regular loading, once they are inside of the frustum again. Meshes and materials stay
+    /// loaded, so that only the objects are re-created.
+    fn cull_objects(&self) {
+        for graph in self.tile_graph.values() {
+            for tile in &graph.terrain {
+                if !self.is_in_frustum(&tile.world_bounds()) {
+                    *tile
+                        .object_handle
+                        .write()
+                        .expect("Object Handle Write Lock") = None;
+                    *tile
+                        .water_object_handle
+                        .write()
+                        .expect("Water Object Handle Write Lock") = None;
+                }
+            }
+
+            self.cull_doodad_objects(&graph.doodads, None);
+
+            for wmo_ref in &graph.wmos {
+                let Some(wmo) = wmo_ref
+                    .reference
+                    .reference
+                    .read()
+                    .expect("WMO Read Lock")
+                    .clone()
+                else {
+                    continue;
+                };
+
+                for doodads in wmo.active_doodad_sets(wmo_ref.map_obj_def.doodadSet) {
+                    self.cull_doodad_objects(doodads, Some(wmo_ref.transform.into()));
+                }
+
+                let local_camera = wmo_ref.to_local(self.camera_location);
+                let view_distance = self.app().settings.view_distance;
+                let handles_lock = wmo_ref.obj_handles.read().expect("Obj Handles");
+                for (idx, (subgroup_ref, subgroup_handles)) in wmo.subgroups.iter().zip(handles_lock.iter()).enumerate()
+                {
+                    if !wmo.is_group_visible(idx, local_camera, view_distance) {
+                        subgroup_handles
+                            .write()
+                            .expect("Subgroup Obj Handle Write Lock")
+                            .clear();
+                        continue;
+                    }
+
+                    let Some(subgroup) = subgroup_ref
+                        .reference
+                        .read()
+                        .expect("Subgroup Read Lock")
+                        .clone()
+                    else {
+                        continue;
+                    };
+
+                    if !self.is_in_frustum(&subgroup.bounding_box.transformed(&wmo_ref.transform)) {
+                        subgroup_handles
+                            .write()
+                            .expect("Subgroup Obj Handle Write Lock")
+                            .clear();
+                    }
+                }
+            }
+        }
+    }
+
+    fn cull_doodad_objects(&self, doodads: &[Arc<DoodadReference>], parent_transform: Option<Mat4>) {
+        for doodad in doodads {
+            if doodad.renderer_object_handle.blocking_read().is_none() {
+                continue; // Also skips emitter only models, which never have an object.
+            }
+
+            let Some(m2) = doodad
+                .reference
+                .reference
+                .read()
+                .expect("M2 Read Lock")
+                .clone()
+            else {
+                continue;
+            };
+
+            if !self.is_in_frustum(&Self::doodad_bounds(&m2, doodad, parent_transform)) {
+                *doodad.renderer_object_handle.blocking_write() = None;
+                doodad.renderer_is_complete.store(false, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn drop_doodad_objects(doodads: &[Arc<DoodadReference>]) {
+        for doodad in doodads {
+            *doodad.renderer_object_handle.blocking_write() = None;
+            doodad.renderer_is_complete.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Handles one-shot actions that should only trigger once per key press, in contrast to the movement keys
+    fn handle_key_down(&mut self, scancode: u32) {
+        if (63u32..=65u32).contains(&scancode) {
+            // F5 - F7
+            let (category, shown) = match scancode {
+                63 => ("Terrain", &mut self.show_terrain),
+                64 => ("WMOs", &mut self.show_wmos),
+                _ => ("Doodads", &mut self.show_doodads),
+            };
+
+            *shown = !*shown;
+            info!("{} {}", category, if *shown { "shown" } else { "hidden" });
+            self.apply_visibility();
+        } else if scancode == 66u32 {
+            // F8
+            self.show_stats = !self.show_stats;
+            self.frame_stats = FrameStats::default();
+        } else if scancode == 67u32 {
+            // F9
+            let dot = self.app().game_state.dump_asset_graph();
+            match std::fs::write("asset_graph.dot", dot) {
+                Ok(_) => info!("Dumped the asset graph to asset_graph.dot"),
+                Err(err) => error!("Failed to dump the asset graph: {}", err),
+            }
+        } else if scancode == 68u32 {
+            // F10
+            self.reload_requested = true;
+        } else if scancode == 87u32 {
+            // F11
+            #[cfg(debug_assertions)]
+            {
+                let mm_lock = self.app().game_state.map_manager.clone();
+                let mm = mm_lock.read().expect("Read Lock on Map Manager");
+                crate::rendering::asset_graph::report_strong_counts(&mm.tile_graph);
+            }
+
+            let blp_stats = self.app().mpq_loader.blp_cache().stats();
+            info!(
+                "BLP cache: {} hits, {} misses, {} entries",
+                blp_stats.hits, blp_stats.misses, blp_stats.entries
+            );
+        } else if scancode == 88u32 {
+            // F12
+            self.screenshot_requested = true;
+        }
+    }
+
+    /// Returns the materials to use for textures that failed loading and textures that are still loading
+    fn fallback_materials(&self) -> (MaterialHandle, MaterialHandle) {
+        let missing = self
+            .missing_texture_material
+            .as_ref()
+            .expect("Missing Texture Material to be initialized already")
+            .clone();
+        let loading = self
+            .texture_still_loading_material
+            .as_ref()
+            .expect("Texture Still Loading Material to be initialized already")
+            .clone();
+        (missing, loading)
+    }
+
+    /// Whether all texture references have been resolved, that is either loaded or failed to load.
     pub fn are_all_textures_loaded(tex_reference: &Vec<Arc<IRTextureReference>>) -> bool {
         !tex_reference.iter().any(|tex| {
             tex.reference
@@ -510,40 +1162,123 @@ impl RenderingApplication {
         })
     }
 
-    pub fn load_material(
-        missing_texture_material: MaterialHandle,
+    /// The state of the texture that the material needs, None if it doesn't need a texture.
+    fn material_texture_state(
         renderer: &Arc<Renderer>,
         material: &RwLock<IRMaterial>,
-        tex_references: &Vec<Arc<IRTextureReference>>,
-    ) -> MaterialHandle {
+        tex_references: &[Arc<IRTextureReference>],
+        base_mip_level: u8,
+    ) -> Option<TextureState> {
         // I think here we have the first important "lazy" design: we'll only gpu load the
         // texture that we need for our material.
-        let tex_name_opt = {
+        let tex_name = {
             let mat_rlock = material.read().expect("Material read lock");
             match &mat_rlock.data.albedo {
-                AlbedoType::TextureWithName(name) => Some(name.clone()),
-                _ => None,
+                AlbedoType::TextureWithName(name) => name.clone(),
+                _ => return None,
             }
         };
 
-        let texture_handle_opt: Option<Texture2DHandle> = match &tex_name_opt {
-            Some(tex_name) => tex_references
+        let texture_state = tex_references
+            .iter()
+            .find(|tex_ref| tex_name.eq(&tex_ref.reference_str))
+            .map(|tex_ref| gpu_loaders::gpu_load_texture(renderer, &tex_ref.reference, base_mip_level))
+            .unwrap_or(TextureState::Failed); // the material references a texture that is not referenced by the node
+        Some(texture_state)
+    }
+
+    /// Texture animations (see [`crate::rendering::common::texture_animation::TextureAnimation`]) update the material
+    /// of the model, which is shared by all of its instances, so they all animate in sync and every model is only
+    /// updated once.
+    fn animate_textures(&self, renderer: &Arc<Renderer>) {
+        let time = self.animation_clock.elapsed().as_millis() as u64;
+        let base_mip_level = self.app().settings.texture_quality.base_mip_level();
+        let mut animated = HashSet::new();
+
+        for graph in self.tile_graph.values() {
+            let wmos = graph
+                .wmos
                 .iter()
-                .find(|tex_ref| tex_name.eq(&tex_ref.reference_str))
-                .and_then(|tex_ref| gpu_loaders::gpu_load_texture(renderer, &tex_ref.reference)),
-            _ => None,
-        };
+                .filter_map(|wmo_ref| {
+                    let wmo = wmo_ref
+                        .reference
+                        .reference
+                        .read()
+                        .expect("WMO Read Lock")
+                        .clone()?;
+                    Some((wmo, wmo_ref.map_obj_def.doodadSet))
+                })
+                .collect_vec();
+            let wmo_doodads = wmos
+                .iter()
+                .flat_map(|(wmo, doodad_set)| wmo.active_doodad_sets(*doodad_set).flatten());
+
+            for doodad in graph.doodads.iter().chain(wmo_doodads) {
+                let Some(m2) = doodad
+                    .reference
+                    .reference
+                    .read()
+                    .expect("M2 Read Lock")
+                    .clone()
+                else {
+                    continue;
+                };
 
-        let material_handle = if tex_name_opt.is_some() && texture_handle_opt.is_none() {
-            // warn!(
-            //     "Failed loading texture {}, falling back",
-            //     tex_name_opt.unwrap()
-            // );
-            missing_texture_material
-        } else {
-            gpu_loaders::gpu_load_material(renderer, material, texture_handle_opt)
+                let Some(animation) = &m2.texture_animation else {
+                    continue;
+                };
+
+                if !animated.insert(Arc::as_ptr(&m2)) {
+                    continue;
+                }
+
+                let texture_handle =
+                    match Self::material_texture_state(renderer, &m2.material, &m2.tex_reference, base_mip_level) {
+                        Some(TextureState::Loaded(handle)) => Some(handle),
+                        Some(_) => continue, // The material is a fallback material until the texture is there.
+                        None => None,
+                    };
+
+                if let Err(err) = gpu_loaders::gpu_update_material_uv(
+                    renderer,
+                    &m2.material,
+                    texture_handle,
+                    animation.uv_transform(time),
+                ) {
+                    warn!(
+                        "Cannot animate the texture of {}: {}",
+                        doodad.reference.reference_str, err
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn load_material(
+        missing_texture_material: MaterialHandle,
+        still_loading_material: MaterialHandle,
+        renderer: &Arc<Renderer>,
+        material: &RwLock<IRMaterial>,
+        tex_references: &Vec<Arc<IRTextureReference>>,
+        base_mip_level: u8,
+    ) -> MaterialHandle {
+        let Some(texture_state) = Self::material_texture_state(renderer, material, tex_references, base_mip_level)
+        else {
+            return gpu_loaders::gpu_load_material(renderer, material, None).unwrap_or_else(|err| {
+                warn!("{}, falling back to the missing texture material", err);
+                missing_texture_material
+            });
         };
-        material_handle
+
+        match texture_state {
+            TextureState::Loaded(handle) => gpu_loaders::gpu_load_material(renderer, material, Some(handle))
+                .unwrap_or_else(|err| {
+                    warn!("{}, falling back to the missing texture material", err);
+                    missing_texture_material
+                }),
+            TextureState::Loading => still_loading_material,
+            TextureState::Failed => missing_texture_material,
+        }
     }
 }
 
@@ -558,6 +1293,14 @@ impl rend3_framework::App for RenderingApplication {
         // intentionally no-opped.
     }
 
+    fn create_iad<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = anyhow::Result<InstanceAdapterDevice>> + 'a>> {
+        Box::pin(async move {
+            let iad = rend3::create_iad(None, None, None, None).await?;
+            self.adapter = Some(iad.adapter.clone());
+            Ok(iad)
+        })
+    }
+
     fn create_base_rendergraph(&mut self, renderer: &Arc<Renderer>, spp: &mut ShaderPreProcessor) -> BaseRenderGraph {
         let mut data_core = renderer.data_core.lock();
         let render_graph = BaseRenderGraph::new(renderer, spp);
@@ -575,31 +1318,39 @@ impl rend3_framework::App for RenderingApplication {
             &render_graph.interfaces,
         )));
 
+        self.water_routine = Some(Mutex::new(WaterRoutine::new(
+            renderer,
+            &mut data_core,
+            spp,
+            &render_graph.interfaces,
+        )));
+
         drop(data_core);
 
         render_graph
     }
 
     fn sample_count(&self) -> SampleCount {
-        SampleCount::One // No MSAA yet
+        self.sample_count
     }
 
     fn present_mode(&self) -> PresentMode {
-        PresentMode::AutoVsync
+        self.app().settings.present_mode
     }
 
     fn setup(&mut self, context: SetupContext<'_, ()>) {
         // Push the Renderer into the GameApplication to preload handles.
-        if self
-            .app
-            .upgrade()
-            .expect("Application to be initialized")
-            .renderer
-            .set(context.renderer.clone())
-            .is_err()
-        {
-            panic!("Setting the renderer on Application failed: already initialized");
-        }
+        let app = self.app.upgrade().expect("Application to be initialized");
+        app.set_renderer(context.renderer.clone());
+
+        self.sample_count = Self::supported_sample_count(self.adapter.as_deref(), app.settings.msaa);
+
+        let texture_quality = app.settings.texture_quality;
+        info!(
+            "Texture quality {:?}: Uploading textures starting at mip level {}",
+            texture_quality,
+            texture_quality.base_mip_level()
+        );
 
         self.grabber = context
             .windowing
@@ -641,13 +1392,17 @@ impl rend3_framework::App for RenderingApplication {
             } => {
                 let scancode = PhysicalKeyExtScancode::to_scancode(physical_key).unwrap();
                 //log::trace!("WE scancode {:x}", scancode);
-                self.scancode_status.insert(
+                let was_pressed = self.scancode_status.insert(
                     scancode,
                     match state {
                         ElementState::Pressed => true,
                         ElementState::Released => false,
                     },
                 );
+
+                if state == ElementState::Pressed && was_pressed != Some(true) {
+                    self.handle_key_down(scancode);
+                }
             }
             // Other events we don't care about
             _ => {}
@@ -680,54 +1435,41 @@ impl rend3_framework::App for RenderingApplication {
         let right: Vec3A = rotation.x_axis;
         let up: Vec3A = rotation.z_axis;
 
-        let fwd_speed = if self.fly_cam { 30.0 } else { 7.0 };
-        let strafe_speed = if self.fly_cam { 20.0 } else { 7.0 };
-        let back_speed = if self.fly_cam { 20.0 } else { 4.5 };
-
-        let mut delta: Vec3A = Vec3A::new(0.0, 0.0, 0.0);
-        let mut yaw = 0.0;
-
-        // TODO: https://github.com/BVE-Reborn/rend3/blob/trunk/examples/scene-viewer/src/platform.rs.
-        //  Make platform independent and also add more, or search other crate, rather.
-        if button_pressed(&self.scancode_status, 17u32) {
-            // W
-            delta += forward * fwd_speed * delta_time.as_secs_f32();
-        }
-        if button_pressed(&self.scancode_status, 31u32) {
-            // S
-            delta -= forward * back_speed * delta_time.as_secs_f32();
-        }
-        if button_pressed(&self.scancode_status, 30u32) {
-            // A
-            delta -= right * strafe_speed * delta_time.as_secs_f32();
-        }
-        if button_pressed(&self.scancode_status, 32u32) {
-            // D
-            delta += right * strafe_speed * delta_time.as_secs_f32();
-        }
         if button_pressed(&self.scancode_status, 33u32) {
             self.fly_cam = !self.fly_cam;
         }
-        if button_pressed(&self.scancode_status, 42u32) {
-            // LSHIFT
-            delta += up * 10.0 * delta_time.as_secs_f32();
-        }
-        if button_pressed(&self.scancode_status, 29u32) {
-            delta -= up * 10.0 * delta_time.as_secs_f32();
-        }
+
+        // TODO: https://github.com/BVE-Reborn/rend3/blob/trunk/examples/scene-viewer/src/platform.rs.
+        //  Make platform independent and also add more, or search other crate, rather.
+        let input = MovementInput {
+            forward: button_pressed(&self.scancode_status, 17u32),  // W
+            backward: button_pressed(&self.scancode_status, 31u32), // S
+            left: button_pressed(&self.scancode_status, 30u32),     // A
+            right: button_pressed(&self.scancode_status, 32u32),    // D
+            up: button_pressed(&self.scancode_status, 42u32),       // LSHIFT
+            down: button_pressed(&self.scancode_status, 29u32),     // LCTRL
+        };
+
+        let dt = delta_time.as_secs_f32();
+        let delta = self
+            .movement
+            .speeds(self.fly_cam)
+            .displacement(&input, forward, right, up, dt);
+        let mut yaw = 0.0;
+
         if button_pressed(&self.scancode_status, 57421u32) {
             // arrow right
-            yaw += PI * delta_time.as_secs_f32();
+            yaw += self.movement.turn_rate * dt;
         }
         if button_pressed(&self.scancode_status, 57419u32) {
             // arrow left
-            yaw -= PI * delta_time.as_secs_f32();
+            yaw -= self.movement.turn_rate * dt;
         }
         if button_pressed(&self.scancode_status, 57416u32) {
-            self.camera_pitch += 0.25 * delta_time.as_secs_f32();
+            self.camera_pitch += self.movement.pitch_rate * dt;
         }
         if button_pressed(&self.scancode_status, 57424u32) {
-            self.camera_pitch -= 0.25 * delta_time.as_secs_f32();
+            self.camera_pitch -= self.movement.pitch_rate * dt;
         }
 
         if self.fly_cam {
@@ -760,29 +1502,16 @@ impl rend3_framework::App for RenderingApplication {
             context.renderer,
             delta_time.as_secs_f32(),
             if self.fly_cam { Vec3A::ZERO } else { delta },
+            !self.fly_cam && button_pressed(&self.scancode_status, 57u32), // SPACE
+            context.resolution,
         );
 
+        self.update_window_title(context.window.unwrap(), delta_time);
         context.window.unwrap().request_redraw();
 
-        // technically, we could also invert the view rotation (remember this is not the cams matrix, but the _view_ matrix, so how do you transform
-        // the world to get to the screen (i.e. 0, 0). Hence we also need to invert the camera_location. Inverting the rotation isn't a deal though,
-        // as we can just control the input angles.
-
-        //let view = Mat4::from_euler(glam::EulerRot::XYZ, -self.camera_pitch + 0.5 * PI, -self.camera_yaw, 0.0);
-        let view = Mat4::from_euler(
-            glam::EulerRot::XYZ,
-            (-0.5 - self.camera_pitch) * PI,
-            0.0 /* roll */ * PI,
-            self.camera_yaw,
-        );
-        let view = view * Mat4::from_translation((-self.camera_location).into());
-
         context.renderer.set_camera_data(Camera {
-            projection: CameraProjection::Perspective {
-                vfov: 90.0,
-                near: 0.1,
-            },
-            view,
+            projection: CameraProjection::Raw(self.projection_matrix(context.resolution)),
+            view: self.view_matrix(),
         });
 
         // Swap the instruction buffers so that our frame's changes can be processed.
@@ -790,6 +1519,17 @@ impl rend3_framework::App for RenderingApplication {
         // Evaluate our frame's world-change instructions
         let mut eval_output = context.renderer.evaluate_instructions();
 
+        // Needs to outlive the graph, as it is imported into it
+        let screenshot = if std::mem::take(&mut self.screenshot_requested) {
+            ScreenshotTarget::new(
+                context.renderer,
+                context.resolution,
+                context.surface_texture.format(),
+            )
+        } else {
+            None
+        };
+
         // Lock the routines
         let pbr_routine = rend3_framework::lock(&context.routines.pbr);
         let tonemapping_routine = rend3_framework::lock(&context.routines.tonemapping);
@@ -805,6 +1545,12 @@ impl rend3_framework::App for RenderingApplication {
             .expect("units routine to be setup")
             .lock()
             .expect("Units Routine Lock");
+        let water_routine = self
+            .water_routine
+            .as_ref()
+            .expect("water routine to be setup")
+            .lock()
+            .expect("Water Routine Lock");
 
         // Build a rendergraph
         let mut graph = rend3::graph::RenderGraph::new();
@@ -817,6 +1563,15 @@ impl rend3_framework::App for RenderingApplication {
             rend3::graph::ViewportRect::from_size(context.resolution),
         );
 
+        let screenshot_handle = screenshot.as_ref().map(|target| {
+            graph.add_imported_render_target(
+                target.texture(),
+                0..1,
+                0..1,
+                rend3::graph::ViewportRect::from_size(context.resolution),
+            )
+        });
+
         base_rendergraph_add_to_graph(
             context.base_rendergraph,
             &mut graph,
@@ -830,7 +1585,7 @@ impl rend3_framework::App for RenderingApplication {
                 target: OutputRenderTarget {
                     handle: frame_handle,
                     resolution: context.resolution,
-                    samples: SampleCount::One,
+                    samples: self.sample_count,
                 },
             },
             rend3_routine::base::BaseRenderGraphSettings {
@@ -839,10 +1594,16 @@ impl rend3_framework::App for RenderingApplication {
             },
             &terrain_routine,
             &units_routine,
+            &water_routine,
+            screenshot_handle,
         );
 
         // Dispatch a render using the built up rendergraph!
         graph.execute(context.renderer, &mut eval_output);
+
+        if let Some(screenshot) = screenshot {
+            screenshot.capture(context.renderer);
+        }
     }
 }
 
@@ -854,6 +1615,8 @@ fn base_rendergraph_add_to_graph<'node>(
     settings: BaseRenderGraphSettings,
     terrain_routine: &'node TerrainRoutine,
     units_routine: &'node UnitsRoutine,
+    water_routine: &'node WaterRoutine,
+    screenshot_target: Option<RenderTargetHandle>,
 ) {
     // Create the data and handles for the graph.
     let mut state = BaseRenderGraphIntermediateState::new(graph, inputs, settings);
@@ -913,6 +1676,31 @@ fn base_rendergraph_add_to_graph<'node>(
     // considered "residual".
     state.pbr_forward_rendering_transparent();
 
+    water_routine
+        .blend_routine
+        .add_forward_to_graph(ForwardRoutineArgs {
+            graph: state.graph,
+            label: "Water Forward Pass",
+            camera: CameraSpecifier::Viewport,
+            binding_data: forward::ForwardRoutineBindingData {
+                whole_frame_uniform_bg: state.forward_uniform_bg,
+                per_material_bgl: &water_routine.per_material,
+                extra_bgs: None,
+            },
+            samples: state.inputs.target.samples,
+            renderpass: state.primary_renderpass.clone(),
+        });
+
     // Tonemap the HDR inner buffer to the output buffer.
     state.tonemapping();
+
+    // The surface can't be read back, so screenshots get their own copy of the tonemapped frame.
+    if let Some(screenshot_target) = screenshot_target {
+        state.inputs.routines.tonemapping.add_to_graph(
+            state.graph,
+            state.resolve.unwrap_or(state.color),
+            screenshot_target,
+            state.forward_uniform_bg,
+        );
+    }
 }
@@ -0,0 +1,174 @@
+use crate::rendering::asset_graph::nodes::adt_node::{
+    ADTNode, DoodadReference, IRTextureReference, NodeReference, WMOReference,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Renders the given tile graph into the Graphviz DOT format (e.g. `dot -Tsvg asset_graph.dot -o graph.svg`).
+/// Resolved nodes are drawn solid, references that are not resolved (yet) are drawn dashed in red.
+/// Deduplicated nodes (e.g. the same M2 referenced by multiple tiles) share the same graph node.
+pub fn tile_graph_to_dot(tile_graph: &HashMap<(u8, u8), Arc<ADTNode>>) -> String {
+    let mut writer = DotWriter::default();
+    writer
+        .out
+        .push_str("digraph AssetGraph {\n    rankdir=LR;\n    node [shape=box];\n");
+
+    let mut tiles = tile_graph.iter().collect::<Vec<_>>();
+    tiles.sort_by_key(|(coords, _)| **coords);
+
+    for ((x, y), adt) in tiles {
+        let adt_id = format!("adt_{}_{}", x, y);
+        writer.node(&adt_id, &format!("ADT ({}, {})", x, y), true);
+
+        let terrain_id = format!("terrain_{}_{}", x, y);
+        writer.node(
+            &terrain_id,
+            &format!("Terrain ({} chunks)", adt.terrain.len()),
+            true,
+        );
+        writer.edge(&adt_id, &terrain_id);
+        for layer in adt.terrain.iter().flat_map(|tile| &tile.texture_layers) {
+            writer.texture(&terrain_id, &layer.base_texture_ref);
+        }
+
+        for doodad in &adt.doodads {
+            writer.doodad(&adt_id, doodad);
+        }
+
+        for wmo in &adt.wmos {
+            writer.wmo(&adt_id, wmo);
+        }
+    }
+
+    writer.out.push_str("}\n");
+    writer.out
+}
+
+#[derive(Default)]
+struct DotWriter {
+    out: String,
+    emitted_nodes: HashSet<String>,
+    emitted_edges: HashSet<(String, String)>,
+}
+
+impl DotWriter {
+    /// Emits a node only once, as many nodes are shared between tiles, returns whether it has been emitted.
+    fn node(&mut self, id: &str, label: &str, resolved: bool) -> bool {
+        if !self.emitted_nodes.insert(id.to_string()) {
+            return false;
+        }
+
+        let style = match resolved {
+            true => "solid",
+            false => "dashed, color=red",
+        };
+
+        // Paths contain backslashes, which are escape characters in DOT.
+        let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(
+            self.out,
+            "    {} [label=\"{}\", style=\"{}\"];",
+            id, label, style
+        )
+        .expect("Writing to a String");
+        true
+    }
+
+    fn edge(&mut self, from: &str, to: &str) {
+        if self
+            .emitted_edges
+            .insert((from.to_string(), to.to_string()))
+        {
+            writeln!(self.out, "    {} -> {};", from, to).expect("Writing to a String");
+        }
+    }
+
+    fn texture(&mut self, parent_id: &str, tex_reference: &Arc<IRTextureReference>) {
+        let resolved = tex_reference
+            .reference
+            .read()
+            .expect("Texture Reference Read Lock")
+            .clone();
+
+        let (id, is_resolved) = match &resolved {
+            Some(texture) => (format!("tex_{:p}", Arc::as_ptr(texture)), true),
+            None => (format!("texref_{:p}", Arc::as_ptr(tex_reference)), false),
+        };
+
+        self.node(&id, &tex_reference.reference_str, is_resolved);
+        self.edge(parent_id, &id);
+    }
+
+    fn doodad(&mut self, parent_id: &str, doodad: &Arc<DoodadReference>) {
+        let Some((id, resolved)) = self.node_reference(parent_id, "m2", &doodad.reference) else {
+            return; // unresolved reference, no children to visit
+        };
+
+        if !self.node(&id, &doodad.reference.reference_str, true) {
+            return; // the children have already been visited
+        }
+
+        for tex_reference in &resolved.tex_reference {
+            self.texture(&id, tex_reference);
+        }
+    }
+
+    fn wmo(&mut self, parent_id: &str, wmo: &Arc<WMOReference>) {
+        let Some((id, resolved)) = self.node_reference(parent_id, "wmo", &wmo.reference) else {
+            return;
+        };
+
+        if !self.node(&id, &wmo.reference.reference_str, true) {
+            return;
+        }
+
+        for (idx, group) in resolved.subgroups.iter().enumerate() {
+            let group_id = format!("{}_group_{}", id, idx);
+            let is_resolved = group
+                .reference
+                .read()
+                .expect("WMO Group Reference Read Lock")
+                .is_some();
+            self.node(&group_id, &group.reference_str, is_resolved);
+            self.edge(&id, &group_id);
+        }
+
+        for tex_reference in &resolved.tex_references {
+            self.texture(&id, tex_reference);
+        }
+
+        for doodad in resolved.all_doodads() {
+            self.doodad(&id, doodad);
+        }
+    }
+
+    /// Connects the parent to the referenced node and returns its id and the resolved node. Unresolved references
+    /// are emitted as a node right away and yield None.
+    fn node_reference<T>(
+        &mut self,
+        parent_id: &str,
+        prefix: &str,
+        reference: &NodeReference<T>,
+    ) -> Option<(String, Arc<T>)> {
+        let resolved = reference
+            .reference
+            .read()
+            .expect("Node Reference Read Lock")
+            .clone();
+
+        match resolved {
+            Some(node) => {
+                let id = format!("{}_{:p}", prefix, Arc::as_ptr(&node));
+                self.edge(parent_id, &id);
+                Some((id, node))
+            }
+            None => {
+                let id = format!("{}ref_{:p}", prefix, reference as *const NodeReference<T>);
+                self.node(&id, &reference.reference_str, false);
+                self.edge(parent_id, &id);
+                None
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, RwLock};
 
 use crate::io::mpq::loader::MPQLoader;
@@ -19,39 +20,47 @@ impl M2Generator {
 }
 
 impl GraphNodeGenerator<M2Node> for M2Generator {
-    fn generate(&self, name: &str) -> Arc<M2Node> {
-        let m2 = M2Loader::load_no_lod_for_graph(&self.mpq_loader, name);
+    fn generate(&self, name: &str) -> Result<Arc<M2Node>, anyhow::Error> {
+        let m2 = M2Loader::load_no_lod_for_graph(&self.mpq_loader, name)?;
         let mesh = RwLock::new(m2.mesh.into());
         let material = RwLock::new(m2.material.into());
         let tex_reference = m2.textures;
         let dynamic_tex_references = m2.dynamic_textures;
 
-        Arc::new(M2Node {
+        Ok(Arc::new(M2Node {
             tex_reference,
             dynamic_tex_references,
             mesh,
             material,
-        })
+            is_emitter_only: m2.is_emitter_only,
+            bounding_box: m2.bounding_box,
+            geosets: m2.geosets,
+            texture_animation: m2.texture_animation,
+            collider_pending: AtomicBool::new(!m2.is_emitter_only),
+        }))
     }
 }
 
 impl GraphNodeGenerator<RwLock<Option<IRTexture>>> for M2Generator {
-    fn generate(&self, name: &str) -> Arc<RwLock<Option<IRTexture>>> {
-        // TODO: textures are the only one that are allowed to fail? feature request..
-        Arc::new(RwLock::new(
+    fn generate(&self, name: &str) -> Result<Arc<RwLock<Option<IRTexture>>>, anyhow::Error> {
+        // Missing textures are not an error, they are rendered with a fallback texture instead.
+        Ok(Arc::new(RwLock::new(
             BLPLoader::load_blp_from_ldr(&self.mpq_loader, name).map(|data| IRTexture { data, handle: None }),
-        ))
+        )))
     }
 }
 
 impl GraphNodeGenerator<WMONode> for M2Generator {
-    fn generate(&self, name: &str) -> Arc<WMONode> {
-        Arc::new(WMOLoader::load_graph(&self.mpq_loader, name).expect("WMO to parse correctly"))
+    fn generate(&self, name: &str) -> Result<Arc<WMONode>, anyhow::Error> {
+        Ok(Arc::new(WMOLoader::load_graph(&self.mpq_loader, name)?))
     }
 }
 
 impl GraphNodeGenerator<WMOGroupNode> for M2Generator {
-    fn generate(&self, name: &str) -> Arc<WMOGroupNode> {
-        Arc::new(WMOGroupImporter::load_wmo_group(&self.mpq_loader, name))
+    fn generate(&self, name: &str) -> Result<Arc<WMOGroupNode>, anyhow::Error> {
+        Ok(Arc::new(WMOGroupImporter::load_wmo_group(
+            &self.mpq_loader,
+            name,
+        )?))
     }
 }
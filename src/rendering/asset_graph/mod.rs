@@ -21,13 +21,18 @@
 //! kind of act like a refcounted handle into GPU Memory. Whenever the relevant handle is [`Drop`]ped,
 //! the GPU Memory will be freed.
 //!
-//! Note: Another technique, that is not implemented yet, would be "node hollowing": As soon as any
-//! given node has a [`rend3::types::ResourceHandle`], it's IR could be freed, because relevant
-//! drawing information is stored on the GPU. Doing so will reduce RAM Usage (technically the "whole"
-//! VRAM (without costly framebuffers, though) will be mirrored in your RAM), but it comes at the
-//! expense of slower re-loading, whenever the handle had been dropped and has to be restored.
+//! Note: Another technique is "node hollowing": As soon as any given node has a
+//! [`rend3::types::ResourceHandle`], it's IR could be freed, because relevant drawing information is
+//! stored on the GPU. Doing so will reduce RAM Usage (technically the "whole" VRAM (without costly
+//! framebuffers, though) will be mirrored in your RAM), but it comes at the expense of slower
+//! re-loading, whenever the handle had been dropped and has to be restored.
 //! This is especially the case with meshes/index buffers, as happens when the LoD level changes.
 //! In most other cases, the handle is only dropped when the node itself has been dropped anyway.
+//! Currently, this is implemented for M2 and WMO meshes (see [`nodes::adt_node::HollowableIRObject`]),
+//! but it's opt-in (`--mesh-memory-budget`): Once the meshes exceed the budget, the resolvers hollow
+//! all uploaded meshes (see [`resolver::Resolver::hollow_all`]). The physics also need the IR, so
+//! meshes aren't hollowed before their collider has been built and instances that show up later
+//! rehydrate hollowed nodes by invoking the generator again (see [`resolver::Resolver::collider_meshes`]).
 //!
 //! Note: Another technique, that is not implemented yet, is "tree pruning": Technically, the game
 //! only needs to know which IR/Handles belong to which terrain tile, so they can be [`Drop`]ped
@@ -69,6 +74,16 @@
 //! construct a [`rend3::types::ObjectHandle`] on the GPU side.
 //!
 //!
+pub mod graphviz;
 pub mod m2_generator;
 pub mod nodes;
+/// Debug-only leak diagnostics, see [`report_strong_counts`].
+#[cfg(debug_assertions)]
+pub mod ref_counts;
 pub mod resolver;
+
+#[cfg(test)]
+mod tests;
+
+#[cfg(debug_assertions)]
+pub use ref_counts::report_strong_counts;
@@ -1,11 +1,15 @@
+use crate::rendering::asset_graph::resolver::HollowableNode;
+use crate::rendering::common::coordinate_systems;
 use crate::rendering::common::special_types::TerrainTextureLayerRend3;
-use crate::rendering::common::types::{Material, Mesh};
-use glam::{Affine3A, Mat4, Vec3A};
+use crate::rendering::common::texture_animation::TextureAnimation;
+use crate::rendering::common::types::{BoundingBox, Material, Mesh};
+use crate::rendering::importer::m2_importer::M2Geoset;
+use glam::{Affine3A, Mat4, Vec3, Vec3A};
 use image_blp::BlpImage;
 use rend3::types::{MaterialHandle, MeshHandle, ObjectHandle, Texture2DHandle};
 use sargerust_files::m2::types::M2Texture;
 use sargerust_files::wdt::types::SMMapObjDef;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
 #[derive(Debug)]
@@ -19,8 +23,61 @@ pub struct ADTNode {
 pub struct TerrainTile {
     pub position: Vec3A,
     pub mesh: RwLock<IRMesh>,
+    /// The MCVT heights relative to `position`, for the physics heightfield. Other than `mesh`, they're never hollowed.
+    pub heights: Vec<f32>,
     pub object_handle: RwLock<Option<ObjectHandle>>,
+    /// Outlives `object_handle`, which is dropped whenever the tile is culled.
+    pub material_handle: RwLock<Option<MaterialHandle>>,
     pub texture_layers: Vec<TerrainTextureLayerRend3>,
+    /// The liquid surfaces (MH2O) of the chunk, relative to `position` like `mesh`.
+    pub water_mesh: Option<RwLock<IRMesh>>,
+    pub water_object_handle: RwLock<Option<ObjectHandle>>,
+    /// The liquid heights of the chunk for gameplay (e.g. swimming), in contrast to `water_mesh` it's never hollowed.
+    pub liquid: Option<LiquidSurface>,
+    /// Encloses both the terrain and the water mesh, relative to `position`.
+    pub bounds: BoundingBox,
+}
+
+impl TerrainTile {
+    /// The world transform (in blender coordinates) of both the terrain and the water mesh.
+    pub fn transform(&self) -> Mat4 {
+        coordinate_systems::adt_to_blender_transform(self.position)
+    }
+
+    pub fn world_bounds(&self) -> BoundingBox {
+        self.bounds
+            .transformed(&Affine3A::from_mat4(self.transform()))
+    }
+
+    /// The absolute height of the liquid surface at the given position (both in ADT space), if the position is within
+    /// a cell of this chunk that contains liquid.
+    pub fn liquid_height_at(&self, position: Vec3A) -> Option<f32> {
+        let liquid = self.liquid.as_ref()?;
+        liquid
+            .height_at(position - self.position)
+            .map(|height| self.position.z + height)
+    }
+}
+
+/// The highest liquid surface per cell of a chunk (8x8 cells, row by row), relative to the chunk position like the
+/// water mesh. `None` for cells without liquid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiquidSurface {
+    pub cell_heights: [Option<f32>; 64],
+}
+
+impl LiquidSurface {
+    /// The liquid height of the cell that contains the given position (relative to the chunk). Rows grow in -x and
+    /// columns in -y, like in [`crate::rendering::importer::water_importer::WaterImporter::create_mesh`].
+    pub fn height_at(&self, local: Vec3A) -> Option<f32> {
+        let row = (-local.x / coordinate_systems::GRID_SIZE).floor();
+        let column = (-local.y / coordinate_systems::GRID_SIZE).floor();
+        if !(0.0..8.0).contains(&row) || !(0.0..8.0).contains(&column) {
+            return None;
+        }
+
+        self.cell_heights[row as usize * 8 + column as usize]
+    }
 }
 
 // TODO: commons.rs in nodes?
@@ -56,10 +113,56 @@ pub struct M2Node {
     pub dynamic_tex_references: Vec<M2Texture>,
     pub mesh: RwLock<IRMesh>,
     pub material: RwLock<IRMaterial>,
+    /// Models that only consist of particle or ribbon emitters have no geometry to render or collide with.
+    pub is_emitter_only: bool,
+    /// In model space, used for frustum culling
+    pub bounding_box: BoundingBox,
+    /// The submeshes of `mesh`, so that entities can hide geoset variants, see
+    /// [`crate::rendering::importer::m2_importer::M2Importer::filter_geosets`]
+    pub geosets: Vec<M2Geoset>,
+    /// Applied to the material every frame, see [`TextureAnimation::uv_transform`]
+    pub texture_animation: Option<TextureAnimation>,
+    /// Whether the physics haven't built a collider of the mesh yet. Until then, the mesh isn't hollowed, see
+    /// [`HollowableNode::collider_meshes`].
+    pub collider_pending: AtomicBool,
     // TODO: RWLock inside IRMaterial#handle instead? As no-one should modify the material contents
     //  and whenever a node has resolved it's reference, it has to be existent/loaded?
 }
 
+impl HollowableNode for M2Node {
+    fn ir_size(&self) -> usize {
+        let mesh = self.mesh.read().expect("Mesh Read Lock");
+        mesh.data().map_or(0, Mesh::memory_size)
+    }
+
+    fn hollow(&self) -> usize {
+        if self.collider_pending.load(Ordering::Acquire) {
+            return 0;
+        }
+
+        let size = self.ir_size();
+        if self.mesh.write().expect("Mesh Write Lock").hollow() {
+            size
+        } else {
+            0
+        }
+    }
+
+    fn collider_meshes(&self, fresh: impl FnOnce() -> Result<Self, anyhow::Error>) -> Result<Vec<Mesh>, anyhow::Error> {
+        let mut mesh = self.mesh.write().expect("Mesh Write Lock");
+        if mesh.is_hollow() {
+            let fresh_mesh = fresh()?.mesh.into_inner().expect("Mesh Lock");
+            mesh.rehydrate(|| {
+                fresh_mesh
+                    .into_data()
+                    .expect("Fresh meshes have their data")
+            });
+        }
+
+        Ok(vec![mesh.data().expect("Rehydrated above").clone()])
+    }
+}
+
 #[derive(Debug)]
 pub struct WMOReference {
     pub map_obj_def: SMMapObjDef,
@@ -70,6 +173,11 @@ pub struct WMOReference {
 }
 
 impl WMOReference {
+    /// Transforms a (render space) position into the space of the root WMO, e.g. to compare it to group bounds.
+    pub fn to_local(&self, position: Vec3A) -> Vec3 {
+        self.transform.inverse().transform_point3a(position).into()
+    }
+
     pub fn new(map_obj_def: SMMapObjDef, transform: Affine3A, reference: String) -> Self {
         Self {
             map_obj_def,
@@ -83,14 +191,73 @@ impl WMOReference {
 #[derive(Debug)]
 pub struct WMONode {
     // Arcs are for the async loaders.
-    pub doodads: Vec<Arc<DoodadReference>>, // TODO: They have DoodadSets that are referenced in the ADT
+    /// The doodads, grouped by their doodad set (MODS). Set 0 is the global set that is always shown, the others are
+    /// alternatives picked by the placement, see [`WMONode::active_doodad_sets`].
+    pub doodad_sets: Vec<Vec<Arc<DoodadReference>>>,
     // If this was a dedicated GroupReference struct, it could carry the group name. But currently we don't need the names anyway,
     // they are debug only.
     pub subgroups: Vec<Arc<NodeReference<WMOGroupNode>>>,
+    /// Parallel to `subgroups`, known from the root already, so that groups can be selected before resolving them.
+    pub group_infos: Vec<WMOGroupInfo>,
     pub materials: Vec<RwLock<IRMaterial>>,
     pub tex_references: Vec<Arc<IRTextureReference>>,
 }
 
+impl WMONode {
+    /// The doodads of the global set (0) and the given set, which is typically [`SMMapObjDef::doodadSet`]. Sets that
+    /// are out of range only yield the global set.
+    pub fn active_doodad_sets(&self, doodad_set: u16) -> impl Iterator<Item = &[Arc<DoodadReference>]> {
+        self.doodad_sets
+            .iter()
+            .enumerate()
+            .filter(move |(idx, _)| *idx == 0 || *idx == doodad_set as usize)
+            .map(|(_, doodads)| doodads.as_slice())
+    }
+
+    /// Whether the group may be visible from the camera (in the space of the root WMO, see [`WMOReference::to_local`]):
+    /// Exterior groups are visible within `view_distance`, but interior groups can only be seen through portals. As
+    /// long as we don't evaluate portals, interior groups are visible when the camera is close to them.
+    pub fn is_group_visible(&self, group: usize, camera: Vec3, view_distance: f32) -> bool {
+        let Some(info) = self.group_infos.get(group) else {
+            return true; // Without bounds, we can't tell.
+        };
+
+        let distance = if info.interior {
+            view_distance.min(WMOGroupInfo::INTERIOR_DISTANCE)
+        } else {
+            view_distance
+        };
+
+        info.bounding_box.distance_to(camera) <= distance
+    }
+
+    /// All doodads, regardless of their set
+    pub fn all_doodads(&self) -> impl Iterator<Item = &Arc<DoodadReference>> {
+        self.doodad_sets.iter().flatten()
+    }
+}
+
+/// What the root WMO (MOGI) knows about a group
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WMOGroupInfo {
+    /// In the space of the root WMO
+    pub bounding_box: BoundingBox,
+    pub interior: bool,
+}
+
+impl WMOGroupInfo {
+    /// How close the camera has to be to an interior group, so that it could look into it through a door or window.
+    pub const INTERIOR_DISTANCE: f32 = 50.0;
+    const FLAG_INTERIOR: u32 = 0x2000;
+
+    pub fn new(bounding_box: BoundingBox, flags: u32) -> Self {
+        Self {
+            bounding_box,
+            interior: flags & Self::FLAG_INTERIOR != 0,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct WMOGroupNode {
     /// According to the wiki, the mesh batches are *not* (as previously noted) LoDs, but rather proper
@@ -98,6 +265,70 @@ pub struct WMOGroupNode {
     /// draw calls.
     pub mesh_batches: Vec<RwLock<IRMesh>>,
     pub material_ids: Vec<u8>,
+    /// Built from the BSP tree, if the group has one. Unlike the mesh batches, this is never hollowed, as the physics
+    /// may need it again whenever the group comes back into range.
+    pub collision_mesh: Option<Mesh>,
+    /// Whether the physics still need the mesh batches, because the group lacks a `collision_mesh`. Until then, the
+    /// batches aren't hollowed, see [`HollowableNode::collider_meshes`].
+    pub collider_pending: AtomicBool,
+    /// In the space of the root WMO, taken from the group header
+    pub bounding_box: BoundingBox,
+}
+
+impl HollowableNode for WMOGroupNode {
+    fn ir_size(&self) -> usize {
+        self.mesh_batches
+            .iter()
+            .filter_map(|batch| {
+                batch
+                    .read()
+                    .expect("Mesh Read Lock")
+                    .data()
+                    .map(Mesh::memory_size)
+            })
+            .sum()
+    }
+
+    fn hollow(&self) -> usize {
+        if self.collider_pending.load(Ordering::Acquire) {
+            return 0;
+        }
+
+        self.mesh_batches
+            .iter()
+            .map(|batch| {
+                let mut batch = batch.write().expect("Mesh Write Lock");
+                let size = batch.data().map_or(0, Mesh::memory_size);
+                if batch.hollow() { size } else { 0 }
+            })
+            .sum()
+    }
+
+    fn collider_meshes(&self, fresh: impl FnOnce() -> Result<Self, anyhow::Error>) -> Result<Vec<Mesh>, anyhow::Error> {
+        // All batches are locked (in order) at once, so that none of them is hollowed while the others are restored.
+        let mut batches = self
+            .mesh_batches
+            .iter()
+            .map(|batch| batch.write().expect("Mesh Write Lock"))
+            .collect::<Vec<_>>();
+
+        if batches.iter().any(|batch| batch.is_hollow()) {
+            let fresh_batches = fresh()?.mesh_batches;
+            for (batch, fresh_batch) in batches.iter_mut().zip(fresh_batches) {
+                let fresh_batch = fresh_batch.into_inner().expect("Mesh Lock");
+                batch.rehydrate(|| {
+                    fresh_batch
+                        .into_data()
+                        .expect("Fresh meshes have their data")
+                });
+            }
+        }
+
+        Ok(batches
+            .iter()
+            .map(|batch| batch.data().expect("Rehydrated above").clone())
+            .collect())
+    }
 }
 
 /// DO NOT DERIVE CLONE FOR NODE REFERENCES, it breaks the renderer. As the renderer polls the lock
@@ -119,11 +350,13 @@ impl<T> NodeReference<T> {
 
 // TODO: the typedefs belong into rend3_backend, as they leak and wrap rend3 types
 pub type IRMaterial = IRObject<Material, MaterialHandle>;
-pub type IRMesh = IRObject<Mesh, MeshHandle>;
+/// Meshes support node hollowing (see [`HollowableIRObject::hollow`]), thus their data may be absent.
+pub type IRMesh = HollowableIRObject<Mesh, MeshHandle>;
 // TODO: Why are textures failable? Depending on the context that may not be a good idea. As is the file location for these.
 // Textures are failable
 pub type IRTextureReference = IRObjectReference<Option<IRTexture>>;
-pub type IRTexture = IRObject<BlpImage, Texture2DHandle>;
+/// The image is shared with the cache of the loader, see [`crate::rendering::loader::blp_loader::BlpCache`]
+pub type IRTexture = IRObject<Arc<BlpImage>, Texture2DHandle>;
 
 // TODO: are IRObjectReferences still needed, considering we have almost similar NodeReference<T>?
 #[derive(Debug)]
@@ -134,19 +367,89 @@ pub struct IRObjectReference<T> {
 
 #[derive(Debug)]
 pub struct IRObject<T, U> {
-    // with hollowing, we would need to make this an Option<T>, but for now it is more
-    // convenient not to have to do this.
+    // IR Objects that support hollowing make T an Option<T>, for all others it is more convenient not to have
+    // to do this.
     pub data: T,
     pub handle: Option<U>,
 }
 
-impl From<Mesh> for IRObject<Mesh, MeshHandle> {
-    fn from(value: Mesh) -> Self {
-        Self {
-            data: value,
-            handle: None,
+/// The [`IRObject`] of IR that supports node hollowing. Contrary to two Options, the states guarantee that there's
+/// always either the data or the handle (or both).
+#[derive(Debug)]
+pub enum HollowableIRObject<T, U> {
+    /// Not uploaded yet
+    Loaded(T),
+    Uploaded(T, U),
+    /// The data has been freed, see [`HollowableIRObject::hollow`]
+    Hollow(U),
+}
+
+impl<T, U> HollowableIRObject<T, U> {
+    pub fn data(&self) -> Option<&T> {
+        match self {
+            Self::Loaded(data) | Self::Uploaded(data, _) => Some(data),
+            Self::Hollow(_) => None,
         }
     }
+
+    pub fn handle(&self) -> Option<&U> {
+        match self {
+            Self::Uploaded(_, handle) | Self::Hollow(handle) => Some(handle),
+            Self::Loaded(_) => None,
+        }
+    }
+
+    pub fn is_hollow(&self) -> bool {
+        matches!(self, Self::Hollow(_))
+    }
+
+    pub fn into_data(self) -> Option<T> {
+        match self {
+            Self::Loaded(data) | Self::Uploaded(data, _) => Some(data),
+            Self::Hollow(_) => None,
+        }
+    }
+}
+
+impl<T: Default, U> HollowableIRObject<T, U> {
+    /// Moves out of self, the placeholder is overwritten before anyone can observe it.
+    fn take(&mut self) -> Self {
+        std::mem::replace(self, Self::Loaded(T::default()))
+    }
+
+    pub fn set_handle(&mut self, handle: U) {
+        *self = match self.take() {
+            Self::Loaded(data) | Self::Uploaded(data, _) => Self::Uploaded(data, handle),
+            Self::Hollow(_) => Self::Hollow(handle),
+        };
+    }
+
+    /// Node hollowing: Frees the IR data as soon as there is a handle, because all relevant drawing information is
+    /// stored on the GPU then. Returns whether the data has been freed. If the data is needed again later on, it
+    /// needs to be re-derived from its source, see [`HollowableIRObject::rehydrate`].
+    pub fn hollow(&mut self) -> bool {
+        *self = match self.take() {
+            Self::Uploaded(_, handle) | Self::Hollow(handle) => Self::Hollow(handle),
+            loaded => loaded,
+        };
+        self.is_hollow()
+    }
+
+    /// Returns the data, calling `load` to re-derive it from its source, if the object has been hollowed.
+    pub fn rehydrate<F: FnOnce() -> T>(&mut self, load: F) -> &T {
+        *self = match self.take() {
+            Self::Hollow(handle) => Self::Uploaded(load(), handle),
+            other => other,
+        };
+
+        self.data().expect("Only hollow objects lack data")
+    }
+}
+
+impl From<Mesh> for IRMesh {
+    fn from(value: Mesh) -> Self {
+        Self::Loaded(value)
+    }
 }
 
 impl From<Material> for IRObject<Material, MaterialHandle> {
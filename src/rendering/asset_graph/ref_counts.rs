@@ -0,0 +1,151 @@
+use crate::rendering::asset_graph::nodes::adt_node::{
+    ADTNode, DoodadReference, IRTextureReference, NodeReference, WMOReference,
+};
+use log::info;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// Walks the given tile graph and logs the strong and weak counts per node type. Shared nodes are only counted once.
+/// Besides the graph itself, the renderer's mirror of the tile graph and resolver caches hold references, but if the
+/// counts don't drop after tiles have been unloaded, something (e.g. a cycle back to a parent) leaks them.
+pub fn report_strong_counts(tile_graph: &HashMap<(u8, u8), Arc<ADTNode>>) {
+    let mut collector = RefCountCollector::default();
+
+    for adt in tile_graph.values() {
+        collector.adt(adt);
+    }
+
+    info!(
+        "Reference counts of the asset graph ({} tiles):",
+        tile_graph.len()
+    );
+    for (node_type, counts) in &collector.counts {
+        info!(
+            "    {}: {} nodes, {} strong (max {}), {} weak",
+            node_type, counts.nodes, counts.strong, counts.max_strong, counts.weak
+        );
+    }
+}
+
+#[derive(Default)]
+struct RefCounts {
+    nodes: usize,
+    strong: usize,
+    max_strong: usize,
+    weak: usize,
+}
+
+#[derive(Default)]
+struct RefCountCollector {
+    counts: BTreeMap<&'static str, RefCounts>,
+    visited: HashSet<(&'static str, usize)>,
+}
+
+impl RefCountCollector {
+    /// Counts the node once, returns whether it hasn't been visited before.
+    fn record<T>(&mut self, node_type: &'static str, node: &Arc<T>) -> bool {
+        if !self.visited.insert((node_type, Arc::as_ptr(node) as usize)) {
+            return false;
+        }
+
+        let strong = Arc::strong_count(node);
+        let counts = self.counts.entry(node_type).or_default();
+        counts.nodes += 1;
+        counts.strong += strong;
+        counts.max_strong = counts.max_strong.max(strong);
+        counts.weak += Arc::weak_count(node);
+        true
+    }
+
+    fn adt(&mut self, adt: &Arc<ADTNode>) {
+        if !self.record("ADTNode", adt) {
+            return;
+        }
+
+        for layer in adt.terrain.iter().flat_map(|tile| &tile.texture_layers) {
+            self.texture(&layer.base_texture_ref);
+        }
+
+        for doodad in &adt.doodads {
+            self.doodad(doodad);
+        }
+
+        for wmo in &adt.wmos {
+            self.wmo(wmo);
+        }
+    }
+
+    fn texture(&mut self, tex_reference: &Arc<IRTextureReference>) {
+        if !self.record("IRTextureReference", tex_reference) {
+            return;
+        }
+
+        // The locks are held instead of cloning the Arcs, as that would change their counts.
+        if let Some(texture) = tex_reference
+            .reference
+            .read()
+            .expect("Texture Reference Read Lock")
+            .as_ref()
+        {
+            self.record("IRTexture", texture);
+        }
+    }
+
+    fn doodad(&mut self, doodad: &Arc<DoodadReference>) {
+        if !self.record("DoodadReference", doodad) {
+            return;
+        }
+
+        let Some(guard) = Self::resolved(&doodad.reference) else {
+            return;
+        };
+        let m2 = guard.as_ref().expect("Checked by resolved");
+
+        if !self.record("M2Node", m2) {
+            return;
+        }
+
+        for tex_reference in &m2.tex_reference {
+            self.texture(tex_reference);
+        }
+    }
+
+    fn wmo(&mut self, wmo: &Arc<WMOReference>) {
+        if !self.record("WMOReference", wmo) {
+            return;
+        }
+
+        let Some(guard) = Self::resolved(&wmo.reference) else {
+            return;
+        };
+        let root = guard.as_ref().expect("Checked by resolved");
+
+        if !self.record("WMONode", root) {
+            return;
+        }
+
+        for group in &root.subgroups {
+            self.record("NodeReference<WMOGroupNode>", group);
+            if let Some(guard) = Self::resolved(group) {
+                self.record("WMOGroupNode", guard.as_ref().expect("Checked by resolved"));
+            }
+        }
+
+        for tex_reference in &root.tex_references {
+            self.texture(tex_reference);
+        }
+
+        for doodad in root.all_doodads() {
+            self.doodad(doodad);
+        }
+    }
+
+    /// The read guard of the reference, if it has been resolved.
+    fn resolved<T>(reference: &NodeReference<T>) -> Option<std::sync::RwLockReadGuard<'_, Option<Arc<T>>>> {
+        let guard = reference
+            .reference
+            .read()
+            .expect("Node Reference Read Lock");
+        guard.is_some().then_some(guard)
+    }
+}
@@ -3,13 +3,29 @@ use std::sync::{Arc, Weak};
 use dashmap::DashMap;
 use dashmap::mapref::entry::Entry;
 
+use crate::rendering::common::types::Mesh;
+
 pub struct Resolver<G: GraphNodeGenerator<T>, T> {
     ref_cache: DashMap<String, Weak<T>>,
     generator: G,
 }
 
 pub trait GraphNodeGenerator<T> {
-    fn generate(&self, name: &str) -> Arc<T>;
+    fn generate(&self, name: &str) -> Result<Arc<T>, anyhow::Error>;
+}
+
+/// Nodes whose IR can be freed once it has been uploaded, see [`crate::rendering::asset_graph`] on node hollowing.
+pub trait HollowableNode: Sized {
+    /// The RAM (in bytes) that is occupied by IR that hasn't been hollowed yet.
+    fn ir_size(&self) -> usize;
+
+    /// Hollows all IR that has been uploaded already, returning the number of bytes that have been freed.
+    fn hollow(&self) -> usize;
+
+    /// The meshes that the physics build colliders of. If they have been hollowed, they're restored from a freshly
+    /// generated node of the same asset first. Both happen under the same lock, so that the meshes can't be hollowed
+    /// again in between.
+    fn collider_meshes(&self, fresh: impl FnOnce() -> Result<Self, anyhow::Error>) -> Result<Vec<Mesh>, anyhow::Error>;
 }
 
 impl<G: GraphNodeGenerator<T>, T> Resolver<G, T> {
@@ -22,14 +38,26 @@ impl<G: GraphNodeGenerator<T>, T> Resolver<G, T> {
         }
     }
 
+    /// Drops all entries whose node isn't referenced anymore, e.g. after the map has been unloaded.
+    pub fn evict_expired(&self) {
+        self.ref_cache.retain(|_, weak| weak.strong_count() > 0);
+    }
+
+    /// Forgets all entries, so that every node is generated anew on the next resolve, even if the old node is still
+    /// referenced somewhere.
+    pub fn clear(&self) {
+        self.ref_cache.clear();
+    }
+
     // TODO: maybe take name by reference and only own it when inserting.
     //  also canonicalize paths: uppercase and forward slashes as in MPQ?
     //  -> Those two requirements do conflict, though.
-    pub fn resolve(&self, name: String) -> Arc<T> {
+    /// Failures aren't cached, so that resolving the same asset again tries to generate it again.
+    pub fn resolve(&self, name: String) -> Result<Arc<T>, anyhow::Error> {
         // optimistic path
         // can be removed without impacting correctness
         if let Some(existing) = self.ref_cache.get(&name).and_then(|x| x.upgrade()) {
-            return existing;
+            return Ok(existing);
         }
 
         // TODO: this may or may not be a performance culprit in the future. Move generate in or out
@@ -40,7 +68,7 @@ impl<G: GraphNodeGenerator<T>, T> Resolver<G, T> {
         // let new = self.generator.generate(&name);
 
         // clone can be removed, when generating outside the critical section
-        match self.ref_cache.entry(name.clone()) {
+        Ok(match self.ref_cache.entry(name.clone()) {
             Entry::Occupied(mut o) => {
                 if let Some(existing) = o.get().upgrade() {
                     // the optimistic path failed earlier,
@@ -48,17 +76,48 @@ impl<G: GraphNodeGenerator<T>, T> Resolver<G, T> {
                     existing
                 } else {
                     // there was already an entry, but it died
-                    let new = self.generator.generate(&name);
+                    let new = self.generator.generate(&name)?;
                     o.insert(Arc::downgrade(&new));
                     new
                 }
             }
             Entry::Vacant(v) => {
                 // there was no entry
-                let new = self.generator.generate(&name);
+                let new = self.generator.generate(&name)?;
                 v.insert(Arc::downgrade(&new));
                 new
             }
-        }
+        })
+    }
+}
+
+impl<G: GraphNodeGenerator<T>, T: HollowableNode> Resolver<G, T> {
+    /// The RAM (in bytes) that is occupied by the IR of all nodes that are still alive.
+    pub fn ir_size(&self) -> usize {
+        self.ref_cache
+            .iter()
+            .filter_map(|entry| entry.value().upgrade())
+            .map(|node| node.ir_size())
+            .sum()
+    }
+
+    /// Hollows all nodes that are still alive, returning the number of bytes that have been freed.
+    pub fn hollow_all(&self) -> usize {
+        self.ref_cache
+            .iter()
+            .filter_map(|entry| entry.value().upgrade())
+            .map(|node| node.hollow())
+            .sum()
+    }
+
+    /// The meshes of the node for its colliders, invoking the generator again to restore them if the node has been
+    /// hollowed (see [`HollowableNode::collider_meshes`]). The node stays the same, only its IR is replaced.
+    pub fn collider_meshes(&self, name: &str, node: &T) -> Result<Vec<Mesh>, anyhow::Error> {
+        node.collider_meshes(|| {
+            let Ok(fresh) = Arc::try_unwrap(self.generator.generate(name)?) else {
+                unreachable!("Generators never share the nodes that they generate");
+            };
+            Ok(fresh)
+        })
     }
 }
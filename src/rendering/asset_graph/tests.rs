@@ -0,0 +1,84 @@
+use crate::rendering::asset_graph::nodes::adt_node::{HollowableIRObject, WMOGroupInfo, WMONode};
+use crate::rendering::common::types::{BoundingBox, Mesh, VertexBuffers};
+use glam::Vec3;
+
+fn triangle() -> Mesh {
+    Mesh {
+        vertex_buffers: VertexBuffers {
+            position_buffer: vec![Vec3::ZERO, Vec3::X, Vec3::Y],
+            ..VertexBuffers::default()
+        },
+        index_buffer: vec![0, 1, 2],
+    }
+}
+
+#[test]
+fn hollowing_keeps_data_or_handle() {
+    // The handle type doesn't matter for the state transitions and rend3 handles need a renderer.
+    let mut object = HollowableIRObject::<Mesh, u32>::Loaded(triangle());
+    assert_eq!(object.data().map(Mesh::memory_size), Some(3 * 12 + 3 * 4));
+
+    // Nothing to hollow without a handle
+    assert!(!object.hollow());
+    assert!(object.data().is_some());
+
+    object.set_handle(7);
+    assert!(object.hollow());
+    assert!(object.is_hollow());
+    assert!(object.data().is_none());
+    assert_eq!(object.handle(), Some(&7));
+
+    // Hollowing twice is fine
+    assert!(object.hollow());
+
+    let mut loads = 0;
+    let data = object.rehydrate(|| {
+        loads += 1;
+        triangle()
+    });
+    assert_eq!(data.index_buffer, [0, 1, 2]);
+    assert!(matches!(object, HollowableIRObject::Uploaded(_, 7)));
+
+    // Only hollow objects invoke the loader
+    object.rehydrate(|| {
+        loads += 1;
+        triangle()
+    });
+    assert_eq!(loads, 1);
+
+    object.set_handle(8);
+    assert_eq!(object.handle(), Some(&8));
+    assert!(object.into_data().is_some());
+}
+
+#[test]
+fn wmo_group_visibility() {
+    let bounding_box = BoundingBox {
+        min: Vec3::ZERO,
+        max: Vec3::splat(10.0),
+    };
+    let wmo = WMONode {
+        doodad_sets: vec![],
+        subgroups: vec![],
+        group_infos: vec![
+            WMOGroupInfo::new(bounding_box, 0x8),    // exterior
+            WMOGroupInfo::new(bounding_box, 0x2000), // interior
+        ],
+        materials: vec![],
+        tex_references: vec![],
+    };
+
+    let inside = Vec3::splat(5.0);
+    let near = Vec3::new(5.0, 5.0, 10.0 + WMOGroupInfo::INTERIOR_DISTANCE - 1.0);
+    let far = Vec3::new(5.0, 5.0, 500.0);
+
+    assert!(wmo.is_group_visible(0, inside, 1000.0));
+    assert!(wmo.is_group_visible(1, inside, 1000.0));
+    assert!(wmo.is_group_visible(1, near, 1000.0));
+    assert!(wmo.is_group_visible(0, far, 1000.0));
+    assert!(!wmo.is_group_visible(1, far, 1000.0));
+    assert!(!wmo.is_group_visible(0, far, 100.0));
+
+    // Groups without info can't be told apart
+    assert!(wmo.is_group_visible(2, far, 100.0));
+}
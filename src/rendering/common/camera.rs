@@ -0,0 +1,92 @@
+use glam::{Mat4, UVec2, Vec3A};
+
+/// Everything closer to the camera than this is clipped.
+pub const NEAR_PLANE: f32 = 0.1;
+
+/// The field of view in degrees, either along the vertical or the horizontal axis of the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldOfView {
+    Vertical(f32),
+    Horizontal(f32),
+}
+
+impl Default for FieldOfView {
+    fn default() -> Self {
+        FieldOfView::Vertical(90.0)
+    }
+}
+
+impl FieldOfView {
+    /// The vertical field of view (in radians) for a screen with the given aspect ratio (width / height).
+    pub fn vertical_radians(self, aspect_ratio: f32) -> f32 {
+        match self {
+            FieldOfView::Vertical(degrees) => degrees.to_radians(),
+            FieldOfView::Horizontal(degrees) => 2.0 * ((degrees.to_radians() * 0.5).tan() / aspect_ratio).atan(),
+        }
+    }
+}
+
+pub fn aspect_ratio(resolution: UVec2) -> f32 {
+    resolution.x as f32 / resolution.y.max(1) as f32
+}
+
+/// Builds the (right-handed, reverse z) projection matrix that is shared by all render paths. Without a far plane,
+/// the projection is infinite, like rend3's built-in perspective projection.
+pub fn projection(fov: FieldOfView, aspect_ratio: f32, far: Option<f32>) -> Mat4 {
+    let vfov = fov.vertical_radians(aspect_ratio);
+    match far {
+        // Swapping near and far yields reverse z
+        Some(far) => Mat4::perspective_rh(vfov, aspect_ratio, far, NEAR_PLANE),
+        None => Mat4::perspective_infinite_reverse_rh(vfov, aspect_ratio, NEAR_PLANE),
+    }
+}
+
+/// An exact placement of the camera (in blender coordinates), bypassing the incremental controls, e.g. for reproducible
+/// screenshots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraPose {
+    /// Yaw (clockwise from north) and pitch (positive looks up) in radians
+    Angles {
+        position: Vec3A,
+        yaw: f32,
+        pitch: f32,
+    },
+    LookAt {
+        position: Vec3A,
+        target: Vec3A,
+    },
+}
+
+impl CameraPose {
+    pub fn position(&self) -> Vec3A {
+        match self {
+            CameraPose::Angles { position, .. } | CameraPose::LookAt { position, .. } => *position,
+        }
+    }
+
+    /// The yaw and pitch in radians, see [`CameraPose::Angles`].
+    pub fn yaw_pitch(&self) -> (f32, f32) {
+        match self {
+            CameraPose::Angles { yaw, pitch, .. } => (*yaw, *pitch),
+            CameraPose::LookAt { position, target } => yaw_pitch_towards(*target - *position),
+        }
+    }
+}
+
+/// The direction that the camera looks at, for the given yaw and pitch in radians.
+pub fn look_direction(yaw: f32, pitch: f32) -> Vec3A {
+    Vec3A::new(
+        yaw.sin() * pitch.cos(),
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+    )
+}
+
+/// The inverse of [`look_direction`], the direction doesn't need to be normalized.
+pub fn yaw_pitch_towards(direction: Vec3A) -> (f32, f32) {
+    let direction = direction.normalize_or_zero();
+    (
+        direction.x.atan2(direction.y),
+        direction.z.clamp(-1.0, 1.0).asin(),
+    )
+}
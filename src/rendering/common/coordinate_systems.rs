@@ -62,3 +62,41 @@ const CHUNK_SIZE: f32 = 100.0 / 3.0;
 // 33.333 yards (100 feet)
 pub const GRID_SIZE: f32 = CHUNK_SIZE / 8.0;
 pub const TILE_SIZE: f32 = 16.0 * CHUNK_SIZE;
+
+/// The order in which the vertices of a front-facing triangle appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// All IR meshes are right-handed and Z-up (just like WoW itself) and their front faces are wound counter-clockwise.
+/// This is what the rendering backend (`Handedness::Right`) and thus backface culling expects, so importers of
+/// clockwise data (e.g. terrain and water) have to pass their index buffers through [`convert_winding`].
+pub const IR_WINDING: Winding = Winding::CounterClockwise;
+
+/// Determines the winding of the triangle (a, b, c) when looking at it from the side that `front` points to.
+#[inline]
+pub fn triangle_winding(a: Vec3, b: Vec3, c: Vec3, front: Vec3) -> Winding {
+    if (b - a).cross(c - a).dot(front) >= 0.0 {
+        Winding::CounterClockwise
+    } else {
+        Winding::Clockwise
+    }
+}
+
+/// Converts an index buffer of triangles that are wound in `source` order into [`IR_WINDING`].
+pub fn convert_winding(indices: &mut [u32], source: Winding) {
+    assert_eq!(
+        indices.len() % 3,
+        0,
+        "Index buffer has to consist of triangles"
+    );
+    if source == IR_WINDING {
+        return;
+    }
+
+    for triangle in indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+}
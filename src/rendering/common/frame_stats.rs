@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+/// How long frames are accumulated before the statistics are updated, so that the values are readable.
+const SAMPLE_WINDOW: Duration = Duration::from_millis(500);
+
+/// Frame rate and frame times, averaged over [`SAMPLE_WINDOW`].
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    window_start: Instant,
+    frames: u32,
+    longest_frame: Duration,
+    pub fps: f32,
+    pub average_frame_time: Duration,
+    pub max_frame_time: Duration,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            window_start: Instant::now(),
+            frames: 0,
+            longest_frame: Duration::ZERO,
+            fps: 0.0,
+            average_frame_time: Duration::ZERO,
+            max_frame_time: Duration::ZERO,
+        }
+    }
+}
+
+impl FrameStats {
+    /// Returns true when a sample window has been completed and the statistics have changed.
+    pub fn record_frame(&mut self, frame_time: Duration) -> bool {
+        self.frames += 1;
+        self.longest_frame = self.longest_frame.max(frame_time);
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed < SAMPLE_WINDOW {
+            return false;
+        }
+
+        self.fps = self.frames as f32 / elapsed.as_secs_f32();
+        self.average_frame_time = elapsed / self.frames;
+        self.max_frame_time = self.longest_frame;
+
+        self.window_start = Instant::now();
+        self.frames = 0;
+        self.longest_frame = Duration::ZERO;
+        true
+    }
+}
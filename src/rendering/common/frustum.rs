@@ -0,0 +1,45 @@
+use crate::rendering::common::types::BoundingBox;
+use glam::{Mat4, Vec3, Vec4};
+
+/// The view frustum of a camera as six inward facing planes (normal in xyz, distance in w), so that a point `p` lies
+/// inside of a plane if `plane.dot(p.extend(1.0)) >= 0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from the combined `projection * view` matrix (Gribb/Hartmann), for a clip space depth
+    /// range of [0, 1], as used by wgpu. This holds for reverse z as well, only near and far swap their meaning.
+    /// With an infinite projection, one of the depth planes degenerates and never culls anything.
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let (row0, row1, row2, row3) = (
+            view_projection.row(0),
+            view_projection.row(1),
+            view_projection.row(2),
+            view_projection.row(3),
+        );
+
+        Self {
+            planes: [
+                row3 + row0, // left
+                row3 - row0, // right
+                row3 + row1, // bottom
+                row3 - row1, // top
+                row2,        // z >= 0
+                row3 - row2, // z <= w
+            ],
+        }
+    }
+
+    /// Whether the (world space) box is at least partially inside of the frustum. This is conservative: boxes close
+    /// to the corners of the frustum may be reported as visible, even though they are outside.
+    pub fn intersects(&self, bounds: &BoundingBox) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner that is the furthest along the plane normal
+            let normal = plane.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), bounds.max, bounds.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
@@ -30,6 +30,34 @@ impl MeshMerger {
         merged_mesh
     }
 
+    /// Merges index buffers that share the same vertex buffer and key (e.g. the material) into one mesh per key,
+    /// so they can be drawn as a single object. The keys keep the order of their first occurrence.
+    pub fn merge_indices_by_key<K: PartialEq>(
+        vertex_buffers: &VertexBuffers,
+        batches: impl IntoIterator<Item = (K, Vec<u32>)>,
+    ) -> Vec<(K, Mesh)> {
+        let mut merged: Vec<(K, Vec<u32>)> = Vec::new();
+        for (key, indices) in batches {
+            match merged.iter_mut().find(|(merged_key, _)| *merged_key == key) {
+                Some((_, merged_indices)) => merged_indices.extend_from_slice(&indices),
+                None => merged.push((key, indices)),
+            }
+        }
+
+        merged
+            .into_iter()
+            .map(|(key, index_buffer)| {
+                (
+                    key,
+                    Mesh {
+                        vertex_buffers: vertex_buffers.clone(),
+                        index_buffer,
+                    },
+                )
+            })
+            .collect()
+    }
+
     // TODO: MeshUtils rather than MeshMerger?
     pub fn mesh_scale_position(mesh: &mut Mesh, scale: Vec3) {
         for pos in &mut mesh.vertex_buffers.position_buffer {
@@ -0,0 +1,97 @@
+use std::f32::consts::PI;
+
+use glam::Vec3A;
+
+/// Movement speeds in units per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovementSpeeds {
+    pub forward: f32,
+    pub backward: f32,
+    pub strafe: f32,
+    pub vertical: f32,
+}
+
+/// All speeds of the camera and the player. They are scaled by the frame time, so movement is independent of the
+/// frame rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovementConfig {
+    pub fly: MovementSpeeds,
+    pub walk: MovementSpeeds,
+    /// Radians per second
+    pub turn_rate: f32,
+    /// Pitch units per second
+    pub pitch_rate: f32,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        Self {
+            fly: MovementSpeeds {
+                forward: 30.0,
+                backward: 20.0,
+                strafe: 20.0,
+                vertical: 10.0,
+            },
+            walk: MovementSpeeds {
+                forward: 7.0,
+                backward: 4.5,
+                strafe: 7.0,
+                vertical: 10.0,
+            },
+            turn_rate: PI,
+            pitch_rate: 0.25,
+        }
+    }
+}
+
+impl MovementConfig {
+    pub fn speeds(&self, fly_cam: bool) -> &MovementSpeeds {
+        if fly_cam { &self.fly } else { &self.walk }
+    }
+}
+
+/// The movement directions that are currently requested (e.g. by pressed keys).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovementInput {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+}
+
+impl MovementSpeeds {
+    /// The displacement along the given (camera) axes after moving for `delta_time` seconds.
+    pub fn displacement(
+        &self,
+        input: &MovementInput,
+        forward: Vec3A,
+        right: Vec3A,
+        up: Vec3A,
+        delta_time: f32,
+    ) -> Vec3A {
+        let mut delta = Vec3A::ZERO;
+
+        if input.forward {
+            delta += forward * self.forward;
+        }
+        if input.backward {
+            delta -= forward * self.backward;
+        }
+        if input.left {
+            delta -= right * self.strafe;
+        }
+        if input.right {
+            delta += right * self.strafe;
+        }
+        if input.up {
+            delta += up * self.vertical;
+        }
+        if input.down {
+            delta -= up * self.vertical;
+        }
+
+        delta * delta_time
+    }
+}
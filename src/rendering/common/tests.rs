@@ -0,0 +1,181 @@
+use crate::rendering::common::camera;
+use crate::rendering::common::camera::{CameraPose, FieldOfView};
+use crate::rendering::common::coordinate_systems::{IR_WINDING, Winding, convert_winding, triangle_winding};
+use crate::rendering::common::frustum::Frustum;
+use crate::rendering::common::mesh_merger::MeshMerger;
+use crate::rendering::common::movement::{MovementConfig, MovementInput};
+use crate::rendering::common::texture_animation::{Keyframes, TextureAnimation};
+use crate::rendering::common::types::{BoundingBox, MeshWithLod, VertexBuffers};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec3A};
+
+#[test]
+fn movement_is_frame_rate_independent() {
+    let config = MovementConfig::default();
+    let input = MovementInput {
+        forward: true,
+        right: true,
+        up: true,
+        ..Default::default()
+    };
+    let forward = Vec3A::Y;
+    let right = Vec3A::X;
+    let up = Vec3A::Z;
+
+    for fly_cam in [true, false] {
+        let speeds = config.speeds(fly_cam);
+        let single_step = speeds.displacement(&input, forward, right, up, 1.0);
+
+        // 144 fps
+        let mut small_steps = Vec3A::ZERO;
+        for _ in 0..144 {
+            small_steps += speeds.displacement(&input, forward, right, up, 1.0 / 144.0);
+        }
+
+        assert!(
+            single_step.abs_diff_eq(small_steps, 1.0e-3),
+            "{} != {}",
+            single_step,
+            small_steps
+        );
+        assert_eq!(
+            single_step,
+            Vec3A::new(speeds.strafe, speeds.forward, speeds.vertical)
+        );
+    }
+}
+
+#[test]
+fn camera_look_at() {
+    let (yaw, pitch) = camera::yaw_pitch_towards(Vec3A::new(1.0, 1.0, 0.0));
+    assert!((yaw - std::f32::consts::FRAC_PI_4).abs() < 1.0e-5);
+    assert!(pitch.abs() < 1.0e-5);
+
+    let pose = CameraPose::LookAt {
+        position: Vec3A::new(10.0, 20.0, 30.0),
+        target: Vec3A::new(7.0, 24.0, 18.0),
+    };
+    let (yaw, pitch) = pose.yaw_pitch();
+    let direction = camera::look_direction(yaw, pitch);
+    assert!(direction.abs_diff_eq(Vec3A::new(-3.0, 4.0, -12.0) / 13.0, 1.0e-5));
+}
+
+#[test]
+fn merge_indices_by_material() {
+    let batches = vec![
+        (3u8, vec![0, 1, 2]),
+        (0xFF, vec![3, 4, 5]),
+        (3, vec![6, 7, 8]),
+    ];
+
+    let merged = MeshMerger::merge_indices_by_key(&VertexBuffers::default(), batches);
+    let merged = merged
+        .iter()
+        .map(|(material_id, mesh)| (*material_id, mesh.index_buffer.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        merged,
+        vec![(3, vec![0, 1, 2, 6, 7, 8]), (0xFF, vec![3, 4, 5])]
+    );
+}
+
+#[test]
+fn frustum_culling() {
+    let unit_box = |center: Vec3| BoundingBox {
+        min: center - Vec3::ONE,
+        max: center + Vec3::ONE,
+    };
+
+    // The camera sits at the origin and looks along -z, like every right-handed view space.
+    let projection = camera::projection(FieldOfView::Vertical(90.0), 1.0, Some(100.0));
+    let frustum = Frustum::from_view_projection(projection * Mat4::IDENTITY);
+
+    assert!(frustum.intersects(&unit_box(Vec3::new(0.0, 0.0, -10.0))));
+    assert!(frustum.intersects(&unit_box(Vec3::new(10.5, 0.0, -10.0)))); // partially inside
+    assert!(!frustum.intersects(&unit_box(Vec3::new(0.0, 0.0, 10.0)))); // behind
+    assert!(!frustum.intersects(&unit_box(Vec3::new(50.0, 0.0, -10.0)))); // beside
+    assert!(!frustum.intersects(&unit_box(Vec3::new(0.0, 0.0, -200.0)))); // beyond the far plane
+
+    // Moving the camera moves the frustum
+    let view = Mat4::from_translation(Vec3::new(0.0, 0.0, -200.0));
+    let frustum = Frustum::from_view_projection(projection * view);
+    assert!(frustum.intersects(&unit_box(Vec3::new(0.0, 0.0, -210.0))));
+    assert!(!frustum.intersects(&unit_box(Vec3::new(0.0, 0.0, -10.0))));
+
+    // Without a far plane, there is no distance limit
+    let infinite = camera::projection(FieldOfView::Vertical(90.0), 1.0, None);
+    let frustum = Frustum::from_view_projection(infinite);
+    assert!(frustum.intersects(&unit_box(Vec3::new(0.0, 0.0, -100_000.0))));
+    assert!(!frustum.intersects(&unit_box(Vec3::new(0.0, 0.0, 10.0))));
+}
+
+#[test]
+fn lod_selection_clamps_to_available_levels() {
+    let mesh = MeshWithLod {
+        vertex_buffers: VertexBuffers::default(),
+        index_buffers: vec![vec![0, 1, 2], vec![0, 1, 2]],
+    };
+
+    assert_eq!(mesh.lod_for_distance(0.0), 0);
+    assert_eq!(mesh.lod_for_distance(MeshWithLod::LOD_DISTANCE * 1.5), 1);
+    // Only two skin profiles, so the further levels clamp to the last one
+    assert_eq!(mesh.lod_for_distance(MeshWithLod::LOD_DISTANCE * 3.5), 1);
+}
+
+#[test]
+fn texture_animation_scrolls_and_loops() {
+    let animation = TextureAnimation {
+        translation: Keyframes {
+            timestamps: vec![0, 1000],
+            values: vec![Vec3::ZERO, Vec3::X],
+            duration: 1000,
+        },
+        rotation: Keyframes {
+            timestamps: vec![],
+            values: vec![],
+            duration: 0,
+        },
+    };
+
+    let uv = |time| animation.uv_transform(time).transform_point2(Vec2::ZERO);
+    assert!(uv(0).abs_diff_eq(Vec2::ZERO, 1e-5));
+    assert!(uv(250).abs_diff_eq(Vec2::new(0.25, 0.0), 1e-5));
+    // loops after the duration
+    assert!(uv(1250).abs_diff_eq(Vec2::new(0.25, 0.0), 1e-5));
+
+    // rotations are around the center of the texture
+    let rotating = TextureAnimation {
+        translation: animation.translation.clone(),
+        rotation: Keyframes {
+            timestamps: vec![0],
+            values: vec![Quat::from_rotation_z(std::f32::consts::PI)],
+            duration: 0,
+        },
+    };
+    let center = rotating.uv_transform(0).transform_point2(Vec2::splat(0.5));
+    assert!(center.abs_diff_eq(Vec2::splat(0.5), 1e-5));
+    let corner = rotating.uv_transform(0).transform_point2(Vec2::ZERO);
+    assert!(corner.abs_diff_eq(Vec2::ONE, 1e-5));
+}
+
+#[test]
+fn winding_conversion() {
+    let positions = [Vec3::ZERO, Vec3::X, Vec3::Y];
+    let winding = |indices: &[u32]| {
+        triangle_winding(
+            positions[indices[0] as usize],
+            positions[indices[1] as usize],
+            positions[indices[2] as usize],
+            Vec3::Z,
+        )
+    };
+
+    let mut clockwise = vec![0, 2, 1];
+    assert_eq!(winding(&clockwise), Winding::Clockwise);
+    convert_winding(&mut clockwise, Winding::Clockwise);
+    assert_eq!(clockwise, vec![0, 1, 2]);
+    assert_eq!(winding(&clockwise), IR_WINDING);
+
+    let mut counter_clockwise = vec![0, 1, 2];
+    convert_winding(&mut counter_clockwise, Winding::CounterClockwise);
+    assert_eq!(counter_clockwise, vec![0, 1, 2]);
+}
@@ -0,0 +1,67 @@
+use glam::{Mat3, Quat, Vec2, Vec3};
+
+/// Keyframes (timestamps in ms) that are linearly interpolated and loop after `duration`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframes<T> {
+    pub timestamps: Vec<u32>,
+    pub values: Vec<T>,
+    pub duration: u32,
+}
+
+impl<T: Copy> Keyframes<T> {
+    /// The value at the given time, None if there are no keyframes.
+    pub fn sample(&self, time_ms: u64, lerp: impl Fn(T, T, f32) -> T) -> Option<T> {
+        let first = *self.values.first()?;
+        if self.duration == 0 {
+            return Some(first);
+        }
+
+        let time = (time_ms % self.duration as u64) as u32;
+        let next = self
+            .timestamps
+            .partition_point(|&timestamp| timestamp <= time);
+        if next == 0 {
+            return Some(first);
+        }
+
+        if next >= self.values.len() {
+            return self.values.last().copied();
+        }
+
+        let (start, end) = (self.timestamps[next - 1], self.timestamps[next]);
+        let factor = (time - start) as f32 / (end - start).max(1) as f32;
+        Some(lerp(self.values[next - 1], self.values[next], factor))
+    }
+}
+
+/// Scrolls and rotates the texture coordinates over time, e.g. for water, fire or banners. Rotations are around the
+/// center of the texture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureAnimation {
+    pub translation: Keyframes<Vec3>,
+    pub rotation: Keyframes<Quat>,
+}
+
+impl TextureAnimation {
+    /// The transform that is applied to the texture coordinates (as `vec3(uv, 1.0)`) at the given time.
+    pub fn uv_transform(&self, time_ms: u64) -> Mat3 {
+        let translation = self
+            .translation
+            .sample(time_ms, Vec3::lerp)
+            .unwrap_or(Vec3::ZERO);
+        let rotation = self
+            .rotation
+            .sample(time_ms, Quat::slerp)
+            .unwrap_or(Quat::IDENTITY);
+
+        // Texture coordinates are 2D, so only the rotation around z matters.
+        let rotated = rotation * Vec3::X;
+        let angle = rotated.y.atan2(rotated.x);
+        let pivot = Vec2::splat(0.5);
+
+        Mat3::from_translation(translation.truncate())
+            * Mat3::from_translation(pivot)
+            * Mat3::from_angle(angle)
+            * Mat3::from_translation(-pivot)
+    }
+}
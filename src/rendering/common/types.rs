@@ -1,13 +1,100 @@
-use glam::{Vec2, Vec3, Vec4};
-use std::fmt::{Debug, Formatter};
+use glam::{Affine3A, Vec2, Vec3, Vec4};
+use std::fmt::{Debug, Display, Formatter};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Mesh {
     pub vertex_buffers: VertexBuffers,
     pub index_buffer: Vec<u32>,
 }
 
 impl Mesh {
+    /// Checks that the mesh can be rendered and used for physics: Every index has to reference a vertex, the index
+    /// buffer has to consist of whole triangles that don't reference the same vertex twice, all the optional
+    /// buffers are either empty or have one entry per vertex and no position is NaN or infinite.
+    pub fn validate(&self) -> Result<(), MeshError> {
+        let buffers = &self.vertex_buffers;
+        let vertex_count = buffers.position_buffer.len();
+
+        let lengths = [
+            ("normals", buffers.normals_buffer.len()),
+            ("tangents", buffers.tangents_buffer.len()),
+            ("texcoord_0", buffers.texcoord_buffer_0.len()),
+            ("texcoord_1", buffers.texcoord_buffer_1.len()),
+            ("vertex_color_0", buffers.vertex_color_0.len()),
+        ];
+
+        for (buffer, length) in lengths {
+            if length != 0 && length != vertex_count {
+                return Err(MeshError::BufferLengthMismatch {
+                    buffer,
+                    length,
+                    vertex_count,
+                });
+            }
+        }
+
+        if let Some(vertex) = buffers
+            .position_buffer
+            .iter()
+            .position(|pos| !pos.is_finite())
+        {
+            return Err(MeshError::NonFinitePosition { vertex });
+        }
+
+        if self.index_buffer.len() % 3 != 0 {
+            return Err(MeshError::IncompleteTriangle {
+                index_count: self.index_buffer.len(),
+            });
+        }
+
+        if let Some((position, &index)) = self
+            .index_buffer
+            .iter()
+            .enumerate()
+            .find(|(_, index)| **index as usize >= vertex_count)
+        {
+            return Err(MeshError::IndexOutOfRange {
+                position,
+                index,
+                vertex_count,
+            });
+        }
+
+        if let Some(triangle) = self
+            .index_buffer
+            .chunks_exact(3)
+            .position(|tri| tri[0] == tri[1] || tri[1] == tri[2] || tri[0] == tri[2])
+        {
+            return Err(MeshError::DegenerateTriangle { triangle });
+        }
+
+        Ok(())
+    }
+
+    /// The RAM that the buffers occupy, in bytes (ignoring excess capacity).
+    pub fn memory_size(&self) -> usize {
+        let buffers = &self.vertex_buffers;
+        size_of_val(buffers.position_buffer.as_slice())
+            + size_of_val(buffers.normals_buffer.as_slice())
+            + size_of_val(buffers.tangents_buffer.as_slice())
+            + size_of_val(buffers.texcoord_buffer_0.as_slice())
+            + size_of_val(buffers.texcoord_buffer_1.as_slice())
+            + size_of_val(buffers.vertex_color_0.as_slice())
+            + size_of_val(self.index_buffer.as_slice())
+    }
+
+    /// The axis aligned box enclosing all vertices (in model space).
+    pub fn bounding_box(&self) -> BoundingBox {
+        let positions = &self.vertex_buffers.position_buffer;
+        BoundingBox {
+            min: positions.iter().copied().fold(Vec3::INFINITY, Vec3::min),
+            max: positions
+                .iter()
+                .copied()
+                .fold(Vec3::NEG_INFINITY, Vec3::max),
+        }
+    }
+
     // TODO: implement in a sane way
     // let mut w = BufWriter::new(File::create("./terrain.obj")?);
     // writeln!(w, "o {}","terrain")?;
@@ -23,6 +110,63 @@ impl Mesh {
     // TODO: Also note that there's another version flying around that supports normals, tangents and everything in it's faces.
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshError {
+    BufferLengthMismatch {
+        buffer: &'static str,
+        length: usize,
+        vertex_count: usize,
+    },
+    NonFinitePosition {
+        vertex: usize,
+    },
+    IncompleteTriangle {
+        index_count: usize,
+    },
+    IndexOutOfRange {
+        position: usize,
+        index: u32,
+        vertex_count: usize,
+    },
+    DegenerateTriangle {
+        triangle: usize,
+    },
+}
+
+impl Display for MeshError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeshError::BufferLengthMismatch {
+                buffer,
+                length,
+                vertex_count,
+            } => write!(
+                f,
+                "The {} buffer has {} entries, but there are {} vertices",
+                buffer, length, vertex_count
+            ),
+            MeshError::NonFinitePosition { vertex } => write!(f, "Vertex {} has a non-finite position", vertex),
+            MeshError::IncompleteTriangle { index_count } => {
+                write!(f, "{} indices don't form whole triangles", index_count)
+            }
+            MeshError::IndexOutOfRange {
+                position,
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "Index {} (at {}) is out of range for {} vertices",
+                index, position, vertex_count
+            ),
+            MeshError::DegenerateTriangle { triangle } => {
+                write!(f, "Triangle {} references the same vertex twice", triangle)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
 impl Debug for Mesh {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{{ vertex_buffers: {:?}, ", self.vertex_buffers)?;
@@ -64,6 +208,57 @@ impl Default for VertexBuffers {
     }
 }
 
+/// An axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl BoundingBox {
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// The axis aligned box that encloses this box after the transform has been applied, which is larger than the
+    /// original box for rotations that aren't multiples of 90 degrees.
+    pub fn transformed(&self, transform: &Affine3A) -> BoundingBox {
+        let mut min = Vec3::INFINITY;
+        let mut max = Vec3::NEG_INFINITY;
+
+        for corner in self.corners() {
+            let corner = transform.transform_point3(corner);
+            min = min.min(corner);
+            max = max.max(corner);
+        }
+
+        BoundingBox { min, max }
+    }
+
+    /// The distance of the point to the box, 0 if it's inside of the box.
+    pub fn distance_to(&self, point: Vec3) -> f32 {
+        point.clamp(self.min, self.max).distance(point)
+    }
+
+    /// The smallest box that encloses both boxes.
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
 // TODO: How would we model LODDABLE Meshes? One vertex buffer, multiple index buffers, Importers can support that
 #[derive(Clone, Debug)]
 pub struct MeshWithLod {
@@ -71,6 +266,17 @@ pub struct MeshWithLod {
     pub index_buffers: Vec<Vec<u32>>,
 }
 
+impl MeshWithLod {
+    /// Every this many units of distance, the next LoD level is used
+    pub const LOD_DISTANCE: f32 = 100.0;
+
+    /// The LoD level for an object at the given distance to the camera, clamped to the highest available level. The
+    /// demos pick it once, when placing the object.
+    pub fn lod_for_distance(&self, distance: f32) -> usize {
+        ((distance / Self::LOD_DISTANCE) as usize).min(self.index_buffers.len().saturating_sub(1))
+    }
+}
+
 /// Note: The structs in here are very much driven by the current backend/use-case and as such may change
 /// quite often. This is especially true for the material, that has a complex structure.
 #[derive(Clone, Debug)]
@@ -1,48 +1,59 @@
-use crate::rendering::common::coordinate_systems::GRID_SIZE;
+use crate::rendering::common::coordinate_systems;
+use crate::rendering::common::coordinate_systems::{GRID_SIZE, IR_WINDING, Winding};
 use crate::rendering::common::special_types::TerrainTextureLayer;
 use crate::rendering::common::types::{Mesh, VertexBuffers};
 use anyhow::Error;
 use glam::Vec3;
 use itertools::Itertools;
 use log::warn;
-use sargerust_files::adt::types::{
-    MCALSubChunk, MCNKChunk, MCNKChunkHeader, MCNKHeaderFlags, MCNREntry, MTEXChunk, SMLayer, SMLayerFlags,
-};
+use sargerust_files::adt::types::{MCNKChunk, MCNREntry, MTEXChunk, SMLayer, SMLayerFlags};
 use sargerust_files::common::types::CImVector;
-use sargerust_files::wdt::types::{MPHDChunk, MPHDFlags};
+use sargerust_files::wdt::types::MPHDChunk;
 
 pub struct ADTImporter {}
 
-fn calculate_normal(entry: &MCNREntry) -> Vec3 {
-    // TODO: Comment from Nieriel suggests a special re-calculation of z from x and y:
-    //  float Z = sqrt(1 - (X / 127)² - (Y / 127)²), Z >= 0)
+fn calculate_normal(entry: &MCNREntry, recalculate: bool) -> Vec3 {
+    let x = entry.normal_x as f32 / 127.0f32;
+    let y = entry.normal_y as f32 / 127.0f32;
 
-    Vec3::new(
-        entry.normal_x as f32 / 127.0f32,
-        entry.normal_y as f32 / 127.0f32,
-        entry.normal_z as f32 / 127.0f32,
-    )
-    .normalize()
+    // Comment from Nieriel suggests a special re-calculation of z from x and y, because the quantized z component
+    // may not yield a normalized vector: float Z = sqrt(1 - (X / 127)² - (Y / 127)²), Z >= 0)
+    let z = match recalculate {
+        true => (1.0 - x * x - y * y).max(0.0).sqrt(),
+        false => entry.normal_z as f32 / 127.0f32,
+    };
 
-    // Also, for some reason, the normals appear odd: For the shader, y seems to be up, not z.
-}
+    Vec3::new(x, y, z).try_normalize().unwrap_or(Vec3::Z)
 
-/// Convert a 4-bit value to an 8-bit value by expanding the bits.
-#[inline(always)]
-fn expand_bits(data: u8) -> u8 {
-    (data * 0x10) | (data & 0xF)
+    // Also, for some reason, the normals appear odd: For the shader, y seems to be up, not z.
 }
 
-#[inline(always)]
-fn unpack_2048_bytes(data: &[u8]) -> Vec<u8> {
-    let mut result = Vec::with_capacity(4096);
-    assert_eq!(data.len(), 2048);
-
-    for byte in data {
-        result.push(expand_bits(byte & 0x0F));
-        result.push(expand_bits(byte >> 4));
-    }
-    result
+/// Calculates the normal of a terrain vertex by central differences of the heightfield (one-sided at the borders),
+/// for chunks that lack a MCNR sub chunk. `row` and `column` are in units of the outer 9x9 grid, so the inner
+/// vertices are at x.5
+fn calculate_normal_from_heightfield(mcvt: &[f32], row: f32, column: f32) -> Vec3 {
+    // sample the outer grid bilinearly, that way we can also handle the inner vertices.
+    let height = |row: f32, column: f32| -> f32 {
+        let row = row.clamp(0.0, 8.0);
+        let column = column.clamp(0.0, 8.0);
+        let (r0, c0) = (row.floor() as u8, column.floor() as u8);
+        let (r1, c1) = ((r0 + 1).min(8), (c0 + 1).min(8));
+        let (fr, fc) = (row - r0 as f32, column - c0 as f32);
+
+        let h = |r: u8, c: u8| mcvt[MCNKChunk::get_index_low(r, c) as usize];
+        let top = h(r0, c0) * (1.0 - fc) + h(r0, c1) * fc;
+        let bottom = h(r1, c0) * (1.0 - fc) + h(r1, c1) * fc;
+        top * (1.0 - fr) + bottom * fr
+    };
+
+    let (r_min, r_max) = ((row - 0.5).max(0.0), (row + 0.5).min(8.0));
+    let (c_min, c_max) = ((column - 0.5).max(0.0), (column + 0.5).min(8.0));
+
+    // rows grow in -x, columns grow in -y (see create_mesh)
+    let dh_dx = (height(r_max, column) - height(r_min, column)) / (-GRID_SIZE * (r_max - r_min));
+    let dh_dy = (height(row, c_max) - height(row, c_min)) / (-GRID_SIZE * (c_max - c_min));
+
+    Vec3::new(-dh_dx, -dh_dy, 1.0).normalize()
 }
 
 /// Transform game file structs into terrain texture layers that can be rendered. Ideally, this
@@ -50,9 +61,8 @@ fn unpack_2048_bytes(data: &[u8]) -> Vec<u8> {
 fn transform_terrain_layer(
     layer: &SMLayer,
     mtex: &MTEXChunk,
-    mcal: &MCALSubChunk,
     mphd: &MPHDChunk,
-    mcnk: &MCNKChunkHeader,
+    mcnk: &MCNKChunk,
 ) -> Option<TerrainTextureLayer> {
     let file_name = mtex
         .filenames
@@ -65,51 +75,37 @@ fn transform_terrain_layer(
     }
 
     let texture_path = file_name.unwrap();
-    let offset = layer.offset_in_mcal as usize;
-    let alpha_map_buf: Vec<u8>;
 
-    if layer.flags.contains(SMLayerFlags::ALPHA_MAP_COMPRESSED) {
-        warn!("Alpha map compression not supported."); // TODO
-        return None;
-    } else if !mphd.flags.contains(MPHDFlags::ADT_HAS_BIG_ALPHA) {
-        if mcnk.flags.contains(MCNKHeaderFlags::DO_NOT_FIX_ALPHA_MAP) {
-            warn!("Alpha map fixing not supported."); // TODO
-            return None;
-        }
-
-        if mcal.len() - offset < 2048 {
-            warn!(
-                "Texture ID {} has an alpha map that is too short. ({} Bytes instead of 2048)",
-                layer.textureId,
-                mcal.len() - offset
-            );
-            return None;
-        }
+    // The first layer is the base layer, which is fully opaque.
+    if !layer.flags.contains(SMLayerFlags::USE_ALPHA_MAP) {
+        return Some(TerrainTextureLayer {
+            texture_path,
+            alpha_map: None,
+        });
+    }
 
-        alpha_map_buf = unpack_2048_bytes(&mcal[layer.offset_in_mcal as usize..layer.offset_in_mcal as usize + 2048]);
-    } else {
-        if mcal.len() - offset < 4096 {
+    match mcnk.decode_alpha_map(layer, mphd) {
+        Ok(alpha_map) => Some(TerrainTextureLayer {
+            texture_path,
+            alpha_map: Some(alpha_map.to_vec()),
+        }),
+        Err(err) => {
             warn!(
-                "Texture ID {} has an alpha map that is too short. ({} Bytes instead of 4096)",
-                layer.textureId,
-                mcal.len() - offset
+                "Texture ID {} has an invalid alpha map: {}",
+                layer.textureId, err
             );
-            return None;
+            None
         }
-
-        alpha_map_buf = mcal[offset..offset + 4096].to_vec();
     }
-
-    Some(TerrainTextureLayer {
-        texture_path,
-        alpha_map: Some(alpha_map_buf),
-    })
 }
 
 impl ADTImporter {
+    /// When `recalculate_normals` is set, the normals are re-normalized from their x and y components or, if the
+    /// chunk has no normals at all, derived from the heightfield.
     pub fn create_mesh(
         mcnk: &MCNKChunk,
         low_res: bool,
+        recalculate_normals: bool,
         mtex: &MTEXChunk,
         mphd: &MPHDChunk,
     ) -> Result<(Vec3, Mesh, Vec<TerrainTextureLayer>), Error> {
@@ -121,16 +117,12 @@ impl ADTImporter {
         let mcvt = mcnk.get_mcvt()?.unwrap();
         let mcnr = mcnk.get_mcnr()?;
         let mcly_opt = mcnk.get_mcly()?;
-        let mcal_opt = mcnk.get_mcal()?;
 
         let texture_references = mcly_opt
-            .and_then(|mcly| {
-                // TODO: We may need to rewrite this completely into an iterator again, because we only need MCAL if we have more than one layer?
-                mcal_opt.map(|mcal| {
-                    mcly.iter()
-                        .flat_map(|layer| transform_terrain_layer(layer, mtex, &mcal, mphd, &mcnk.header))
-                        .collect_vec()
-                })
+            .map(|mcly| {
+                mcly.iter()
+                    .flat_map(|layer| transform_terrain_layer(layer, mtex, mphd, mcnk))
+                    .collect_vec()
             })
             .unwrap_or(vec![]);
 
@@ -158,8 +150,17 @@ impl ADTImporter {
                     .unwrap_or(CImVector::from(0xFFFFFFFFu32));
                 vertex_color_0.push([color.r, color.g, color.b, color.a]); // TODO: Where is the format defined?
 
-                if let Some(normal) = mcnr.as_ref().map(|x| calculate_normal(&x[low as usize])) {
+                if let Some(normal) = mcnr
+                    .as_ref()
+                    .map(|x| calculate_normal(&x[low as usize], recalculate_normals))
+                {
                     normals_buffer.push(normal);
+                } else if recalculate_normals {
+                    normals_buffer.push(calculate_normal_from_heightfield(
+                        &mcvt,
+                        row as f32,
+                        column as f32,
+                    ));
                 }
             }
 
@@ -184,15 +185,24 @@ impl ADTImporter {
                     .unwrap_or(CImVector::from(0xFFFFFFFFu32));
                 vertex_color_0.push([color.r, color.g, color.b, color.a]); // TODO: Where is the format defined?
 
-                if let Some(normal) = mcnr.as_ref().map(|x| calculate_normal(&x[high as usize])) {
+                if let Some(normal) = mcnr
+                    .as_ref()
+                    .map(|x| calculate_normal(&x[high as usize], recalculate_normals))
+                {
                     normals_buffer.push(normal);
+                } else if recalculate_normals {
+                    normals_buffer.push(calculate_normal_from_heightfield(
+                        &mcvt,
+                        row as f32 + 0.5,
+                        column as f32 + 0.5,
+                    ));
                 }
             }
         }
 
         // build the index buffer, this is probably the most difficult part.
         // TODO: technically, this could be multiple index buffers and swapping them
-        // TODO: apparently this is exactly the wrong index buffer winding order. Fix it here instead of the lazy way further down.
+        // Note: Since rows grow in -x and columns in -y, the triangles below are wound clockwise when seen from above.
 
         if low_res {
             for row in 0..8 {
@@ -236,11 +246,17 @@ impl ADTImporter {
             }
         }
 
-        // TODO: as outlined above, just fix the statements instead.
-        assert_eq!(index_buffer.len() % 3, 0);
-        for chunk in index_buffer.chunks_exact_mut(3) {
-            chunk.swap(1, 2);
-        }
+        coordinate_systems::convert_winding(&mut index_buffer, Winding::Clockwise);
+        debug_assert_eq!(
+            coordinate_systems::triangle_winding(
+                position_buffer[index_buffer[0] as usize],
+                position_buffer[index_buffer[1] as usize],
+                position_buffer[index_buffer[2] as usize],
+                Vec3::Z,
+            ),
+            IR_WINDING,
+            "Terrain has to face upwards"
+        );
 
         let mesh = Mesh {
             vertex_buffers: VertexBuffers {
@@ -251,6 +267,12 @@ impl ADTImporter {
             },
             index_buffer,
         };
+
+        #[cfg(debug_assertions)]
+        if let Err(err) = mesh.validate() {
+            warn!("Terrain mesh is invalid: {}", err);
+        }
+
         let pos = Vec3::new(
             mcnk.header.position.x,
             mcnk.header.position.y,
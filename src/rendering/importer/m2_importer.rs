@@ -1,13 +1,26 @@
-use crate::rendering::common::types::{AlbedoType, Material, Mesh, TransparencyType, VertexBuffers};
-use glam::{Vec2, Vec3, Vec4};
+use crate::rendering::common::texture_animation::{Keyframes, TextureAnimation};
+use crate::rendering::common::types::{AlbedoType, BoundingBox, Material, Mesh, TransparencyType, VertexBuffers};
+use glam::{Quat, Vec2, Vec3, Vec4};
 use image_blp::BlpImage;
 use itertools::Itertools;
-use sargerust_files::m2::types::{M2Asset, M2SkinProfile};
+use sargerust_files::m2::types::{M2Asset, M2Sequence, M2SkinProfile, M2Track};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
 
 pub struct M2Importer {}
 
+/// A submesh (skin section) of a M2, as a range of the index buffer. Geosets are grouped by `id / 100` and in every
+/// group except 0, only one variant (e.g. one hairstyle or one helmet) is supposed to be shown at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct M2Geoset {
+    pub id: u16,
+    pub index_range: Range<usize>,
+}
+
 impl M2Importer {
-    pub fn create_mesh(asset: &M2Asset, skin: &M2SkinProfile) -> Mesh {
+    pub fn create_mesh(asset: &M2Asset, skin: &M2SkinProfile) -> Result<Mesh, anyhow::Error> {
+        skin.validate(asset)?;
         let mut verts = Vec::<Vec3>::with_capacity(skin.vertices.len());
 
         // TODO: does every m2 have UVs?
@@ -20,12 +33,13 @@ impl M2Importer {
             // TODO: multiple UVs
         }
 
+        // M2 triangles are already wound like the IR (see coordinate_systems::IR_WINDING).
         let mut indices = Vec::<u32>::with_capacity(skin.indices.len());
         for &i in &skin.indices {
             indices.push(i as u32);
         }
 
-        Mesh {
+        let mesh = Mesh {
             index_buffer: indices,
             vertex_buffers: VertexBuffers {
                 position_buffer: verts,
@@ -35,6 +49,108 @@ impl M2Importer {
                 texcoord_buffer_1: vec![],
                 vertex_color_0: vec![],
             },
+        };
+
+        #[cfg(debug_assertions)]
+        if let Err(err) = mesh.validate() {
+            log::warn!("M2 mesh is invalid: {}", err);
+        }
+
+        Ok(mesh)
+    }
+
+    /// The index ranges of all submeshes, matching the index buffer of [`M2Importer::create_mesh`].
+    pub fn create_geosets(skin: &M2SkinProfile) -> Vec<M2Geoset> {
+        skin.submeshes
+            .iter()
+            .map(|section| {
+                // The level is the high word of the start, to exceed the limits of u16.
+                let start = (((section.Level as usize) << 16) | section.indexStart as usize).min(skin.indices.len());
+                let end = (start + section.indexCount as usize).min(skin.indices.len());
+                M2Geoset {
+                    id: section.skinSectionId,
+                    index_range: start..end,
+                }
+            })
+            .collect()
+    }
+
+    /// Group 0 is always visible, all other geosets only when they have been whitelisted.
+    pub fn is_geoset_visible(id: u16, geoset_whitelist: &HashSet<u16>) -> bool {
+        id / 100 == 0 || geoset_whitelist.contains(&id)
+    }
+
+    /// Only keeps the triangles of the visible geosets (see [`M2Importer::is_geoset_visible`]), without a whitelist,
+    /// the whole mesh is kept. The vertex buffers are kept as is, so unused vertices remain.
+    pub fn filter_geosets(mesh: &Mesh, geosets: &[M2Geoset], geoset_whitelist: Option<&HashSet<u16>>) -> Mesh {
+        let Some(whitelist) = geoset_whitelist else {
+            return mesh.clone();
+        };
+
+        let index_buffer = geosets
+            .iter()
+            .filter(|geoset| Self::is_geoset_visible(geoset.id, whitelist))
+            .flat_map(|geoset| &mesh.index_buffer[geoset.index_range.clone()])
+            .copied()
+            .collect();
+
+        Mesh {
+            vertex_buffers: mesh.vertex_buffers.clone(),
+            index_buffer,
+        }
+    }
+
+    /// Only the texture transform of the first combo is supported for now, as we don't read the batches, yet. Tracks
+    /// are either timed by their global loop or by the first sequence that has keyframes.
+    pub fn create_texture_animation(asset: &M2Asset) -> Option<TextureAnimation> {
+        let transform = asset.texture_transform(0)?;
+        if !transform.translation.is_animated() && !transform.rotation.is_animated() {
+            return None;
+        }
+
+        let translation = Self::create_keyframes(asset, &transform.translation, |v| Vec3::new(v.x, v.y, v.z));
+        let rotation = Self::create_keyframes(asset, &transform.rotation, |q| {
+            Quat::from_xyzw(q.x, q.y, q.z, q.w)
+        });
+        Some(TextureAnimation {
+            translation,
+            rotation,
+        })
+    }
+
+    fn create_keyframes<T, U>(asset: &M2Asset, track: &M2Track<T>, convert: impl Fn(&T) -> U) -> Keyframes<U> {
+        let Some(sequence) = track
+            .timestamps
+            .iter()
+            .position(|timestamps| !timestamps.is_empty())
+        else {
+            return Keyframes {
+                timestamps: vec![],
+                values: vec![],
+                duration: 0,
+            };
+        };
+
+        let timestamps = track.timestamps[sequence].clone();
+        let duration = match track.global_sequence() {
+            Some(global_loop) => asset.global_loops.get(global_loop).copied(),
+            None => asset.sequences.get(sequence).map(M2Sequence::duration),
+        }
+        .filter(|&duration| duration > 0)
+        .unwrap_or_else(|| timestamps.last().copied().unwrap_or(0));
+
+        Keyframes {
+            timestamps,
+            values: track.values[sequence].iter().map(convert).collect(),
+            duration,
+        }
+    }
+
+    pub fn create_bounding_box(asset: &M2Asset) -> BoundingBox {
+        let bounds = asset.bounding_box();
+        BoundingBox {
+            min: Vec3::new(bounds.min.x, bounds.min.y, bounds.min.z),
+            max: Vec3::new(bounds.max.x, bounds.max.y, bounds.max.z),
         }
     }
 
@@ -69,7 +185,7 @@ impl M2Importer {
             .collect_vec()
     }
 
-    pub fn create_material(blp_opt: &Option<BlpImage> /* TODO */) -> Material {
+    pub fn create_material(blp_opt: &Option<Arc<BlpImage>> /* TODO */) -> Material {
         Material {
             albedo: match blp_opt {
                 Some(texture_handle) => AlbedoType::Texture, /*(TODO)*/
@@ -6,4 +6,8 @@ pub mod adt_importer;
 /// multiple times (especially also for instancing), without directly working in the complexity
 /// that is the asset files themselves.
 pub mod m2_importer;
+pub mod water_importer;
 pub mod wmo_importer;
+
+#[cfg(test)]
+mod tests;
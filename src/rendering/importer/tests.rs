@@ -0,0 +1,378 @@
+use crate::rendering::common::types::{BoundingBox, Mesh, MeshError, VertexBuffers};
+use crate::rendering::importer::adt_importer::ADTImporter;
+use crate::rendering::importer::m2_importer::{M2Geoset, M2Importer};
+use crate::rendering::importer::water_importer::WaterImporter;
+use crate::rendering::importer::wmo_importer::WMOGroupImporter;
+use glam::{Affine3A, Quat, Vec3, Vec3A};
+use sargerust_files::adt::reader::ADTReader;
+use sargerust_files::wdt::types::{MPHDChunk, MPHDFlags};
+use sargerust_files::wmo::reader::WMOReader;
+use sargerust_files::wmo::types::SMOPoly;
+use std::collections::HashSet;
+use std::io::Cursor;
+
+const VERTEX_COUNT: usize = 9 * 9 + 8 * 8;
+const MCNK_HEADER_SIZE: usize = 128;
+
+fn chunk(magic: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    // IFF magics are stored reversed
+    let mut buf = magic.iter().rev().copied().collect::<Vec<u8>>();
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+    buf
+}
+
+/// A MCNK with a wavy heightfield, optionally with (upwards facing) normals.
+fn mcnk(index_x: u32, index_y: u32, with_normals: bool) -> Vec<u8> {
+    let heights = (0..VERTEX_COUNT)
+        .flat_map(|i| ((i as f32 * 0.5).sin() * 10.0).to_le_bytes())
+        .collect::<Vec<u8>>();
+    let mcvt = chunk(b"MCVT", &heights);
+    let mcnr = chunk(b"MCNR", &[0u8, 127, 0].repeat(VERTEX_COUNT)); // x, z, y
+
+    let write_u32 = |header: &mut [u8], offset: usize, value: u32| {
+        header[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+
+    // offsets are relative to the start of the MCNK, including its IFF header
+    let ofs_height = 8 + MCNK_HEADER_SIZE;
+    let mut header = vec![0u8; MCNK_HEADER_SIZE];
+    write_u32(&mut header, 0x04, index_x);
+    write_u32(&mut header, 0x08, index_y);
+    write_u32(&mut header, 0x14, ofs_height as u32);
+    if with_normals {
+        write_u32(&mut header, 0x18, (ofs_height + mcvt.len()) as u32);
+    }
+
+    // position
+    write_u32(&mut header, 0x68, (-33.3 * index_y as f32).to_bits());
+    write_u32(&mut header, 0x6C, (-33.3 * index_x as f32).to_bits());
+    write_u32(&mut header, 0x70, 100.0f32.to_bits());
+
+    let mut data = header;
+    data.extend_from_slice(&mcvt);
+    if with_normals {
+        data.extend_from_slice(&mcnr);
+    }
+
+    chunk(b"MCNK", &data)
+}
+
+fn synthetic_adt() -> Vec<u8> {
+    let mut adt = chunk(b"MVER", &18u32.to_le_bytes());
+    adt.extend(chunk(b"MHDR", &[0; 0x40]));
+    adt.extend(chunk(b"MCIN", &[0; 16 * 16 * 16]));
+
+    for magic in [
+        b"MTEX", b"MMDX", b"MMID", b"MWMO", b"MWID", b"MDDF", b"MODF",
+    ] {
+        adt.extend(chunk(magic, &[]));
+    }
+
+    adt.extend(mcnk(0, 0, true));
+    adt.extend(mcnk(1, 0, true));
+    adt.extend(mcnk(0, 1, true));
+    adt.extend(mcnk(1, 1, false));
+    adt
+}
+
+#[test]
+fn synthetic_adt_to_mesh() -> Result<(), anyhow::Error> {
+    let adt = ADTReader::parse_asset(&mut Cursor::new(synthetic_adt()))?;
+    assert_eq!(adt.mcnks.len(), 4);
+
+    let mphd = MPHDChunk {
+        flags: MPHDFlags::empty(),
+        something: 0,
+        unused: [0; 6],
+    };
+
+    for mcnk in &adt.mcnks {
+        for (low_res, index_count) in [(true, 8 * 8 * 2 * 3), (false, 8 * 8 * 4 * 3)] {
+            let (position, mesh, layers) = ADTImporter::create_mesh(mcnk, low_res, true, &adt.mtex, &mphd)?;
+            let buffers = &mesh.vertex_buffers;
+
+            assert!(position.is_finite());
+            assert!(layers.is_empty());
+            assert_eq!(buffers.position_buffer.len(), VERTEX_COUNT);
+            assert_eq!(buffers.normals_buffer.len(), VERTEX_COUNT);
+            assert_eq!(buffers.vertex_color_0.len(), VERTEX_COUNT);
+            assert_eq!(mesh.index_buffer.len(), index_count);
+
+            assert!(
+                mesh.index_buffer
+                    .iter()
+                    .all(|&index| (index as usize) < VERTEX_COUNT)
+            );
+            assert!(buffers.position_buffer.iter().all(|pos| pos.is_finite()));
+            assert!(
+                buffers
+                    .normals_buffer
+                    .iter()
+                    .all(|normal| normal.is_normalized() && normal.z > 0.0)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn synthetic_adt_meshes_are_valid() -> Result<(), anyhow::Error> {
+    let adt = ADTReader::parse_asset(&mut Cursor::new(synthetic_adt()))?;
+    let mphd = MPHDChunk {
+        flags: MPHDFlags::empty(),
+        something: 0,
+        unused: [0; 6],
+    };
+
+    for mcnk in &adt.mcnks {
+        for low_res in [true, false] {
+            let (_, mesh, _) = ADTImporter::create_mesh(mcnk, low_res, true, &adt.mtex, &mphd)?;
+            assert_eq!(mesh.validate(), Ok(()));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn mesh_validation_errors() {
+    let mut mesh = Mesh {
+        vertex_buffers: VertexBuffers {
+            position_buffer: vec![Vec3::ZERO, Vec3::X, Vec3::Y],
+            ..VertexBuffers::default()
+        },
+        index_buffer: vec![0, 1, 2],
+    };
+    assert_eq!(mesh.validate(), Ok(()));
+
+    mesh.index_buffer = vec![0, 1, 3];
+    assert!(matches!(
+        mesh.validate(),
+        Err(MeshError::IndexOutOfRange { index: 3, .. })
+    ));
+
+    mesh.index_buffer = vec![0, 1, 1];
+    assert_eq!(
+        mesh.validate(),
+        Err(MeshError::DegenerateTriangle { triangle: 0 })
+    );
+
+    mesh.index_buffer = vec![0, 1];
+    assert!(matches!(
+        mesh.validate(),
+        Err(MeshError::IncompleteTriangle { index_count: 2 })
+    ));
+
+    mesh.index_buffer = vec![0, 1, 2];
+    mesh.vertex_buffers.normals_buffer = vec![Vec3::Z];
+    assert!(matches!(
+        mesh.validate(),
+        Err(MeshError::BufferLengthMismatch {
+            buffer: "normals",
+            ..
+        })
+    ));
+
+    mesh.vertex_buffers.normals_buffer.clear();
+    mesh.vertex_buffers.position_buffer[1] = Vec3::NAN;
+    assert_eq!(
+        mesh.validate(),
+        Err(MeshError::NonFinitePosition { vertex: 1 })
+    );
+}
+
+#[test]
+fn transformed_bounding_box() {
+    let bounds = BoundingBox {
+        min: Vec3::new(-1.0, -2.0, 0.0),
+        max: Vec3::new(1.0, 2.0, 3.0),
+    };
+
+    let translated = bounds.transformed(&Affine3A::from_translation(Vec3::new(10.0, 0.0, 0.0)));
+    assert_eq!(translated.min, Vec3::new(9.0, -2.0, 0.0));
+    assert_eq!(translated.max, Vec3::new(11.0, 2.0, 3.0));
+
+    // a quarter turn around z swaps the extents along x and y
+    let rotated = bounds.transformed(&Affine3A::from_quat(Quat::from_rotation_z(
+        std::f32::consts::FRAC_PI_2,
+    )));
+    assert!(rotated.min.abs_diff_eq(Vec3::new(-2.0, -1.0, 0.0), 1e-5));
+    assert!(rotated.max.abs_diff_eq(Vec3::new(2.0, 1.0, 3.0), 1e-5));
+}
+
+/// An MH2O with a single ocean instance (no height map) covering the whole first chunk.
+fn synthetic_mh2o() -> Vec<u8> {
+    const HEADERS_SIZE: u32 = 16 * 16 * 12;
+
+    let mut data = Vec::new();
+    for value in [HEADERS_SIZE, 1, 0] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.resize(HEADERS_SIZE as usize, 0);
+
+    data.extend_from_slice(&2u16.to_le_bytes()); // liquid type
+    data.extend_from_slice(&2u16.to_le_bytes()); // format: depth only
+    data.extend_from_slice(&95.0f32.to_le_bytes());
+    data.extend_from_slice(&95.0f32.to_le_bytes());
+    data.extend_from_slice(&[0, 0, 8, 8]);
+    data.extend_from_slice(&[0; 8]); // no exists mask, no height map
+    data
+}
+
+#[test]
+fn synthetic_water_to_mesh() -> Result<(), anyhow::Error> {
+    let mut data = synthetic_adt();
+    data.extend(chunk(b"MH2O", &synthetic_mh2o()));
+    let adt = ADTReader::parse_asset(&mut Cursor::new(data))?;
+    let mh2o = adt.mh2o.as_ref().expect("MH2O chunk");
+
+    let water = WaterImporter::create_mesh(mh2o, &adt.mcnks[0])?.expect("Water in the first chunk");
+    assert_eq!(water.vertex_buffers.position_buffer.len(), 9 * 9);
+    assert_eq!(water.index_buffer.len(), 8 * 8 * 2 * 3);
+    assert_eq!(water.validate(), Ok(()));
+    // relative to the chunk position (z = 100)
+    assert!(
+        water
+            .vertex_buffers
+            .position_buffer
+            .iter()
+            .all(|pos| pos.z == -5.0)
+    );
+
+    for mcnk in &adt.mcnks[1..] {
+        assert!(WaterImporter::create_mesh(mh2o, mcnk)?.is_none());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn geoset_filtering() {
+    let mesh = Mesh {
+        vertex_buffers: VertexBuffers {
+            position_buffer: vec![Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z],
+            ..VertexBuffers::default()
+        },
+        index_buffer: vec![0, 1, 2, 1, 2, 3, 0, 2, 3],
+    };
+    let geosets = [
+        M2Geoset {
+            id: 0,
+            index_range: 0..3,
+        },
+        M2Geoset {
+            id: 101,
+            index_range: 3..6,
+        },
+        M2Geoset {
+            id: 102,
+            index_range: 6..9,
+        },
+    ];
+
+    let whitelist = HashSet::from([102]);
+    let filtered = M2Importer::filter_geosets(&mesh, &geosets, Some(&whitelist));
+    // Group 0 is always shown
+    assert_eq!(filtered.index_buffer, vec![0, 1, 2, 0, 2, 3]);
+    assert_eq!(filtered.vertex_buffers.position_buffer.len(), 4);
+
+    let unfiltered = M2Importer::filter_geosets(&mesh, &geosets, None);
+    assert_eq!(unfiltered.index_buffer, mesh.index_buffer);
+}
+
+#[test]
+fn synthetic_water_surface() -> Result<(), anyhow::Error> {
+    let mut data = synthetic_adt();
+    data.extend(chunk(b"MH2O", &synthetic_mh2o()));
+    let adt = ADTReader::parse_asset(&mut Cursor::new(data))?;
+    let mh2o = adt.mh2o.as_ref().expect("MH2O chunk");
+
+    let surface = WaterImporter::create_surface(mh2o, &adt.mcnks[0])?.expect("Water in the first chunk");
+    assert!(
+        surface
+            .cell_heights
+            .iter()
+            .all(|height| *height == Some(-5.0))
+    );
+
+    // Cells are in -x/-y of the chunk position, like the water mesh
+    assert_eq!(surface.height_at(Vec3A::new(-1.0, -1.0, 0.0)), Some(-5.0));
+    assert_eq!(surface.height_at(Vec3A::new(-33.0, -33.0, 0.0)), Some(-5.0));
+    assert_eq!(surface.height_at(Vec3A::new(1.0, -1.0, 0.0)), None);
+    assert_eq!(surface.height_at(Vec3A::new(-1.0, -34.0, 0.0)), None);
+
+    for mcnk in &adt.mcnks[1..] {
+        assert!(WaterImporter::create_surface(mh2o, mcnk)?.is_none());
+    }
+
+    Ok(())
+}
+
+/// A WMO group with a render face, a detail face and a collision-only face, all referenced by a single BSP leaf.
+fn synthetic_wmo_group() -> Vec<u8> {
+    let polys = [
+        (SMOPoly::F_RENDER, 0u8),
+        (SMOPoly::F_RENDER | SMOPoly::F_DETAIL, 0),
+        (SMOPoly::F_COLLISION, 0xFF),
+    ];
+    let mopy = polys
+        .iter()
+        .flat_map(|&(flags, material_id)| [flags, material_id])
+        .collect::<Vec<u8>>();
+    let movi = [0u16, 1, 2, 1, 2, 3, 2, 3, 4]
+        .iter()
+        .flat_map(|i| i.to_le_bytes())
+        .collect::<Vec<u8>>();
+    let movt = (0..5)
+        .flat_map(|i| [i as f32, (i % 2) as f32, 0.0])
+        .flat_map(f32::to_le_bytes)
+        .collect::<Vec<u8>>();
+
+    let mut mobn = Vec::new();
+    mobn.extend_from_slice(&0x4u16.to_le_bytes()); // leaf
+    mobn.extend_from_slice(&(-1i16).to_le_bytes());
+    mobn.extend_from_slice(&(-1i16).to_le_bytes());
+    mobn.extend_from_slice(&4u16.to_le_bytes());
+    mobn.extend_from_slice(&0u32.to_le_bytes());
+    mobn.extend_from_slice(&0f32.to_le_bytes());
+    // Face 0 is referenced twice, as if it were straddling a splitting plane.
+    let mobr = [0u16, 1, 2, 0]
+        .iter()
+        .flat_map(|i| i.to_le_bytes())
+        .collect::<Vec<u8>>();
+
+    let mut mogp = vec![0u8; 0x44];
+    mogp.extend(chunk(b"MOPY", &mopy));
+    mogp.extend(chunk(b"MOVI", &movi));
+    mogp.extend(chunk(b"MOVT", &movt));
+    mogp.extend(chunk(b"MONR", &[]));
+    mogp.extend(chunk(b"MOTV", &[]));
+    mogp.extend(chunk(b"MOBA", &[]));
+    mogp.extend(chunk(b"MOBN", &mobn));
+    mogp.extend(chunk(b"MOBR", &mobr));
+
+    let mut data = chunk(b"MVER", &17u32.to_le_bytes());
+    data.extend(chunk(b"MOGP", &mogp));
+    data
+}
+
+#[test]
+fn wmo_collision_mesh_from_bsp() -> Result<(), anyhow::Error> {
+    let mut group = WMOReader::parse_group(&mut Cursor::new(synthetic_wmo_group()))?;
+
+    let mesh = WMOGroupImporter::create_collision_mesh(&group).expect("Group has a BSP");
+    assert!(mesh.validate().is_ok());
+    assert_eq!(mesh.vertex_buffers.position_buffer.len(), 5);
+
+    // The detail face is skipped, the collision-only face is included and face 0 is only added once.
+    let mut expected = WMOGroupImporter::create_lodable_mesh_lod(&group, 0, 3);
+    expected.extend(WMOGroupImporter::create_lodable_mesh_lod(&group, 6, 3));
+    assert_eq!(mesh.index_buffer, expected);
+
+    group.mobn = None;
+    assert!(WMOGroupImporter::create_collision_mesh(&group).is_none());
+
+    Ok(())
+}
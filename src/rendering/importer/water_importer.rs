@@ -0,0 +1,140 @@
+use crate::rendering::asset_graph::nodes::adt_node::LiquidSurface;
+use crate::rendering::common::coordinate_systems;
+use crate::rendering::common::coordinate_systems::{GRID_SIZE, Winding};
+use crate::rendering::common::types::{Mesh, VertexBuffers};
+use anyhow::Error;
+use glam::Vec3;
+use sargerust_files::adt::types::{MCNKChunk, MH2OChunk};
+
+pub struct WaterImporter {}
+
+impl WaterImporter {
+    /// Builds the liquid surfaces (MH2O) of the given chunk, in the same space as the terrain mesh of
+    /// [`crate::rendering::importer::adt_importer::ADTImporter::create_mesh`], i.e. relative to the chunk position.
+    /// Returns `None` if the chunk has no liquids.
+    pub fn create_mesh(mh2o: &MH2OChunk, mcnk: &MCNKChunk) -> Result<Option<Mesh>, Error> {
+        let index = (mcnk.header.IndexY * 16 + mcnk.header.IndexX) as usize;
+        let Some(chunk) = mh2o.chunks.get(index) else {
+            return Ok(None);
+        };
+
+        let mut index_buffer = Vec::<u32>::new();
+        let mut position_buffer = Vec::new();
+
+        for instance in mh2o.instances(chunk)? {
+            let exists = mh2o.exists_mask(&instance)?;
+            let heights = mh2o.heights(&instance)?;
+            let columns = instance.width as usize + 1;
+            let first_vertex = position_buffer.len() as u32;
+
+            // Here we're in ADT Terrain space, that is +x -> north, +y -> west. Thus rows grow in -x, columns go to -y.
+            for row in 0..=instance.height as usize {
+                for column in 0..columns {
+                    let height = heights
+                        .as_ref()
+                        .map_or(instance.max_height_leve, |heights| {
+                            heights[row * columns + column]
+                        });
+
+                    position_buffer.push(Vec3::new(
+                        -GRID_SIZE * (instance.y_offset as usize + row) as f32,
+                        -GRID_SIZE * (instance.x_offset as usize + column) as f32,
+                        height - mcnk.header.position.z,
+                    ));
+                }
+            }
+
+            let vertex = |row: usize, column: usize| first_vertex + (row * columns + column) as u32;
+            for row in 0..instance.height as usize {
+                for column in 0..instance.width as usize {
+                    if !exists[row * instance.width as usize + column] {
+                        continue;
+                    }
+
+                    // same as the low resolution terrain
+                    index_buffer.push(vertex(row, column));
+                    index_buffer.push(vertex(row, column + 1));
+                    index_buffer.push(vertex(row + 1, column));
+
+                    index_buffer.push(vertex(row, column + 1));
+                    index_buffer.push(vertex(row + 1, column + 1));
+                    index_buffer.push(vertex(row + 1, column));
+                }
+            }
+        }
+
+        if index_buffer.is_empty() {
+            return Ok(None);
+        }
+
+        coordinate_systems::convert_winding(&mut index_buffer, Winding::Clockwise);
+
+        let mesh = Mesh {
+            vertex_buffers: VertexBuffers {
+                normals_buffer: vec![Vec3::Z; position_buffer.len()],
+                position_buffer,
+                ..VertexBuffers::default()
+            },
+            index_buffer,
+        };
+
+        #[cfg(debug_assertions)]
+        if let Err(err) = mesh.validate() {
+            log::warn!("Water mesh is invalid: {}", err);
+        }
+
+        Ok(Some(mesh))
+    }
+
+    /// The liquid heights per cell of the given chunk for gameplay queries, see [`LiquidSurface`]. Each cell gets the
+    /// highest of its corner heights, overlapping instances keep the higher surface. Returns `None` if the chunk has
+    /// no liquids.
+    pub fn create_surface(mh2o: &MH2OChunk, mcnk: &MCNKChunk) -> Result<Option<LiquidSurface>, Error> {
+        let index = (mcnk.header.IndexY * 16 + mcnk.header.IndexX) as usize;
+        let Some(chunk) = mh2o.chunks.get(index) else {
+            return Ok(None);
+        };
+
+        let mut cell_heights = [None; 64];
+        for instance in mh2o.instances(chunk)? {
+            let exists = mh2o.exists_mask(&instance)?;
+            let heights = mh2o.heights(&instance)?;
+            let columns = instance.width as usize + 1;
+            let height_at = |row: usize, column: usize| {
+                heights
+                    .as_ref()
+                    .map_or(instance.max_height_leve, |heights| {
+                        heights[row * columns + column]
+                    })
+            };
+
+            for row in 0..instance.height as usize {
+                for column in 0..instance.width as usize {
+                    if !exists[row * instance.width as usize + column] {
+                        continue;
+                    }
+
+                    let cell_row = instance.y_offset as usize + row;
+                    let cell_column = instance.x_offset as usize + column;
+                    if cell_row >= 8 || cell_column >= 8 {
+                        continue;
+                    }
+
+                    let height = height_at(row, column)
+                        .max(height_at(row, column + 1))
+                        .max(height_at(row + 1, column))
+                        .max(height_at(row + 1, column + 1))
+                        - mcnk.header.position.z;
+
+                    let cell = &mut cell_heights[cell_row * 8 + cell_column];
+                    *cell = Some(cell.map_or(height, |existing: f32| existing.max(height)));
+                }
+            }
+        }
+
+        Ok(cell_heights
+            .iter()
+            .any(Option::is_some)
+            .then_some(LiquidSurface { cell_heights }))
+    }
+}
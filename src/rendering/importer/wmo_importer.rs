@@ -1,4 +1,5 @@
 use std::sync::RwLock;
+use std::sync::atomic::AtomicBool;
 
 use glam::{Vec2, Vec3, Vec4};
 use itertools::Itertools;
@@ -7,10 +8,12 @@ use log::trace;
 use sargerust_files::wmo::reader::WMOReader;
 use sargerust_files::wmo::types::{WMOGroupAsset, WMORootAsset};
 
-use crate::io::common::loader::RawAssetLoader;
 use crate::io::mpq::loader::MPQLoader;
 use crate::rendering::asset_graph::nodes::adt_node::WMOGroupNode;
-use crate::rendering::common::types::{AlbedoType, Material, Mesh, MeshWithLod, TransparencyType, VertexBuffers};
+use crate::rendering::common::mesh_merger::MeshMerger;
+use crate::rendering::common::types::{
+    AlbedoType, BoundingBox, Material, Mesh, MeshWithLod, TransparencyType, VertexBuffers,
+};
 
 pub struct WMOGroupImporter {}
 
@@ -51,12 +54,61 @@ impl WMOGroupImporter {
     }
 
     pub fn create_lodable_mesh_lod(asset: &WMOGroupAsset, start_index: usize, index_count: usize) -> Vec<u32> {
+        // WMO triangles are already wound like the IR (see coordinate_systems::IR_WINDING).
         asset.movi.indices[start_index..start_index + index_count]
             .iter()
             .map(|&i| i as u32)
             .collect_vec()
     }
 
+    /// Builds the collision geometry from the faces that are referenced by the leaves of the group's BSP tree and
+    /// flagged as collidable. In contrast to the render batches, this excludes detail faces and includes the invisible
+    /// collision-only faces. Returns None when the group has no BSP, so callers can fall back to the render mesh.
+    pub fn create_collision_mesh(asset: &WMOGroupAsset) -> Option<Mesh> {
+        let (Some(mobn), Some(mobr)) = (&asset.mobn, &asset.mobr) else {
+            return None;
+        };
+
+        // Faces that straddle a splitting plane are referenced by multiple leaves.
+        let mut face_used = vec![false; asset.mopy.polyList.len()];
+        for node in mobn.nodeList.iter().filter(|node| node.is_leaf()) {
+            let start = node.faceStart as usize;
+            let faces = mobr
+                .nodeFaceIndices
+                .get(start..start + node.nFaces as usize)
+                .unwrap_or_default();
+            for &face in faces {
+                if let Some(used) = face_used.get_mut(face as usize) {
+                    *used = true;
+                }
+            }
+        }
+
+        let index_buffer = face_used
+            .iter()
+            .zip(&asset.mopy.polyList)
+            .enumerate()
+            .filter(|(_, (&used, poly))| used && poly.is_collidable())
+            .flat_map(|(face, _)| &asset.movi.indices[face * 3..face * 3 + 3])
+            .map(|&i| i as u32)
+            .collect_vec();
+
+        let position_buffer = asset
+            .movt
+            .vertexList
+            .iter()
+            .map(|v| Vec3::new(v.x, v.y, v.z))
+            .collect();
+
+        Some(Mesh {
+            vertex_buffers: VertexBuffers {
+                position_buffer,
+                ..Default::default()
+            },
+            index_buffer,
+        })
+    }
+
     // MPQLoader: we dynamically load the WMO Groups based upon WMORootAsset. Could change that but this yields error potential.
     // TODO: Still do it, to separate loading/parsing from importing (which is asset -> IR)
     pub fn load_wmo_groups(loader: &MPQLoader, wmo: &WMORootAsset, path: &str) -> Vec<(MeshWithLod, Vec<Material>)> {
@@ -68,13 +120,14 @@ impl WMOGroupImporter {
             }
         }
 
+        let group_paths = (0..wmo.mohd.nGroups)
+            .map(|x| format!("{}_{:0>3}.wmo", path, x))
+            .collect_vec();
+        let group_paths = group_paths.iter().map(String::as_str).collect_vec();
+
         let mut group_list = Vec::new();
-        for x in 0..wmo.mohd.nGroups {
-            let cursor = &mut std::io::Cursor::new(
-                loader
-                    .load_raw_owned(&format!("{}_{:0>3}.wmo", path, x))
-                    .unwrap(),
-            );
+        for buf in loader.load_many(&group_paths) {
+            let cursor = &mut std::io::Cursor::new(buf.unwrap());
             group_list.push(WMOReader::parse_group(cursor).unwrap());
         }
 
@@ -139,35 +192,39 @@ impl WMOGroupImporter {
             .collect_vec()
     }
 
-    pub fn load_wmo_group(loader: &MPQLoader, path: &str) -> WMOGroupNode {
+    pub fn load_wmo_group(loader: &MPQLoader, path: &str) -> Result<WMOGroupNode, anyhow::Error> {
         // This duplicates the above, sadly.
-        let group = WMOReader::parse_group(&mut std::io::Cursor::new(
-            loader.load_raw_owned(path).unwrap(),
-        ))
-        .unwrap();
+        let group = loader.load_parsed(path, |buf| {
+            WMOReader::parse_group(&mut std::io::Cursor::new(buf))
+        })?;
 
         // TODO: Currently we can't slice down the vertex buffer properly anyway. But at some point MeshhWithLod should also work with the asset graph
         let mesh_base = WMOGroupImporter::create_lodable_mesh_base(&group);
-        let mut material_ids = Vec::new();
-        let mut mesh_batches = Vec::new();
 
-        for batch in &group.moba.batchList {
+        // Batches only differ in their range of the index buffer, so all batches with the same material can be drawn
+        // as one object. Large WMOs (e.g. cities) otherwise end up with thousands of objects.
+        let batches = group.moba.batchList.iter().map(|batch| {
             let index =
                 WMOGroupImporter::create_lodable_mesh_lod(&group, batch.startIndex as usize, batch.count as usize);
-            material_ids.push(batch.material_id); // 0xFF is no material.
+            (batch.material_id, index) // 0xFF is no material.
+        });
 
-            mesh_batches.push(RwLock::new(
-                Mesh {
-                    vertex_buffers: mesh_base.clone(),
-                    index_buffer: index,
-                }
-                .into(),
-            ));
-        }
+        let (material_ids, mesh_batches) = MeshMerger::merge_indices_by_key(&mesh_base, batches)
+            .into_iter()
+            .map(|(material_id, mesh)| (material_id, RwLock::new(mesh.into())))
+            .unzip();
 
-        WMOGroupNode {
+        let bounds = &group.mogp.boundingBox;
+        let collision_mesh = WMOGroupImporter::create_collision_mesh(&group);
+        Ok(WMOGroupNode {
             mesh_batches,
             material_ids,
-        }
+            collider_pending: AtomicBool::new(collision_mesh.is_none()),
+            collision_mesh,
+            bounding_box: BoundingBox {
+                min: Vec3::new(bounds.min.x, bounds.min.y, bounds.min.z),
+                max: Vec3::new(bounds.max.x, bounds.max.y, bounds.max.z),
+            },
+        })
     }
 }
@@ -1,30 +1,132 @@
 use crate::io::common::loader::RawAssetLoader;
 use crate::io::mpq::loader::MPQLoader;
+use anyhow::anyhow;
+use image::RgbaImage;
 use image_blp::BlpImage;
+use image_blp::convert::blp_to_image;
 use image_blp::parser::parse_blp_with_externals;
+use image_blp::types::BlpContent;
 use log::{error, warn};
+use quick_cache::Weighter;
+use quick_cache::sync::Cache;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The decoded size (in bytes) of the parsed BLPs that are kept in memory. The raw files are cached by the
+/// [`MPQLoader`] as well, but parsing (and decompressing) them again is what makes loading the same texture for many
+/// doodads or units slow.
+const BLP_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+/// Only used to size the cache up front, the images are bounded by [`BLP_CACHE_BYTES`].
+const BLP_CACHE_ESTIMATED_IMAGES: usize = 512;
+
+/// Weighs the cached images by the size of their mip map chain when decoded to RGBA8, which is an upper bound of
+/// their actual size.
+#[derive(Clone)]
+struct DecodedSizeWeighter;
+
+impl Weighter<String, Arc<BlpImage>> for DecodedSizeWeighter {
+    fn weight(&self, _key: &String, val: &Arc<BlpImage>) -> u64 {
+        (0..val.image_count())
+            .map(|level| {
+                let (width, height) = val.header.mipmap_size(level);
+                width as u64 * height as u64 * 4
+            })
+            .sum()
+    }
+}
+
+/// Parsed BLPs shared by all callers of [`BLPLoader::load_blp_from_ldr`] with the same [`MPQLoader`], keyed by the
+/// normalized path.
+pub struct BlpCache {
+    images: Cache<String, Arc<BlpImage>, DecodedSizeWeighter>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl Default for BlpCache {
+    fn default() -> Self {
+        Self {
+            images: Cache::with_weighter(
+                BLP_CACHE_ESTIMATED_IMAGES,
+                BLP_CACHE_BYTES,
+                DecodedSizeWeighter,
+            ),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl BlpCache {
+    /// The hits and misses of [`BLPLoader::load_blp_from_ldr`] since the start, to debug load times.
+    pub fn stats(&self) -> BlpCacheStats {
+        BlpCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.images.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlpCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
 
 pub struct BLPLoader {}
 
 impl BLPLoader {
-    pub fn load_blp_from_ldr(mpq_loader: &MPQLoader, file_name: &str) -> Option<BlpImage> {
-        // TODO: The blp crate has bad error handling, as it doesn't mix with anyhow::Error.
-        // furthermore, the built in error types stem from nom, that we don't have as dependency.
+    /// The dimensions are stored as u16, which limits the mip map chain to 16 levels.
+    const MAX_MIPMAP_LEVELS: usize = 16;
 
-        // load_blp uses the fs to load mip maps next to it.
-        // we don't want to extract blps into temporary files, though, so we use the other API
-        // and there, we either don't support BLP0 Mipmaps or we properly implement the callback at some time
+    /// Loads the BLP through the cache of the loader (see [`MPQLoader::blp_cache`]), only failed loads are attempted
+    /// again.
+    pub fn load_blp_from_ldr(mpq_loader: &MPQLoader, file_name: &str) -> Option<Arc<BlpImage>> {
+        let cache = mpq_loader.blp_cache();
+        let key = MPQLoader::normalize_path(file_name);
+        if let Some(image) = cache.images.get(&key) {
+            cache.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(image);
+        }
 
-        let owned_file = mpq_loader.load_raw_owned(file_name);
+        cache.misses.fetch_add(1, Ordering::Relaxed);
+        let image = Arc::new(Self::load_blp_uncached(mpq_loader, file_name)?);
+        cache.images.insert(key, image.clone());
+        Some(image)
+    }
+
+    fn load_blp_uncached(mpq_loader: &MPQLoader, file_name: &str) -> Option<BlpImage> {
+        let owned_file = mpq_loader.load_raw_shared(file_name);
         if owned_file.is_none() {
             warn!("Could not load BLP {}", file_name);
             return None;
         }
 
-        let root_input = owned_file.unwrap();
-        let image = parse_blp_with_externals(&root_input, |_i| {
-            // This could also be no_mipmaps from the image-blp parser crate.
-            panic!("Loading of BLP Mip Maps is unsupported. File {}", file_name)
+        Self::parse_blp(file_name, &owned_file.unwrap(), |path| {
+            mpq_loader.load_raw_owned(path)
+        })
+    }
+
+    /// Parses a BLP, whose external mip maps (BLP0 stores them next to the image, as `foo.b00`, `foo.b01`, ...) are
+    /// loaded by `load_sibling`, so that they don't need to be extracted to the file system. Every other version stores
+    /// its mip maps internally and doesn't load anything.
+    pub fn parse_blp(
+        file_name: &str,
+        root_input: &[u8],
+        load_sibling: impl FnMut(&str) -> Option<Vec<u8>>,
+    ) -> Option<BlpImage> {
+        // TODO: The blp crate has bad error handling, as it doesn't mix with anyhow::Error.
+        // furthermore, the built in error types stem from nom, that we don't have as dependency.
+        let external_mipmaps = if root_input.starts_with(b"BLP0") {
+            Self::load_external_mipmaps(file_name, load_sibling)
+        } else {
+            vec![]
+        };
+
+        let image = parse_blp_with_externals(root_input, |i| {
+            Ok(external_mipmaps.get(i).map(Vec::as_slice))
         });
 
         if image.is_err() {
@@ -36,4 +138,88 @@ impl BLPLoader {
         }
         Some(image.unwrap().1)
     }
+
+    /// Loads the mip map chain up until the first missing level.
+    fn load_external_mipmaps(file_name: &str, mut load_sibling: impl FnMut(&str) -> Option<Vec<u8>>) -> Vec<Vec<u8>> {
+        let stem = file_name
+            .rsplit_once('.')
+            .map_or(file_name, |(stem, _)| stem);
+
+        (0..Self::MAX_MIPMAP_LEVELS)
+            .map_while(|level| load_sibling(&format!("{}.b{:02}", stem, level)))
+            .collect()
+    }
+
+    /// Decodes the given mip map level into RGBA8. Palettized (RAW1) images are expanded by [`expand_palette`] with
+    /// their actual alpha depth, everything else is decoded by image-blp.
+    pub fn blp_to_rgba8(blp: &BlpImage, mipmap_level: usize) -> Result<RgbaImage, anyhow::Error> {
+        let BlpContent::Raw1(raw1) = &blp.content else {
+            return blp_to_image(blp, mipmap_level)
+                .map(|image| image.into_rgba8())
+                .map_err(|err| anyhow!("Decoding the BLP failed: {}", err));
+        };
+
+        let image = raw1
+            .images
+            .get(mipmap_level)
+            .ok_or_else(|| anyhow!("Missing mip map level {}", mipmap_level))?;
+        let (width, height) = blp.header.mipmap_size(mipmap_level);
+
+        let pixels = expand_palette(
+            &raw1.cmap,
+            &image.indexed_rgb,
+            &image.indexed_alpha,
+            blp.header.alpha_bits(),
+            (width * height) as usize,
+        )?;
+
+        RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow!("Pixel count doesn't match the size"))
+    }
+}
+
+/// Expands a palettized image into RGBA8. The palette entries are stored as BGRA, but their alpha is unused, instead
+/// there is a separate alpha channel with 0, 1, 4 or 8 bits per pixel, where the lower bits belong to the earlier
+/// pixels.
+pub fn expand_palette(
+    palette: &[u32],
+    indices: &[u8],
+    alpha: &[u8],
+    alpha_bits: u32,
+    pixel_count: usize,
+) -> Result<Vec<u8>, anyhow::Error> {
+    if indices.len() < pixel_count {
+        return Err(anyhow!(
+            "Expected {} palette indices, got {}",
+            pixel_count,
+            indices.len()
+        ));
+    }
+
+    if alpha.len() < (pixel_count * alpha_bits as usize).div_ceil(8) {
+        return Err(anyhow!(
+            "Not enough alpha data for {} pixels with {} bits",
+            pixel_count,
+            alpha_bits
+        ));
+    }
+
+    let mut pixels = Vec::with_capacity(pixel_count * 4);
+    for (i, index) in indices[..pixel_count].iter().enumerate() {
+        let color = *palette
+            .get(*index as usize)
+            .ok_or_else(|| anyhow!("Palette index {} out of bounds", index))?;
+
+        let a = match alpha_bits {
+            0 => 255,
+            1 => ((alpha[i / 8] >> (i % 8)) & 0x1) * 255,
+            4 => ((alpha[i / 2] >> ((i % 2) * 4)) & 0xF) * 17,
+            8 => alpha[i],
+            _ => return Err(anyhow!("Unsupported alpha depth {}", alpha_bits)),
+        };
+
+        let [b, g, r, _] = color.to_le_bytes();
+        pixels.extend_from_slice(&[r, g, b, a]);
+    }
+
+    Ok(pixels)
 }
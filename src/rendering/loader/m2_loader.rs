@@ -1,11 +1,15 @@
 use std::sync::Arc;
 
+use crate::io;
 use crate::io::common::loader::RawAssetLoader;
 use crate::io::mpq::loader::MPQLoader;
 use crate::rendering::asset_graph::nodes::adt_node::IRTextureReference;
-use crate::rendering::common::types::{Material, Mesh};
-use crate::rendering::importer::m2_importer::M2Importer;
+use crate::rendering::common::texture_animation::TextureAnimation;
+use crate::rendering::common::types::{BoundingBox, Material, Mesh, MeshWithLod};
+use crate::rendering::importer::m2_importer::{M2Geoset, M2Importer};
 use crate::rendering::loader::blp_loader::BLPLoader;
+use anyhow::{Context, anyhow};
+use glam::Affine3A;
 use image_blp::BlpImage;
 use log::warn;
 use sargerust_files::m2::reader::M2Reader;
@@ -17,7 +21,20 @@ pub struct LoadedM2 {
     pub material: Material,
 
     // TODO: The Material will probably contain texture reference, but at least texture paths, so they can be loaded independently.
-    pub blp_opt: Option<BlpImage>,
+    pub blp_opt: Option<Arc<BlpImage>>,
+    pub is_emitter_only: bool,
+    /// All LoD levels (one index buffer per skin profile) in addition to `mesh`, see [`M2Loader::load_with_lods`]
+    pub lods: Option<MeshWithLod>,
+    /// In model space, see [`LoadedM2::bounds`]
+    pub bounding_box: BoundingBox,
+}
+
+impl LoadedM2 {
+    /// The world space bounds of an instance of this model that is placed with the given transform.
+    #[allow(unused)]
+    pub fn bounds(&self, transform: &Affine3A) -> BoundingBox {
+        self.bounding_box.transformed(transform)
+    }
 }
 
 #[derive(Debug)]
@@ -26,55 +43,105 @@ pub struct LoadedM2Graph {
     pub material: Material,
     pub textures: Vec<Arc<IRTextureReference>>,
     pub dynamic_textures: Vec<M2Texture>, // TODO: This can't be a reference sadly.
+    pub is_emitter_only: bool,
+    pub bounding_box: BoundingBox,
+    pub geosets: Vec<M2Geoset>,
+    pub texture_animation: Option<TextureAnimation>,
 }
 
 pub struct M2Loader {}
 
 impl M2Loader {
-    #[deprecated]
-    pub fn load_no_lod(loader: &MPQLoader, name: &str) -> LoadedM2 {
+    /// The client only ever ships up to four skin profiles (`*00.skin`..`*03.skin`) per model
+    pub const MAX_LOD_LEVELS: u32 = 4;
+
+    /// The skin profile of the given LoD level, e.g. `creature\\talbuk\\talbuk01.skin` for level 1 of
+    /// `creature\\talbuk\\talbuk.m2`.
+    pub fn skin_path(model_path: &str, level: u32) -> String {
+        let model_path = io::normalize_model_path(model_path);
+        let stem = model_path.strip_suffix(".m2").unwrap_or(&model_path);
+        format!("{}{:02}.skin", stem, level)
+    }
+
+    /// Loads the model with all of its LoD levels, i.e. one index buffer per skin profile. Models with fewer skin
+    /// profiles only have as many levels, so selecting a level has to clamp (see [`MeshWithLod::lod_for_distance`]).
+    /// Only the demos use this, the asset graph (see [`M2Loader::load_no_lod_for_graph`]) still renders level 0 at any
+    /// distance.
+    pub fn load_with_lods(loader: &MPQLoader, name: &str) -> Result<LoadedM2, anyhow::Error> {
+        let name = &io::normalize_model_path(name);
         let m2_asset = M2Reader::parse_asset(&mut std::io::Cursor::new(
-            loader.load_raw_owned(name).unwrap(),
-        ))
-        .unwrap();
-        // In theory, we could investigate the number of LoD Levels, but we will just use "0"
-        let mut skin_file = std::io::Cursor::new(
             loader
-                .load_raw_owned(&name.replace(".m2", "00.skin"))
-                .unwrap(),
-        );
-        let skin = M2Reader::parse_skin_profile(&mut skin_file).unwrap();
+                .load_raw_shared(name)
+                .ok_or_else(|| anyhow!("Cannot load {}", name))?,
+        ))?;
+
+        let mut skins = Vec::new();
+        for level in 0..m2_asset.num_skin_profiles.clamp(1, Self::MAX_LOD_LEVELS) {
+            let skin_name = Self::skin_path(name, level);
+            let Some(skin_buf) = loader.load_raw_shared(&skin_name) else {
+                if level == 0 {
+                    return Err(anyhow!("Cannot load {}", skin_name));
+                }
+
+                warn!(
+                    "Missing LoD level {}, using {} levels for {}",
+                    skin_name, level, name
+                );
+                break;
+            };
+
+            let skin = M2Reader::parse_skin_profile(&mut std::io::Cursor::new(skin_buf))?;
+            skin.validate(&m2_asset)
+                .with_context(|| format!("Importing {}", skin_name))?;
+            skins.push(skin);
+        }
 
         let mut blp_opt = None;
         if !m2_asset.textures.is_empty() {
             blp_opt = BLPLoader::load_blp_from_ldr(loader, &m2_asset.textures[0].filename);
         }
 
-        let mesh = M2Importer::create_mesh(&m2_asset, &skin);
+        let mesh = M2Importer::create_mesh(&m2_asset, &skins[0]).with_context(|| format!("Importing {}", name))?;
+        let lods = MeshWithLod {
+            vertex_buffers: M2Importer::create_lodable_mesh_base(&m2_asset),
+            index_buffers: skins
+                .iter()
+                .map(M2Importer::create_lodable_mesh_lod)
+                .collect(),
+        };
         let material = M2Importer::create_material(&blp_opt); // TODO: the texture should be intrinsic to the material.
 
-        LoadedM2 {
+        Ok(LoadedM2 {
             mesh,
             material,
             blp_opt,
-        }
+            is_emitter_only: m2_asset.is_emitter_only(),
+            lods: Some(lods),
+            bounding_box: M2Importer::create_bounding_box(&m2_asset),
+        })
     }
 
     // TODO: this could immediately return a M2Node as all that it additionally does is some .into()
-    pub fn load_no_lod_for_graph(loader: &MPQLoader, name: &str) -> LoadedM2Graph {
-        let m2_asset = M2Reader::parse_asset(&mut std::io::Cursor::new(
-            loader.load_raw_owned(name).unwrap(),
-        ))
-        .unwrap();
-        // In theory, we could investigate the number of LoD Levels, but we will just use "0"
+    pub fn load_no_lod_for_graph(loader: &MPQLoader, name: &str) -> Result<LoadedM2Graph, anyhow::Error> {
+        let name = &io::normalize_model_path(name);
+        let m2_asset = loader.load_parsed(name, |buf| {
+            M2Reader::parse_asset(&mut std::io::Cursor::new(buf))
+        })?;
+        // In theory, we could investigate the number of LoD Levels, but we will just use "0". The geosets, the
+        // hollowing and the colliders all rely on that single skin.
+        let skin_name = Self::skin_path(name, 0);
         let mut skin_file = std::io::Cursor::new(
             loader
-                .load_raw_owned(&name.replace(".m2", "00.skin"))
-                .unwrap(),
+                .load_raw_shared(&skin_name)
+                .ok_or_else(|| anyhow!("Cannot load {}", skin_name))?,
         );
 
-        let skin = M2Reader::parse_skin_profile(&mut skin_file).unwrap();
-        let mesh = M2Importer::create_mesh(&m2_asset, &skin);
+        let skin = M2Reader::parse_skin_profile(&mut skin_file)?;
+        let mesh = M2Importer::create_mesh(&m2_asset, &skin).with_context(|| format!("Importing {}", name))?;
+        let is_emitter_only = m2_asset.is_emitter_only();
+        let bounding_box = M2Importer::create_bounding_box(&m2_asset);
+        let geosets = M2Importer::create_geosets(&skin);
+        let texture_animation = M2Importer::create_texture_animation(&m2_asset);
 
         let textures: Vec<Arc<IRTextureReference>> = m2_asset
             .textures
@@ -99,11 +166,15 @@ impl M2Loader {
 
         let material = M2Importer::create_material_texname(&textures.first().map(|tex| tex.reference_str.clone()));
 
-        LoadedM2Graph {
+        Ok(LoadedM2Graph {
             mesh,
             material,
             textures,
             dynamic_textures,
-        }
+            is_emitter_only,
+            bounding_box,
+            geosets,
+            texture_animation,
+        })
     }
 }
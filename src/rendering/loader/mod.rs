@@ -3,3 +3,6 @@
 pub mod blp_loader;
 pub mod m2_loader;
 pub mod wmo_loader;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,41 @@
+use crate::rendering::loader::blp_loader::expand_palette;
+
+// BGRA
+const PALETTE: [u32; 3] = [0xFF0000FF, 0x0000FF00, 0x80FF0000];
+
+#[test]
+fn expand_palette_without_alpha() -> Result<(), anyhow::Error> {
+    let pixels = expand_palette(&PALETTE, &[0, 1, 2], &[], 0, 3)?;
+    // The palette alpha is ignored
+    assert_eq!(pixels, [0, 0, 255, 255, 0, 255, 0, 255, 255, 0, 0, 255]);
+    Ok(())
+}
+
+#[test]
+fn expand_palette_alpha_bits() -> Result<(), anyhow::Error> {
+    let indices = [0u8; 10];
+    let alpha_of = |pixels: Vec<u8>| {
+        pixels
+            .chunks_exact(4)
+            .map(|pixel| pixel[3])
+            .collect::<Vec<u8>>()
+    };
+
+    let one_bit = expand_palette(&PALETTE, &indices, &[0b1010_0101, 0b10], 1, 10)?;
+    assert_eq!(alpha_of(one_bit), [255, 0, 255, 0, 0, 255, 0, 255, 0, 255]);
+
+    let four_bits = expand_palette(&PALETTE, &indices[..4], &[0xF0, 0x18], 4, 4)?;
+    assert_eq!(alpha_of(four_bits), [0, 255, 136, 17]);
+
+    let eight_bits = expand_palette(&PALETTE, &indices[..2], &[12, 200], 8, 2)?;
+    assert_eq!(alpha_of(eight_bits), [12, 200]);
+    Ok(())
+}
+
+#[test]
+fn expand_palette_errors() {
+    assert!(expand_palette(&PALETTE, &[3], &[], 0, 1).is_err());
+    assert!(expand_palette(&PALETTE, &[0, 0], &[], 0, 3).is_err());
+    assert!(expand_palette(&PALETTE, &[0; 9], &[0xFF], 1, 9).is_err());
+    assert!(expand_palette(&PALETTE, &[0], &[0xFF], 2, 1).is_err());
+}
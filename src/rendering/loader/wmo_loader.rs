@@ -1,30 +1,38 @@
+use crate::io;
 use crate::io::common::loader::RawAssetLoader;
 use crate::io::mpq::loader::MPQLoader;
 use crate::rendering::asset_graph::nodes::adt_node::{
-    DoodadReference, IRTextureReference, NodeReference, WMOGroupNode, WMONode,
+    DoodadReference, IRTextureReference, NodeReference, WMOGroupInfo, WMOGroupNode, WMONode,
 };
 use crate::rendering::common::highlevel_types::{PlaceableDoodad, PlaceableWMO};
-use crate::rendering::common::types::{AlbedoType, Material, TransparencyType};
+use crate::rendering::common::types::{AlbedoType, BoundingBox, Material, TransparencyType};
 use crate::rendering::importer::wmo_importer::WMOGroupImporter;
 use glam::{Affine3A, Quat, Vec3, Vec4};
 use log::debug;
 use sargerust_files::wmo::reader::WMOReader;
-use sargerust_files::wmo::types::WMORootAsset;
+use sargerust_files::wmo::types::{SMODoodadSet, WMORootAsset};
 use std::sync::{Arc, RwLock};
 
 pub struct WMOLoader {}
 
 impl WMOLoader {
+    /// Loads the WMO with the global doodad set only, see [`WMOLoader::load_with_set`]
     pub fn load(loader: &MPQLoader, wmo_path: &str) -> Result<PlaceableWMO, anyhow::Error> {
+        Self::load_with_set(loader, wmo_path, 0)
+    }
+
+    /// Loads the WMO with the doodads of the global set (0) and the given set, which is typically the one that the
+    /// placement requests (see [`sargerust_files::wdt::types::SMMapObjDef::doodadSet`]).
+    pub fn load_with_set(loader: &MPQLoader, wmo_path: &str, set_index: u16) -> Result<PlaceableWMO, anyhow::Error> {
         // TODO: thiserror
         let wmo: WMORootAsset = WMOReader::parse_root(&mut std::io::Cursor::new(
-            loader.load_raw_owned(wmo_path).unwrap(),
+            loader.load_raw_shared(wmo_path).unwrap(),
         ))?;
-        let doodads = WMOLoader::collect_dooads_for_wmo_root(&wmo);
+        let doodads = WMOLoader::collect_doodads_for_set(&wmo, set_index);
         let group_list = WMOGroupImporter::load_wmo_groups(
             loader,
             &wmo,
-            wmo_path.to_uppercase().trim_end_matches(".WMO"),
+            MPQLoader::normalize_path(wmo_path).trim_end_matches(".WMO"),
         );
 
         Ok(PlaceableWMO {
@@ -35,18 +43,21 @@ impl WMOLoader {
 
     pub fn load_graph(loader: &MPQLoader, wmo_path: &str) -> Result<WMONode, anyhow::Error> {
         // TODO: thiserror
-        let wmo: WMORootAsset = WMOReader::parse_root(&mut std::io::Cursor::new(
-            loader.load_raw_owned(wmo_path).unwrap(),
-        ))?;
-
-        // TODO: doodad sets?
-        let mut doodads = Vec::new();
-        for dad in WMOLoader::collect_dooads_for_wmo_root(&wmo) {
-            doodads.push(Arc::new(DoodadReference::new(
-                dad.transform.into(),
-                dad.m2_ref,
-            )));
-        }
+        let wmo: WMORootAsset = loader.load_parsed(wmo_path, |buf| {
+            WMOReader::parse_root(&mut std::io::Cursor::new(buf))
+        })?;
+
+        // Each placement picks its doodad sets, see WMONode::active_doodad_sets
+        let doodad_sets = wmo
+            .mods
+            .doodadSetList
+            .iter()
+            .map(|set| {
+                WMOLoader::doodads_in_set(&wmo, set)
+                    .map(|dad| Arc::new(DoodadReference::new(dad.transform.into(), dad.m2_ref)))
+                    .collect()
+            })
+            .collect();
 
         let mut subgroups = Vec::with_capacity(wmo.mohd.nGroups as usize);
         let mut materials = Vec::with_capacity(wmo.momt.materialList.len());
@@ -87,7 +98,7 @@ impl WMOLoader {
             }
         }
 
-        let path_upper = wmo_path.to_uppercase();
+        let path_upper = MPQLoader::normalize_path(wmo_path);
         let path = path_upper.trim_end_matches(".WMO");
 
         for x in 0..wmo.mohd.nGroups {
@@ -97,49 +108,76 @@ impl WMOLoader {
             }));
         }
 
+        let group_infos = wmo
+            .mogi
+            .groupInfoList
+            .iter()
+            .map(|info| {
+                let bounds = &info.bounding_box;
+                let bounding_box = BoundingBox {
+                    min: Vec3::new(bounds.min.x, bounds.min.y, bounds.min.z),
+                    max: Vec3::new(bounds.max.x, bounds.max.y, bounds.max.z),
+                };
+                WMOGroupInfo::new(bounding_box, info.flags)
+            })
+            .collect();
+
         Ok(WMONode {
-            doodads,
+            doodad_sets,
             subgroups,
+            group_infos,
             materials,
             tex_references,
         })
     }
 
-    /// Extracts the doodads (i.e. M2 models that have been placed into the world at a specific position) that are defined in the WMO Root
+    /// Extracts the doodads (i.e. M2 models that have been placed into the world at a specific position) of all
+    /// doodad sets that are defined in the WMO Root, e.g. to find all dependencies.
     pub fn collect_dooads_for_wmo_root(wmo: &WMORootAsset) -> Vec<PlaceableDoodad> {
-        let mut render_list = Vec::new();
-        for mods in &wmo.mods.doodadSetList {
-            let start = mods.startIndex as usize;
-            let end = (mods.startIndex + mods.count) as usize;
-            debug!("Doodad Set: {} from {} to {}", mods.name, start, end);
-            // TODO: at some point we need logic to selectively filter dooddad sets.
-            for modd in &wmo.modd.doodadDefList[start..end] {
-                let idx = wmo.modn.doodadNameListLookup[&modd.nameIndex];
-                let name = wmo.modn.doodadNameList[idx].as_str();
-
-                // fix name: currently it ends with .mdx, but we need .m2
-                let name = name.replace(".MDX", ".m2").replace(".MDL", ".m2");
-                if name.to_lowercase().contains("emitter") {
-                    continue;
-                }
+        wmo.mods
+            .doodadSetList
+            .iter()
+            .flat_map(|set| WMOLoader::doodads_in_set(wmo, set))
+            .collect()
+    }
 
-                let scale = Vec3::new(modd.scale, modd.scale, modd.scale);
-                let rotation = Quat::from_xyzw(
-                    modd.orientation.x,
-                    modd.orientation.y,
-                    modd.orientation.z,
-                    modd.orientation.w,
-                );
-                let translation = Vec3::new(modd.position.x, modd.position.y, modd.position.z);
-
-                let transform: Affine3A = Affine3A::from_scale_rotation_translation(scale, rotation, translation);
-                render_list.push(PlaceableDoodad {
-                    transform,
-                    m2_ref: name,
-                });
-            }
-        }
+    /// Like [`WMOLoader::collect_dooads_for_wmo_root`], but only the doodads of the global set (0) and the given set.
+    /// Sets are alternatives (e.g. the furniture of an open and a closed inn), so rendering all of them clutters the
+    /// interior.
+    pub fn collect_doodads_for_set(wmo: &WMORootAsset, set_index: u16) -> Vec<PlaceableDoodad> {
+        wmo.mods
+            .doodadSetList
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx == 0 || *idx == set_index as usize)
+            .flat_map(|(_, set)| WMOLoader::doodads_in_set(wmo, set))
+            .collect()
+    }
 
-        render_list
+    /// The doodads within `[startIndex, startIndex + count)` of the set
+    fn doodads_in_set<'a>(wmo: &'a WMORootAsset, set: &SMODoodadSet) -> impl Iterator<Item = PlaceableDoodad> + 'a {
+        let start = set.startIndex as usize;
+        let end = (set.startIndex + set.count) as usize;
+        debug!("Doodad Set: {} from {} to {}", set.name, start, end);
+
+        wmo.modd.doodadDefList[start..end].iter().map(|modd| {
+            let idx = wmo.modn.doodadNameListLookup[&modd.nameIndex];
+            let name = io::normalize_model_path(&wmo.modn.doodadNameList[idx]);
+
+            let scale = Vec3::new(modd.scale, modd.scale, modd.scale);
+            let rotation = Quat::from_xyzw(
+                modd.orientation.x,
+                modd.orientation.y,
+                modd.orientation.z,
+                modd.orientation.w,
+            );
+            let translation = Vec3::new(modd.position.x, modd.position.y, modd.position.z);
+
+            let transform: Affine3A = Affine3A::from_scale_rotation_translation(scale, rotation, translation);
+            PlaceableDoodad {
+                transform,
+                m2_ref: name,
+            }
+        })
     }
 }
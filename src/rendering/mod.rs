@@ -4,15 +4,16 @@ use std::sync::Arc;
 
 use glam::{Affine3A, Vec3, Vec3A};
 use image_blp::BlpImage;
-use image_blp::convert::blp_to_image;
+use log::warn;
 use rend3::Renderer;
-use rend3::types::{MaterialHandle, MeshHandle, Object, ObjectHandle};
+use rend3::types::{MaterialHandle, MeshHandle, Object, ObjectHandle, Texture2DHandle};
 
 use crate::rendering::common::coordinate_systems;
 use crate::rendering::common::highlevel_types::PlacedDoodad;
 use crate::rendering::common::special_types::TerrainTextureLayer;
 use crate::rendering::common::types::{AlbedoType, Material, Mesh, MeshWithLod, TransparencyType};
-use crate::rendering::rend3_backend::Rend3BackendConverter;
+use crate::rendering::loader::blp_loader::BLPLoader;
+use crate::rendering::rend3_backend::{BackendError, Rend3BackendConverter};
 
 pub mod application;
 pub mod asset_graph;
@@ -21,19 +22,20 @@ pub mod importer;
 pub mod loader;
 pub mod rend3_backend;
 
-fn create_texture_rgba8(blp: &BlpImage, mipmap_level: usize) -> rend3::types::Texture {
-    let image = blp_to_image(blp, mipmap_level).expect("decode");
-    let image_dims = glam::UVec2::new(image.width(), image.height());
-    let image_data = image.into_rgba8();
+/// Uploads the given mip level, or the smallest level if the image doesn't have that many.
+fn create_texture_rgba8(blp: &BlpImage, mipmap_level: usize) -> Result<rend3::types::Texture, BackendError> {
+    let mipmap_level = mipmap_level.min(blp.image_count().saturating_sub(1));
+    let image_data = BLPLoader::blp_to_rgba8(blp, mipmap_level).map_err(BackendError::UnsupportedFormat)?;
+    let image_dims = glam::UVec2::new(image_data.width(), image_data.height());
 
-    rend3::types::Texture {
+    Ok(rend3::types::Texture {
         label: None,
         data: image_data.into_raw(),
         format: rend3::types::TextureFormat::Rgba8UnormSrgb,
         size: image_dims,
         mip_count: rend3::types::MipmapCount::ONE,
         mip_source: rend3::types::MipmapSource::Uploaded,
-    }
+    })
 }
 
 fn create_object(transform: Affine3A, mesh_handle: MeshHandle, material_handle: MaterialHandle) -> Object {
@@ -62,7 +64,8 @@ pub fn add_terrain_chunks(
             albedo: AlbedoType::Vertex { srgb: true },
             transparency: TransparencyType::Opaque,
         };
-        let material = Rend3BackendConverter::create_material_from_ir(&_material, None);
+        let material =
+            Rend3BackendConverter::create_material_from_ir(&_material, None).expect("Vertex colors need no texture");
         let material_handle = renderer.add_material(material);
 
         // TODO: per definition, IR should be in blender-space, so we need to transform the translation at the very least. Or rather directly return "tt"
@@ -83,7 +86,7 @@ pub fn add_terrain_chunks(
 
 pub fn add_wmo_groups<'a, W>(
     wmos: W,
-    textures: &HashMap<String, BlpImage>,
+    textures: &HashMap<String, Arc<BlpImage>>,
     renderer: &Arc<Renderer>,
     object_list: &mut Vec<ObjectHandle>,
 ) where
@@ -109,13 +112,10 @@ pub fn add_wmo_groups<'a, W>(
                     _ => None,
                 };
 
-                let mapped_tex = blp_opt.as_ref().map(|tex| {
-                    renderer
-                        .add_texture_2d(create_texture_rgba8(tex, 0))
-                        .expect("Creating the texture is successful")
-                });
-                let material = Rend3BackendConverter::create_material_from_ir(material, mapped_tex);
-                let material_handle = renderer.add_material(material);
+                let mapped_tex = blp_opt.and_then(|tex| add_texture(renderer, tex));
+                let Some(material_handle) = add_material(renderer, material, mapped_tex) else {
+                    continue;
+                };
 
                 // Combine the mesh and the material with a location to give an object.
                 let object = create_object(*transform, mesh_handle, material_handle);
@@ -126,25 +126,34 @@ pub fn add_wmo_groups<'a, W>(
     }
 }
 
+/// The LoD level of doodads is picked once, based on their distance to the (initial) camera location.
 pub fn add_placed_doodads(
     placed_doodads: &Vec<PlacedDoodad>,
+    camera_location: Vec3A,
     renderer: &Arc<Renderer>,
     object_list: &mut Vec<ObjectHandle>,
 ) {
     for dad in placed_doodads {
         let m2 = dad.m2.deref();
         // Create mesh and calculate smooth normals based on vertices
-        let mesh = Rend3BackendConverter::create_mesh_from_ir(&m2.mesh).unwrap();
+        let mesh = match &m2.lods {
+            Some(lods) => {
+                let lod_level = lods.lod_for_distance(dad.transform.translation.distance(camera_location));
+                Rend3BackendConverter::create_mesh_from_ir_lod(lods, lod_level)
+            }
+            None => Rend3BackendConverter::create_mesh_from_ir(&m2.mesh),
+        }
+        .unwrap();
         let mesh_handle = renderer.add_mesh(mesh).expect("Mesh creation successful");
 
         // TODO: concept work for textures
-        let mapped_tex = m2.blp_opt.as_ref().map(|tex| {
-            renderer
-                .add_texture_2d(create_texture_rgba8(tex, 0))
-                .expect("Texture creation successful")
-        });
-        let material = Rend3BackendConverter::create_material_from_ir(&m2.material, mapped_tex);
-        let material_handle = renderer.add_material(material);
+        let mapped_tex = m2
+            .blp_opt
+            .as_ref()
+            .and_then(|tex| add_texture(renderer, tex));
+        let Some(material_handle) = add_material(renderer, &m2.material, mapped_tex) else {
+            continue;
+        };
 
         // Combine the mesh and the material with a location to give an object.
         let object = create_object(dad.transform, mesh_handle, material_handle);
@@ -157,3 +166,31 @@ pub fn add_placed_doodads(
         object_list.push(_object_handle);
     }
 }
+
+fn add_texture(renderer: &Arc<Renderer>, blp: &BlpImage) -> Option<Texture2DHandle> {
+    match create_texture_rgba8(blp, 0) {
+        Ok(texture) => Some(
+            renderer
+                .add_texture_2d(texture)
+                .expect("Texture creation successful"),
+        ),
+        Err(err) => {
+            warn!("Skipping texture: {}", err);
+            None
+        }
+    }
+}
+
+fn add_material(
+    renderer: &Arc<Renderer>,
+    material: &Material,
+    texture: Option<Texture2DHandle>,
+) -> Option<MaterialHandle> {
+    match Rend3BackendConverter::create_material_from_ir(material, texture) {
+        Ok(material) => Some(renderer.add_material(material)),
+        Err(err) => {
+            warn!("Skipping object: {}", err);
+            None
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use crate::rendering::asset_graph::nodes::adt_node::{IRMaterial, IRMesh, IRTexture};
-use crate::rendering::rend3_backend::Rend3BackendConverter;
+use crate::rendering::rend3_backend::{BackendError, Rend3BackendConverter};
+use glam::Mat3;
+use log::error;
 use rend3::Renderer;
 use rend3::types::{MaterialHandle, MeshHandle, Texture2DHandle};
 use std::ops::DerefMut;
@@ -7,60 +9,107 @@ use std::sync::{Arc, RwLock};
 
 pub fn gpu_load_mesh(renderer: &Arc<Renderer>, mesh: &RwLock<IRMesh>) -> MeshHandle {
     {
-        if let Some(handle) = mesh.read().expect("Mesh Read Lock").handle.as_ref() {
+        if let Some(handle) = mesh.read().expect("Mesh Read Lock").handle() {
             return handle.clone();
         }
     }
 
     let mut mesh_lock = mesh.write().expect("Mesh Write Lock");
-    let render_mesh = Rend3BackendConverter::create_mesh_from_ir(&mesh_lock.data).expect("Mesh building successful");
+    if let Some(handle) = mesh_lock.handle() {
+        return handle.clone(); // Someone else raced us
+    }
+
+    let mesh_data = mesh_lock
+        .data()
+        .expect("Only hollowed meshes lack data, but those have a handle");
+    let render_mesh = Rend3BackendConverter::create_mesh_from_ir(mesh_data)
+        .unwrap_or_else(|err| panic!("Mesh building failed: {}", err));
     let mesh_handle = renderer
         .add_mesh(render_mesh)
         .expect("Mesh creation successful");
-    mesh_lock.deref_mut().handle = Some(mesh_handle.clone());
+    mesh_lock.set_handle(mesh_handle.clone());
     mesh_handle
 }
 
+/// Fails if the material requires a texture, but none has been passed, see
+/// [`Rend3BackendConverter::create_material_from_ir`].
 pub fn gpu_load_material(
     renderer: &Arc<Renderer>,
     material: &RwLock<IRMaterial>,
     texture_handle: Option<Texture2DHandle>,
-) -> MaterialHandle {
+) -> Result<MaterialHandle, BackendError> {
     {
         if let Some(handle) = material.read().expect("Material Read Lock").handle.as_ref() {
-            return handle.clone();
+            return Ok(handle.clone());
         }
     }
     let mut material_lock = material.write().expect("Material Write Lock");
-    let render_mat = Rend3BackendConverter::create_material_from_ir(&material_lock.data, texture_handle);
+    let render_mat = Rend3BackendConverter::create_material_from_ir(&material_lock.data, texture_handle)?;
     let material_handle = renderer.add_material(render_mat);
     material_lock.deref_mut().handle = Some(material_handle.clone());
-    material_handle
+    Ok(material_handle)
+}
+
+/// Replaces an uploaded material by one with the given uv transform, e.g. for texture animations. Materials that
+/// haven't been uploaded yet are skipped, they are animated once they are.
+pub fn gpu_update_material_uv(
+    renderer: &Arc<Renderer>,
+    material: &RwLock<IRMaterial>,
+    texture_handle: Option<Texture2DHandle>,
+    uv_transform: Mat3,
+) -> Result<(), BackendError> {
+    let material_lock = material.read().expect("Material Read Lock");
+    let Some(handle) = material_lock.handle.as_ref() else {
+        return Ok(());
+    };
+
+    let mut render_mat = Rend3BackendConverter::create_material_from_ir(&material_lock.data, texture_handle)?;
+    render_mat.uv_transform0 = uv_transform;
+    renderer.update_material(handle, render_mat);
+    Ok(())
 }
 
+/// The state of a texture on the GPU side. A texture that is still loading has to be distinguished from a texture
+/// that failed to load, so that the renderer only shows the missing texture material for actual failures and the
+/// loading material while the texture is pending.
+#[derive(Debug, Clone)]
+pub enum TextureState {
+    Loading,
+    Loaded(Texture2DHandle),
+    Failed,
+}
+
+impl TextureState {
+    pub fn handle(self) -> Option<Texture2DHandle> {
+        match self {
+            TextureState::Loaded(handle) => Some(handle),
+            _ => None,
+        }
+    }
+}
+
+/// Uploads the texture starting at the given mip level, see [`crate::game::settings::TextureQuality`].
 pub fn gpu_load_texture(
     renderer: &Arc<Renderer>,
     texture_reference: &RwLock<Option<Arc<RwLock<Option<IRTexture>>>>>,
-) -> Option<Texture2DHandle> {
+    base_mip_level: u8,
+) -> TextureState {
     {
         let tex_arc = texture_reference.read().expect("Texture Read Lock");
-        if let Some(opt_handle) = tex_arc.as_ref() {
-            {
-                let tex_lock = opt_handle.read().expect("Texture Read Lock 2");
-                if let Some(tex_handle) = tex_lock.as_ref() {
-                    if let Some(handle) = tex_handle.handle.as_ref() {
-                        return Some(handle.clone());
-                    } // else: texture not added to the GPU yet - continue with the write lock
-                } else {
-                    // texture loading error?
-                    return None;
-                }
-            }
-        } else {
-            // else: texture (reference?) not loaded yet.
-            // TODO: the caller should prevent calling in that case and unwrap the lock? The caller should at least distinguish between texture not loaded (grey diffuse color) and texture loading error (pink!)
-            return None;
-        }
+        let Some(opt_handle) = tex_arc.as_ref() else {
+            // the reference has not been resolved yet.
+            return TextureState::Loading;
+        };
+
+        let tex_lock = opt_handle.read().expect("Texture Read Lock 2");
+        let Some(tex_handle) = tex_lock.as_ref() else {
+            // the resolver failed loading the texture.
+            return TextureState::Failed;
+        };
+
+        if let Some(handle) = tex_handle.handle.as_ref() {
+            return TextureState::Loaded(handle.clone());
+        } // else: texture not added to the GPU yet - continue with the write lock
     }
 
     let tex_wlock = texture_reference.write().expect("Texture Write Lock");
@@ -72,11 +121,18 @@ pub fn gpu_load_texture(
         .expect("Texture internal write lock");
 
     let tex = tex_iwlock.as_mut().expect("unreachable!");
-    // TODO: What do we do with the mipmap level? From 0 to tex.data.image_count() as u8 - 1
-    let texture = Rend3BackendConverter::create_texture_from_ir(&tex.data, 0);
+    let texture = match Rend3BackendConverter::create_texture_from_ir(&tex.data, base_mip_level) {
+        Ok(texture) => texture,
+        Err(err) => {
+            error!("{}, treating the texture as failed", err);
+            // Like a texture that the resolver failed to load, so it isn't decoded again
+            *tex_iwlock = None;
+            return TextureState::Failed;
+        }
+    };
     let texture_handle = renderer
         .add_texture_2d(texture)
         .expect("Texture creation successful");
     tex.handle = Some(texture_handle.clone());
-    Some(texture_handle)
+    TextureState::Loaded(texture_handle)
 }
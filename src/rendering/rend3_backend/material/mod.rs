@@ -2,6 +2,7 @@ use rust_embed::RustEmbed;
 
 pub mod terrain;
 pub mod units;
+pub mod water;
 
 #[derive(RustEmbed)]
 #[folder = "$CARGO_MANIFEST_DIR/shaders/src"]
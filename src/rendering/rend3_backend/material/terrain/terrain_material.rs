@@ -5,10 +5,14 @@ use rend3::types::{
 };
 use rend3_routine::pbr::TransparencyType;
 
+/// The maximum amount of layers that are blended on top of the base layer, one per channel of the alpha map.
+pub const MAX_ALPHA_LAYERS: usize = 4;
+
 pub struct TerrainMaterial {
     pub base_texture: Texture2DHandle,
-    // 3 layers with alpha map each
-    pub additional_layers: [Option<Texture2DHandle>; 6],
+    pub layers: [Option<Texture2DHandle>; MAX_ALPHA_LAYERS],
+    /// The alpha maps of all layers, packed into the channels (RGBA) of a single texture.
+    pub alpha_map: Option<Texture2DHandle>,
 }
 
 #[derive(Debug, Default, Copy, Clone, ShaderType)]
@@ -18,7 +22,7 @@ pub struct TerrainShaderMaterial {
 
 impl Material for TerrainMaterial {
     type DataType = TerrainShaderMaterial;
-    type TextureArrayType = [Option<RawTexture2DHandle>; 7];
+    type TextureArrayType = [Option<RawTexture2DHandle>; MAX_ALPHA_LAYERS + 2];
     type RequiredAttributeArrayType = [&'static VertexAttributeId; 1];
     type SupportedAttributeArrayType = [&'static VertexAttributeId; 2];
 
@@ -39,26 +43,15 @@ impl Material for TerrainMaterial {
     }
 
     fn to_textures(&self) -> Self::TextureArrayType {
+        let raw = |handle: &Option<Texture2DHandle>| handle.as_ref().map(|handle| handle.get_raw());
+
         [
             Some(self.base_texture.get_raw()),
-            self.additional_layers
-                .get(0)
-                .and_then(|handle_opt| handle_opt.as_ref().map(|handle| handle.get_raw())),
-            self.additional_layers
-                .get(1)
-                .and_then(|handle_opt| handle_opt.as_ref().map(|handle| handle.get_raw())),
-            self.additional_layers
-                .get(2)
-                .and_then(|handle_opt| handle_opt.as_ref().map(|handle| handle.get_raw())),
-            self.additional_layers
-                .get(3)
-                .and_then(|handle_opt| handle_opt.as_ref().map(|handle| handle.get_raw())),
-            self.additional_layers
-                .get(4)
-                .and_then(|handle_opt| handle_opt.as_ref().map(|handle| handle.get_raw())),
-            self.additional_layers
-                .get(5)
-                .and_then(|handle_opt| handle_opt.as_ref().map(|handle| handle.get_raw())),
+            raw(&self.layers[0]),
+            raw(&self.layers[1]),
+            raw(&self.layers[2]),
+            raw(&self.layers[3]),
+            raw(&self.alpha_map),
         ]
     }
 
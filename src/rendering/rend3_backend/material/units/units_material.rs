@@ -1,4 +1,5 @@
 use encase::ShaderType;
+use glam::Mat3;
 use rend3::types::{
     Material, RawTexture2DHandle, Sorting, Texture2DHandle, VERTEX_ATTRIBUTE_POSITION,
     VERTEX_ATTRIBUTE_TEXTURE_COORDINATES_0, VertexAttributeId,
@@ -8,11 +9,14 @@ use rend3_routine::pbr::TransparencyType;
 #[derive(Debug, Clone, Default)]
 pub struct UnitsMaterial {
     pub texture_layers: [Option<Texture2DHandle>; 3],
+    /// Applied to the texture coordinates, e.g. for texture animations
+    pub uv_transform: Mat3,
 }
 
 #[derive(Debug, Default, Copy, Clone, ShaderType)]
 pub struct UnitsShaderMaterial {
     pub material_flag: u32,
+    pub uv_transform: Mat3,
 }
 
 impl Material for UnitsMaterial {
@@ -59,6 +63,9 @@ impl Material for UnitsMaterial {
     }
 
     fn to_data(&self) -> Self::DataType {
-        UnitsShaderMaterial { material_flag: 0 }
+        UnitsShaderMaterial {
+            material_flag: 0,
+            uv_transform: self.uv_transform,
+        }
     }
 }
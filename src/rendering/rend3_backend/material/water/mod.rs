@@ -0,0 +1,2 @@
+pub mod water_material;
+pub mod water_routine;
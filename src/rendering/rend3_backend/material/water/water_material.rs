@@ -0,0 +1,54 @@
+use encase::ShaderType;
+use glam::Vec4;
+use rend3::types::{Material, RawTexture2DHandle, Sorting, VERTEX_ATTRIBUTE_POSITION, VertexAttributeId};
+use rend3_routine::pbr::TransparencyType;
+
+/// A plain translucent color for all liquids, the liquid types (and their textures) aren't considered yet.
+#[derive(Debug, Clone)]
+pub struct WaterMaterial {
+    pub color: Vec4,
+}
+
+impl Default for WaterMaterial {
+    fn default() -> Self {
+        Self {
+            color: Vec4::new(0.1, 0.3, 0.45, 0.6),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, ShaderType)]
+pub struct WaterShaderMaterial {
+    pub color: Vec4,
+}
+
+impl Material for WaterMaterial {
+    type DataType = WaterShaderMaterial;
+    type TextureArrayType = [Option<RawTexture2DHandle>; 0];
+    type RequiredAttributeArrayType = [&'static VertexAttributeId; 1];
+    type SupportedAttributeArrayType = [&'static VertexAttributeId; 1];
+
+    fn required_attributes() -> Self::RequiredAttributeArrayType {
+        [&VERTEX_ATTRIBUTE_POSITION]
+    }
+
+    fn supported_attributes() -> Self::SupportedAttributeArrayType {
+        [&VERTEX_ATTRIBUTE_POSITION]
+    }
+
+    fn key(&self) -> u64 {
+        TransparencyType::Blend as u64
+    }
+
+    fn sorting(&self) -> Sorting {
+        Sorting::BLENDING
+    }
+
+    fn to_textures(&self) -> Self::TextureArrayType {
+        []
+    }
+
+    fn to_data(&self) -> Self::DataType {
+        WaterShaderMaterial { color: self.color }
+    }
+}
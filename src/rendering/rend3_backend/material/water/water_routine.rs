@@ -0,0 +1,85 @@
+use crate::rendering::rend3_backend::material::SargerustShaderSources;
+use crate::rendering::rend3_backend::material::water::water_material::WaterMaterial;
+use rend3::RendererProfile::GpuDriven;
+use rend3::{Renderer, RendererDataCore, ShaderConfig, ShaderPreProcessor, ShaderVertexBufferConfig};
+use rend3_routine::common::{PerMaterialArchetypeInterface, WholeFrameInterfaces};
+use rend3_routine::forward::{ForwardRoutine, ForwardRoutineCreateArgs, RoutineType, ShaderModulePair};
+use rend3_routine::pbr::TransparencyType;
+use std::borrow::Cow;
+use std::sync::Arc;
+use wgpu::{BlendState, ShaderModuleDescriptor, ShaderSource};
+
+pub struct WaterRoutine {
+    pub blend_routine: ForwardRoutine<WaterMaterial>,
+    pub per_material: PerMaterialArchetypeInterface<WaterMaterial>,
+}
+
+impl WaterRoutine {
+    pub fn new(
+        renderer: &Arc<Renderer>,
+        data_core: &mut RendererDataCore,
+        spp: &mut ShaderPreProcessor,
+        interfaces: &WholeFrameInterfaces,
+    ) -> Self {
+        // TODO: This is not really in-sync with how the other shaders do it, but:
+        spp.add_shaders_embed::<SargerustShaderSources>("sargerust");
+        // profiling::scope!("WaterRoutine::new");
+
+        // This ensures the BGLs for the material are created
+        data_core
+            .material_manager
+            .ensure_archetype::<WaterMaterial>(&renderer.device, renderer.profile);
+
+        let per_material = PerMaterialArchetypeInterface::<WaterMaterial>::new(&renderer.device);
+
+        let module = renderer
+            .device
+            .create_shader_module(ShaderModuleDescriptor {
+                label: Some("water blend sm"),
+                source: ShaderSource::Wgsl(Cow::Owned(
+                    spp.render_shader(
+                        "sargerust/water-blend.wgsl",
+                        &ShaderConfig {
+                            //profile: Some(renderer.profile),
+                            profile: Some(GpuDriven),
+                            ..Default::default()
+                        },
+                        Some(&ShaderVertexBufferConfig::from_material::<WaterMaterial>()),
+                    )
+                    .unwrap(),
+                )),
+            });
+
+        let routine_type = RoutineType::Forward;
+        let transparency = TransparencyType::Blend;
+
+        let blend_routine = ForwardRoutine::new(ForwardRoutineCreateArgs {
+            name: &format!("Water {routine_type:?} {transparency:?}"),
+            renderer,
+            data_core,
+            spp,
+            interfaces,
+            per_material: &per_material,
+            material_key: transparency as u64,
+            routine_type,
+            shaders: ShaderModulePair {
+                vs_entry: "vs_main",
+                vs_module: &module,
+                fs_entry: "fs_main",
+                fs_module: &module,
+            },
+            extra_bgls: &[],
+            descriptor_callback: Some(&|desc, targets| {
+                if transparency == TransparencyType::Blend {
+                    desc.depth_stencil.as_mut().unwrap().depth_write_enabled = false;
+                    targets[0].as_mut().unwrap().blend = Some(BlendState::ALPHA_BLENDING)
+                }
+            }),
+        });
+
+        Self {
+            blend_routine,
+            per_material,
+        }
+    }
+}
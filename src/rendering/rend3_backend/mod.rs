@@ -1,6 +1,7 @@
+use std::fmt::{Display, Formatter};
+
 use image_blp::BlpImage;
-use log::error;
-use rend3::types::{Texture, Texture2DHandle};
+use rend3::types::{MeshValidationError, Texture, Texture2DHandle};
 use rend3_routine::pbr::{AlbedoComponent, PbrMaterial, Transparency};
 
 use crate::rendering::common::types::TransparencyType::{Blend, Cutout, Opaque};
@@ -9,6 +10,34 @@ use crate::rendering::create_texture_rgba8;
 
 pub mod gpu_loaders;
 pub mod material;
+pub mod screenshot;
+
+/// Failures when converting our IR into rend3 types.
+#[derive(Debug)]
+pub enum BackendError {
+    MeshBuild(MeshValidationError),
+    /// The material's albedo is a texture, but no texture handle has been passed.
+    MissingTexture {
+        name: Option<String>,
+    },
+    /// The texture can't be decoded into a format that can be uploaded.
+    UnsupportedFormat(anyhow::Error),
+}
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::MeshBuild(err) => write!(f, "Building the mesh failed: {}", err),
+            BackendError::MissingTexture { name: Some(name) } => {
+                write!(f, "Material requires the presence of texture {}", name)
+            }
+            BackendError::MissingTexture { name: None } => write!(f, "Material requires the presence of a texture"),
+            BackendError::UnsupportedFormat(err) => write!(f, "Unsupported texture: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
 
 pub struct Rend3BackendConverter {}
 
@@ -16,8 +45,9 @@ impl Rend3BackendConverter {
     fn create_mesh_from_ir_internal(
         vertex_buffers: &VertexBuffers,
         indices: &Vec<u32>,
-    ) -> Result<rend3::types::Mesh, anyhow::Error> {
+    ) -> Result<rend3::types::Mesh, BackendError> {
         // TODO: introspect the individual buffers, and if they are >0, call .with_foo().
+        // The importers have already converted the indices to coordinate_systems::IR_WINDING, which is right-handed.
         let mut builder = rend3::types::MeshBuilder::new(
             vertex_buffers.position_buffer.clone(),
             rend3::types::Handedness::Right,
@@ -36,47 +66,52 @@ impl Rend3BackendConverter {
             builder = builder.with_vertex_color_0(vertex_buffers.vertex_color_0.clone());
         }
 
-        Ok(builder.build()?)
+        builder.build().map_err(BackendError::MeshBuild)
     }
-    pub fn create_mesh_from_ir(mesh: &Mesh) -> Result<rend3::types::Mesh, anyhow::Error> {
+    pub fn create_mesh_from_ir(mesh: &Mesh) -> Result<rend3::types::Mesh, BackendError> {
         Rend3BackendConverter::create_mesh_from_ir_internal(&mesh.vertex_buffers, &mesh.index_buffer)
     }
-    pub fn create_mesh_from_ir_lod(mesh: &MeshWithLod, lod_level: usize) -> Result<rend3::types::Mesh, anyhow::Error> {
+    pub fn create_mesh_from_ir_lod(mesh: &MeshWithLod, lod_level: usize) -> Result<rend3::types::Mesh, BackendError> {
         Rend3BackendConverter::create_mesh_from_ir_internal(&mesh.vertex_buffers, &mesh.index_buffers[lod_level])
     }
 
-    pub fn create_material_from_ir(material: &Material, texture_handle: Option<Texture2DHandle>) -> PbrMaterial {
-        if texture_handle.is_none() {
-            // TODO: fail-safe somehow setting the material type differently.
-            if let AlbedoType::Texture = material.albedo {
-                error!("Material requires the presence of a texture");
+    /// Fails if the material needs a texture, but none has been passed, so that the caller can fall back to a
+    /// different material (e.g. the missing texture material) instead of producing a broken one.
+    pub fn create_material_from_ir(
+        material: &Material,
+        texture_handle: Option<Texture2DHandle>,
+    ) -> Result<PbrMaterial, BackendError> {
+        let albedo = match &material.albedo {
+            AlbedoType::None => AlbedoComponent::None,
+            AlbedoType::Vertex { srgb } => AlbedoComponent::Vertex { srgb: *srgb },
+            AlbedoType::Texture => {
+                AlbedoComponent::Texture(texture_handle.ok_or(BackendError::MissingTexture { name: None })?)
             }
-            if let AlbedoType::TextureWithName(name) = &material.albedo {
-                error!("Material requires the presence of texture {name}");
+            AlbedoType::TextureWithName(name) => {
+                AlbedoComponent::Texture(texture_handle.ok_or_else(|| BackendError::MissingTexture {
+                    name: Some(name.clone()),
+                })?)
             }
-        }
+            AlbedoType::Value(rgba) => AlbedoComponent::Value(*rgba),
+            AlbedoType::ValueVertex { value, srgb } => AlbedoComponent::ValueVertex {
+                value: *value,
+                srgb: *srgb,
+            },
+        };
 
-        let ret = PbrMaterial {
+        Ok(PbrMaterial {
             unlit: material.is_unlit,
-            albedo: match material.albedo {
-                AlbedoType::None => AlbedoComponent::None,
-                AlbedoType::Vertex { srgb } => AlbedoComponent::Vertex { srgb },
-                AlbedoType::Texture => AlbedoComponent::Texture(texture_handle.unwrap()),
-                AlbedoType::TextureWithName(_) => AlbedoComponent::Texture(texture_handle.unwrap()),
-                AlbedoType::Value(rgba) => AlbedoComponent::Value(rgba),
-                AlbedoType::ValueVertex { value, srgb } => AlbedoComponent::ValueVertex { value, srgb },
-            },
+            albedo,
             transparency: match material.transparency {
                 Cutout { cutout } => Transparency::Cutout { cutout },
                 Opaque => Transparency::Opaque,
                 Blend => Transparency::Blend,
             },
             ..PbrMaterial::default()
-        };
-        ret
+        })
     }
 
-    pub fn create_texture_from_ir(texture: &BlpImage, mipmap_level: u8) -> Texture {
+    pub fn create_texture_from_ir(texture: &BlpImage, mipmap_level: u8) -> Result<Texture, BackendError> {
         create_texture_rgba8(texture, mipmap_level as usize)
     }
 }
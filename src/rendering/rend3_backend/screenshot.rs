@@ -0,0 +1,149 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glam::UVec2;
+use log::{error, info, warn};
+use rend3::Renderer;
+use wgpu::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, MapMode, Origin3d, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages,
+};
+
+/// An offscreen render target that the frame is additionally tonemapped into, as the surface texture can't be copied
+/// from. After the frame has been rendered, [`ScreenshotTarget::capture`] reads it back and stores it as PNG.
+pub struct ScreenshotTarget {
+    texture: wgpu::Texture,
+    resolution: UVec2,
+}
+
+impl ScreenshotTarget {
+    /// Returns None if the format can't be written as 8-bit RGBA PNG.
+    pub fn new(renderer: &Renderer, resolution: UVec2, format: TextureFormat) -> Option<Self> {
+        if !matches!(
+            format,
+            TextureFormat::Rgba8Unorm
+                | TextureFormat::Rgba8UnormSrgb
+                | TextureFormat::Bgra8Unorm
+                | TextureFormat::Bgra8UnormSrgb
+        ) {
+            warn!(
+                "Screenshots are not supported for the surface format {:?}",
+                format
+            );
+            return None;
+        }
+
+        let texture = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("Screenshot Target"),
+            size: Extent3d {
+                width: resolution.x,
+                height: resolution.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        Some(Self {
+            texture,
+            resolution,
+        })
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// Copies the rendered frame into a buffer, which is then mapped, encoded and written to disk on a worker thread,
+    /// so that the render loop isn't blocked. Needs to be called after the frame has been submitted.
+    pub fn capture(self, renderer: &Arc<Renderer>) {
+        // Rows in buffers need to be aligned for copies
+        let unpadded_bytes_per_row = self.resolution.x * 4;
+        let bytes_per_row =
+            unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("Screenshot Readback"),
+            size: bytes_per_row as u64 * self.resolution.y as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = renderer
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Screenshot Copy"),
+            });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.size(),
+        );
+        renderer.queue.submit(Some(encoder.finish()));
+
+        let device = renderer.device.clone();
+        let is_bgra = matches!(
+            self.texture.format(),
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        );
+        let resolution = self.resolution;
+
+        std::thread::spawn(move || {
+            let slice = buffer.slice(..);
+            let (sender, receiver) = std::sync::mpsc::channel();
+            slice.map_async(MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+            device.poll(wgpu::Maintain::Wait);
+
+            if let Err(err) = receiver.recv().expect("Map callback to be called") {
+                error!("Failed to read back the screenshot: {}", err);
+                return;
+            }
+
+            let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * resolution.y) as usize);
+            for row in slice
+                .get_mapped_range()
+                .chunks_exact(bytes_per_row as usize)
+            {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+
+            if is_bgra {
+                pixels
+                    .chunks_exact_mut(4)
+                    .for_each(|pixel| pixel.swap(0, 2));
+            }
+
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis())
+                .unwrap_or_default();
+            let file_name = format!("screenshot_{}.png", timestamp);
+
+            let image = image::RgbaImage::from_raw(resolution.x, resolution.y, pixels)
+                .expect("Buffer size to match the resolution");
+            match image.save(&file_name) {
+                Ok(_) => info!("Saved screenshot to {}", file_name),
+                Err(err) => error!("Failed to save the screenshot: {}", err),
+            }
+        });
+    }
+}